@@ -0,0 +1,53 @@
+//! Times decoding large DXT1 and DXT5 textures with the portable decoder and
+//! with whatever `xnb::dxt::decode` picks, which only differ when built with
+//! the `simd` feature.
+//!
+//! Run with `cargo bench --bench dxt --features simd`.
+
+use std::time::{Duration, Instant};
+use xnb::dxt::{decode, decode_scalar};
+use xnb::SurfaceFormat;
+
+const SIZE: usize = 2048;
+const ITERATIONS: u32 = 10;
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+// Arbitrary but repeatable block data.
+fn blocks(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn main() {
+    for &(format, block_size) in &[(SurfaceFormat::Dxt1, 8), (SurfaceFormat::Dxt5, 16)] {
+        let data = blocks(SIZE / 4 * SIZE / 4 * block_size);
+        assert_eq!(
+            decode(format, &data, SIZE, SIZE).unwrap(),
+            decode_scalar(format, &data, SIZE, SIZE).unwrap()
+        );
+        let scalar = time(|| {
+            decode_scalar(format, &data, SIZE, SIZE).unwrap();
+        });
+        let best = time(|| {
+            decode(format, &data, SIZE, SIZE).unwrap();
+        });
+        println!("{:?}, {}x{}:", format, SIZE, SIZE);
+        println!("  scalar:  {:?}", scalar);
+        println!("  decode:  {:?}", best);
+    }
+}
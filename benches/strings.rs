@@ -0,0 +1,64 @@
+//! Times parsing a large `Dictionary<String, String>`, like the dialogue files
+//! in Stardew Valley, and reading its strings with `read_string_with_length`
+//! on their own. Run it on revisions before and after a change to the string
+//! readers to compare them.
+//!
+//! Run with `cargo bench --bench strings`.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use xnb::testing::{fixture, read_string_with_length};
+use xnb::XNB;
+
+const ENTRIES: usize = 50_000;
+const ITERATIONS: u32 = 20;
+
+fn dialogue() -> HashMap<String, String> {
+    (0..ENTRIES)
+        .map(|i| {
+            let line = format!(
+                "Entry {}: Héllo, farmer! Lovely weather, isn't it?#$b#Well, see you around.",
+                i
+            );
+            (format!("Dialogue_{}", i), line)
+        })
+        .collect()
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let dialogue = dialogue();
+    let bytes = fixture(&dialogue).unwrap();
+    let parsed = time(|| {
+        let xnb = XNB::<HashMap<String, String>>::from_buffer(&mut &bytes[..]).unwrap();
+        assert_eq!(xnb.primary.len(), ENTRIES);
+    });
+
+    let mut strings = vec![];
+    let mut lengths = vec![];
+    for (key, value) in &dialogue {
+        strings.extend_from_slice(key.as_bytes());
+        strings.extend_from_slice(value.as_bytes());
+        lengths.push(key.len() as u32);
+        lengths.push(value.len() as u32);
+    }
+    let read = time(|| {
+        let mut rdr = &strings[..];
+        for &len in &lengths {
+            read_string_with_length(&mut rdr, len).unwrap();
+        }
+        assert!(rdr.is_empty());
+    });
+
+    println!("{} dialogue entries:", ENTRIES);
+    println!("  parsing the XNB:    {:?}", parsed);
+    println!("  reading strings:    {:?}", read);
+}
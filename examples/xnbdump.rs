@@ -1,14 +1,12 @@
 extern crate image;
-extern crate squish;
 extern crate xnb;
 
 use image::{DynamicImage, ImageBuffer};
-use squish::{decompress_image, CompressType};
 use std::env;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
 use std::process;
-use xnb::{/*tide,*/ SurfaceFormat, Texture2d, XNB};
+use xnb::{/*tide,*/ LayoutOptions, SurfaceFormat, Texture2d, XNB};
 
 fn usage() {
     println!("xnbdump [file.xnb] [type]");
@@ -31,6 +29,17 @@ impl Dumpable for xnb::Texture2d {
 
 impl Dumpable for xnb::SpriteFont {
     fn dump(self) {
+        // A preview of every character, wrapped to the width of the atlas.
+        let text: String = self.char_map.iter().collect();
+        let options = LayoutOptions::new().max_width(self.texture.width as f32);
+        match self.render_with(&text, &options) {
+            Ok(preview) => {
+                if let Err(e) = preview.save("preview.png") {
+                    println!("Error saving preview: {}", e);
+                }
+            }
+            Err(e) => println!("Error rendering preview: {}", e),
+        }
         dump_texture(self.texture);
         println!("glyphs, cropping, char_map:");
         for ((g, c), m) in self
@@ -61,7 +70,8 @@ impl<T: std::fmt::Debug> Dumpable for Vec<T> {
 fn dump_xnb<T: xnb::Parse + Dumpable>(xnb: xnb::MaybeCompressedXNB) -> Result<(), xnb::Error> {
     let xnb: XNB<T> = match xnb {
         xnb::MaybeCompressedXNB::Uncompressed(xnb) => xnb.xnb()?,
-        xnb::MaybeCompressedXNB::Compressed(xnb) => xnb.xnb(xnb::WindowSize::KB64)?,
+        xnb::MaybeCompressedXNB::Compressed(xnb) => xnb.xnb(xnb::DEFAULT_WINDOW_SIZE)?,
+        xnb::MaybeCompressedXNB::Lz4Compressed(xnb) => xnb.xnb()?,
     };
     xnb.primary.dump();
     Ok(())
@@ -167,18 +177,18 @@ fn main() {
         Asset::Tide(map) => {
             if !map.properties.is_empty() {
                 println!("Map properties:");
-                tide::print_properties(&map.properties);
+                print!("{}", tide::format_properties(&map.properties));
             }
             for ts in &map.tilesheets {
                 if !ts.properties.is_empty() {
                     println!("Tilesheet {} properties:", ts.id);
-                    tide::print_properties(&ts.properties);
+                    print!("{}", tide::format_properties(&ts.properties));
                 }
             }
             for layer in &map.layers {
                 if !layer.properties.is_empty() {
                     println!("Layer {} properties:", layer.id);
-                    tide::print_properties(&layer.properties);
+                    print!("{}", tide::format_properties(&layer.properties));
                 }
                 for tile in &layer.tiles {
                     match *tile {
@@ -186,14 +196,14 @@ fn main() {
                             for tile in &tile.frames {
                                 if !tile.properties.is_empty() {
                                     println!("Tile {} properties:", tile.idx);
-                                    tide::print_properties(&tile.properties);
+                                    print!("{}", tide::format_properties(&tile.properties));
                                 }
                             }
                         }
                         tide::Tile::Static(ref tile) => {
                             if !tile.properties.is_empty() {
                                 println!("Tile {} properties:", tile.idx);
-                                tide::print_properties(&tile.properties);
+                                print!("{}", tide::format_properties(&tile.properties));
                             }
                         }
                     }
@@ -203,24 +213,59 @@ fn main() {
     }*/
 }
 
+fn is_hdr(format: SurfaceFormat) -> bool {
+    matches!(
+        format,
+        SurfaceFormat::Single
+            | SurfaceFormat::Vector2
+            | SurfaceFormat::Vector4
+            | SurfaceFormat::HalfSingle
+            | SurfaceFormat::HalfVector2
+            | SurfaceFormat::HalfVector4
+            | SurfaceFormat::HdrBlendable
+    )
+}
+
+// Floating point surfaces are saved as Radiance HDR files to keep their range.
+fn dump_hdr(texture: &Texture2d, mip: usize) {
+    let path = format!("data_{}.hdr", mip);
+    let result = texture
+        .decode_f32(mip)
+        .map_err(|e| e.to_string())
+        .and_then(|decoded| {
+            let mut wtr = BufWriter::new(File::create(&path).map_err(|e| e.to_string())?);
+            decoded.write_hdr(&mut wtr).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        println!("Error saving HDR: {}", e);
+        err();
+    }
+}
+
 fn dump_texture(texture: Texture2d) {
-    for (i, data) in texture.mip_data.into_iter().enumerate() {
+    for i in 0..texture.mip_data.len() {
+        if is_hdr(texture.format) {
+            dump_hdr(&texture, i);
+            continue;
+        }
         let path = format!("data_{}.png", i);
-        let dynamic_image = {
-            let data = match texture.format {
-                SurfaceFormat::Color => data,
-                SurfaceFormat::Dxt3 => decompress_image(
-                    texture.width as i32,
-                    texture.height as i32,
-                    data.as_ptr() as *const _,
-                    CompressType::Dxt3,
-                ),
-                f => panic!("can't handle surface format {:?}", f),
-            };
-
-            let img =
-                ImageBuffer::from_raw(texture.width as u32, texture.height as u32, data).unwrap();
-            DynamicImage::ImageRgba8(img)
+        let decoded = match texture.format {
+            // Keep the precision of wide formats by saving 16-bit PNGs.
+            SurfaceFormat::Rg32 | SurfaceFormat::Rgba64 | SurfaceFormat::Rgba1010102 => {
+                texture.decode_rgba16(i).map(|decoded| {
+                    let (w, h) = (decoded.width as u32, decoded.height as u32);
+                    let img = ImageBuffer::from_raw(w, h, decoded.pixels).unwrap();
+                    DynamicImage::ImageRgba16(img)
+                })
+            }
+            _ => texture.to_image(i, false).map(DynamicImage::ImageRgba8),
+        };
+        let dynamic_image = match decoded {
+            Ok(image) => image,
+            Err(e) => {
+                println!("Error decoding mip level {}: {}", i, e);
+                return err();
+            }
         };
         if let Err(e) = dynamic_image.save(path) {
             println!("Error saving PNG: {}", e);
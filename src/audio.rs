@@ -0,0 +1,143 @@
+use crate::external::resolve_relative;
+use crate::{read_object, Error, Parse, TypeReaders};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The header of a sound's sample data, as a `WAVEFORMATEX` structure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WaveFormat(pub Vec<u8>);
+
+impl WaveFormat {
+    fn u16_at(&self, offset: usize) -> u16 {
+        self.0
+            .get(offset..offset + 2)
+            .map_or(0, |b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        self.0
+            .get(offset..offset + 4)
+            .map_or(0, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// 1 for PCM, 2 for MS-ADPCM.
+    pub fn format_tag(&self) -> u16 {
+        self.u16_at(0)
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.u16_at(2)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.u32_at(4)
+    }
+
+    pub fn avg_bytes_per_sec(&self) -> u32 {
+        self.u32_at(8)
+    }
+
+    pub fn block_align(&self) -> u16 {
+        self.u16_at(12)
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        self.u16_at(14)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SoundEffect {
+    pub format: WaveFormat,
+    pub data: Vec<u8>,
+    /// The loop region, in samples.
+    pub loop_start: u32,
+    pub loop_length: u32,
+    pub duration: Duration,
+}
+
+impl SoundEffect {
+    /// Write the sound out as a RIFF/WAV file.
+    pub fn write_wav(&self, wtr: &mut dyn Write) -> Result<(), Error> {
+        let fmt = &self.format.0;
+        // Chunks are padded to an even length.
+        let fmt_len = fmt.len() + fmt.len() % 2;
+        let data_len = self.data.len() + self.data.len() % 2;
+        wtr.write_all(b"RIFF")?;
+        wtr.write_u32::<LittleEndian>((4 + 8 + fmt_len + 8 + data_len) as u32)?;
+        wtr.write_all(b"WAVE")?;
+        wtr.write_all(b"fmt ")?;
+        wtr.write_u32::<LittleEndian>(fmt.len() as u32)?;
+        wtr.write_all(fmt)?;
+        if fmt.len() % 2 == 1 {
+            wtr.write_u8(0)?;
+        }
+        wtr.write_all(b"data")?;
+        wtr.write_u32::<LittleEndian>(self.data.len() as u32)?;
+        wtr.write_all(&self.data)?;
+        if self.data.len() % 2 == 1 {
+            wtr.write_u8(0)?;
+        }
+        Ok(())
+    }
+}
+
+impl Parse for SoundEffect {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.SoundEffectReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let format_size = rdr.read_u32::<LittleEndian>()?;
+        let format = readers.read_bytes(rdr, format_size as usize)?;
+        let data_size = rdr.read_u32::<LittleEndian>()?;
+        let data = readers.read_data(rdr, data_size as usize)?;
+        let loop_start = rdr.read_u32::<LittleEndian>()?;
+        let loop_length = rdr.read_u32::<LittleEndian>()?;
+        let duration = rdr.read_u32::<LittleEndian>()?;
+        Ok(SoundEffect {
+            format: WaveFormat(format),
+            data,
+            loop_start,
+            loop_length,
+            duration: Duration::from_millis(duration as u64),
+        })
+    }
+}
+
+/// A piece of music. The audio itself lives in a separate file (usually .wma
+/// or .ogg) next to the XNB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Song {
+    pub filename: String,
+    pub duration: Duration,
+}
+
+impl Song {
+    /// The path of the audio file, given the path of the song's XNB.
+    pub fn path(&self, xnb: &Path) -> PathBuf {
+        resolve_relative(xnb, &self.filename)
+    }
+}
+
+impl Parse for Song {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.SongReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let filename = readers.read_string(rdr)?;
+        let duration: i32 = read_object(rdr, readers)?;
+        Ok(Song {
+            filename,
+            duration: Duration::from_millis(duration.max(0) as u64),
+        })
+    }
+}
@@ -0,0 +1,57 @@
+//! Loading a whole content folder at once, parsing files in parallel.
+
+use crate::{Error, Parse, XNB};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Parse every `.xnb` file under `dir` as a `T`, keyed by asset name: the
+/// path relative to `dir`, without the extension and with `/` separators
+/// (e.g. `Characters/Abigail`). A file that fails to parse doesn't stop the
+/// others; its error is recorded under its name. Only failing to walk the
+/// directory is an error for the whole batch.
+pub fn load_dir<T: Parse + Send>(
+    dir: impl AsRef<Path>,
+) -> io::Result<BTreeMap<String, Result<T, Error>>> {
+    let dir = dir.as_ref();
+    let mut paths = vec![];
+    find_xnbs(dir, &mut paths)?;
+    Ok(paths
+        .into_par_iter()
+        .map(|path| {
+            let name = asset_name(dir, &path);
+            (name, load(&path))
+        })
+        .collect())
+}
+
+fn load<T: Parse>(path: &Path) -> Result<T, Error> {
+    let mut rdr = BufReader::new(File::open(path)?);
+    XNB::<T>::from_buffer(&mut rdr).map(|xnb| xnb.primary)
+}
+
+fn find_xnbs(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_xnbs(&path, paths)?;
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("xnb"))
+        {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn asset_name(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path).with_extension("");
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,271 @@
+//! Untyped parsing that borrows strings and texture data from the file's bytes
+//! rather than copying them, for tools that scan whole content folders.
+
+use crate::shared::read_any_object;
+use crate::value::read_leaf;
+use crate::walk::{optional_object, Visitor};
+use crate::{
+    limit, read_7bit_encoded_int, Error, MaybeCompressedXNB, Parse, ParseOptions, SharedResource,
+    SurfaceFormat, Texture2d, TypeReader, TypeReaders, Value,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::io::{self, Read};
+use std::rc::Rc;
+use std::str;
+
+/// Like `Value`, but borrowing from the bytes it was read from. Only strings,
+/// collections and textures are broken down; anything else is read into an
+/// owned `Value`.
+#[derive(Debug, Clone)]
+pub enum BorrowedValue<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Char(char),
+    /// Borrowed unless it had to be decoded lossily.
+    String(Cow<'a, str>),
+    Array(Vec<BorrowedValue<'a>>),
+    Dictionary(Vec<(BorrowedValue<'a>, BorrowedValue<'a>)>),
+    Nullable(Option<Box<BorrowedValue<'a>>>),
+    Texture(BorrowedTexture<'a>),
+    Owned(Value),
+}
+
+#[derive(Debug, Clone)]
+pub struct BorrowedTexture<'a> {
+    pub format: SurfaceFormat,
+    pub width: usize,
+    pub height: usize,
+    pub mip_data: Vec<&'a [u8]>,
+}
+
+impl<'a> BorrowedValue<'a> {
+    /// Copy out everything borrowed.
+    pub fn into_owned(self) -> Value {
+        match self {
+            BorrowedValue::Null => Value::Null,
+            BorrowedValue::Bool(b) => Value::Bool(b),
+            BorrowedValue::Int(i) => Value::Int(i),
+            BorrowedValue::UInt(u) => Value::UInt(u),
+            BorrowedValue::Float(f) => Value::Float(f),
+            BorrowedValue::Char(c) => Value::Char(c),
+            BorrowedValue::String(s) => Value::String(s.into_owned()),
+            BorrowedValue::Array(values) => {
+                Value::Array(values.into_iter().map(BorrowedValue::into_owned).collect())
+            }
+            BorrowedValue::Dictionary(entries) => Value::Dictionary(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+            ),
+            BorrowedValue::Nullable(value) => {
+                Value::Nullable(value.map(|v| Box::new(v.into_owned())))
+            }
+            BorrowedValue::Texture(t) => Value::Texture(Rc::new(Texture2d {
+                format: t.format,
+                width: t.width,
+                height: t.height,
+                mip_data: t.mip_data.iter().map(|mip| mip.to_vec()).collect(),
+            })),
+            BorrowedValue::Owned(value) => value,
+        }
+    }
+
+    fn from_value(value: Value) -> BorrowedValue<'a> {
+        match value {
+            Value::Null => BorrowedValue::Null,
+            Value::Bool(b) => BorrowedValue::Bool(b),
+            Value::Int(i) => BorrowedValue::Int(i),
+            Value::UInt(u) => BorrowedValue::UInt(u),
+            Value::Float(f) => BorrowedValue::Float(f),
+            Value::Char(c) => BorrowedValue::Char(c),
+            value => BorrowedValue::Owned(value),
+        }
+    }
+}
+
+/// An uncompressed XNB parsed into `BorrowedValue`s. Shared resources are
+/// read as they would be by `XNB`.
+pub struct BorrowedXnb<'a> {
+    pub primary: BorrowedValue<'a>,
+    pub shared: Vec<Option<SharedResource>>,
+    /// The type reader table, in file order.
+    pub readers: Vec<TypeReader>,
+    /// Deviations from the format that were tolerated while parsing.
+    pub warnings: Vec<Error>,
+}
+
+impl<'a> BorrowedXnb<'a> {
+    /// Parse an uncompressed XNB. Compressed ones are rejected with
+    /// `Error::Void`; run them through `decompress_xnb` first.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<BorrowedXnb<'a>, Error> {
+        BorrowedXnb::from_bytes_with_options(bytes, &ParseOptions::default())
+    }
+
+    pub fn from_bytes_with_options(
+        bytes: &'a [u8],
+        options: &ParseOptions,
+    ) -> Result<BorrowedXnb<'a>, Error> {
+        let mut rdr = bytes;
+        let file_size = match MaybeCompressedXNB::from_buffer(&mut rdr)? {
+            MaybeCompressedXNB::Uncompressed(xnb) => xnb.1,
+            _ => return Err(Error::Void),
+        };
+        let mut warnings = vec![];
+        if bytes.len() != file_size {
+            let error = Error::FileSizeMismatch {
+                expected: file_size,
+                actual: bytes.len(),
+            };
+            options.recover(&mut warnings, error)?;
+        }
+
+        let mut readers = TypeReaders {
+            options: options.clone(),
+            ..TypeReaders::default()
+        };
+        let rdr = &mut Content {
+            rest: rdr,
+            len: rdr.len(),
+            position: readers.position.clone(),
+        };
+        let num_readers = read_7bit_encoded_int(rdr)?;
+        for _ in 0..num_readers {
+            readers.readers.push(TypeReader {
+                name: readers.read_string(rdr)?,
+                version: rdr.read_i32::<LittleEndian>()?,
+            });
+        }
+        let num_shared = read_7bit_encoded_int(rdr)?;
+        let primary =
+            optional_object(&mut Borrower, rdr, &readers)?.ok_or(Error::UnexpectedNull)?;
+        let mut shared = vec![];
+        for _ in 0..num_shared {
+            shared.push(read_any_object(rdr, &readers)?);
+        }
+        readers.resolve_shared(&shared)?;
+        if !rdr.rest.is_empty() {
+            readers.recoverable(Error::TrailingData(rdr.rest.len() as u64))?;
+        }
+        warnings.append(&mut readers.warnings.into_inner());
+        Ok(BorrowedXnb {
+            primary,
+            shared,
+            readers: readers.readers,
+            warnings,
+        })
+    }
+}
+
+// The content of an XNB, read in place so strings and texture data can be
+// borrowed from it.
+struct Content<'a> {
+    rest: &'a [u8],
+    len: usize,
+    // Shared with the `TypeReaders`, so errors can say where they happened.
+    position: Rc<Cell<u64>>,
+}
+
+impl<'a> Content<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.rest.len() < len {
+            return Err(Error::Truncated {
+                expected: len,
+                actual: self.rest.len(),
+            });
+        }
+        let (taken, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        self.position.set((self.len - rest.len()) as u64);
+        Ok(taken)
+    }
+}
+
+impl<'a> Read for Content<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.rest.read(buf)?;
+        self.position.set((self.len - self.rest.len()) as u64);
+        Ok(read)
+    }
+}
+
+// Builds a `BorrowedValue` as an object is walked.
+struct Borrower;
+
+impl<'a> Visitor<Content<'a>> for Borrower {
+    type Output = BorrowedValue<'a>;
+    type Mip = &'a [u8];
+
+    fn null(&mut self) -> Result<BorrowedValue<'a>, Error> {
+        Ok(BorrowedValue::Null)
+    }
+
+    fn leaf(
+        &mut self,
+        main: &str,
+        args: Vec<&str>,
+        rdr: &mut Content<'a>,
+        readers: &TypeReaders,
+    ) -> Result<BorrowedValue<'a>, Error> {
+        if main != <String as Parse>::READER {
+            return read_leaf(main, args, rdr, readers).map(BorrowedValue::from_value);
+        }
+        let len = read_7bit_encoded_int(rdr)? as usize;
+        limit("string length", len, readers.options.max_string_len)?;
+        let bytes = rdr.take(len)?;
+        match str::from_utf8(bytes) {
+            Ok(s) => Ok(BorrowedValue::String(Cow::Borrowed(s))),
+            Err(_) if readers.options.lossy_strings => {
+                Ok(BorrowedValue::String(String::from_utf8_lossy(bytes)))
+            }
+            Err(_) => Err(Error::InvalidString(
+                String::from_utf8(bytes.to_vec()).unwrap_err(),
+            )),
+        }
+    }
+
+    fn array(&mut self, items: Vec<BorrowedValue<'a>>) -> Result<BorrowedValue<'a>, Error> {
+        Ok(BorrowedValue::Array(items))
+    }
+
+    fn dictionary(
+        &mut self,
+        entries: Vec<(BorrowedValue<'a>, BorrowedValue<'a>)>,
+    ) -> Result<BorrowedValue<'a>, Error> {
+        Ok(BorrowedValue::Dictionary(entries))
+    }
+
+    fn nullable(&mut self, value: Option<BorrowedValue<'a>>) -> Result<BorrowedValue<'a>, Error> {
+        Ok(BorrowedValue::Nullable(value.map(Box::new)))
+    }
+
+    fn mip(
+        &mut self,
+        _level: usize,
+        size: usize,
+        rdr: &mut Content<'a>,
+        _readers: &TypeReaders,
+    ) -> Result<&'a [u8], Error> {
+        rdr.take(size)
+    }
+
+    fn texture(
+        &mut self,
+        format: SurfaceFormat,
+        width: usize,
+        height: usize,
+        mip_data: Vec<&'a [u8]>,
+    ) -> Result<BorrowedValue<'a>, Error> {
+        Ok(BorrowedValue::Texture(BorrowedTexture {
+            format,
+            width,
+            height,
+            mip_data,
+        }))
+    }
+}
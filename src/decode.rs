@@ -0,0 +1,501 @@
+//! Conversion of texture data in any `SurfaceFormat` to 8-bit, 16-bit or
+//! floating point RGBA.
+
+#[cfg(feature = "image")]
+use crate::mipmap::{self, MipFilter};
+use crate::{dxt, Error, SurfaceFormat, Texture2d};
+use std::convert::TryInto;
+use std::io::{self, Write};
+use std::slice::{ChunksExact, ChunksExactMut};
+#[cfg(feature = "image")]
+use std::{io::Error as IoError, path::Path};
+
+/// A mip level decoded to 8-bit RGBA, row by row from the top left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rgba8Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Rgba8Image {
+    /// Convert colours from sRGB to linear space, in place. Dark colours lose
+    /// precision at 8 bits; prefer `RgbaF32Image` where that matters.
+    pub fn srgb_to_linear(&mut self) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            for c in &mut pixel[..3] {
+                *c = unorm(srgb_to_linear(*c as f32 / 255.0));
+            }
+        }
+    }
+
+    /// Convert colours from linear to sRGB space, in place.
+    pub fn linear_to_srgb(&mut self) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            for c in &mut pixel[..3] {
+                *c = unorm(linear_to_srgb(*c as f32 / 255.0));
+            }
+        }
+    }
+
+    /// Multiply each pixel's colour by its alpha, as XNA does when building
+    /// textures by default.
+    pub fn premultiply(&mut self) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            let alpha = pixel[3] as u32;
+            for c in &mut pixel[..3] {
+                *c = ((*c as u32 * alpha + 127) / 255) as u8;
+            }
+        }
+    }
+
+    /// Divide each pixel's colour by its alpha, undoing `premultiply` as far
+    /// as the lost precision allows. Fully transparent pixels are left black.
+    pub fn unpremultiply(&mut self) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            let alpha = pixel[3] as u32;
+            if alpha == 0 {
+                continue;
+            }
+            for c in &mut pixel[..3] {
+                *c = ((*c as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+            }
+        }
+    }
+}
+
+/// A mip level decoded to floating point RGBA, row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaF32Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<f32>,
+}
+
+impl RgbaF32Image {
+    /// Convert colours from sRGB to linear space, in place.
+    pub fn srgb_to_linear(&mut self) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            for c in &mut pixel[..3] {
+                *c = srgb_to_linear(*c);
+            }
+        }
+    }
+
+    /// Convert colours from linear to sRGB space, in place.
+    pub fn linear_to_srgb(&mut self) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            for c in &mut pixel[..3] {
+                *c = linear_to_srgb(*c);
+            }
+        }
+    }
+
+    /// Write the image as a Radiance HDR (`.hdr`) file. Alpha is dropped and
+    /// negative values are clamped to 0.
+    pub fn write_hdr(&self, wtr: &mut dyn Write) -> io::Result<()> {
+        write!(
+            wtr,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.height, self.width
+        )?;
+        // Scanlines are written flat, which readers tell apart from run
+        // length encoded ones by their first pixel.
+        let mut line = Vec::with_capacity(self.width * 4);
+        for row in self.rows() {
+            line.clear();
+            for pixel in row.chunks_exact(4) {
+                line.extend_from_slice(&rgbe(pixel[0], pixel[1], pixel[2]));
+            }
+            wtr.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+// The standard sRGB transfer functions. Negative values are mirrored.
+fn srgb_to_linear(c: f32) -> f32 {
+    let v = c.abs();
+    let linear = if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    };
+    linear.copysign(c)
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let v = c.abs();
+    let srgb = if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    srgb.copysign(c)
+}
+
+// A colour as a shared exponent and a mantissa for each channel.
+fn rgbe(r: f32, g: f32, b: f32) -> [u8; 4] {
+    // Anything too large for the format becomes its largest value.
+    let clamp = |v: f32| if v.is_nan() { 0.0 } else { v.clamp(0.0, 1e38) };
+    let (r, g, b) = (clamp(r), clamp(g), clamp(b));
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0; 4];
+    }
+    // max = m * 2^exponent, with m in [0.5, 1).
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max / 2f32.powi(exponent) >= 1.0 {
+        exponent += 1;
+    }
+    let scale = 256.0 / 2f32.powi(exponent);
+    [
+        (r * scale).min(255.0) as u8,
+        (g * scale).min(255.0) as u8,
+        (b * scale).min(255.0) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+/// A mip level decoded to 16-bit RGBA, row by row from the top left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rgba16Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u16>,
+}
+
+macro_rules! pixel_access {
+    ($image:ty, $channel:ty) => {
+        impl $image {
+            /// The RGBA channels of the pixel at `x`, `y`, if it's in the
+            /// image.
+            pub fn get_pixel(&self, x: usize, y: usize) -> Option<&[$channel; 4]> {
+                let start = self.offset(x, y)?;
+                self.pixels.get(start..start + 4)?.try_into().ok()
+            }
+
+            pub fn get_pixel_mut(&mut self, x: usize, y: usize) -> Option<&mut [$channel; 4]> {
+                let start = self.offset(x, y)?;
+                self.pixels.get_mut(start..start + 4)?.try_into().ok()
+            }
+
+            /// Each row of pixels from the top, as `width * 4` channels.
+            pub fn rows(&self) -> ChunksExact<'_, $channel> {
+                self.pixels.chunks_exact(self.width.max(1) * 4)
+            }
+
+            pub fn rows_mut(&mut self) -> ChunksExactMut<'_, $channel> {
+                self.pixels.chunks_exact_mut(self.width.max(1) * 4)
+            }
+
+            fn offset(&self, x: usize, y: usize) -> Option<usize> {
+                if x < self.width && y < self.height {
+                    Some((y * self.width + x) * 4)
+                } else {
+                    None
+                }
+            }
+        }
+    };
+}
+
+pixel_access!(Rgba8Image, u8);
+pixel_access!(Rgba16Image, u16);
+pixel_access!(RgbaF32Image, f32);
+
+impl Texture2d {
+    /// Decode mip level `mip` to 8-bit RGBA. Signed and floating point
+    /// formats are clamped to `[0, 1]`; channels a format lacks are 0, with
+    /// alpha 255 (except for `Alpha8`, which has no colour). sRGB formats
+    /// stay in sRGB space.
+    pub fn decode(&self, mip: usize) -> Result<Rgba8Image, Error> {
+        let (data, width, height) = self.mip_level(mip)?;
+        let pixels = match self.format.without_srgb() {
+            SurfaceFormat::Dxt1 | SurfaceFormat::Dxt3 | SurfaceFormat::Dxt5 => {
+                dxt::decode(self.format, data, width, height)?
+            }
+            SurfaceFormat::Color => data.to_vec(),
+            format => {
+                let size = format.block_size();
+                let mut pixels = Vec::with_capacity(width * height * 4);
+                for pixel in data.chunks_exact(size) {
+                    pixels.extend_from_slice(&rgba8(format, pixel));
+                }
+                pixels
+            }
+        };
+        Ok(Rgba8Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Decode mip level `mip` to 16-bit RGBA, keeping the full precision of
+    /// `Rg32`, `Rgba64` and `Rgba1010102` surfaces. Other formats are decoded
+    /// as by `decode` and widened.
+    pub fn decode_rgba16(&self, mip: usize) -> Result<Rgba16Image, Error> {
+        let (data, width, height) = self.mip_level(mip)?;
+        let pixels = match self.format {
+            SurfaceFormat::Rg32 | SurfaceFormat::Rgba64 | SurfaceFormat::Rgba1010102 => {
+                let size = self.format.block_size();
+                let mut pixels = Vec::with_capacity(width * height * 4);
+                for pixel in data.chunks_exact(size) {
+                    pixels.extend_from_slice(&rgba16(self.format, pixel));
+                }
+                pixels
+            }
+            _ => {
+                let pixels = self.decode(mip)?.pixels;
+                pixels.into_iter().map(|c| c as u16 * 257).collect()
+            }
+        };
+        Ok(Rgba16Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Decode mip level `mip` to floating point RGBA, keeping the range of
+    /// `Single`, `Vector2`, `Vector4`, `HdrBlendable` and half precision
+    /// surfaces. Other formats are decoded as by `decode_rgba16` and scaled
+    /// to `[0, 1]`, with sRGB formats converted to linear.
+    pub fn decode_f32(&self, mip: usize) -> Result<RgbaF32Image, Error> {
+        let (data, width, height) = self.mip_level(mip)?;
+        let pixels = match self.format {
+            SurfaceFormat::Single
+            | SurfaceFormat::Vector2
+            | SurfaceFormat::Vector4
+            | SurfaceFormat::HalfSingle
+            | SurfaceFormat::HalfVector2
+            | SurfaceFormat::HalfVector4
+            | SurfaceFormat::HdrBlendable => {
+                let size = self.format.block_size();
+                let mut pixels = Vec::with_capacity(width * height * 4);
+                for pixel in data.chunks_exact(size) {
+                    pixels.extend_from_slice(&rgba_f32(self.format, pixel));
+                }
+                pixels
+            }
+            _ => {
+                let pixels = self.decode_rgba16(mip)?.pixels;
+                pixels.into_iter().map(|c| c as f32 / 65535.0).collect()
+            }
+        };
+        let mut decoded = RgbaF32Image {
+            width,
+            height,
+            pixels,
+        };
+        if self.format.is_srgb() {
+            decoded.srgb_to_linear();
+        }
+        Ok(decoded)
+    }
+
+    /// Re-encode every mip level as `Dxt1`, `Dxt3` or `Dxt5`, decoding it
+    /// to 8-bit RGBA first as by `decode`.
+    pub fn compress(&self, format: SurfaceFormat) -> Result<Texture2d, Error> {
+        let mip_data = (0..self.mip_data.len())
+            .map(|mip| {
+                let decoded = self.decode(mip)?;
+                dxt::encode(format, &decoded.pixels, decoded.width, decoded.height)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Texture2d {
+            format,
+            width: self.width,
+            height: self.height,
+            mip_data,
+        })
+    }
+
+    // The data and dimensions of a mip level, checked to be long enough.
+    fn mip_level(&self, mip: usize) -> Result<(&[u8], usize, usize), Error> {
+        let data = self.mip_data.get(mip).ok_or(Error::MissingMipLevel(mip))?;
+        let shift = |n: usize| n.checked_shr(mip as u32).unwrap_or(0).max(1);
+        let (width, height) = (shift(self.width), shift(self.height));
+        let expected = self.format.mip_byte_len(width, height);
+        if data.len() < expected {
+            return Err(Error::Truncated {
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok((&data[..expected], width, height))
+    }
+}
+
+#[cfg(feature = "image")]
+impl Texture2d {
+    /// A `Color` texture holding `image`, followed by box filtered mip levels
+    /// down to 1x1 if `mips` is set. Use `compress` for a DXT texture, or
+    /// `generate_mips` for other filters.
+    ///
+    /// With `premultiply`, colours are multiplied by alpha (before filtering)
+    /// to match the XNA pipeline's default.
+    pub fn from_image(image: &image::DynamicImage, mips: bool, premultiply: bool) -> Texture2d {
+        let image = image.to_rgba8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut image = Rgba8Image {
+            width,
+            height,
+            pixels: image.into_raw(),
+        };
+        if premultiply {
+            image.premultiply();
+        }
+        let pixels = image.pixels;
+        let mut mip_data = vec![];
+        if mips && width > 0 && height > 0 {
+            mip_data = mipmap::chain(&pixels, width, height, MipFilter::Box);
+        }
+        mip_data.insert(0, pixels);
+        Texture2d {
+            format: SurfaceFormat::Color,
+            width,
+            height,
+            mip_data,
+        }
+    }
+
+    /// Decode mip level `mip` as by `decode`, into an `image` crate buffer.
+    /// Textures built with premultiplied alpha look dark around their edges
+    /// in most viewers unless `unpremultiply` is set.
+    pub fn to_image(&self, mip: usize, unpremultiply: bool) -> Result<image::RgbaImage, Error> {
+        let mut decoded = self.decode(mip)?;
+        if unpremultiply {
+            decoded.unpremultiply();
+        }
+        let (width, height) = (decoded.width as u32, decoded.height as u32);
+        Ok(image::RgbaImage::from_raw(width, height, decoded.pixels).unwrap())
+    }
+
+    /// Decode mip level `mip` as by `to_image` and save it as a PNG.
+    pub fn save_png(
+        &self,
+        mip: usize,
+        path: impl AsRef<Path>,
+        unpremultiply: bool,
+    ) -> Result<(), Error> {
+        self.to_image(mip, unpremultiply)?
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|e| match e {
+                image::ImageError::IoError(e) => e.into(),
+                e => IoError::other(e).into(),
+            })
+    }
+}
+
+fn rgba8(format: SurfaceFormat, p: &[u8]) -> [u8; 4] {
+    match format {
+        SurfaceFormat::Bgr565 => {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [bits(v, 11, 5), bits(v, 5, 6), bits(v, 0, 5), 0xFF]
+        }
+        SurfaceFormat::Bgra5551 => {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [bits(v, 10, 5), bits(v, 5, 5), bits(v, 0, 5), bits(v, 15, 1)]
+        }
+        SurfaceFormat::Bgra4444 => {
+            let v = u16::from_le_bytes([p[0], p[1]]);
+            [bits(v, 8, 4), bits(v, 4, 4), bits(v, 0, 4), bits(v, 12, 4)]
+        }
+        SurfaceFormat::NormalizedByte2 => [snorm(p[0]), snorm(p[1]), 0, 0xFF],
+        SurfaceFormat::NormalizedByte4 => [snorm(p[0]), snorm(p[1]), snorm(p[2]), snorm(p[3])],
+        SurfaceFormat::Rgba1010102 => {
+            let v = u32::from_le_bytes(p.try_into().unwrap());
+            [
+                bits(v, 0, 10),
+                bits(v, 10, 10),
+                bits(v, 20, 10),
+                bits(v, 30, 2),
+            ]
+        }
+        SurfaceFormat::Rg32 | SurfaceFormat::Rgba64 => {
+            rgba16(format, p).map(|c| ((c as u32 + 128) / 257) as u8)
+        }
+        SurfaceFormat::Alpha8 => [0, 0, 0, p[0]],
+        SurfaceFormat::Bgr32 => [p[2], p[1], p[0], 0xFF],
+        SurfaceFormat::Bgra32 => [p[2], p[1], p[0], p[3]],
+        SurfaceFormat::Single
+        | SurfaceFormat::Vector2
+        | SurfaceFormat::Vector4
+        | SurfaceFormat::HalfSingle
+        | SurfaceFormat::HalfVector2
+        | SurfaceFormat::HalfVector4
+        | SurfaceFormat::HdrBlendable => rgba_f32(format, p).map(unorm),
+        _ => unreachable!(),
+    }
+}
+
+fn rgba16(format: SurfaceFormat, p: &[u8]) -> [u16; 4] {
+    let channel = |i: usize| u16::from_le_bytes([p[i * 2], p[i * 2 + 1]]);
+    match format {
+        SurfaceFormat::Rg32 => [channel(0), channel(1), 0, 0xFFFF],
+        SurfaceFormat::Rgba64 => [channel(0), channel(1), channel(2), channel(3)],
+        SurfaceFormat::Rgba1010102 => {
+            let v = u32::from_le_bytes(p.try_into().unwrap());
+            let bits = |shift: u32, width: u32| {
+                let max = (1 << width) - 1;
+                (((v >> shift & max) * 0xFFFF + max / 2) / max) as u16
+            };
+            [bits(0, 10), bits(10, 10), bits(20, 10), bits(30, 2)]
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn rgba_f32(format: SurfaceFormat, p: &[u8]) -> [f32; 4] {
+    match format {
+        SurfaceFormat::Single => [f32_at(p, 0), 0.0, 0.0, 1.0],
+        SurfaceFormat::Vector2 => [f32_at(p, 0), f32_at(p, 1), 0.0, 1.0],
+        SurfaceFormat::Vector4 => [f32_at(p, 0), f32_at(p, 1), f32_at(p, 2), f32_at(p, 3)],
+        SurfaceFormat::HalfSingle => [f16_at(p, 0), 0.0, 0.0, 1.0],
+        SurfaceFormat::HalfVector2 => [f16_at(p, 0), f16_at(p, 1), 0.0, 1.0],
+        // HdrBlendable is stored as a HalfVector4 on Windows.
+        SurfaceFormat::HalfVector4 | SurfaceFormat::HdrBlendable => {
+            [f16_at(p, 0), f16_at(p, 1), f16_at(p, 2), f16_at(p, 3)]
+        }
+        _ => unreachable!(),
+    }
+}
+
+// Scales the `width`-bit channel at `shift` to 8 bits, rounding to nearest.
+// For 5 and 6 bit channels this is the same as repeating the top bits, as
+// DXT endpoints are expanded.
+fn bits<T: Into<u32>>(v: T, shift: u32, width: u32) -> u8 {
+    let max = (1 << width) - 1;
+    (((v.into() >> shift & max) * 255 + max / 2) / max) as u8
+}
+
+fn snorm(v: u8) -> u8 {
+    unorm((v as i8).max(-127) as f32 / 127.0 * 0.5 + 0.5)
+}
+
+fn unorm(v: f32) -> u8 {
+    // NaN becomes 0.
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn f32_at(p: &[u8], i: usize) -> f32 {
+    f32::from_le_bytes(p[i * 4..i * 4 + 4].try_into().unwrap())
+}
+
+fn f16_at(p: &[u8], i: usize) -> f32 {
+    f16_to_f32(u16::from_le_bytes([p[i * 2], p[i * 2 + 1]]))
+}
+
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (h >> 10 & 0x1F) as i32;
+    let mantissa = (h & 0x3FF) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1F if mantissa == 0.0 => f32::INFINITY,
+        0x1F => f32::NAN,
+        e => (1.0 + mantissa / 1024.0) * 2f32.powi(e - 15),
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{lz4, BufferPool, Error, WindowSize};
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Take};
+
+// LZ4 matches can reach at most 64KB back into the decompressed output.
+const LZ4_HISTORY: usize = 0x10000;
+
+enum Decoder {
+    Lzx(Box<lzxd::Lzxd>),
+    Lz4,
+}
+
+/// Decompresses the body of a compressed XNB on demand, one frame (or LZ4
+/// sequence) at a time, so the asset can be parsed without first holding the
+/// whole decompressed file in memory.
+pub struct DecompressReader<'a> {
+    decoder: Decoder,
+    state: State<'a>,
+    pool: Option<BufferPool>,
+}
+
+struct State<'a> {
+    rdr: Take<&'a mut dyn Read>,
+    remaining: usize,
+    buf: Vec<u8>,
+    pos: usize,
+    // Compressed LZX blocks, kept to save allocating one per frame.
+    block: Vec<u8>,
+}
+
+impl<'a> DecompressReader<'a> {
+    pub fn lzx(
+        rdr: &'a mut dyn Read,
+        window_size: WindowSize,
+        compressed_size: usize,
+        decompressed_size: usize,
+    ) -> DecompressReader<'a> {
+        DecompressReader {
+            decoder: Decoder::Lzx(Box::new(lzxd::Lzxd::new(window_size))),
+            state: State {
+                rdr: rdr.take(compressed_size as u64),
+                remaining: decompressed_size,
+                buf: vec![],
+                pos: 0,
+                block: vec![],
+            },
+            pool: None,
+        }
+    }
+
+    pub fn lz4(
+        rdr: &'a mut dyn Read,
+        compressed_size: usize,
+        decompressed_size: usize,
+    ) -> DecompressReader<'a> {
+        DecompressReader {
+            decoder: Decoder::Lz4,
+            state: State {
+                rdr: rdr.take(compressed_size as u64),
+                remaining: decompressed_size,
+                buf: vec![],
+                pos: 0,
+                block: vec![],
+            },
+            pool: None,
+        }
+    }
+}
+
+impl<'a> DecompressReader<'a> {
+    /// Take working buffers from `pool`, returning them when dropped.
+    pub fn with_pool(mut self, pool: &BufferPool) -> DecompressReader<'a> {
+        self.state.buf = pool.get();
+        self.state.block = pool.get();
+        self.pool = Some(pool.clone());
+        self
+    }
+}
+
+impl<'a> Drop for DecompressReader<'a> {
+    fn drop(&mut self) {
+        if let Some(ref pool) = self.pool {
+            pool.put(std::mem::take(&mut self.state.buf));
+            pool.put(std::mem::take(&mut self.state.block));
+        }
+    }
+}
+
+impl<'a> State<'a> {
+    fn next_lzx_frame(&mut self, lzxd: &mut lzxd::Lzxd) -> Result<(), Error> {
+        self.buf.clear();
+        self.pos = 0;
+        let (frame_size, block_size) = match read_lzx_frame_header(&mut self.rdr)? {
+            Some(sizes) => sizes,
+            None => {
+                self.remaining = 0;
+                return Ok(());
+            }
+        };
+        self.block.resize(block_size, 0);
+        self.rdr.read_exact(&mut self.block)?;
+        let decompressed = lzxd.decompress_next(&self.block)?;
+        let len = frame_size.min(decompressed.len()).min(self.remaining);
+        self.buf.extend_from_slice(&decompressed[..len]);
+        self.remaining -= len;
+        Ok(())
+    }
+
+    fn next_lz4_sequence(&mut self) -> Result<(), Error> {
+        // Keep enough decompressed history around for later matches to refer to.
+        if self.buf.len() > LZ4_HISTORY * 2 {
+            let excess = self.buf.len() - LZ4_HISTORY;
+            self.buf.drain(..excess);
+            self.pos -= excess;
+        }
+
+        if self.rdr.limit() == 0 {
+            return Err(lz4::DecodeFailed::SizeMismatch {
+                expected: self.remaining,
+                actual: 0,
+            }
+            .into());
+        }
+        let token = self.rdr.read_u8()?;
+
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_lz4_length(&mut self.rdr)?;
+        }
+        if literals > self.remaining {
+            return Err(lz4::DecodeFailed::SizeMismatch {
+                expected: self.remaining,
+                actual: literals,
+            }
+            .into());
+        }
+        let start = self.buf.len();
+        self.buf.resize(start + literals, 0);
+        self.rdr.read_exact(&mut self.buf[start..])?;
+        self.remaining -= literals;
+
+        // The last sequence of a block only contains literals.
+        if self.rdr.limit() == 0 {
+            return Ok(());
+        }
+
+        let offset = self.rdr.read_u16::<LittleEndian>()? as usize;
+        if offset == 0 || offset > self.buf.len() {
+            return Err(lz4::DecodeFailed::InvalidOffset(offset).into());
+        }
+        let mut match_len = (token & 0x0F) as usize;
+        if match_len == 15 {
+            match_len += read_lz4_length(&mut self.rdr)?;
+        }
+        match_len += 4;
+        if match_len > self.remaining {
+            return Err(lz4::DecodeFailed::SizeMismatch {
+                expected: self.remaining,
+                actual: match_len,
+            }
+            .into());
+        }
+        let start = self.buf.len() - offset;
+        for i in 0..match_len {
+            let b = self.buf[start + i];
+            self.buf.push(b);
+        }
+        self.remaining -= match_len;
+        Ok(())
+    }
+}
+
+impl<'a> Read for DecompressReader<'a> {
+    fn read(&mut self, out: &mut [u8]) -> IoResult<usize> {
+        let state = &mut self.state;
+        while state.pos == state.buf.len() {
+            if state.remaining == 0 {
+                return Ok(0);
+            }
+            let result = match self.decoder {
+                Decoder::Lzx(ref mut lzxd) => state.next_lzx_frame(lzxd),
+                Decoder::Lz4 => state.next_lz4_sequence(),
+            };
+            // Other errors are carried through as they are, to be taken out
+            // again by `From<IoError> for Error`.
+            result.map_err(|e| match e {
+                Error::Io(e) => e,
+                e => IoError::new(ErrorKind::InvalidData, e),
+            })?;
+        }
+        let len = out.len().min(state.buf.len() - state.pos);
+        out[..len].copy_from_slice(&state.buf[state.pos..state.pos + len]);
+        state.pos += len;
+        Ok(len)
+    }
+}
+
+fn read_lz4_length(rdr: &mut dyn Read) -> Result<usize, Error> {
+    let mut len = 0usize;
+    loop {
+        let b = rdr.read_u8()?;
+        len = len
+            .checked_add(b as usize)
+            .ok_or(lz4::DecodeFailed::UnexpectedEnd)?;
+        if b != 0xFF {
+            return Ok(len);
+        }
+    }
+}
+
+// Each LZX frame is prefixed by the size of its compressed block. Frames that
+// don't decompress to the default 32KB are marked with 0xFF and carry their
+// decompressed size first.
+fn read_lzx_frame_header(rdr: &mut dyn Read) -> Result<Option<(usize, usize)>, Error> {
+    let hi = rdr.read_u8()?;
+    let lo = rdr.read_u8()?;
+    let (frame_size, block_size) = if hi == 0xFF {
+        let frame_size = (lo as usize) << 8 | rdr.read_u8()? as usize;
+        let block_size = rdr.read_u16::<BigEndian>()? as usize;
+        (frame_size, block_size)
+    } else {
+        (0x8000, (hi as usize) << 8 | lo as usize)
+    };
+    if frame_size == 0 || block_size == 0 {
+        return Ok(None);
+    }
+    Ok(Some((frame_size, block_size)))
+}
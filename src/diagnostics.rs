@@ -0,0 +1,77 @@
+//! A report of everything questionable about an XNB, for triaging many files
+//! at once rather than stopping at the first error.
+
+use crate::value::can_read;
+use crate::{
+    read_7bit_encoded_int, read_content, Error, ParseMode, ParseOptions, Source, TypeReaders,
+    Value, XNB,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read};
+
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    /// Readers in the type reader table that can't be read, either by this
+    /// crate or by any registered reader.
+    pub unknown_readers: Vec<String>,
+    /// Readers with a version other than 0, which is the only one XNA writes.
+    pub version_mismatches: Vec<(String, i32)>,
+    /// Bytes left unread after the primary asset and shared resources.
+    pub trailing_bytes: u64,
+    /// Any other deviations from the format that were tolerated.
+    pub warnings: Vec<Error>,
+    /// The error that stopped parsing, if any.
+    pub error: Option<Error>,
+}
+
+fn read_reader_table(
+    rdr: &mut dyn Read,
+    options: &ParseOptions,
+) -> Result<Vec<(String, i32)>, Error> {
+    let readers = TypeReaders {
+        options: options.clone(),
+        ..TypeReaders::default()
+    };
+    let count = read_7bit_encoded_int(rdr)?;
+    (0..count)
+        .map(|_| Ok((readers.read_string(rdr)?, rdr.read_i32::<LittleEndian>()?)))
+        .collect()
+}
+
+impl Diagnostics {
+    /// Examine an XNB, parsing it leniently with `options`.
+    pub fn from_buffer(rdr: &mut dyn Read, options: &ParseOptions) -> Diagnostics {
+        let mut diagnostics = Diagnostics::default();
+        if let Err(e) = diagnostics.examine(rdr, options) {
+            diagnostics.error = Some(e);
+        }
+        diagnostics
+    }
+
+    fn examine(&mut self, rdr: &mut dyn Read, options: &ParseOptions) -> Result<(), Error> {
+        let content = read_content(rdr)?;
+        for (name, version) in read_reader_table(&mut &content[..], options)? {
+            let main = name.split('`').next().unwrap().split(',').next().unwrap();
+            if !can_read(main, &options.registry) {
+                self.unknown_readers.push(name.clone());
+            }
+            if version != 0 {
+                self.version_mismatches.push((name, version));
+            }
+        }
+
+        let options = options.clone().mode(ParseMode::Lenient);
+        let xnb = XNB::<Value>::new(
+            Source::Read(&mut Cursor::new(&content)),
+            content.len() as u64,
+            &options,
+        )?;
+        for warning in xnb.warnings {
+            match warning {
+                Error::TrailingData(len) => self.trailing_bytes = len,
+                warning => self.warnings.push(warning),
+            }
+        }
+        Ok(())
+    }
+}
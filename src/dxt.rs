@@ -0,0 +1,387 @@
+//! Decoding of DXT (S3TC) compressed textures into RGBA8 pixels, rounding
+//! interpolated colours the same way as libsquish, and compressing them back.
+//!
+//! With the `simd` feature, blocks are expanded with SSSE3 byte shuffles on
+//! x86 processors that support them, falling back to the portable decoder
+//! elsewhere.
+
+use crate::{Error, SurfaceFormat};
+use std::convert::TryInto;
+
+// Writes the 16 pixels of a block, given its 4 colour palette and 2-bit
+// indices, as 4 rows of 4 RGBA8 pixels.
+type Expand = fn(&[u8; 16], u32, &mut [u8; 64]);
+
+/// Decode the top mip level of a `Dxt1`, `Dxt3` or `Dxt5` texture (or an
+/// sRGB one) into `width * height` RGBA8 pixels.
+pub fn decode(
+    format: SurfaceFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, Error> {
+    decode_with(format, data, width, height, expander())
+}
+
+/// Like `decode`, but never uses SIMD. Mostly useful for comparing against.
+pub fn decode_scalar(
+    format: SurfaceFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, Error> {
+    decode_with(format, data, width, height, expand_scalar)
+}
+
+fn decode_with(
+    format: SurfaceFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+    expand: Expand,
+) -> Result<Vec<u8>, Error> {
+    if !format.is_compressed() {
+        return Err(Error::UnsupportedSurfaceFormat(format));
+    }
+    let format = format.without_srgb();
+    let block_size = format.block_size();
+    if width == 0 || height == 0 {
+        return Ok(vec![]);
+    }
+    let expected = format.mip_byte_len(width, height);
+    if data.len() < expected {
+        return Err(Error::Truncated {
+            expected,
+            actual: data.len(),
+        });
+    }
+
+    let stride = width * 4;
+    let mut pixels = vec![0; stride * height];
+    let mut block = [0; 64];
+    let block_rows = data[..expected].chunks_exact(width.div_ceil(4) * block_size);
+    for (rows, src) in pixels.chunks_mut(stride * 4).zip(block_rows) {
+        for (x, src) in src.chunks_exact(block_size).enumerate() {
+            let (alpha, color) = src.split_at(block_size - 8);
+            let palette = palette(color, format == SurfaceFormat::Dxt1);
+            let indices = u32::from_le_bytes(color[4..8].try_into().unwrap());
+            expand(&palette, indices, &mut block);
+            match format {
+                SurfaceFormat::Dxt3 => dxt3_alpha(alpha, &mut block),
+                SurfaceFormat::Dxt5 => dxt5_alpha(alpha, &mut block),
+                _ => (),
+            }
+
+            // Blocks on the right and bottom edges can hang over the image.
+            let start = x * 16;
+            let len = (stride - start).min(16);
+            for (row, out) in block.chunks_exact(16).zip(rows.chunks_exact_mut(stride)) {
+                if len == 16 {
+                    out[start..start + 16].copy_from_slice(row);
+                } else {
+                    out[start..start + len].copy_from_slice(&row[..len]);
+                }
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+fn rgb565(c: u16) -> [u8; 4] {
+    let (r, g, b) = ((c >> 11) as u8, (c >> 5 & 0x3F) as u8, (c & 0x1F) as u8);
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2, 0xFF]
+}
+
+fn mix(a: [u8; 4], b: [u8; 4], wa: u16, wb: u16) -> [u8; 4] {
+    let mut c = [0xFF; 4];
+    for i in 0..3 {
+        c[i] = ((a[i] as u16 * wa + b[i] as u16 * wb) / (wa + wb)) as u8;
+    }
+    c
+}
+
+fn palette(color: &[u8], dxt1: bool) -> [u8; 16] {
+    let c0 = u16::from_le_bytes([color[0], color[1]]);
+    let c1 = u16::from_le_bytes([color[2], color[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    // DXT1 blocks with the colours in ascending order have a transparent
+    // fourth colour instead of a second interpolated one.
+    let (c, d) = if c0 > c1 || !dxt1 {
+        (mix(a, b, 2, 1), mix(a, b, 1, 2))
+    } else {
+        (mix(a, b, 1, 1), [0; 4])
+    };
+    let mut palette = [0; 16];
+    for (i, color) in [a, b, c, d].iter().enumerate() {
+        palette[i * 4..i * 4 + 4].copy_from_slice(color);
+    }
+    palette
+}
+
+fn expand_scalar(palette: &[u8; 16], indices: u32, block: &mut [u8; 64]) {
+    for (i, pixel) in block.chunks_exact_mut(4).enumerate() {
+        let index = (indices >> (i * 2) & 3) as usize;
+        pixel.copy_from_slice(&palette[index * 4..index * 4 + 4]);
+    }
+}
+
+fn dxt3_alpha(alpha: &[u8], block: &mut [u8; 64]) {
+    for (i, pixel) in block.chunks_exact_mut(4).enumerate() {
+        pixel[3] = (alpha[i / 2] >> (i % 2 * 4) & 0xF) * 17;
+    }
+}
+
+fn alpha_palette(a0: u8, a1: u8) -> [u8; 8] {
+    let (a0, a1) = (a0 as u16, a1 as u16);
+    let mut values = [0; 8];
+    values[0] = a0 as u8;
+    values[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7u16 {
+            values[i as usize + 1] = ((a0 * (7 - i) + a1 * i) / 7) as u8;
+        }
+    } else {
+        for i in 1..5u16 {
+            values[i as usize + 1] = ((a0 * (5 - i) + a1 * i) / 5) as u8;
+        }
+        values[7] = 0xFF;
+    }
+    values
+}
+
+fn dxt5_alpha(alpha: &[u8], block: &mut [u8; 64]) {
+    let values = alpha_palette(alpha[0], alpha[1]);
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&alpha[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    for (i, pixel) in block.chunks_exact_mut(4).enumerate() {
+        pixel[3] = values[(indices >> (i * 3) & 7) as usize];
+    }
+}
+
+/// Compress `width * height` RGBA8 pixels into `Dxt1`, `Dxt3` or `Dxt5`
+/// blocks. `Dxt1` keeps pixels with alpha below 128 as transparent and makes
+/// the rest opaque.
+pub fn encode(
+    format: SurfaceFormat,
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, Error> {
+    if !format.is_compressed() {
+        return Err(Error::UnsupportedSurfaceFormat(format));
+    }
+    let format = format.without_srgb();
+    let expected = width * height * 4;
+    if pixels.len() < expected {
+        return Err(Error::Truncated {
+            expected,
+            actual: pixels.len(),
+        });
+    }
+    let mut data = Vec::with_capacity(format.mip_byte_len(width, height));
+    for by in (0..height).step_by(4) {
+        for bx in (0..width).step_by(4) {
+            // Blocks hanging over the edge repeat the last row and column.
+            let mut block = [[0; 4]; 16];
+            for (i, pixel) in block.iter_mut().enumerate() {
+                let x = (bx + i % 4).min(width - 1);
+                let y = (by + i / 4).min(height - 1);
+                let start = (y * width + x) * 4;
+                pixel.copy_from_slice(&pixels[start..start + 4]);
+            }
+            match format {
+                SurfaceFormat::Dxt3 => {
+                    for pair in block.chunks_exact(2) {
+                        let alpha = |p: [u8; 4]| (p[3] as u32 * 15 + 127) / 255;
+                        data.push((alpha(pair[0]) | alpha(pair[1]) << 4) as u8);
+                    }
+                }
+                SurfaceFormat::Dxt5 => data.extend_from_slice(&encode_alpha(&block)),
+                _ => (),
+            }
+            data.extend_from_slice(&encode_color(&block, format == SurfaceFormat::Dxt1));
+        }
+    }
+    Ok(data)
+}
+
+fn encode_alpha(block: &[[u8; 4]; 16]) -> [u8; 8] {
+    let alphas = block.iter().map(|p| p[3]);
+    let (min, max) = (alphas.clone().min().unwrap(), alphas.clone().max().unwrap());
+    // Blocks using 0 or 255 may do better with the six value mode, which
+    // has those two as well as the range of the values in between.
+    let inner = alphas.clone().filter(|&a| a != 0 && a != 0xFF);
+    let inner_min = inner.clone().min().unwrap_or(0);
+    let inner_max = inner.max().unwrap_or(0xFF);
+    let (a0, a1, indices) = [(max, min), (inner_min, inner_max)]
+        .iter()
+        .map(|&(a0, a1)| {
+            let values = alpha_palette(a0, a1);
+            let (mut indices, mut error) = (0u64, 0);
+            for (i, a) in alphas.clone().enumerate() {
+                let index = nearest(values.iter().map(|&v| (v as i32 - a as i32).pow(2)));
+                error += (values[index] as i32 - a as i32).pow(2);
+                indices |= (index as u64) << (i * 3);
+            }
+            (a0, a1, indices, error)
+        })
+        .min_by_key(|&(.., error)| error)
+        .map(|(a0, a1, indices, _)| (a0, a1, indices))
+        .unwrap();
+    let mut encoded = [0; 8];
+    encoded[0] = a0;
+    encoded[1] = a1;
+    encoded[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    encoded
+}
+
+fn encode_color(block: &[[u8; 4]; 16], dxt1: bool) -> [u8; 8] {
+    let transparent = |p: &[u8; 4]| dxt1 && p[3] < 128;
+    let opaque = block.iter().filter(|p| !transparent(p)).collect::<Vec<_>>();
+    let (mut c0, mut c1) = match opaque.is_empty() {
+        true => (0, 0),
+        false => endpoints(&opaque),
+    };
+    // DXT1 blocks with transparency need the colours in ascending order,
+    // and otherwise in descending order for four colours.
+    let three_colour = opaque.len() < block.len();
+    if (c0 < c1) != three_colour && c0 != c1 {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+    let mut encoded = [0; 8];
+    encoded[..2].copy_from_slice(&c0.to_le_bytes());
+    encoded[2..4].copy_from_slice(&c1.to_le_bytes());
+    let palette = palette(&encoded, dxt1);
+    // Equal endpoints select the three colour palette too, so go by what
+    // was encoded rather than what was wanted.
+    let usable = if dxt1 && c0 <= c1 { 3 } else { 4 };
+    let mut indices = 0u32;
+    for (i, pixel) in block.iter().enumerate() {
+        let index = if transparent(pixel) {
+            3
+        } else {
+            nearest(
+                palette
+                    .chunks_exact(4)
+                    .take(usable)
+                    .map(|c| (0..3).map(|j| (c[j] as i32 - pixel[j] as i32).pow(2)).sum()),
+            )
+        };
+        indices |= (index as u32) << (i * 2);
+    }
+    encoded[4..].copy_from_slice(&indices.to_le_bytes());
+    encoded
+}
+
+// The colours at either end of the line through the pixels along which they
+// vary most, as RGB565.
+fn endpoints(pixels: &[&[u8; 4]]) -> (u16, u16) {
+    let rgb = |p: &[u8; 4]| [p[0] as f32, p[1] as f32, p[2] as f32];
+    let n = pixels.len() as f32;
+    let mut mean = [0.0; 3];
+    for p in pixels {
+        for (m, c) in mean.iter_mut().zip(&rgb(p)) {
+            *m += c / n;
+        }
+    }
+    let mut covariance = [[0.0f32; 3]; 3];
+    for p in pixels {
+        let d = rgb(p);
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] += (d[i] - mean[i]) * (d[j] - mean[j]);
+            }
+        }
+    }
+    // A few rounds of power iteration find the principal axis.
+    let mut axis = [1.0f32; 3];
+    for _ in 0..8 {
+        let mut next = [0.0; 3];
+        for (n, row) in next.iter_mut().zip(&covariance) {
+            *n = row.iter().zip(&axis).map(|(c, a)| c * a).sum();
+        }
+        let len = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if len < 1e-6 {
+            break;
+        }
+        axis = next.map(|v| v / len);
+    }
+    let project = |p: &&[u8; 4]| {
+        let d = rgb(p);
+        (0..3).map(|i| (d[i] - mean[i]) * axis[i]).sum::<f32>()
+    };
+    let by_projection = |a: &&&[u8; 4], b: &&&[u8; 4]| project(a).total_cmp(&project(b));
+    let min = pixels.iter().min_by(by_projection).unwrap();
+    let max = pixels.iter().max_by(by_projection).unwrap();
+    (rgb565_from(max), rgb565_from(min))
+}
+
+fn rgb565_from(p: &[u8; 4]) -> u16 {
+    let scale = |c: u8, max: u32| (c as u32 * max + 127) / 255;
+    (scale(p[0], 31) << 11 | scale(p[1], 63) << 5 | scale(p[2], 31)) as u16
+}
+
+fn nearest(distances: impl Iterator<Item = i32>) -> usize {
+    distances
+        .enumerate()
+        .min_by_key(|&(_, d)| d)
+        .map_or(0, |(i, _)| i)
+}
+
+#[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+fn expander() -> Expand {
+    if is_x86_feature_detected!("ssse3") {
+        simd::expand
+    } else {
+        expand_scalar
+    }
+}
+
+#[cfg(not(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64"))))]
+fn expander() -> Expand {
+    expand_scalar
+}
+
+#[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+mod simd {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    // For each possible byte of indices, the shuffle that picks the palette
+    // entries for that row of four pixels.
+    static SHUFFLES: [[u8; 16]; 256] = shuffles();
+
+    const fn shuffles() -> [[u8; 16]; 256] {
+        let mut table = [[0; 16]; 256];
+        let mut row = 0;
+        while row < 256 {
+            let mut i = 0;
+            while i < 16 {
+                let index = (row >> (i / 4 * 2)) & 3;
+                table[row][i] = (index * 4 + i % 4) as u8;
+                i += 1;
+            }
+            row += 1;
+        }
+        table
+    }
+
+    pub(super) fn expand(palette: &[u8; 16], indices: u32, block: &mut [u8; 64]) {
+        // Safety: only chosen by `expander` once SSSE3 support is detected.
+        unsafe { expand_ssse3(palette, indices, block) }
+    }
+
+    #[target_feature(enable = "ssse3")]
+    unsafe fn expand_ssse3(palette: &[u8; 16], indices: u32, block: &mut [u8; 64]) {
+        let palette = _mm_loadu_si128(palette.as_ptr() as *const __m128i);
+        for (row, out) in block.chunks_exact_mut(16).enumerate() {
+            let shuffle = &SHUFFLES[(indices >> (row * 8) & 0xFF) as usize];
+            let shuffle = _mm_loadu_si128(shuffle.as_ptr() as *const __m128i);
+            let pixels = _mm_shuffle_epi8(palette, shuffle);
+            _mm_storeu_si128(out.as_mut_ptr() as *mut __m128i, pixels);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use crate::math::read_vector3;
+use crate::shared::read_any_object;
+use crate::{
+    read_7bit_encoded_int, read_object, Error, ExternalReference, Parse, Tag, Texture2d,
+    TextureCube, TypeReaders, Vector3,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::collections::HashMap;
+use std::io::Read;
+
+// The signature of a compiled DirectX 9 effect (fx_2_0), as written by XNA.
+const FXB_SIGNATURE: [u8; 4] = [0xCF, 0x0B, 0xF0, 0xBC];
+const MGFX_SIGNATURE: &[u8] = b"MGFX";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum EffectFormat {
+    /// A DirectX 9 effect compiled by XNA.
+    Fxb,
+    /// A MonoGame effect, with its format version.
+    Mgfx(u8),
+    Unknown,
+}
+
+/// A compiled effect. The bytecode is left as-is for other tools to inspect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Effect {
+    pub bytecode: Vec<u8>,
+}
+
+impl Effect {
+    pub fn format(&self) -> EffectFormat {
+        if self.bytecode.starts_with(&FXB_SIGNATURE) {
+            EffectFormat::Fxb
+        } else if self.bytecode.starts_with(MGFX_SIGNATURE) {
+            let version = self.bytecode.get(MGFX_SIGNATURE.len()).copied();
+            EffectFormat::Mgfx(version.unwrap_or(0))
+        } else {
+            EffectFormat::Unknown
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.bytecode.len()
+    }
+}
+
+impl Parse for Effect {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.EffectReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let size = rdr.read_u32::<LittleEndian>()?;
+        let bytecode = readers.read_data(rdr, size as usize)?;
+        Ok(Effect { bytecode })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CompareFunction {
+    Always,
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater,
+    NotEqual,
+}
+
+impl CompareFunction {
+    fn from(val: u32) -> Result<CompareFunction, Error> {
+        Ok(match val {
+            0 => CompareFunction::Always,
+            1 => CompareFunction::Never,
+            2 => CompareFunction::Less,
+            3 => CompareFunction::LessEqual,
+            4 => CompareFunction::Equal,
+            5 => CompareFunction::GreaterEqual,
+            6 => CompareFunction::Greater,
+            7 => CompareFunction::NotEqual,
+            f => return Err(Error::UnrecognizedCompareFunction(f)),
+        })
+    }
+}
+
+// Effect textures are stored in separate XNBs and referenced by name. An empty
+// name means the effect has no texture.
+fn read_texture_reference<T>(
+    rdr: &mut dyn Read,
+    readers: &TypeReaders,
+) -> Result<Option<ExternalReference<T>>, Error> {
+    let reference = ExternalReference::read(rdr, readers)?;
+    Ok(Some(reference).filter(|r| !r.name.is_empty()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BasicEffect {
+    pub texture: Option<ExternalReference<Texture2d>>,
+    pub diffuse_color: Vector3,
+    pub emissive_color: Vector3,
+    pub specular_color: Vector3,
+    pub specular_power: f32,
+    pub alpha: f32,
+    pub vertex_color_enabled: bool,
+}
+
+impl Parse for BasicEffect {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.BasicEffectReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(BasicEffect {
+            texture: read_texture_reference(rdr, readers)?,
+            diffuse_color: read_vector3(rdr)?,
+            emissive_color: read_vector3(rdr)?,
+            specular_color: read_vector3(rdr)?,
+            specular_power: rdr.read_f32::<LittleEndian>()?,
+            alpha: rdr.read_f32::<LittleEndian>()?,
+            vertex_color_enabled: rdr.read_u8()? != 0,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DualTextureEffect {
+    pub texture: Option<ExternalReference<Texture2d>>,
+    pub texture2: Option<ExternalReference<Texture2d>>,
+    pub diffuse_color: Vector3,
+    pub alpha: f32,
+    pub vertex_color_enabled: bool,
+}
+
+impl Parse for DualTextureEffect {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.DualTextureEffectReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(DualTextureEffect {
+            texture: read_texture_reference(rdr, readers)?,
+            texture2: read_texture_reference(rdr, readers)?,
+            diffuse_color: read_vector3(rdr)?,
+            alpha: rdr.read_f32::<LittleEndian>()?,
+            vertex_color_enabled: rdr.read_u8()? != 0,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AlphaTestEffect {
+    pub texture: Option<ExternalReference<Texture2d>>,
+    pub alpha_function: CompareFunction,
+    pub reference_alpha: u32,
+    pub diffuse_color: Vector3,
+    pub alpha: f32,
+    pub vertex_color_enabled: bool,
+}
+
+impl Parse for AlphaTestEffect {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.AlphaTestEffectReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(AlphaTestEffect {
+            texture: read_texture_reference(rdr, readers)?,
+            alpha_function: CompareFunction::from(rdr.read_u32::<LittleEndian>()?)?,
+            reference_alpha: rdr.read_u32::<LittleEndian>()?,
+            diffuse_color: read_vector3(rdr)?,
+            alpha: rdr.read_f32::<LittleEndian>()?,
+            vertex_color_enabled: rdr.read_u8()? != 0,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EnvironmentMapEffect {
+    pub texture: Option<ExternalReference<Texture2d>>,
+    pub environment_map: Option<ExternalReference<TextureCube>>,
+    pub environment_map_amount: f32,
+    pub environment_map_specular: Vector3,
+    pub fresnel_factor: f32,
+    pub diffuse_color: Vector3,
+    pub emissive_color: Vector3,
+    pub alpha: f32,
+}
+
+impl Parse for EnvironmentMapEffect {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.EnvironmentMapEffectReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(EnvironmentMapEffect {
+            texture: read_texture_reference(rdr, readers)?,
+            environment_map: read_texture_reference(rdr, readers)?,
+            environment_map_amount: rdr.read_f32::<LittleEndian>()?,
+            environment_map_specular: read_vector3(rdr)?,
+            fresnel_factor: rdr.read_f32::<LittleEndian>()?,
+            diffuse_color: read_vector3(rdr)?,
+            emissive_color: read_vector3(rdr)?,
+            alpha: rdr.read_f32::<LittleEndian>()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SkinnedEffect {
+    pub texture: Option<ExternalReference<Texture2d>>,
+    pub weights_per_vertex: u32,
+    pub diffuse_color: Vector3,
+    pub emissive_color: Vector3,
+    pub specular_color: Vector3,
+    pub specular_power: f32,
+    pub alpha: f32,
+}
+
+impl Parse for SkinnedEffect {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.SkinnedEffectReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(SkinnedEffect {
+            texture: read_texture_reference(rdr, readers)?,
+            weights_per_vertex: rdr.read_u32::<LittleEndian>()?,
+            diffuse_color: read_vector3(rdr)?,
+            emissive_color: read_vector3(rdr)?,
+            specular_color: read_vector3(rdr)?,
+            specular_power: rdr.read_f32::<LittleEndian>()?,
+            alpha: rdr.read_f32::<LittleEndian>()?,
+        })
+    }
+}
+
+/// A custom effect along with values for its parameters.
+#[derive(Debug, Clone)]
+pub struct EffectMaterial {
+    pub effect: ExternalReference<Effect>,
+    pub parameters: HashMap<String, Tag>,
+}
+
+impl Parse for EffectMaterial {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.EffectMaterialReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let effect = ExternalReference::read(rdr, readers)?;
+        // The parameters are a Dictionary<string, object>, whose values can be
+        // of any type.
+        let mut parameters = HashMap::new();
+        if read_7bit_encoded_int(rdr)? != 0 {
+            let count = rdr.read_u32::<LittleEndian>()?;
+            for _ in 0..count {
+                let name = read_object(rdr, readers)?;
+                if let Some(value) = read_any_object(rdr, readers)? {
+                    parameters.insert(name, Tag(value));
+                }
+            }
+        }
+        Ok(EffectMaterial { effect, parameters })
+    }
+}
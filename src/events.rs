@@ -0,0 +1,223 @@
+//! Event-based parsing, for streaming through assets too large to hold in
+//! memory, or building representations other than `Value`.
+
+use crate::value::read_leaf;
+use crate::walk::{optional_object, Visitor};
+use crate::{
+    content_reader, read_7bit_encoded_int, read_exactly_into, CountingReader, Error, ParseOptions,
+    ParseStage, Source, SurfaceFormat, TypeReader, TypeReaders, Value,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Read;
+use std::mem;
+
+// The most mip data handed over in one `Data` event.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Something read from an XNB, in file order.
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// The type reader table, before anything else.
+    Readers(&'a [TypeReader]),
+    /// The start of the shared resource at this index. Shared resources
+    /// follow the primary asset.
+    SharedResource(usize),
+    /// The start of an object with its own entry in the reader table, ended
+    /// by `EndObject`.
+    BeginObject {
+        reader: &'a str,
+    },
+    EndObject,
+    /// A missing object or an empty nullable.
+    Null,
+    /// Anything not broken down further: numbers, strings and structures
+    /// other than collections and textures, read whole.
+    Primitive(Value),
+    BeginArray {
+        len: usize,
+    },
+    EndArray,
+    /// Followed by each key and then its value.
+    BeginDictionary {
+        len: usize,
+    },
+    EndDictionary,
+    BeginTexture {
+        format: SurfaceFormat,
+        width: usize,
+        height: usize,
+        mip_count: usize,
+    },
+    /// Followed by the level's data in one or more `Data` events.
+    MipLevel {
+        level: usize,
+        size: usize,
+    },
+    Data(&'a [u8]),
+    EndTexture,
+}
+
+/// Parse an XNB, handing each event to `handler` as soon as it's read. Mip
+/// data is streamed through in chunks; collections are never built up. An
+/// error from `handler` stops parsing.
+///
+/// Returns the deviations from the format that were tolerated, as for
+/// `XNB::warnings`. Shared resource references are left unresolved.
+pub fn parse<F>(
+    rdr: &mut dyn Read,
+    options: &ParseOptions,
+    mut handler: F,
+) -> Result<Vec<Error>, Error>
+where
+    F: FnMut(Event) -> Result<(), Error>,
+{
+    let (mut content, total) = content_reader(rdr)?;
+    let mut readers = TypeReaders {
+        options: options.clone(),
+        ..TypeReaders::default()
+    };
+    let rdr = &mut CountingReader {
+        rdr: Source::Read(&mut *content),
+        position: readers.position.clone(),
+        skip: readers.skip.clone(),
+        total,
+        stage: ParseStage::ReaderTable,
+        options,
+    };
+    rdr.enter(ParseStage::ReaderTable);
+    let num_readers = read_7bit_encoded_int(rdr)?;
+    for _ in 0..num_readers {
+        readers.readers.push(TypeReader {
+            name: readers.read_string(rdr)?,
+            version: rdr.read_i32::<LittleEndian>()?,
+        });
+    }
+    handler(Event::Readers(&readers.readers))?;
+    let num_shared = read_7bit_encoded_int(rdr)?;
+
+    let mut emitter = Emitter {
+        handler: &mut handler,
+        buf: vec![],
+    };
+    rdr.enter(ParseStage::PrimaryAsset);
+    if optional_object(&mut emitter, rdr, &readers)?.is_none() {
+        return Err(Error::UnexpectedNull);
+    }
+    rdr.enter(ParseStage::SharedResources);
+    for index in 0..num_shared as usize {
+        emitter.emit(Event::SharedResource(index))?;
+        if optional_object(&mut emitter, rdr, &readers)?.is_none() {
+            emitter.null()?;
+        }
+    }
+    rdr.enter(ParseStage::Done);
+    Ok(readers.warnings.into_inner())
+}
+
+struct Emitter<'a> {
+    handler: &'a mut dyn FnMut(Event) -> Result<(), Error>,
+    buf: Vec<u8>,
+}
+
+impl<'a> Emitter<'a> {
+    fn emit(&mut self, event: Event) -> Result<(), Error> {
+        (self.handler)(event)
+    }
+}
+
+impl<'a, 'r> Visitor<dyn Read + 'r> for Emitter<'a> {
+    type Output = ();
+    type Mip = ();
+
+    fn null(&mut self) -> Result<(), Error> {
+        self.emit(Event::Null)
+    }
+
+    fn begin_object(&mut self, reader: &str) -> Result<(), Error> {
+        self.emit(Event::BeginObject { reader })
+    }
+
+    fn end_object(&mut self, _: ()) -> Result<(), Error> {
+        self.emit(Event::EndObject)
+    }
+
+    fn leaf(
+        &mut self,
+        main: &str,
+        args: Vec<&str>,
+        rdr: &mut (dyn Read + 'r),
+        readers: &TypeReaders,
+    ) -> Result<(), Error> {
+        let value = read_leaf(main, args, rdr, readers)?;
+        self.emit(Event::Primitive(value))
+    }
+
+    fn begin_array(&mut self, len: usize) -> Result<(), Error> {
+        self.emit(Event::BeginArray { len })
+    }
+
+    fn array(&mut self, _: Vec<()>) -> Result<(), Error> {
+        self.emit(Event::EndArray)
+    }
+
+    fn begin_dictionary(&mut self, len: usize) -> Result<(), Error> {
+        self.emit(Event::BeginDictionary { len })
+    }
+
+    fn dictionary(&mut self, _: Vec<((), ())>) -> Result<(), Error> {
+        self.emit(Event::EndDictionary)
+    }
+
+    fn nullable(&mut self, value: Option<()>) -> Result<(), Error> {
+        match value {
+            Some(()) => Ok(()),
+            None => self.emit(Event::Null),
+        }
+    }
+
+    fn begin_texture(
+        &mut self,
+        format: SurfaceFormat,
+        width: usize,
+        height: usize,
+        mip_count: usize,
+    ) -> Result<(), Error> {
+        self.emit(Event::BeginTexture {
+            format,
+            width,
+            height,
+            mip_count,
+        })
+    }
+
+    fn mip(
+        &mut self,
+        level: usize,
+        size: usize,
+        rdr: &mut (dyn Read + 'r),
+        _readers: &TypeReaders,
+    ) -> Result<(), Error> {
+        self.emit(Event::MipLevel { level, size })?;
+        let mut remaining = size;
+        while remaining > 0 {
+            let len = remaining.min(CHUNK_SIZE);
+            let mut buf = mem::take(&mut self.buf);
+            buf.clear();
+            let chunk = read_exactly_into(rdr, len, buf)?;
+            self.emit(Event::Data(&chunk))?;
+            self.buf = chunk;
+            remaining -= len;
+        }
+        Ok(())
+    }
+
+    fn texture(
+        &mut self,
+        _format: SurfaceFormat,
+        _width: usize,
+        _height: usize,
+        _mips: Vec<()>,
+    ) -> Result<(), Error> {
+        self.emit(Event::EndTexture)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{Error, Parse, TypeReaders};
+use std::fmt;
+use std::io::Read;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// A reference to an asset stored in a separate XNB file, named relative to
+/// the file containing the reference and without an extension.
+pub struct ExternalReference<T> {
+    pub name: String,
+    asset: PhantomData<T>,
+}
+
+impl<T> ExternalReference<T> {
+    pub fn new(name: String) -> ExternalReference<T> {
+        ExternalReference {
+            name,
+            asset: PhantomData,
+        }
+    }
+
+    /// The path of the referenced XNB, given the path of the XNB that
+    /// contains this reference.
+    pub fn resolve(&self, referrer: &Path) -> PathBuf {
+        let mut path = resolve_relative(referrer, &self.name).into_os_string();
+        path.push(".xnb");
+        path.into()
+    }
+
+    pub(crate) fn read(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+    ) -> Result<ExternalReference<T>, Error> {
+        Ok(ExternalReference::new(readers.read_string(rdr)?))
+    }
+}
+
+// Resolve a name stored in an XNB relative to the directory holding that XNB.
+pub(crate) fn resolve_relative(referrer: &Path, name: &str) -> PathBuf {
+    let dir = referrer.parent().unwrap_or_else(|| Path::new(""));
+    // Names use Windows separators.
+    let mut path = dir.to_path_buf();
+    path.extend(name.split(['\\', '/']));
+    path
+}
+
+impl<T> fmt::Debug for ExternalReference<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ExternalReference")
+            .field(&self.name)
+            .finish()
+    }
+}
+
+impl<T> Clone for ExternalReference<T> {
+    fn clone(&self) -> ExternalReference<T> {
+        ExternalReference::new(self.name.clone())
+    }
+}
+
+impl<T> PartialEq for ExternalReference<T> {
+    fn eq(&self, other: &ExternalReference<T>) -> bool {
+        self.name == other.name
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for ExternalReference<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.name.serialize(serializer)
+    }
+}
+
+impl<T> Parse for ExternalReference<T> {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.ExternalReferenceReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        ExternalReference::read(rdr, readers)
+    }
+}
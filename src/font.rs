@@ -0,0 +1,504 @@
+//! Looking up, measuring, laying out and exporting text in a `SpriteFont`.
+
+#[cfg(feature = "image")]
+use crate::Rgba8Image;
+use crate::{xml_escape, Error, Rectangle, SpriteFont, SurfaceFormat, Texture2d};
+#[cfg(feature = "image")]
+use std::{fs::File, io::BufWriter, path::Path};
+use std::{
+    io::{self, Write},
+    mem,
+};
+
+/// A character's place in a `SpriteFont` and how it's spaced.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glyph {
+    pub character: char,
+    /// Where the character is in the font's texture.
+    pub source: Rectangle,
+    /// The offset from the pen position to draw `source` at, in `x` and
+    /// `y`, and the height of the character's cell in `h`.
+    pub cropping: Rectangle,
+    /// Space before the character, which may be negative to overlap the one
+    /// before.
+    pub left_side_bearing: f32,
+    /// How far the character advances the pen, before bearings.
+    pub width: f32,
+    /// Space after the character.
+    pub right_side_bearing: f32,
+}
+
+/// A glyph placed by `SpriteFont::layout`: its `source` rectangle in the
+/// font's texture is drawn with its top left at `x`, `y`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlacedGlyph {
+    pub glyph: Glyph,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// The descriptor formats of AngelCode BMFont.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BmFontFormat {
+    Text,
+    Xml,
+}
+
+/// How `SpriteFont::layout_with` lines up each line of text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// Settings for `SpriteFont::layout_with`. By default text isn't wrapped and
+/// is aligned left, as by `SpriteFont::layout`.
+#[derive(Debug, Clone)]
+pub struct LayoutOptions {
+    pub(crate) max_width: Option<f32>,
+    pub(crate) alignment: Alignment,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> LayoutOptions {
+        LayoutOptions::new()
+    }
+}
+
+impl LayoutOptions {
+    pub fn new() -> LayoutOptions {
+        LayoutOptions {
+            max_width: None,
+            alignment: Alignment::Left,
+        }
+    }
+
+    /// Wrap lines wider than `width` at the last space that keeps them
+    /// within it, or between characters for words that don't fit at all.
+    pub fn max_width(mut self, width: f32) -> LayoutOptions {
+        self.max_width = Some(width);
+        self
+    }
+
+    /// Align lines within `max_width` if set, or else within the widest
+    /// line.
+    pub fn alignment(mut self, alignment: Alignment) -> LayoutOptions {
+        self.alignment = alignment;
+        self
+    }
+}
+
+impl SpriteFont {
+    /// The glyph for `c`, if the font has one.
+    pub fn glyph(&self, c: char) -> Option<Glyph> {
+        let i = self.char_map.iter().position(|&m| m == c)?;
+        let kerning = self.kerning.get(i);
+        Some(Glyph {
+            character: c,
+            source: *self.glyphs.get(i)?,
+            cropping: *self.cropping.get(i)?,
+            left_side_bearing: kerning.map_or(0.0, |k| k.x),
+            width: kerning.map_or(0.0, |k| k.y),
+            right_side_bearing: kerning.map_or(0.0, |k| k.z),
+        })
+    }
+
+    // The glyph drawn for `c`, falling back on the default character.
+    fn glyph_or_default(&self, c: char) -> Option<Glyph> {
+        self.glyph(c).or_else(|| self.glyph(self.default?))
+    }
+
+    /// The width and height `text` takes up, as measured by XNA's
+    /// `SpriteFont.MeasureString`. Characters the font lacks are measured as
+    /// the default character, or skipped if it has none.
+    pub fn measure_string(&self, text: &str) -> (f32, f32) {
+        self.place(text).1
+    }
+
+    /// Where each glyph of `text` is drawn, relative to the top left of the
+    /// text, as by XNA's `SpriteBatch.DrawString`. Characters the font lacks
+    /// are drawn as the default character, or skipped if it has none.
+    pub fn layout(&self, text: &str) -> Vec<PlacedGlyph> {
+        self.place(text).0
+    }
+
+    /// Like `layout`, but wrapping and aligning lines as set in `options`.
+    pub fn layout_with(&self, text: &str, options: &LayoutOptions) -> Vec<PlacedGlyph> {
+        self.place_with(text, options).0
+    }
+
+    /// Like `measure_string`, for text laid out by `layout_with`. The width
+    /// is that of the widest line.
+    pub fn measure_string_with(&self, text: &str, options: &LayoutOptions) -> (f32, f32) {
+        self.place_with(text, options).1
+    }
+
+    fn place_with(&self, text: &str, options: &LayoutOptions) -> (Vec<PlacedGlyph>, (f32, f32)) {
+        let mut lines = vec![];
+        for line in text.split('\n') {
+            match options.max_width {
+                Some(max_width) => self.wrap(line, max_width, &mut lines),
+                None => lines.push(line.to_string()),
+            }
+        }
+        let laid_out: Vec<_> = lines.iter().map(|line| self.place(line)).collect();
+        let widest = laid_out.iter().map(|(_, (w, _))| *w).fold(0.0, f32::max);
+        let bounds = options.max_width.unwrap_or(widest);
+        let line_spacing = self.v_spacing as f32;
+        let last_height = laid_out.last().map_or(line_spacing, |(_, (_, h))| *h);
+        let height = (laid_out.len() - 1) as f32 * line_spacing + last_height;
+
+        let mut placed = vec![];
+        for (i, (glyphs, (width, _))) in laid_out.into_iter().enumerate() {
+            let dx = match options.alignment {
+                Alignment::Left => 0.0,
+                Alignment::Center => (bounds - width) / 2.0,
+                Alignment::Right => bounds - width,
+            };
+            let dy = i as f32 * line_spacing;
+            placed.extend(glyphs.into_iter().map(|p| PlacedGlyph {
+                x: p.x + dx,
+                y: p.y + dy,
+                ..p
+            }));
+        }
+        (placed, (widest, height))
+    }
+
+    // Breaks a line into lines no wider than `max_width`.
+    fn wrap(&self, line: &str, max_width: f32, lines: &mut Vec<String>) {
+        let fits = |text: &str| self.place(text).1 .0 <= max_width;
+        let mut current = String::new();
+        for word in line.split(' ') {
+            let candidate = match current.is_empty() {
+                true => word.to_string(),
+                false => format!("{} {}", current, word),
+            };
+            if fits(&candidate) {
+                current = candidate;
+                continue;
+            }
+            if !current.is_empty() {
+                lines.push(mem::take(&mut current));
+            }
+            for c in word.chars() {
+                current.push(c);
+                if !fits(&current) && current.chars().count() > 1 {
+                    current.pop();
+                    lines.push(mem::replace(&mut current, c.to_string()));
+                }
+            }
+        }
+        lines.push(current);
+    }
+
+    /// Write an AngelCode BMFont descriptor for the font, with its texture as
+    /// the single page in the file `page`. XNA's spacing and bearings are
+    /// folded into each character's offset and advance; the default
+    /// character has no equivalent and is left out.
+    pub fn write_bmfont(
+        &self,
+        wtr: &mut dyn Write,
+        page: &str,
+        format: BmFontFormat,
+    ) -> io::Result<()> {
+        let line_height = self.v_spacing;
+        let (scale_w, scale_h) = (self.texture.width, self.texture.height);
+        let chars: Vec<Glyph> = self
+            .char_map
+            .iter()
+            .filter_map(|&c| self.glyph(c))
+            .collect();
+        match format {
+            BmFontFormat::Text => {
+                writeln!(wtr, "info face=\"\" size={} unicode=1", line_height)?;
+                writeln!(
+                    wtr,
+                    "common lineHeight={} base={} scaleW={} scaleH={} pages=1 packed=0",
+                    line_height, line_height, scale_w, scale_h
+                )?;
+                writeln!(wtr, "page id=0 file=\"{}\"", page)?;
+                writeln!(wtr, "chars count={}", chars.len())?;
+            }
+            BmFontFormat::Xml => {
+                writeln!(wtr, "<?xml version=\"1.0\"?>\n<font>")?;
+                writeln!(
+                    wtr,
+                    "  <info face=\"\" size=\"{}\" unicode=\"1\"/>",
+                    line_height
+                )?;
+                writeln!(
+                    wtr,
+                    "  <common lineHeight=\"{}\" base=\"{}\" scaleW=\"{}\" scaleH=\"{}\" \
+                     pages=\"1\" packed=\"0\"/>",
+                    line_height, line_height, scale_w, scale_h
+                )?;
+                writeln!(
+                    wtr,
+                    "  <pages>\n    <page id=\"0\" file=\"{}\"/>\n  </pages>",
+                    xml_escape(page)
+                )?;
+                writeln!(wtr, "  <chars count=\"{}\">", chars.len())?;
+            }
+        }
+        for glyph in &chars {
+            let bearing = glyph.left_side_bearing;
+            let x_offset = (bearing + glyph.cropping.x as f32).round();
+            let advance = bearing + glyph.width + glyph.right_side_bearing + self.h_spacing;
+            let fields = [
+                ("id", glyph.character as i64),
+                ("x", glyph.source.x as i64),
+                ("y", glyph.source.y as i64),
+                ("width", glyph.source.w as i64),
+                ("height", glyph.source.h as i64),
+                ("xoffset", x_offset as i64),
+                ("yoffset", glyph.cropping.y as i64),
+                ("xadvance", advance.round() as i64),
+                ("page", 0),
+                ("chnl", 15),
+            ];
+            match format {
+                BmFontFormat::Text => {
+                    write!(wtr, "char")?;
+                    for (name, value) in &fields {
+                        write!(wtr, " {}={}", name, value)?;
+                    }
+                }
+                BmFontFormat::Xml => {
+                    write!(wtr, "    <char")?;
+                    for (name, value) in &fields {
+                        write!(wtr, " {}=\"{}\"", name, value)?;
+                    }
+                    write!(wtr, "/>")?;
+                }
+            }
+            writeln!(wtr)?;
+        }
+        if format == BmFontFormat::Xml {
+            writeln!(wtr, "  </chars>\n</font>")?;
+        }
+        Ok(())
+    }
+
+    /// A copy of the font with each glyph trimmed of fully transparent
+    /// edges and packed into a new `Color` atlas (with `padding` pixels
+    /// between glyphs), as small as that allows. Cropping is adjusted so
+    /// text draws the same as before; `Texture2d::compress` can bring the
+    /// atlas back to DXT.
+    pub fn repack(&self, padding: u32) -> Result<SpriteFont, Error> {
+        let atlas = self.texture.decode(0)?;
+        let padding = padding as usize;
+        let bounds = Rectangle {
+            x: 0,
+            y: 0,
+            w: atlas.width as i32,
+            h: atlas.height as i32,
+        };
+        // The opaque part of each glyph, and how far it's moved in from the
+        // glyph's top left.
+        let trimmed: Vec<(Rectangle, i32, i32)> = self
+            .glyphs
+            .iter()
+            .map(|glyph| {
+                let empty = (Rectangle::from((0, 0, 0, 0)), 0, 0);
+                let visible = match glyph.intersection(&bounds) {
+                    Some(visible) => visible,
+                    None => return empty,
+                };
+                let opaque =
+                    |x: i32, y: i32| atlas.get_pixel(x as usize, y as usize).unwrap()[3] > 0;
+                let columns: Vec<i32> = (visible.x..visible.right())
+                    .filter(|&x| (visible.y..visible.bottom()).any(|y| opaque(x, y)))
+                    .collect();
+                let rows: Vec<i32> = (visible.y..visible.bottom())
+                    .filter(|&y| columns.iter().any(|&x| opaque(x, y)))
+                    .collect();
+                match (columns.first(), columns.last(), rows.first(), rows.last()) {
+                    (Some(&left), Some(&right), Some(&top), Some(&bottom)) => {
+                        let rect = Rectangle::from((left, top, right - left + 1, bottom - top + 1));
+                        (rect, left - glyph.x, top - glyph.y)
+                    }
+                    _ => empty,
+                }
+            })
+            .collect();
+
+        // Shelves of glyphs, tallest first, in an atlas about as wide as it
+        // is tall.
+        let area: usize = trimmed
+            .iter()
+            .map(|(r, ..)| (r.w as usize + padding) * (r.h as usize + padding))
+            .sum();
+        let widest = trimmed
+            .iter()
+            .map(|(r, ..)| r.w as usize)
+            .max()
+            .unwrap_or(0);
+        let width = ((area as f64).sqrt().ceil() as usize).max(widest).max(1);
+        let mut order: Vec<usize> = (0..trimmed.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(trimmed[i].0.h));
+        let mut placed = vec![(0, 0); trimmed.len()];
+        let (mut x, mut y, mut shelf) = (0, 0, 0);
+        for &i in &order {
+            let rect = trimmed[i].0;
+            if rect.is_empty() {
+                continue;
+            }
+            if x > 0 && x + rect.w as usize > width {
+                x = 0;
+                y += shelf + padding;
+                shelf = 0;
+            }
+            placed[i] = (x, y);
+            x += rect.w as usize + padding;
+            shelf = shelf.max(rect.h as usize);
+        }
+        let height = (y + shelf).max(1);
+
+        let mut pixels = vec![0; width * height * 4];
+        let mut glyphs = vec![];
+        let mut cropping = self.cropping.clone();
+        for (i, &(rect, dx, dy)) in trimmed.iter().enumerate() {
+            let (px, py) = placed[i];
+            for row in 0..rect.h as usize {
+                let src = ((rect.y as usize + row) * atlas.width + rect.x as usize) * 4;
+                let dst = ((py + row) * width + px) * 4;
+                let len = rect.w as usize * 4;
+                pixels[dst..dst + len].copy_from_slice(&atlas.pixels[src..src + len]);
+            }
+            glyphs.push(Rectangle::from((px as i32, py as i32, rect.w, rect.h)));
+            if let Some(crop) = cropping.get_mut(i) {
+                crop.x += dx;
+                crop.y += dy;
+            }
+        }
+        Ok(SpriteFont {
+            texture: Texture2d {
+                format: SurfaceFormat::Color,
+                width,
+                height,
+                mip_data: vec![pixels],
+            },
+            glyphs,
+            cropping,
+            char_map: self.char_map.clone(),
+            v_spacing: self.v_spacing,
+            h_spacing: self.h_spacing,
+            kerning: self.kerning.clone(),
+            default: self.default,
+        })
+    }
+
+    // Lays out text and measures it in one pass.
+    fn place(&self, text: &str) -> (Vec<PlacedGlyph>, (f32, f32)) {
+        let line_spacing = self.v_spacing as f32;
+        let mut placed = vec![];
+        let (mut width, mut line_height) = (0.0f32, line_spacing);
+        let (mut x, mut y) = (0.0, 0.0);
+        let mut first_of_line = true;
+        for c in text.chars() {
+            match c {
+                '\r' => continue,
+                '\n' => {
+                    x = 0.0;
+                    y += line_spacing;
+                    line_height = line_spacing;
+                    first_of_line = true;
+                    continue;
+                }
+                _ => (),
+            }
+            let glyph = match self.glyph_or_default(c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            if first_of_line {
+                x = glyph.left_side_bearing.max(0.0);
+                first_of_line = false;
+            } else {
+                x += self.h_spacing + glyph.left_side_bearing;
+            }
+            placed.push(PlacedGlyph {
+                glyph,
+                x: x + glyph.cropping.x as f32,
+                y: y + glyph.cropping.y as f32,
+            });
+            x += glyph.width;
+            width = width.max(x + glyph.right_side_bearing.max(0.0));
+            x += glyph.right_side_bearing;
+            line_height = line_height.max(glyph.cropping.h as f32);
+        }
+        (placed, (width, y + line_height))
+    }
+}
+
+#[cfg(feature = "image")]
+impl SpriteFont {
+    /// Save the font as a BMFont descriptor at `path`, with its texture (with
+    /// alpha unpremultiplied) as a PNG alongside it, named after the
+    /// descriptor with `_0.png` on the end.
+    pub fn save_bmfont(&self, path: impl AsRef<Path>, format: BmFontFormat) -> Result<(), Error> {
+        let path = path.as_ref();
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let page = format!("{}_0.png", stem);
+        self.texture.save_png(0, path.with_file_name(&page), true)?;
+        let mut wtr = BufWriter::new(File::create(path)?);
+        self.write_bmfont(&mut wtr, &page, format)?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Draw `text` as laid out by `layout` onto a transparent image just big
+    /// enough for it. The font's texture is taken to have premultiplied
+    /// alpha, as XNA builds fonts; the image doesn't.
+    pub fn render(&self, text: &str) -> Result<image::RgbaImage, Error> {
+        self.render_with(text, &LayoutOptions::new())
+    }
+
+    /// Like `render`, laying text out as by `layout_with`.
+    pub fn render_with(
+        &self,
+        text: &str,
+        options: &LayoutOptions,
+    ) -> Result<image::RgbaImage, Error> {
+        let atlas = self.texture.decode(0)?;
+        let (width, height) = self.measure_string_with(text, options);
+        let placed = self.layout_with(text, options);
+        // Glyphs can reach past their advance, so make room for them too.
+        let right = placed.iter().map(|p| p.x + p.glyph.source.w as f32);
+        let bottom = placed.iter().map(|p| p.y + p.glyph.source.h as f32);
+        let width = right.fold(width, f32::max).ceil() as u32;
+        let height = bottom.fold(height, f32::max).ceil() as u32;
+
+        let mut image = image::RgbaImage::new(width, height);
+        for p in &placed {
+            let source = p.glyph.source;
+            let (left, top) = (p.x.round() as i64, p.y.round() as i64);
+            for sy in 0..source.h.max(0) as i64 {
+                for sx in 0..source.w.max(0) as i64 {
+                    let (x, y) = (left + sx, top + sy);
+                    let (ax, ay) = (source.x as i64 + sx, source.y as i64 + sy);
+                    let in_atlas = (0..atlas.width as i64).contains(&ax)
+                        && (0..atlas.height as i64).contains(&ay);
+                    if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 || !in_atlas {
+                        continue;
+                    }
+                    let src = atlas.get_pixel(ax as usize, ay as usize).unwrap();
+                    let dst = image.get_pixel_mut(x as u32, y as u32);
+                    let inverse = 255 - src[3] as u32;
+                    for (d, &s) in dst.0.iter_mut().zip(src) {
+                        *d = (s as u32 + (*d as u32 * inverse + 127) / 255).min(255) as u8;
+                    }
+                }
+            }
+        }
+        let mut straight = Rgba8Image {
+            width: width as usize,
+            height: height as usize,
+            pixels: image.into_raw(),
+        };
+        straight.unpremultiply();
+        Ok(image::RgbaImage::from_raw(width, height, straight.pixels).unwrap())
+    }
+}
@@ -0,0 +1,105 @@
+//! JSON export of untyped values, for inspecting content or keeping it under
+//! version control.
+
+use crate::{Texture2d, Value};
+use serde_json::{Map, Number, Value as Json};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64(data: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn texture(texture: &Texture2d) -> Json {
+    let mut object = Map::new();
+    object.insert("format".into(), format!("{:?}", texture.format).into());
+    object.insert("width".into(), texture.width.into());
+    object.insert("height".into(), texture.height.into());
+    object.insert(
+        "mips".into(),
+        texture
+            .mip_data
+            .iter()
+            .map(|mip| Json::from(base64(mip)))
+            .collect(),
+    );
+    Json::Object(object)
+}
+
+/// The string form of a dictionary key, if it has one. JSON object keys
+/// must be strings, so dictionaries with other keys become lists of pairs.
+pub(crate) fn key(value: &Value) -> Option<String> {
+    match *value {
+        Value::String(ref s) => Some(s.clone()),
+        Value::Char(c) => Some(c.to_string()),
+        Value::Int(i) => Some(i.to_string()),
+        Value::UInt(u) => Some(u.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+impl Value {
+    /// Convert to JSON. Objects become JSON objects (with their reader under
+    /// `$reader`, if any), dictionaries become objects when their keys allow
+    /// it, and texture data is base64 encoded per mip level. Assets kept in
+    /// `Value::Other` only record their reader.
+    pub fn to_json(&self) -> Json {
+        match *self {
+            Value::Null | Value::Nullable(None) => Json::Null,
+            Value::Bool(b) => b.into(),
+            Value::Int(i) => i.into(),
+            Value::UInt(u) => u.into(),
+            Value::Float(f) => Number::from_f64(f).map_or(Json::Null, Json::Number),
+            Value::Char(c) => c.to_string().into(),
+            Value::String(ref s) | Value::ExternalReference(ref s) => s.clone().into(),
+            Value::Array(ref values) => values.iter().map(Value::to_json).collect(),
+            Value::Dictionary(ref entries) => {
+                match entries
+                    .iter()
+                    .map(|(k, v)| key(k).map(|k| (k, v.to_json())))
+                    .collect::<Option<Map<_, _>>>()
+                {
+                    Some(object) => Json::Object(object),
+                    None => entries
+                        .iter()
+                        .map(|(k, v)| Json::Array(vec![k.to_json(), v.to_json()]))
+                        .collect(),
+                }
+            }
+            Value::Nullable(Some(ref value)) => value.to_json(),
+            Value::Texture(ref t) => texture(t),
+            Value::Object {
+                ref reader,
+                ref fields,
+            } => {
+                let mut object = Map::new();
+                if !reader.is_empty() {
+                    object.insert("$reader".into(), reader.clone().into());
+                }
+                for (name, value) in fields {
+                    object.insert(name.clone(), value.to_json());
+                }
+                Json::Object(object)
+            }
+            Value::Other { ref reader, .. } => {
+                let mut object = Map::new();
+                object.insert("$reader".into(), reader.clone().into());
+                Json::Object(object)
+            }
+        }
+    }
+}
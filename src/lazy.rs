@@ -0,0 +1,95 @@
+//! Textures whose data is read only when asked for, for tools that need just
+//! the dimensions or the first mip level of large textures.
+
+use crate::{
+    read_7bit_encoded_int, read_exactly, Error, MaybeCompressedXNB, Parse, ParseOptions,
+    SurfaceFormat, Texture2d, TypeReaders,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Read, Seek, SeekFrom};
+
+/// A texture from an uncompressed XNB, with the offset and length of each mip
+/// level recorded instead of its data.
+#[derive(Debug, Clone)]
+pub struct LazyTexture2d {
+    pub format: SurfaceFormat,
+    pub width: usize,
+    pub height: usize,
+    mips: Vec<(u64, usize)>,
+}
+
+impl LazyTexture2d {
+    /// Read the header of an XNB holding a texture, seeking past its mip
+    /// levels. Compressed files can't be seeked through and are rejected with
+    /// `Error::Void`. Names in the type reader table are read subject to
+    /// `options`.
+    pub fn from_reader<R: Read + Seek>(
+        rdr: &mut R,
+        options: &ParseOptions,
+    ) -> Result<LazyTexture2d, Error> {
+        if !matches!(
+            MaybeCompressedXNB::from_buffer(rdr)?,
+            MaybeCompressedXNB::Uncompressed(_)
+        ) {
+            return Err(Error::Void);
+        }
+        let table = TypeReaders {
+            options: options.clone(),
+            ..TypeReaders::default()
+        };
+        let num_readers = read_7bit_encoded_int(rdr)?;
+        let mut readers = vec![];
+        for _ in 0..num_readers {
+            readers.push(table.read_string(rdr)?);
+            rdr.read_i32::<LittleEndian>()?;
+        }
+        read_7bit_encoded_int(rdr)?;
+        let id = read_7bit_encoded_int(rdr)? as usize;
+        let reader = id
+            .checked_sub(1)
+            .and_then(|i| readers.get(i))
+            .ok_or_else(|| Error::UnknownReader(id.to_string()))?;
+        let main = reader.split(',').next().unwrap();
+        if main != <Texture2d as Parse>::READER {
+            return Err(Error::ReaderMismatch(
+                main.to_string(),
+                <Texture2d as Parse>::READER.to_string(),
+            ));
+        }
+
+        let format = SurfaceFormat::from(rdr.read_u32::<LittleEndian>()?)?;
+        let width = rdr.read_u32::<LittleEndian>()? as usize;
+        let height = rdr.read_u32::<LittleEndian>()? as usize;
+        let mip_count = rdr.read_u32::<LittleEndian>()?;
+        let mut mips = vec![];
+        for _ in 0..mip_count {
+            let len = rdr.read_u32::<LittleEndian>()?;
+            let offset = rdr.stream_position()?;
+            rdr.seek(SeekFrom::Current(len.into()))?;
+            mips.push((offset, len as usize));
+        }
+        Ok(LazyTexture2d {
+            format,
+            width,
+            height,
+            mips,
+        })
+    }
+
+    pub fn mip_count(&self) -> usize {
+        self.mips.len()
+    }
+
+    /// The size in bytes of a mip level, without reading it.
+    pub fn mip_len(&self, level: usize) -> Option<usize> {
+        self.mips.get(level).map(|&(_, len)| len)
+    }
+
+    /// Read the data of a mip level from `rdr`, which must be the same file
+    /// the texture was read from.
+    pub fn mip<R: Read + Seek>(&self, rdr: &mut R, level: usize) -> Result<Vec<u8>, Error> {
+        let &(offset, len) = self.mips.get(level).ok_or(Error::MissingMipLevel(level))?;
+        rdr.seek(SeekFrom::Start(offset))?;
+        read_exactly(rdr, len)
+    }
+}
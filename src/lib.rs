@@ -2,66 +2,351 @@ extern crate bitreader;
 extern crate byteorder;
 
 use byteorder::{LittleEndian, ReadBytesExt};
-use std::collections::HashMap;
+use shared::{read_any_object, Fixup};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fmt;
 use std::hash::Hash;
-use std::io::{Cursor, Error as IoError, Read};
+use std::io::{self, Error as IoError, ErrorKind, Read, Result as IoResult, Seek, SeekFrom};
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::string::FromUtf8Error;
+use std::time::Duration;
 
 pub use lzxd::WindowSize;
 
+/// The window size used by the stock XNA content pipeline.
+pub const DEFAULT_WINDOW_SIZE: WindowSize = WindowSize::KB64;
+
+pub use audio::{Song, SoundEffect, WaveFormat};
+pub use borrowed::{BorrowedTexture, BorrowedValue, BorrowedXnb};
+pub use decode::{Rgba16Image, Rgba8Image, RgbaF32Image};
+pub use decompress::DecompressReader;
+pub use diagnostics::Diagnostics;
+pub use effect::{
+    AlphaTestEffect, BasicEffect, CompareFunction, DualTextureEffect, Effect, EffectFormat,
+    EffectMaterial, EnvironmentMapEffect, SkinnedEffect,
+};
+pub use external::ExternalReference;
+pub use font::{Alignment, BmFontFormat, Glyph, LayoutOptions, PlacedGlyph};
+pub use lazy::LazyTexture2d;
+pub use math::{BoundingBox, BoundingSphere, Color, Matrix, Plane, Quaternion, Ray, Vector3};
+pub use mipmap::MipFilter;
+pub use model::{Model, ModelBone, ModelMesh, ModelMeshPart, Tag};
+pub use options::{ParseMode, ParseOptions, ParseStage, Progress};
+pub use pool::BufferPool;
+pub use reflective::{Fields, Reflect, Reflective, REFLECTIVE_READER};
+pub use registry::ReaderRegistry;
+pub use shared::{FromShared, Shared, SharedResource};
+pub use value::{FromValue, Value};
+pub use vertex::{
+    IndexBuffer, VertexBuffer, VertexDeclaration, VertexElement, VertexElementFormat,
+    VertexElementUsage,
+};
+pub use video::{Video, VideoSoundtrackType};
+pub use writer::{
+    write_7bit_encoded_int, write_nullable, write_object, write_string, Compression, Serialize,
+    TypeWriters, XnbWriter,
+};
+
+mod audio;
+#[cfg(feature = "rayon")]
+pub mod batch;
+mod borrowed;
+mod decode;
+mod decompress;
+mod diagnostics;
+pub mod dxt;
+mod effect;
+pub mod events;
+mod external;
+mod font;
+#[cfg(feature = "serde_json")]
+mod json;
+mod lazy;
+pub mod lz4;
+mod lzx_compress;
+mod math;
+mod mipmap;
+mod model;
+mod options;
+mod pool;
+mod reflective;
+mod region;
+mod registry;
+#[cfg(feature = "serde")]
+pub mod ser;
+mod shared;
+pub mod testing;
 pub mod tide;
+mod tmx;
+mod value;
+mod vertex;
+mod video;
+mod walk;
+mod writer;
+pub mod xact;
+#[cfg(feature = "xnbcli")]
+pub mod xnbcli;
 
 #[derive(Debug)]
 pub struct TypeReader {
     name: String,
-    _version: i32,
-}
-
-fn generic_types_from_reader(name: &str) -> Vec<&str> {
-    let mut parts = name.split('`');
-    let _main = parts.next().unwrap();
-    let args = parts.next();
-    if let Some(args) = args {
-        let mut count = 0;
-        let mut starts = vec![];
-        let mut ends = vec![];
-        let offset = 2;
-        for (i, c) in args[offset..args.len()].chars().enumerate() {
-            if c == '[' {
-                if count == 0 {
-                    starts.push(i + 1);
+    version: i32,
+}
+
+impl TypeReader {
+    /// The full name of the reader, including its assembly.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+}
+
+/// The type reader table of an XNB being read, along with any state that
+/// readers need to share while parsing it.
+#[derive(Default)]
+pub struct TypeReaders {
+    readers: Vec<TypeReader>,
+    fixups: RefCell<Vec<Fixup>>,
+    options: ParseOptions,
+    position: Rc<Cell<u64>>,
+    skip: Rc<Cell<u64>>,
+    allocated: Cell<usize>,
+    depth: Cell<usize>,
+    warnings: RefCell<Vec<Error>>,
+}
+
+impl TypeReaders {
+    /// Read a reference to a shared resource. The resource itself isn't read
+    /// until after the primary asset, so the returned handle is filled in once
+    /// the whole file has been parsed.
+    pub fn read_shared<T: FromShared + ?Sized + 'static>(
+        &self,
+        rdr: &mut dyn Read,
+    ) -> Result<Option<Shared<T>>, Error> {
+        let index = read_7bit_encoded_int(rdr)? as usize;
+        if index == 0 {
+            return Ok(None);
+        }
+        let shared = Shared::<T>::new(index - 1);
+        self.fixups.borrow_mut().push(shared.fixup());
+        Ok(Some(shared))
+    }
+
+    /// How far into the content (after the header) parsing has reached.
+    pub fn position(&self) -> u64 {
+        self.position.get()
+    }
+
+    /// Report a deviation from the format that parsing can continue past. It
+    /// is an error in strict mode, and recorded as a warning otherwise.
+    pub fn recoverable(&self, error: Error) -> Result<(), Error> {
+        self.options.recover(&mut self.warnings.borrow_mut(), error)
+    }
+
+    fn allocate(&self, bytes: usize) -> Result<(), Error> {
+        let total = self.allocated.get().saturating_add(bytes);
+        limit("total allocation", total, self.options.max_total_allocation)?;
+        self.allocated.set(total);
+        Ok(())
+    }
+
+    /// Check the length of a collection of `T` read from the file against the
+    /// parse options, before reading its elements.
+    pub fn check_len<T>(&self, len: usize) -> Result<(), Error> {
+        limit("collection length", len, self.options.max_collection_len)?;
+        self.allocate(len.saturating_mul(mem::size_of::<T>()))
+    }
+
+    /// Read `len` bytes of raw data (samples, bytecode, ...), subject to the
+    /// parse options.
+    pub fn read_bytes(&self, rdr: &mut dyn Read, len: usize) -> Result<Vec<u8>, Error> {
+        self.allocate(len)?;
+        let buf = self
+            .options
+            .pool
+            .as_ref()
+            .map_or_else(Vec::new, BufferPool::get);
+        read_exactly_into(rdr, len, buf)
+    }
+
+    /// Read `len` bytes of bulk data (texture data, samples, bytecode, ...),
+    /// or skip over it if the parse options say to, returning nothing.
+    pub fn read_data(&self, rdr: &mut dyn Read, len: usize) -> Result<Vec<u8>, Error> {
+        if !self.options.skip_data {
+            return self.read_bytes(rdr, len);
+        }
+        // The content reader seeks or reads past it before its next read.
+        self.skip.set(self.skip.get().saturating_add(len as u64));
+        Ok(vec![])
+    }
+
+    fn read_texture_data(&self, rdr: &mut dyn Read) -> Result<Vec<u8>, Error> {
+        let len = rdr.read_u32::<LittleEndian>()? as usize;
+        limit("texture data", len, self.options.max_texture_bytes)?;
+        self.read_data(rdr, len)
+    }
+
+    // Check the size of a texture's mip level against the parse options, and
+    // against the size its format and dimensions call for.
+    fn check_mip_size(
+        &self,
+        format: SurfaceFormat,
+        width: usize,
+        height: usize,
+        level: usize,
+        size: usize,
+    ) -> Result<(), Error> {
+        limit("texture data", size, self.options.max_texture_bytes)?;
+        let expected = format.mip_size(width, height, level);
+        if size != expected {
+            self.recoverable(Error::InvalidMipSize {
+                level,
+                expected,
+                actual: size,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn read_string(&self, rdr: &mut dyn Read) -> Result<String, Error> {
+        let len = read_7bit_encoded_int(rdr)?;
+        limit("string length", len as usize, self.options.max_string_len)?;
+        self.allocate(len as usize)?;
+        match String::from_utf8(read_exactly(rdr, len as usize)?) {
+            Ok(s) => Ok(s),
+            Err(e) if self.options.lossy_strings => {
+                Ok(String::from_utf8_lossy(e.as_bytes()).into_owned())
+            }
+            Err(e) => Err(Error::InvalidString(e)),
+        }
+    }
+
+    // Run `f` one level further into nested objects. Files can nest objects
+    // arbitrarily deep, so the depth is limited to keep from exhausting the stack.
+    fn nested<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        if self.options.cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let depth = self.depth.get() + 1;
+        limit("nesting depth", depth, self.options.max_depth)?;
+        self.depth.set(depth);
+        let result = f();
+        self.depth.set(depth - 1);
+        result
+    }
+
+    fn resolve_shared(&self, resources: &[Option<SharedResource>]) -> Result<(), Error> {
+        for fixup in self.fixups.borrow_mut().drain(..) {
+            fixup(resources)?;
+        }
+        Ok(())
+    }
+}
+
+fn limit(what: &'static str, requested: usize, limit: usize) -> Result<(), Error> {
+    if requested > limit {
+        return Err(Error::LimitExceeded {
+            what,
+            requested,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+// Escapes text for use in XML attributes.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Deref for TypeReaders {
+    type Target = [TypeReader];
+    fn deref(&self) -> &[TypeReader] {
+        &self.readers
+    }
+}
+
+// Strip the assembly from an assembly-qualified type name, leaving any
+// (possibly qualified) generic arguments intact.
+fn without_assembly(name: &str) -> &str {
+    let mut depth = 0;
+    for (i, c) in name.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => return &name[..i],
+            _ => {}
+        }
+    }
+    name
+}
+
+fn generic_types_from_reader(name: &str) -> Result<Vec<&str>, Error> {
+    // Only the outermost generic arguments matter; they may be generic too.
+    let args = match name.split_once('`') {
+        Some((_, args)) => args,
+        None => return Ok(vec![]),
+    };
+    let invalid = || Error::InvalidReaderName(name.to_string());
+    // Skip the arity and the bracket around the argument list.
+    let list = args
+        .find('[')
+        .map(|idx| &args[idx + 1..])
+        .ok_or_else(invalid)?;
+    let mut depth = 0;
+    let mut start = 0;
+    let mut types = vec![];
+    for (i, c) in list.char_indices() {
+        match c {
+            '[' => {
+                if depth == 0 {
+                    start = i + 1;
                 }
-                count += 1;
+                depth += 1;
             }
-            if c == ']' {
-                count -= 1;
-                if count == 0 {
-                    ends.push(i);
+            ']' if depth == 0 => return Ok(types),
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    types.push(without_assembly(&list[start..i]));
                 }
             }
+            _ => {}
         }
-        assert_eq!(starts.len(), ends.len());
-        starts
-            .into_iter()
-            .zip(ends.into_iter())
-            .map(|(s, e)| &args[s + offset..e + offset])
-            .map(|s| s.split(',').next().unwrap())
-            .collect()
-    } else {
-        vec![]
     }
+    Err(invalid())
+}
+
+// The generic argument at `index`, which a malformed reader name may lack.
+fn type_arg<'a>(args: &[&'a str], index: usize) -> Result<&'a str, Error> {
+    args.get(index)
+        .cloned()
+        .ok_or(Error::MissingTypeArgument(index))
 }
 
 pub trait Parse: Sized {
     const READER: &'static str;
     fn try_parse(
         _rdr: &mut dyn Read,
-        _readers: &[TypeReader],
+        _readers: &TypeReaders,
         _args: Vec<&str>,
     ) -> Result<Self, Error>;
     fn parse(
         name: &str,
         rdr: &mut dyn Read,
-        readers: &[TypeReader],
+        readers: &TypeReaders,
         args: Vec<&str>,
     ) -> Result<Self, Error> {
         if name != Self::READER {
@@ -74,43 +359,174 @@ pub trait Parse: Sized {
     }
 }
 
+impl Texture2d {
+    /// Hand the texture's data back to `pool` for later parses to reuse.
+    pub fn recycle(self, pool: &BufferPool) {
+        for mip in self.mip_data {
+            pool.put(mip);
+        }
+    }
+}
+
 impl Parse for Texture2d {
     const READER: &'static str = "Microsoft.Xna.Framework.Content.Texture2DReader";
     fn try_parse(
         rdr: &mut dyn Read,
-        _readers: &[TypeReader],
+        readers: &TypeReaders,
         _args: Vec<&str>,
     ) -> Result<Self, Error> {
-        Texture2d::new(rdr)
+        Texture2d::new(rdr, readers)
     }
 }
 
+const ARRAY_READER: &str = "Microsoft.Xna.Framework.Content.ArrayReader";
+const LIST_READER: &str = "Microsoft.Xna.Framework.Content.ListReader";
+
+// Arrays and lists share the same encoding, so sequence types accept either.
+fn check_sequence_reader(name: &str) -> Result<(), Error> {
+    if name != ARRAY_READER && name != LIST_READER {
+        return Err(Error::ReaderMismatch(
+            name.to_string(),
+            ARRAY_READER.to_string(),
+        ));
+    }
+    Ok(())
+}
+
 impl<T: Parse> Parse for Vec<T> {
-    //TODO: support list reader too: "Microsoft.Xna.Framework.Content.ListReader"
-    const READER: &'static str = "Microsoft.Xna.Framework.Content.ArrayReader";
+    const READER: &'static str = ARRAY_READER;
+    fn parse(
+        name: &str,
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        check_sequence_reader(name)?;
+        Self::try_parse(rdr, readers, args)
+    }
     fn try_parse(
         rdr: &mut dyn Read,
-        readers: &[TypeReader],
+        readers: &TypeReaders,
         args: Vec<&str>,
     ) -> Result<Self, Error> {
         let count = rdr.read_u32::<LittleEndian>()?;
+        readers.check_len::<T>(count as usize)?;
         let mut vec = vec![];
         for _ in 0..count {
-            let val = read_dictionary_member(args[0], rdr, readers)?;
+            let val = read_dictionary_member(type_arg(&args, 0)?, rdr, readers)?;
             vec.push(val);
         }
         Ok(vec)
     }
 }
 
+impl<T: Parse, const N: usize> Parse for [T; N] {
+    const READER: &'static str = ARRAY_READER;
+    fn parse(
+        name: &str,
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        check_sequence_reader(name)?;
+        Self::try_parse(rdr, readers, args)
+    }
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let vec = Vec::try_parse(rdr, readers, args)?;
+        let actual = vec.len();
+        vec.try_into().map_err(|_| Error::LengthMismatch {
+            expected: N,
+            actual,
+        })
+    }
+}
+
+// Tuples are read from arrays or lists of exactly the right length. Each
+// member is read with its own reader, so a `List<object>` can hold a mix of
+// types.
+macro_rules! parse_tuple {
+    ($len:expr, $($name:ident),+) => {
+        impl<$($name: Parse),+> Parse for ($($name,)+) {
+            const READER: &'static str = ARRAY_READER;
+            fn parse(
+                name: &str,
+                rdr: &mut dyn Read,
+                readers: &TypeReaders,
+                args: Vec<&str>,
+            ) -> Result<Self, Error> {
+                check_sequence_reader(name)?;
+                Self::try_parse(rdr, readers, args)
+            }
+            fn try_parse(
+                rdr: &mut dyn Read,
+                readers: &TypeReaders,
+                args: Vec<&str>,
+            ) -> Result<Self, Error> {
+                let count = rdr.read_u32::<LittleEndian>()? as usize;
+                if count != $len {
+                    return Err(Error::LengthMismatch {
+                        expected: $len,
+                        actual: count,
+                    });
+                }
+                Ok(($(read_dictionary_member::<$name>(type_arg(&args, 0)?, rdr, readers)?,)+))
+            }
+        }
+    };
+}
+
+parse_tuple!(1, A);
+parse_tuple!(2, A, B);
+parse_tuple!(3, A, B, C);
+parse_tuple!(4, A, B, C, D);
+
+impl<T: Parse> Parse for Array<T> {
+    const READER: &'static str = ARRAY_READER;
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(Array {
+            vec: Vec::try_parse(rdr, readers, args)?,
+        })
+    }
+}
+
 impl<K: Parse + Eq + Hash, V: Parse> Parse for Dictionary<K, V> {
     const READER: &'static str = "Microsoft.Xna.Framework.Content.DictionaryReader";
     fn try_parse(
         rdr: &mut dyn Read,
-        readers: &[TypeReader],
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Dictionary::new(type_arg(&args, 0)?, type_arg(&args, 1)?, rdr, readers)
+    }
+}
+
+impl<K: Parse + Eq + Hash, V: Parse> Parse for HashMap<K, V> {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.DictionaryReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
         args: Vec<&str>,
     ) -> Result<Self, Error> {
-        Dictionary::new(args[0], args[1], rdr, readers)
+        read_dictionary(type_arg(&args, 0)?, type_arg(&args, 1)?, rdr, readers)
+    }
+}
+
+impl<K: Parse + Ord, V: Parse> Parse for BTreeMap<K, V> {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.DictionaryReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        read_dictionary(type_arg(&args, 0)?, type_arg(&args, 1)?, rdr, readers)
     }
 }
 
@@ -118,7 +534,7 @@ impl Parse for Rectangle {
     const READER: &'static str = "Microsoft.Xna.Framework.Content.RectangleReader";
     fn try_parse(
         rdr: &mut dyn Read,
-        _readers: &[TypeReader],
+        _readers: &TypeReaders,
         _args: Vec<&str>,
     ) -> Result<Self, Error> {
         Rectangle::new(rdr)
@@ -129,21 +545,90 @@ impl Parse for i32 {
     const READER: &'static str = "Microsoft.Xna.Framework.Content.Int32Reader";
     fn try_parse(
         rdr: &mut dyn Read,
-        _readers: &[TypeReader],
+        _readers: &TypeReaders,
         _args: Vec<&str>,
     ) -> Result<Self, Error> {
         rdr.read_i32::<LittleEndian>().map_err(Error::from)
     }
 }
 
+macro_rules! parse_le_primitive {
+    ($ty:ty, $name:expr, $read:ident) => {
+        impl Parse for $ty {
+            const READER: &'static str =
+                concat!("Microsoft.Xna.Framework.Content.", $name, "Reader");
+            fn try_parse(
+                rdr: &mut dyn Read,
+                _readers: &TypeReaders,
+                _args: Vec<&str>,
+            ) -> Result<Self, Error> {
+                rdr.$read::<LittleEndian>().map_err(Error::from)
+            }
+        }
+    };
+}
+
+parse_le_primitive!(i16, "Int16", read_i16);
+parse_le_primitive!(u16, "UInt16", read_u16);
+parse_le_primitive!(u32, "UInt32", read_u32);
+parse_le_primitive!(i64, "Int64", read_i64);
+parse_le_primitive!(u64, "UInt64", read_u64);
+parse_le_primitive!(f32, "Single", read_f32);
+parse_le_primitive!(f64, "Double", read_f64);
+
+impl Parse for u8 {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.ByteReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        rdr.read_u8().map_err(Error::from)
+    }
+}
+
+impl Parse for i8 {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.SByteReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        rdr.read_i8().map_err(Error::from)
+    }
+}
+
+impl Parse for bool {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.BooleanReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        rdr.read_u8().map(|b| b != 0).map_err(Error::from)
+    }
+}
+
 impl Parse for char {
     const READER: &'static str = "Microsoft.Xna.Framework.Content.CharReader";
     fn try_parse(
         rdr: &mut dyn Read,
-        _readers: &[TypeReader],
+        readers: &TypeReaders,
         _args: Vec<&str>,
     ) -> Result<Self, Error> {
-        rdr.read_u8().map(|b| b as char).map_err(Error::from)
+        // Chars are UTF-8, so the first byte says how many follow.
+        let first = rdr.read_u8()?;
+        let len = match first.leading_ones() {
+            n @ 2..=4 => n as usize,
+            _ => 1,
+        };
+        let mut buf = vec![first];
+        buf.extend(read_exactly(rdr, len - 1)?);
+        match String::from_utf8(buf) {
+            Ok(s) => Ok(s.chars().next().unwrap()),
+            Err(_) if readers.options.lossy_strings => Ok(char::REPLACEMENT_CHARACTER),
+            Err(e) => Err(Error::InvalidString(e)),
+        }
     }
 }
 
@@ -151,10 +636,10 @@ impl Parse for String {
     const READER: &'static str = "Microsoft.Xna.Framework.Content.StringReader";
     fn try_parse(
         rdr: &mut dyn Read,
-        _readers: &[TypeReader],
+        readers: &TypeReaders,
         _args: Vec<&str>,
     ) -> Result<Self, Error> {
-        read_string(rdr)
+        readers.read_string(rdr)
     }
 }
 
@@ -162,50 +647,240 @@ impl Parse for SpriteFont {
     const READER: &'static str = "Microsoft.Xna.Framework.Content.SpriteFontReader";
     fn try_parse(
         rdr: &mut dyn Read,
-        readers: &[TypeReader],
+        readers: &TypeReaders,
         _args: Vec<&str>,
     ) -> Result<Self, Error> {
         SpriteFont::new(rdr, readers)
     }
 }
 
+impl Parse for Decimal {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.DecimalReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(Decimal {
+            lo: rdr.read_u32::<LittleEndian>()?,
+            mid: rdr.read_u32::<LittleEndian>()?,
+            hi: rdr.read_u32::<LittleEndian>()?,
+            flags: rdr.read_u32::<LittleEndian>()?,
+        })
+    }
+}
+
+impl Parse for DateTime {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.DateTimeReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(DateTime::from_packed(rdr.read_u64::<LittleEndian>()?))
+    }
+}
+
+impl Parse for TimeSpan {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.TimeSpanReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(TimeSpan {
+            ticks: rdr.read_i64::<LittleEndian>()?,
+        })
+    }
+}
+
+impl Parse for Duration {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.TimeSpanReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let span = TimeSpan::try_parse(rdr, readers, args)?;
+        if span.is_negative() {
+            return Err(Error::NegativeTimeSpan(span.ticks));
+        }
+        Ok(span.abs_duration())
+    }
+}
+
 impl Parse for Vector3 {
     const READER: &'static str = "Microsoft.Xna.Framework.Content.Vector3Reader";
     fn try_parse(
         rdr: &mut dyn Read,
-        _readers: &[TypeReader],
+        _readers: &TypeReaders,
         _args: Vec<&str>,
     ) -> Result<Self, Error> {
-        Ok(Vector3(
-            rdr.read_f32::<LittleEndian>()?,
-            rdr.read_f32::<LittleEndian>()?,
-            rdr.read_f32::<LittleEndian>()?,
-        ))
+        math::read_vector3(rdr)
     }
 }
 
 fn read_with_reader<T: Parse>(
     name: &str,
     rdr: &mut dyn Read,
-    readers: &[TypeReader],
+    readers: &TypeReaders,
 ) -> Result<T, Error> {
     let main = name.split('`').next().unwrap().split(',').next().unwrap();
-    let args = generic_types_from_reader(name);
+    let args = generic_types_from_reader(name)?;
     //println!("reading with {:?}", name);
-    T::parse(main, rdr, readers, args)
+    readers
+        .nested(|| T::parse(main, rdr, readers, args))
+        .map_err(|e| e.within(main, readers.position()))
+}
+
+// How often, in bytes of content, to report progress within a stage.
+const PROGRESS_INTERVAL: u64 = 64 * 1024;
+
+// Keeps count of the bytes read from the content of an XNB, so errors can
+// say where they happened and progress can be reported.
+struct CountingReader<'a> {
+    rdr: Source<'a>,
+    position: Rc<Cell<u64>>,
+    // Bytes to pass over before the next read.
+    skip: Rc<Cell<u64>>,
+    total: u64,
+    stage: ParseStage,
+    options: &'a ParseOptions,
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+// The content of an XNB, which can be seeked through if it's uncompressed
+// and the caller provided a `Seek`.
+enum Source<'a> {
+    Read(&'a mut dyn Read),
+    Seek(&'a mut dyn ReadSeek),
 }
 
+// Carried through `io::Error` when a read notices parsing was cancelled.
 #[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl<'a> CountingReader<'a> {
+    fn enter(&mut self, stage: ParseStage) {
+        self.stage = stage;
+        self.report();
+    }
+
+    fn skip(&mut self, len: u64) -> IoResult<()> {
+        let skipped = match self.rdr {
+            Source::Read(ref mut rdr) => io::copy(&mut rdr.take(len), &mut io::sink())?,
+            Source::Seek(ref mut rdr) => {
+                let start = rdr.stream_position()?;
+                let end = rdr.seek(SeekFrom::End(0))?;
+                let target = start.saturating_add(len).min(end);
+                rdr.seek(SeekFrom::Start(target))?;
+                target - start
+            }
+        };
+        self.position.set(self.position.get() + skipped);
+        if skipped != len {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
+    fn report(&self) {
+        if let Some(ref progress) = self.options.progress {
+            progress.report(Progress {
+                stage: self.stage,
+                processed: self.position.get(),
+                total: self.total,
+            });
+        }
+    }
+}
+
+impl<'a> Read for CountingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let before = self.position.get();
+        let skip = self.skip.replace(0);
+        if skip != 0 {
+            self.skip(skip)?;
+        }
+        let n = match self.rdr {
+            Source::Read(ref mut rdr) => rdr.read(buf)?,
+            Source::Seek(ref mut rdr) => rdr.read(buf)?,
+        };
+        self.position.set(self.position.get() + n as u64);
+        if before / PROGRESS_INTERVAL != self.position.get() / PROGRESS_INTERVAL {
+            if self.options.cancelled() {
+                return Err(IoError::other(Cancelled));
+            }
+            self.report();
+        }
+        Ok(n)
+    }
+}
+
+/// A `T[]`, as opposed to a `List<T>`. Both are read the same way, but an
+/// `Array` only accepts assets that were written with `ArrayReader`.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Array<T> {
     pub vec: Vec<T>,
 }
 
+impl<T> From<Vec<T>> for Array<T> {
+    fn from(vec: Vec<T>) -> Array<T> {
+        Array { vec }
+    }
+}
+
+impl<T> Deref for Array<T> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &self.vec
+    }
+}
+
+impl<T> DerefMut for Array<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.vec
+    }
+}
+
+impl<T> IntoIterator for Array<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.vec.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Array<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.vec.iter()
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Dictionary<K: Eq + Hash, V> {
     pub map: HashMap<K, V>,
 }
 
 #[derive(PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum DictionaryKey {
     Int(i32),
     String(String),
@@ -213,9 +888,35 @@ pub enum DictionaryKey {
 
 fn reader_from_type(typename: &str) -> Option<&'static str> {
     match typename {
+        "System.Byte" => Some("Microsoft.Xna.Framework.Content.ByteReader"),
+        "System.SByte" => Some("Microsoft.Xna.Framework.Content.SByteReader"),
+        "System.Int16" => Some("Microsoft.Xna.Framework.Content.Int16Reader"),
+        "System.UInt16" => Some("Microsoft.Xna.Framework.Content.UInt16Reader"),
         "System.Int32" => Some("Microsoft.Xna.Framework.Content.Int32Reader"),
+        "System.UInt32" => Some("Microsoft.Xna.Framework.Content.UInt32Reader"),
+        "System.Int64" => Some("Microsoft.Xna.Framework.Content.Int64Reader"),
+        "System.UInt64" => Some("Microsoft.Xna.Framework.Content.UInt64Reader"),
+        "System.Single" => Some("Microsoft.Xna.Framework.Content.SingleReader"),
+        "System.Double" => Some("Microsoft.Xna.Framework.Content.DoubleReader"),
+        "System.Decimal" => Some("Microsoft.Xna.Framework.Content.DecimalReader"),
+        "System.DateTime" => Some("Microsoft.Xna.Framework.Content.DateTimeReader"),
+        "System.TimeSpan" => Some("Microsoft.Xna.Framework.Content.TimeSpanReader"),
         "System.Char" => Some("Microsoft.Xna.Framework.Content.CharReader"),
+        "System.Boolean" => Some("Microsoft.Xna.Framework.Content.BooleanReader"),
         "Microsoft.Xna.Framework.Vector3" => Some("Microsoft.Xna.Framework.Content.Vector3Reader"),
+        "Microsoft.Xna.Framework.Quaternion" => {
+            Some("Microsoft.Xna.Framework.Content.QuaternionReader")
+        }
+        "Microsoft.Xna.Framework.BoundingBox" => {
+            Some("Microsoft.Xna.Framework.Content.BoundingBoxReader")
+        }
+        "Microsoft.Xna.Framework.BoundingSphere" => {
+            Some("Microsoft.Xna.Framework.Content.BoundingSphereReader")
+        }
+        "Microsoft.Xna.Framework.Plane" => Some("Microsoft.Xna.Framework.Content.PlaneReader"),
+        "Microsoft.Xna.Framework.Ray" => Some("Microsoft.Xna.Framework.Content.RayReader"),
+        "Microsoft.Xna.Framework.Color" => Some("Microsoft.Xna.Framework.Content.ColorReader"),
+        "Microsoft.Xna.Framework.Matrix" => Some("Microsoft.Xna.Framework.Content.MatrixReader"),
         "Microsoft.Xna.Framework.Rectangle" => {
             Some("Microsoft.Xna.Framework.Content.RectangleReader")
         }
@@ -226,7 +927,7 @@ fn reader_from_type(typename: &str) -> Option<&'static str> {
 fn read_dictionary_member<T: Parse>(
     typename: &str,
     rdr: &mut dyn Read,
-    readers: &[TypeReader],
+    readers: &TypeReaders,
 ) -> Result<T, Error> {
     //println!("checking {}" ,typename);
     if let Some(reader) = reader_from_type(typename) {
@@ -236,27 +937,37 @@ fn read_dictionary_member<T: Parse>(
     }
 }
 
+fn read_dictionary<K: Parse, V: Parse, C: FromIterator<(K, V)>>(
+    keytype: &str,
+    valtype: &str,
+    rdr: &mut dyn Read,
+    readers: &TypeReaders,
+) -> Result<C, Error> {
+    let count = rdr.read_u32::<LittleEndian>()?;
+    readers.check_len::<(K, V)>(count as usize)?;
+    (0..count)
+        .map(|_| {
+            let key = read_dictionary_member(keytype, rdr, readers)?;
+            let value = read_dictionary_member(valtype, rdr, readers)?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
 impl<K: Parse + Eq + Hash, V: Parse> Dictionary<K, V> {
     fn new(
         keytype: &str,
         valtype: &str,
         rdr: &mut dyn Read,
-        readers: &[TypeReader],
+        readers: &TypeReaders,
     ) -> Result<Dictionary<K, V>, Error> {
-        let count = rdr.read_u32::<LittleEndian>()?;
-        let mut map = HashMap::new();
-        for _ in 0..count {
-            //println!("getting item {}/{}", i + 1, count);
-            let key = read_dictionary_member(keytype, rdr, readers)?;
-            let value = read_dictionary_member(valtype, rdr, readers)?;
-            //println!("got {:?} => {:?}", key, value);
-            map.insert(key, value);
-        }
+        let map = read_dictionary(keytype, valtype, rdr, readers)?;
         Ok(Dictionary { map: map })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum SurfaceFormat {
     Color,
     Bgr565,
@@ -278,6 +989,15 @@ pub enum SurfaceFormat {
     HalfVector2,
     HalfVector4,
     HdrBlendable,
+    // MonoGame's additions, the sRGB ones holding colours in sRGB space.
+    Bgr32,
+    Bgra32,
+    ColorSRgb = 30,
+    Bgr32SRgb,
+    Bgra32SRgb,
+    Dxt1SRgb,
+    Dxt3SRgb,
+    Dxt5SRgb,
 }
 
 impl SurfaceFormat {
@@ -303,31 +1023,139 @@ impl SurfaceFormat {
             17 => SurfaceFormat::HalfVector2,
             18 => SurfaceFormat::HalfVector4,
             19 => SurfaceFormat::HdrBlendable,
+            20 => SurfaceFormat::Bgr32,
+            21 => SurfaceFormat::Bgra32,
+            30 => SurfaceFormat::ColorSRgb,
+            31 => SurfaceFormat::Bgr32SRgb,
+            32 => SurfaceFormat::Bgra32SRgb,
+            33 => SurfaceFormat::Dxt1SRgb,
+            34 => SurfaceFormat::Dxt3SRgb,
+            35 => SurfaceFormat::Dxt5SRgb,
             f => return Err(Error::UnrecognizedSurfaceFormat(f)),
         })
     }
+
+    /// Whether the format holds colours in sRGB space.
+    pub fn is_srgb(&self) -> bool {
+        *self != self.without_srgb()
+    }
+
+    /// The format with the same layout whose colours aren't flagged as sRGB,
+    /// or this one if they aren't.
+    pub fn without_srgb(&self) -> SurfaceFormat {
+        match *self {
+            SurfaceFormat::ColorSRgb => SurfaceFormat::Color,
+            SurfaceFormat::Bgr32SRgb => SurfaceFormat::Bgr32,
+            SurfaceFormat::Bgra32SRgb => SurfaceFormat::Bgra32,
+            SurfaceFormat::Dxt1SRgb => SurfaceFormat::Dxt1,
+            SurfaceFormat::Dxt3SRgb => SurfaceFormat::Dxt3,
+            SurfaceFormat::Dxt5SRgb => SurfaceFormat::Dxt5,
+            format => format,
+        }
+    }
+
+    /// Whether the format stores 4x4 blocks of pixels (DXT) rather than
+    /// individual pixels.
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self.without_srgb(),
+            SurfaceFormat::Dxt1 | SurfaceFormat::Dxt3 | SurfaceFormat::Dxt5
+        )
+    }
+
+    /// The width and height in pixels of the blocks the format stores: 4 for
+    /// compressed formats and 1 for the rest.
+    pub fn block_dim(&self) -> usize {
+        if self.is_compressed() {
+            4
+        } else {
+            1
+        }
+    }
+
+    /// The number of bytes each block takes up. Blocks of uncompressed
+    /// formats are single pixels.
+    pub fn block_size(&self) -> usize {
+        match *self {
+            SurfaceFormat::Alpha8 => 1,
+            SurfaceFormat::Bgr565
+            | SurfaceFormat::Bgra5551
+            | SurfaceFormat::Bgra4444
+            | SurfaceFormat::NormalizedByte2
+            | SurfaceFormat::HalfSingle => 2,
+            SurfaceFormat::Color
+            | SurfaceFormat::NormalizedByte4
+            | SurfaceFormat::Rgba1010102
+            | SurfaceFormat::Rg32
+            | SurfaceFormat::Single
+            | SurfaceFormat::HalfVector2
+            | SurfaceFormat::Bgr32
+            | SurfaceFormat::Bgra32
+            | SurfaceFormat::ColorSRgb
+            | SurfaceFormat::Bgr32SRgb
+            | SurfaceFormat::Bgra32SRgb => 4,
+            SurfaceFormat::Dxt1
+            | SurfaceFormat::Dxt1SRgb
+            | SurfaceFormat::Rgba64
+            | SurfaceFormat::Vector2
+            | SurfaceFormat::HalfVector4
+            | SurfaceFormat::HdrBlendable => 8,
+            SurfaceFormat::Dxt3
+            | SurfaceFormat::Dxt5
+            | SurfaceFormat::Dxt3SRgb
+            | SurfaceFormat::Dxt5SRgb
+            | SurfaceFormat::Vector4 => 16,
+        }
+    }
+
+    /// The number of bytes each pixel takes up, or `None` for compressed
+    /// formats.
+    pub fn bytes_per_pixel(&self) -> Option<usize> {
+        if self.is_compressed() {
+            None
+        } else {
+            Some(self.block_size())
+        }
+    }
+
+    /// The number of bytes needed to store a `width` by `height` surface.
+    /// Partial blocks on the right and bottom edges count as whole ones.
+    pub fn mip_byte_len(&self, width: usize, height: usize) -> usize {
+        // Dimensions come straight from the file, so none of this may overflow.
+        let dim = self.block_dim();
+        let blocks = width.div_ceil(dim).saturating_mul(height.div_ceil(dim));
+        blocks.saturating_mul(self.block_size())
+    }
+
+    // The number of bytes needed to store the given mip level of a surface.
+    fn mip_size(&self, width: usize, height: usize, level: usize) -> usize {
+        let shift = |n: usize| n.checked_shr(level as u32).unwrap_or(0).max(1);
+        self.mip_byte_len(shift(width), shift(height))
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Texture2d {
     pub format: SurfaceFormat,
     pub width: usize,
     pub height: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub mip_data: Vec<Vec<u8>>,
 }
 
 impl Texture2d {
-    fn new(rdr: &mut dyn Read) -> Result<Texture2d, Error> {
+    fn new(rdr: &mut dyn Read, readers: &TypeReaders) -> Result<Texture2d, Error> {
         let format = SurfaceFormat::from(rdr.read_u32::<LittleEndian>()?)?;
         let w = rdr.read_u32::<LittleEndian>()? as usize;
         let h = rdr.read_u32::<LittleEndian>()? as usize;
         let mip_count = rdr.read_u32::<LittleEndian>()?;
+        readers.check_len::<Vec<u8>>(mip_count as usize)?;
         let mut mip_data = vec![];
-        for _ in 0..mip_count {
-            let data_size = rdr.read_u32::<LittleEndian>()? as usize;
-            let mut data = vec![0; data_size];
-            rdr.read(&mut data)?;
-            mip_data.push(data);
+        for level in 0..mip_count as usize {
+            let len = rdr.read_u32::<LittleEndian>()? as usize;
+            readers.check_mip_size(format, w, h, level, len)?;
+            mip_data.push(readers.read_data(rdr, len)?);
         }
         Ok(Texture2d {
             format: format,
@@ -338,7 +1166,46 @@ impl Texture2d {
     }
 }
 
+/// A cube map: six square faces, each with its own mip chain, in the order
+/// +X, -X, +Y, -Y, +Z, -Z.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TextureCube {
+    pub format: SurfaceFormat,
+    pub size: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub faces: Vec<Vec<Vec<u8>>>,
+}
+
+impl Parse for TextureCube {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.TextureCubeReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let format = SurfaceFormat::from(rdr.read_u32::<LittleEndian>()?)?;
+        let size = rdr.read_u32::<LittleEndian>()? as usize;
+        let mip_count = rdr.read_u32::<LittleEndian>()?;
+        readers.check_len::<Vec<u8>>(mip_count as usize)?;
+        let mut faces = vec![];
+        for _ in 0..6 {
+            let mut mips = vec![];
+            for _ in 0..mip_count {
+                mips.push(readers.read_texture_data(rdr)?);
+            }
+            faces.push(mips);
+        }
+        Ok(TextureCube {
+            format,
+            size,
+            faces,
+        })
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SpriteFont {
     pub texture: Texture2d,
     pub glyphs: Vec<Rectangle>,
@@ -351,7 +1218,7 @@ pub struct SpriteFont {
 }
 
 impl SpriteFont {
-    fn new(rdr: &mut dyn Read, readers: &[TypeReader]) -> Result<SpriteFont, Error> {
+    fn new(rdr: &mut dyn Read, readers: &TypeReaders) -> Result<SpriteFont, Error> {
         let texture = read_object::<Texture2d>(rdr, readers)?;
         let glyphs = read_object::<Vec<Rectangle>>(rdr, readers)?;
         let cropping = read_object::<Vec<Rectangle>>(rdr, readers)?;
@@ -359,10 +1226,7 @@ impl SpriteFont {
         let v_spacing = rdr.read_i32::<LittleEndian>()?;
         let h_spacing = rdr.read_f32::<LittleEndian>()?;
         let kerning = read_object::<Vec<Vector3>>(rdr, readers)?;
-        //XXXjdm should be full UTF-8 char read
-        let default = read_nullable::<char, _>(rdr, |rdr| {
-            rdr.read_u8().map(|b| b as char).map_err(Error::Io)
-        })?;
+        let default = read_nullable(rdr, |rdr| char::try_parse(rdr, readers, vec![]))?;
         Ok(SpriteFont {
             texture: texture,
             glyphs: glyphs,
@@ -376,7 +1240,8 @@ impl SpriteFont {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Rectangle {
     pub x: i32,
     pub y: i32,
@@ -393,32 +1258,269 @@ impl Rectangle {
             h: rdr.read_i32::<LittleEndian>()?,
         })
     }
+
+    /// One past the rightmost column in the rectangle.
+    pub fn right(&self) -> i32 {
+        self.x.saturating_add(self.w)
+    }
+
+    /// One past the bottom row in the rectangle.
+    pub fn bottom(&self) -> i32 {
+        self.y.saturating_add(self.h)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.w <= 0 || self.h <= 0
+    }
+
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        (self.x..self.right()).contains(&x) && (self.y..self.bottom()).contains(&y)
+    }
+
+    /// Whether the rectangles overlap. Rectangles that only touch don't.
+    pub fn intersects(&self, other: &Rectangle) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// The area the rectangles share, if they overlap. Empty rectangles
+    /// overlap nothing.
+    pub fn intersection(&self, other: &Rectangle) -> Option<Rectangle> {
+        if self.is_empty() || other.is_empty() {
+            return None;
+        }
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let shared = Rectangle {
+            x,
+            y,
+            w: self.right().min(other.right()).checked_sub(x)?,
+            h: self.bottom().min(other.bottom()).checked_sub(y)?,
+        };
+        if shared.is_empty() {
+            None
+        } else {
+            Some(shared)
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct Vector3(f32, f32, f32);
+impl From<(i32, i32, i32, i32)> for Rectangle {
+    fn from((x, y, w, h): (i32, i32, i32, i32)) -> Rectangle {
+        Rectangle { x, y, w, h }
+    }
+}
+
+impl From<Rectangle> for (i32, i32, i32, i32) {
+    fn from(r: Rectangle) -> (i32, i32, i32, i32) {
+        (r.x, r.y, r.w, r.h)
+    }
+}
+
+/// A .NET `System.Decimal`: a 96-bit integer mantissa, a sign and a power of
+/// ten (0-28) to divide it by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Decimal {
+    pub lo: u32,
+    pub mid: u32,
+    pub hi: u32,
+    pub flags: u32,
+}
 
-pub struct UncompressedXNB<'a>(&'a mut dyn Read);
+impl Decimal {
+    pub fn mantissa(&self) -> u128 {
+        (self.hi as u128) << 64 | (self.mid as u128) << 32 | self.lo as u128
+    }
+
+    pub fn scale(&self) -> u32 {
+        (self.flags >> 16) & 0xFF
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.flags & 0x8000_0000 != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum DateTimeKind {
+    Unspecified,
+    Utc,
+    Local,
+}
+
+/// A .NET `System.DateTime`: a count of 100ns ticks since 0001-01-01 00:00:00.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DateTime {
+    pub ticks: u64,
+    pub kind: DateTimeKind,
+}
+
+const DATE_TIME_TICKS_MASK: u64 = (1 << 62) - 1;
+
+impl DateTime {
+    // DateTimeReader stores the kind in the top two bits of the tick count.
+    fn from_packed(value: u64) -> DateTime {
+        let kind = match value >> 62 {
+            1 => DateTimeKind::Utc,
+            2 => DateTimeKind::Local,
+            _ => DateTimeKind::Unspecified,
+        };
+        DateTime {
+            ticks: value & DATE_TIME_TICKS_MASK,
+            kind,
+        }
+    }
+
+    fn packed(&self) -> u64 {
+        let kind = match self.kind {
+            DateTimeKind::Unspecified => 0,
+            DateTimeKind::Utc => 1,
+            DateTimeKind::Local => 2,
+        };
+        (self.ticks & DATE_TIME_TICKS_MASK) | kind << 62
+    }
+}
+
+/// A .NET `System.TimeSpan`: a signed count of 100ns ticks. Unsigned spans
+/// can be read directly as a `Duration` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TimeSpan {
+    pub ticks: i64,
+}
+
+const TICKS_PER_SECOND: u64 = 10_000_000;
+
+impl TimeSpan {
+    pub fn is_negative(&self) -> bool {
+        self.ticks < 0
+    }
+
+    /// The length of this span, ignoring its sign.
+    pub fn abs_duration(&self) -> Duration {
+        ticks_to_duration(self.ticks.unsigned_abs())
+    }
+}
+
+fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::new(
+        ticks / TICKS_PER_SECOND,
+        (ticks % TICKS_PER_SECOND) as u32 * 100,
+    )
+}
+
+#[cfg(feature = "chrono")]
+impl From<DateTime> for chrono::NaiveDateTime {
+    fn from(d: DateTime) -> chrono::NaiveDateTime {
+        let epoch = chrono::NaiveDate::from_ymd_opt(1, 1, 1)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .expect("0001-01-01 is a valid date");
+        epoch
+            + chrono::Duration::seconds((d.ticks / 10_000_000) as i64)
+            + chrono::Duration::nanoseconds((d.ticks % 10_000_000) as i64 * 100)
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl From<Decimal> for rust_decimal::Decimal {
+    fn from(d: Decimal) -> rust_decimal::Decimal {
+        rust_decimal::Decimal::from_parts(d.lo, d.mid, d.hi, d.is_negative(), d.scale())
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl From<rust_decimal::Decimal> for Decimal {
+    fn from(d: rust_decimal::Decimal) -> Decimal {
+        let mantissa = d.mantissa().unsigned_abs();
+        let sign = if d.is_sign_negative() { 0x8000_0000 } else { 0 };
+        Decimal {
+            lo: mantissa as u32,
+            mid: (mantissa >> 32) as u32,
+            hi: (mantissa >> 64) as u32,
+            flags: sign | d.scale() << 16,
+        }
+    }
+}
+
+pub struct UncompressedXNB<'a>(&'a mut dyn Read, usize);
 pub struct CompressedXNB<'a>(&'a mut dyn Read, usize);
 
 impl<'a> UncompressedXNB<'a> {
     pub fn xnb<T: Parse>(self) -> Result<XNB<T>, Error> {
-        XNB::from_uncompressed_buffer(self.0)
+        self.xnb_with_options(&ParseOptions::default())
+    }
+
+    pub fn xnb_with_registry<T: Parse>(self, registry: &ReaderRegistry) -> Result<XNB<T>, Error> {
+        self.xnb_with_options(&ParseOptions::new().registry(registry.clone()))
+    }
+
+    pub fn xnb_with_options<T: Parse>(self, options: &ParseOptions) -> Result<XNB<T>, Error> {
+        XNB::from_uncompressed_buffer(self.0, self.1, options)
     }
 }
 
 impl<'a> CompressedXNB<'a> {
     pub fn xnb<T: Parse>(self, window_size: WindowSize) -> Result<XNB<T>, Error> {
+        self.xnb_with_options(window_size, &ParseOptions::default())
+    }
+
+    pub fn xnb_with_registry<T: Parse>(
+        self,
+        window_size: WindowSize,
+        registry: &ReaderRegistry,
+    ) -> Result<XNB<T>, Error> {
+        self.xnb_with_options(window_size, &ParseOptions::new().registry(registry.clone()))
+    }
+
+    pub fn xnb_with_options<T: Parse>(
+        self,
+        window_size: WindowSize,
+        options: &ParseOptions,
+    ) -> Result<XNB<T>, Error> {
         let decompressed_size = self.0.read_u32::<LittleEndian>()?;
-        let buffer =
-            XNB::<T>::decompress(self.0, window_size, self.1 - 14, decompressed_size as usize)?;
-        XNB::from_uncompressed_buffer(&mut Cursor::new(&buffer))
+        let mut rdr = DecompressReader::lzx(
+            self.0,
+            window_size,
+            self.1.saturating_sub(14),
+            decompressed_size as usize,
+        );
+        if let Some(ref pool) = options.pool {
+            rdr = rdr.with_pool(pool);
+        }
+        XNB::new(Source::Read(&mut rdr), decompressed_size.into(), options)
+    }
+}
+
+pub struct Lz4CompressedXNB<'a>(&'a mut dyn Read, usize);
+
+impl<'a> Lz4CompressedXNB<'a> {
+    pub fn xnb<T: Parse>(self) -> Result<XNB<T>, Error> {
+        self.xnb_with_options(&ParseOptions::default())
+    }
+
+    pub fn xnb_with_registry<T: Parse>(self, registry: &ReaderRegistry) -> Result<XNB<T>, Error> {
+        self.xnb_with_options(&ParseOptions::new().registry(registry.clone()))
+    }
+
+    pub fn xnb_with_options<T: Parse>(self, options: &ParseOptions) -> Result<XNB<T>, Error> {
+        let decompressed_size = self.0.read_u32::<LittleEndian>()?;
+        let mut rdr = DecompressReader::lz4(
+            self.0,
+            self.1.saturating_sub(14),
+            decompressed_size as usize,
+        );
+        if let Some(ref pool) = options.pool {
+            rdr = rdr.with_pool(pool);
+        }
+        XNB::new(Source::Read(&mut rdr), decompressed_size.into(), options)
     }
 }
 
 pub enum MaybeCompressedXNB<'a> {
     Uncompressed(UncompressedXNB<'a>),
     Compressed(CompressedXNB<'a>),
+    Lz4Compressed(Lz4CompressedXNB<'a>),
 }
 
 impl<'a> MaybeCompressedXNB<'a> {
@@ -444,44 +1546,284 @@ impl<'a> MaybeCompressedXNB<'a> {
 
         let flag = rdr.read_u8()?;
         let is_compressed = flag & 0x80 != 0;
+        let is_lz4_compressed = flag & 0x40 != 0;
 
         let compressed_size = rdr.read_u32::<LittleEndian>()?;
 
         Ok(if is_compressed {
             MaybeCompressedXNB::Compressed(CompressedXNB(rdr, compressed_size as usize))
+        } else if is_lz4_compressed {
+            MaybeCompressedXNB::Lz4Compressed(Lz4CompressedXNB(rdr, compressed_size as usize))
         } else {
-            MaybeCompressedXNB::Uncompressed(UncompressedXNB(rdr))
+            MaybeCompressedXNB::Uncompressed(UncompressedXNB(rdr, compressed_size as usize))
         })
     }
 }
 
+/// Convert an uncompressed XNB file into its LZX-compressed equivalent, as
+/// produced by the XNA content pipeline.
+pub fn compress_xnb(uncompressed: &[u8]) -> Result<Vec<u8>, Error> {
+    if uncompressed.len() < 10 || &uncompressed[..3] != b"XNB" {
+        return Err(Error::Void);
+    }
+    let flag = uncompressed[5];
+    if flag & 0xC0 != 0 {
+        return Err(Error::Void);
+    }
+    let body = &uncompressed[10..];
+    let compressed = lzx_compress::compress(body);
+
+    let mut out = Vec::with_capacity(compressed.len() + 14);
+    out.extend_from_slice(&uncompressed[..5]);
+    out.push(flag | 0x80);
+    out.extend_from_slice(&((compressed.len() + 14) as u32).to_le_bytes());
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&compressed);
+    Ok(out)
+}
+
+/// Convert an XNB file into its uncompressed equivalent. Uncompressed files
+/// are returned as they are.
+pub fn decompress_xnb(xnb: &[u8]) -> Result<Vec<u8>, Error> {
+    let body = read_content(&mut &xnb[..])?;
+    let mut out = Vec::with_capacity(body.len() + 10);
+    out.extend_from_slice(&xnb[..5]);
+    out.push(xnb[5] & !0xC0);
+    out.extend_from_slice(&((body.len() + 10) as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+// The content of an XNB after its header, decompressed.
+pub(crate) fn read_content(rdr: &mut dyn Read) -> Result<Vec<u8>, Error> {
+    let mut content = vec![];
+    content_reader(rdr)?.0.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+// The content of an XNB, decompressed as it's read, and its declared size
+// once decompressed.
+pub(crate) fn content_reader<'a>(
+    rdr: &'a mut dyn Read,
+) -> Result<(Box<dyn Read + 'a>, u64), Error> {
+    Ok(match MaybeCompressedXNB::from_buffer(rdr)? {
+        MaybeCompressedXNB::Uncompressed(xnb) => (Box::new(xnb.0), xnb.1.saturating_sub(10) as u64),
+        MaybeCompressedXNB::Compressed(xnb) => {
+            let size = xnb.0.read_u32::<LittleEndian>()? as usize;
+            let compressed = xnb.1.saturating_sub(14);
+            let rdr = DecompressReader::lzx(xnb.0, DEFAULT_WINDOW_SIZE, compressed, size);
+            (Box::new(rdr), size as u64)
+        }
+        MaybeCompressedXNB::Lz4Compressed(xnb) => {
+            let size = xnb.0.read_u32::<LittleEndian>()? as usize;
+            let rdr = DecompressReader::lz4(xnb.0, xnb.1.saturating_sub(14), size);
+            (Box::new(rdr), size as u64)
+        }
+    })
+}
+
 pub struct XNB<T> {
     pub primary: T,
+    pub shared: Vec<Option<SharedResource>>,
+    /// The type reader table, in file order.
+    pub readers: Vec<TypeReader>,
+    /// Deviations from the format that were tolerated while parsing.
+    pub warnings: Vec<Error>,
+}
+
+impl<T> XNB<T> {
+    /// The shared resource at `index`, if there is one of type `R`.
+    pub fn shared_resource<R: Any>(&self, index: usize) -> Option<Rc<R>> {
+        let resource = self.shared.get(index)?.clone()?;
+        resource.downcast().ok()
+    }
+}
+
+impl<T: Parse> XNB<T> {
+    pub fn from_buffer(rdr: &mut dyn Read) -> Result<XNB<T>, Error> {
+        XNB::from_buffer_with_window_size(rdr, DEFAULT_WINDOW_SIZE)
+    }
+
+    pub fn from_buffer_with_window_size(
+        rdr: &mut dyn Read,
+        window_size: WindowSize,
+    ) -> Result<XNB<T>, Error> {
+        XNB::read(rdr, window_size, &ParseOptions::default())
+    }
+
+    /// Read an XNB that may contain objects written by readers registered
+    /// in `registry`.
+    pub fn from_buffer_with_registry(
+        rdr: &mut dyn Read,
+        registry: &ReaderRegistry,
+    ) -> Result<XNB<T>, Error> {
+        XNB::from_buffer_with_options(rdr, &ParseOptions::new().registry(registry.clone()))
+    }
+
+    /// Read an XNB with the given limits and registered readers.
+    pub fn from_buffer_with_options(
+        rdr: &mut dyn Read,
+        options: &ParseOptions,
+    ) -> Result<XNB<T>, Error> {
+        XNB::read(rdr, DEFAULT_WINDOW_SIZE, options)
+    }
+
+    /// Read an XNB from an untrusted source. Whatever the input, this returns
+    /// an error rather than panicking, overflowing the stack or allocating
+    /// without bound: sizes declared by the file are checked against the
+    /// limits of `ParseOptions::untrusted`.
+    pub fn from_buffer_checked(rdr: &mut dyn Read) -> Result<XNB<T>, Error> {
+        XNB::from_buffer_with_options(rdr, &ParseOptions::untrusted())
+    }
+
+    /// Read an XNB file by mapping it into memory rather than reading it.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this process or any
+    /// other, while it is being parsed. Doing so is undefined behavior.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_path_mmap<P: AsRef<std::path::Path>>(path: P) -> Result<XNB<T>, Error> {
+        XNB::from_path_mmap_with_options(path, &ParseOptions::default())
+    }
+
+    /// Like `from_path_mmap`, with the given limits and registered readers.
+    ///
+    /// # Safety
+    ///
+    /// As for `from_path_mmap`.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_path_mmap_with_options<P: AsRef<std::path::Path>>(
+        path: P,
+        options: &ParseOptions,
+    ) -> Result<XNB<T>, Error> {
+        let file = std::fs::File::open(path)?;
+        // Safety: the mapping is only read during parsing, and the caller
+        // guarantees the file isn't changed meanwhile.
+        let map = memmap2::Mmap::map(&file)?;
+        XNB::from_buffer_with_options(&mut &map[..], options)
+    }
+
+    /// Read an XNB from a seekable source, such as a file. With
+    /// `ParseOptions::skip_data`, uncompressed files are seeked through rather
+    /// than read past the data being skipped.
+    pub fn from_seekable_with_options<R: Read + Seek>(
+        rdr: &mut R,
+        options: &ParseOptions,
+    ) -> Result<XNB<T>, Error> {
+        let file_size = match MaybeCompressedXNB::from_buffer(rdr)? {
+            MaybeCompressedXNB::Uncompressed(xnb) => xnb.1,
+            xnb => return XNB::from_container(xnb, DEFAULT_WINDOW_SIZE, options),
+        };
+        XNB::new(
+            Source::Seek(rdr),
+            file_size.saturating_sub(10) as u64,
+            options,
+        )
+    }
+
+    fn read(
+        rdr: &mut dyn Read,
+        window_size: WindowSize,
+        options: &ParseOptions,
+    ) -> Result<XNB<T>, Error> {
+        XNB::from_container(MaybeCompressedXNB::from_buffer(rdr)?, window_size, options)
+    }
+
+    fn from_container(
+        xnb: MaybeCompressedXNB,
+        window_size: WindowSize,
+        options: &ParseOptions,
+    ) -> Result<XNB<T>, Error> {
+        match xnb {
+            MaybeCompressedXNB::Uncompressed(xnb) => xnb.xnb_with_options(options),
+            MaybeCompressedXNB::Compressed(xnb) => xnb.xnb_with_options(window_size, options),
+            MaybeCompressedXNB::Lz4Compressed(xnb) => xnb.xnb_with_options(options),
+        }
+    }
 }
 
 impl<T: Parse> XNB<T> {
-    fn new(buffer: Vec<u8>) -> Result<XNB<T>, Error> {
-        let mut rdr = Cursor::new(&buffer);
-        let num_readers = read_7bit_encoded_int(&mut rdr)?;
-        let mut readers = vec![];
+    // Parse the content of an XNB, declared to be `total` bytes long once
+    // decompressed.
+    fn new<'a>(rdr: Source<'a>, total: u64, options: &'a ParseOptions) -> Result<XNB<T>, Error> {
+        let mut readers = TypeReaders {
+            options: options.clone(),
+            ..TypeReaders::default()
+        };
+        let rdr = &mut CountingReader {
+            rdr,
+            position: readers.position.clone(),
+            skip: readers.skip.clone(),
+            total,
+            stage: ParseStage::ReaderTable,
+            options,
+        };
+        rdr.enter(ParseStage::ReaderTable);
+        let num_readers = read_7bit_encoded_int(rdr)?;
         for _ in 0..num_readers {
-            readers.push(TypeReader {
-                name: read_string(&mut rdr)?,
-                _version: rdr.read_i32::<LittleEndian>()?,
+            readers.readers.push(TypeReader {
+                name: readers.read_string(rdr)?,
+                version: rdr.read_i32::<LittleEndian>()?,
             });
             //println!("reader: {}", readers.last().unwrap().name);
         }
-        let num_shared = read_7bit_encoded_int(&mut rdr)?;
-        assert_eq!(num_shared, 0);
-        let asset = read_object(&mut rdr, &readers)?;
-        Ok(XNB { primary: asset })
+        let num_shared = read_7bit_encoded_int(rdr)?;
+        rdr.enter(ParseStage::PrimaryAsset);
+        let asset = read_object(rdr, &readers)?;
+        rdr.enter(ParseStage::SharedResources);
+        let mut shared = vec![];
+        for _ in 0..num_shared {
+            shared.push(read_any_object(rdr, &readers)?);
+        }
+        readers.resolve_shared(&shared)?;
+        let trailing = io::copy(rdr, &mut io::sink())?;
+        // Only uncompressed content can differ from its declared size; the
+        // decompressor stops at it.
+        let size = readers.position();
+        if size != total {
+            readers.recoverable(Error::FileSizeMismatch {
+                expected: total as usize + 10,
+                actual: size as usize + 10,
+            })?;
+        }
+        if trailing != 0 {
+            readers.recoverable(Error::TrailingData(trailing))?;
+        }
+        rdr.enter(ParseStage::Done);
+        Ok(XNB {
+            primary: asset,
+            shared,
+            readers: readers.readers,
+            warnings: readers.warnings.into_inner(),
+        })
     }
 }
 
-fn read_object<T: Parse>(rdr: &mut dyn Read, readers: &[TypeReader]) -> Result<T, Error> {
+fn read_object<T: Parse>(rdr: &mut dyn Read, readers: &TypeReaders) -> Result<T, Error> {
     let id = read_7bit_encoded_int(rdr)? as usize;
-    assert!(id != 0);
-    read_with_reader(&readers[id - 1].name, rdr, readers)
+    if id == 0 {
+        return Err(Error::UnexpectedNull);
+    }
+    let reader = readers
+        .get(id - 1)
+        .ok_or_else(|| Error::UnknownReader(id.to_string()))?;
+    read_with_reader(&reader.name, rdr, readers)
+}
+
+// Reads an object whose reader id may be 0, meaning null.
+fn read_optional_object<T: Parse>(
+    rdr: &mut dyn Read,
+    readers: &TypeReaders,
+) -> Result<Option<T>, Error> {
+    let id = read_7bit_encoded_int(rdr)? as usize;
+    if id == 0 {
+        return Ok(None);
+    }
+    let reader = readers
+        .get(id - 1)
+        .ok_or_else(|| Error::UnknownReader(id.to_string()))?;
+    read_with_reader(&reader.name, rdr, readers).map(Some)
 }
 
 fn read_nullable<T: Parse, F: Fn(&mut dyn Read) -> Result<T, Error>>(
@@ -500,9 +1842,229 @@ pub enum Error {
     Void,
     Io(IoError),
     Decompress(lzxd::DecodeFailed),
+    Lz4Decompress(lz4::DecodeFailed),
     UnknownReader(String),
     UnrecognizedSurfaceFormat(u32),
+    UnsupportedSurfaceFormat(SurfaceFormat),
+    UnrecognizedVertexElementFormat(u32),
+    UnrecognizedVertexElementUsage(u32),
     ReaderMismatch(String, String),
+    InvalidMipSize {
+        level: usize,
+        expected: usize,
+        actual: usize,
+    },
+    MissingMipLevel(usize),
+    /// A region that isn't entirely within its texture.
+    RegionOutOfBounds(Rectangle),
+    NegativeTimeSpan(i64),
+    LengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    SharedResourceMismatch(usize),
+    InvalidBoneReference(u32),
+    UnrecognizedCompareFunction(u32),
+    UnrecognizedSoundtrackType(i32),
+    InvalidSoundBank,
+    InvalidEngineSettings,
+    UnsupportedXactVersion(u16),
+    UnrecognizedSoundEvent(u32),
+    UnrecognizedVariationTable(u16),
+    InvalidSoundReference(u32),
+    UnrecognizedCurveType(u8),
+    Serialize(String),
+    UnsupportedUnpack(String),
+    InvalidDescriptor(String),
+    InvalidReaderName(String),
+    MissingTypeArgument(usize),
+    /// A non-null object was expected.
+    UnexpectedNull,
+    InvalidString(FromUtf8Error),
+    InvalidEncodedInt,
+    /// An unexpected tag byte at `offset` into a tide map's data.
+    InvalidTide {
+        tag: u8,
+        offset: u64,
+    },
+    /// A Tiled map or tileset that can't be read or turned into a tide map.
+    InvalidTmx(String),
+    /// Data left over after the content and shared resources.
+    TrailingData(u64),
+    FileSizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The file ended `actual` bytes into data that should be `expected`
+    /// bytes long.
+    Truncated {
+        expected: usize,
+        actual: usize,
+    },
+    /// A size read from the file is over one of the `ParseOptions` limits.
+    LimitExceeded {
+        what: &'static str,
+        requested: usize,
+        limit: usize,
+    },
+    /// Parsing was cancelled through `ParseOptions::cancel_flag`.
+    Cancelled,
+    /// An error while parsing, with the offset into the content (after the
+    /// header) where it was noticed and the readers that were active, outermost
+    /// first.
+    Parse {
+        offset: u64,
+        readers: Vec<String>,
+        error: Box<Error>,
+    },
+}
+
+impl Error {
+    fn within(self, reader: &str, offset: u64) -> Error {
+        let (offset, mut readers, error) = match self {
+            Error::Cancelled => return self,
+            Error::Parse {
+                offset,
+                readers,
+                error,
+            } => (offset, readers, error),
+            error => (offset, vec![], Box::new(error)),
+        };
+        readers.insert(0, reader.to_string());
+        Error::Parse {
+            offset,
+            readers,
+            error,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Void => write!(f, "invalid or unsupported file"),
+            Error::Cancelled => write!(f, "parsing was cancelled"),
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Decompress(ref e) => write!(f, "LZX decompression failed: {}", e),
+            Error::Lz4Decompress(ref e) => write!(f, "LZ4 decompression failed: {}", e),
+            Error::UnknownReader(ref name) => write!(f, "unknown type reader {}", name),
+            Error::UnrecognizedSurfaceFormat(v) => write!(f, "unrecognized surface format {}", v),
+            Error::UnrecognizedVertexElementFormat(v) => {
+                write!(f, "unrecognized vertex element format {}", v)
+            }
+            Error::UnrecognizedVertexElementUsage(v) => {
+                write!(f, "unrecognized vertex element usage {}", v)
+            }
+            Error::ReaderMismatch(ref found, ref expected) => {
+                write!(f, "expected reader {}, found {}", expected, found)
+            }
+            Error::InvalidMipSize {
+                level,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "mip level {} is {} bytes, expected {}",
+                level, actual, expected
+            ),
+            Error::NegativeTimeSpan(ticks) => write!(f, "negative time span of {} ticks", ticks),
+            Error::LengthMismatch { expected, actual } => {
+                write!(f, "expected {} elements, found {}", expected, actual)
+            }
+            Error::SharedResourceMismatch(index) => {
+                write!(
+                    f,
+                    "shared resource {} is missing or of the wrong type",
+                    index
+                )
+            }
+            Error::InvalidBoneReference(id) => write!(f, "invalid bone reference {}", id),
+            Error::UnrecognizedCompareFunction(v) => {
+                write!(f, "unrecognized compare function {}", v)
+            }
+            Error::UnrecognizedSoundtrackType(v) => write!(f, "unrecognized soundtrack type {}", v),
+            Error::InvalidSoundBank => write!(f, "invalid sound bank"),
+            Error::InvalidEngineSettings => write!(f, "invalid engine settings"),
+            Error::UnsupportedXactVersion(v) => write!(f, "unsupported XACT version {}", v),
+            Error::UnrecognizedSoundEvent(v) => write!(f, "unrecognized sound event {}", v),
+            Error::UnrecognizedVariationTable(v) => {
+                write!(f, "unrecognized variation table type {}", v)
+            }
+            Error::InvalidSoundReference(offset) => {
+                write!(f, "no sound at offset {:#x}", offset)
+            }
+            Error::UnrecognizedCurveType(v) => write!(f, "unrecognized curve type {}", v),
+            Error::Serialize(ref msg) => write!(f, "serialization failed: {}", msg),
+            Error::UnsupportedUnpack(ref what) => write!(f, "can't unpack {}", what),
+            Error::InvalidDescriptor(ref msg) => write!(f, "invalid descriptor: {}", msg),
+            Error::InvalidReaderName(ref name) => write!(f, "invalid reader name {}", name),
+            Error::MissingTypeArgument(index) => {
+                write!(f, "reader is missing generic argument {}", index)
+            }
+            Error::UnexpectedNull => write!(f, "unexpected null object"),
+            Error::InvalidString(ref e) => write!(f, "invalid string: {}", e),
+            Error::InvalidEncodedInt => write!(f, "7-bit encoded integer is too long"),
+            Error::InvalidTide { tag, offset } => write!(
+                f,
+                "unexpected tag {:#04x} in map data at offset {:#x}",
+                tag, offset
+            ),
+            Error::InvalidTmx(ref msg) => write!(f, "invalid TMX: {}", msg),
+            Error::TrailingData(len) => write!(f, "{} bytes of data after the content", len),
+            Error::FileSizeMismatch { expected, actual } => write!(
+                f,
+                "file is {} bytes, but the header says {}",
+                actual, expected
+            ),
+            Error::Truncated { expected, actual } => write!(
+                f,
+                "expected {} bytes of data, but only {} remain",
+                expected, actual
+            ),
+            Error::UnsupportedSurfaceFormat(format) => {
+                write!(f, "{:?} textures can't be decoded", format)
+            }
+            Error::MissingMipLevel(level) => write!(f, "texture has no mip level {}", level),
+            Error::RegionOutOfBounds(rect) => write!(
+                f,
+                "{}x{} region at ({}, {}) is outside the texture",
+                rect.w, rect.h, rect.x, rect.y
+            ),
+            Error::LimitExceeded {
+                what,
+                requested,
+                limit,
+            } => write!(
+                f,
+                "{} of {} is over the limit of {}",
+                what, requested, limit
+            ),
+            Error::Parse {
+                offset,
+                ref readers,
+                ref error,
+            } => write!(
+                f,
+                "{} at offset {:#x} (in {})",
+                error,
+                offset,
+                readers.join(" > ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Decompress(ref e) => Some(e),
+            Error::Lz4Decompress(ref e) => Some(e),
+            Error::InvalidString(ref e) => Some(e),
+            Error::Parse { ref error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
 }
 
 impl From<lzxd::DecodeFailed> for Error {
@@ -511,75 +2073,76 @@ impl From<lzxd::DecodeFailed> for Error {
     }
 }
 
+impl From<lz4::DecodeFailed> for Error {
+    fn from(e: lz4::DecodeFailed) -> Error {
+        Error::Lz4Decompress(e)
+    }
+}
+
 impl From<IoError> for Error {
     fn from(e: IoError) -> Error {
+        if e.get_ref().is_some_and(|e| e.is::<Cancelled>()) {
+            return Error::Cancelled;
+        }
+        if e.get_ref().is_some_and(|e| e.is::<Error>()) {
+            return *e.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
         Error::Io(e)
     }
 }
 
-fn read_string(rdr: &mut dyn Read) -> Result<String, Error> {
-    let len = read_7bit_encoded_int(rdr)?;
-    read_string_with_length(rdr, len)
+// Read exactly `len` bytes. Only as much as the file actually holds is
+// allocated, so a bogus length can't exhaust memory.
+pub(crate) fn read_exactly(rdr: &mut dyn Read, len: usize) -> Result<Vec<u8>, Error> {
+    read_exactly_into(rdr, len, vec![])
 }
 
-fn read_string_with_length(rdr: &mut dyn Read, len: u32) -> Result<String, Error> {
-    let mut s = String::new();
-    for _ in 0..len {
-        let val = rdr.read_u8()?;
-        s.push(val as char);
+fn read_exactly_into(rdr: &mut dyn Read, len: usize, mut buf: Vec<u8>) -> Result<Vec<u8>, Error> {
+    rdr.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(Error::Truncated {
+            expected: len,
+            actual: buf.len(),
+        });
     }
-    assert_eq!(s.len(), len as usize);
-    Ok(s)
+    Ok(buf)
+}
+
+fn read_string_with_length(rdr: &mut dyn Read, len: u32) -> Result<String, Error> {
+    let buf = read_exactly(rdr, len as usize)?;
+    String::from_utf8(buf).map_err(Error::InvalidString)
 }
 
 #[allow(dead_code)]
-fn read_7bit_encoded_int(rdr: &mut dyn Read) -> Result<u32, Error> {
+fn read_7bit_encoded_int<R: Read + ?Sized>(rdr: &mut R) -> Result<u32, Error> {
     let mut result = 0;
-    let mut bits_read = 0;
-    loop {
+    // Like .NET's BinaryReader, accept at most five bytes, the last of which
+    // only holds the top four bits.
+    for shift in (0..28).step_by(7) {
         let value = rdr.read_u8()?;
-        result |= ((value & 0x7F) as u32) << bits_read;
-        bits_read += 7;
+        result |= ((value & 0x7F) as u32) << shift;
         if value & 0x80 == 0 {
             return Ok(result);
         }
     }
+    let value = rdr.read_u8()?;
+    if value > 0x0F {
+        return Err(Error::InvalidEncodedInt);
+    }
+    Ok(result | (value as u32) << 28)
 }
 
 impl<T: Parse> XNB<T> {
-    fn decompress(
+    fn from_uncompressed_buffer(
         rdr: &mut dyn Read,
-        window_size: WindowSize,
-        _compressed_size: usize,
-        _decompressed_size: usize,
-    ) -> Result<Vec<u8>, Error> {
-        let mut lzxd = lzxd::Lzxd::new(window_size);
-        let mut compressed = vec![];
-        rdr.read_to_end(&mut compressed)?;
-        let chunk_size = 2usize.pow(match window_size {
-            WindowSize::KB32 => 15,
-            WindowSize::KB64 => 16,
-            WindowSize::KB128 => 17,
-            WindowSize::KB256 => 18,
-            WindowSize::KB512 => 19,
-            WindowSize::MB1 => 20,
-            WindowSize::MB2 => 21,
-            WindowSize::MB4 => 22,
-            WindowSize::MB8 => 23,
-            WindowSize::MB16 => 24,
-            WindowSize::MB32 => 25,
-        });
-        let mut decompressed_body = vec![];
-        for chunk in compressed.chunks(chunk_size) {
-            let decompressed = lzxd.decompress_next(&chunk)?;
-            decompressed_body.extend(&decompressed[..]);
-        }
-        Ok(decompressed_body)
-    }
-
-    fn from_uncompressed_buffer(rdr: &mut dyn Read) -> Result<XNB<T>, Error> {
-        let mut buffer = vec![];
-        rdr.read_to_end(&mut buffer)?;
-        XNB::new(buffer)
+        file_size: usize,
+        options: &ParseOptions,
+    ) -> Result<XNB<T>, Error> {
+        // The size in the header covers the whole file, header included.
+        XNB::new(
+            Source::Read(rdr),
+            file_size.saturating_sub(10) as u64,
+            options,
+        )
     }
 }
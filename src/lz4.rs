@@ -0,0 +1,171 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum DecodeFailed {
+    UnexpectedEnd,
+    InvalidOffset(usize),
+    SizeMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for DecodeFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeFailed::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeFailed::InvalidOffset(offset) => write!(f, "invalid match offset {}", offset),
+            DecodeFailed::SizeMismatch { expected, actual } => {
+                write!(f, "decompressed to {} bytes, expected {}", actual, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeFailed {}
+
+fn read_length(src: &[u8], pos: &mut usize) -> Result<usize, DecodeFailed> {
+    let mut len = 0usize;
+    loop {
+        let b = *src.get(*pos).ok_or(DecodeFailed::UnexpectedEnd)?;
+        *pos += 1;
+        len = len
+            .checked_add(b as usize)
+            .ok_or(DecodeFailed::UnexpectedEnd)?;
+        if b != 0xFF {
+            return Ok(len);
+        }
+    }
+}
+
+/// Decompress a single raw LZ4 block, as written by the MonoGame content pipeline.
+pub fn decompress(src: &[u8], decompressed_size: usize) -> Result<Vec<u8>, DecodeFailed> {
+    let mut dst = Vec::with_capacity(decompressed_size);
+    let mut pos = 0;
+    loop {
+        let token = *src.get(pos).ok_or(DecodeFailed::UnexpectedEnd)?;
+        pos += 1;
+
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_length(src, &mut pos)?;
+        }
+        let end = pos
+            .checked_add(literals)
+            .filter(|&end| end <= src.len())
+            .ok_or(DecodeFailed::UnexpectedEnd)?;
+        dst.extend_from_slice(&src[pos..end]);
+        pos = end;
+
+        // The last sequence of a block only contains literals.
+        if pos == src.len() {
+            break;
+        }
+
+        let offset = match src.get(pos..pos + 2) {
+            Some(b) => b[0] as usize | (b[1] as usize) << 8,
+            None => return Err(DecodeFailed::UnexpectedEnd),
+        };
+        pos += 2;
+        if offset == 0 || offset > dst.len() {
+            return Err(DecodeFailed::InvalidOffset(offset));
+        }
+
+        let mut match_len = (token & 0x0F) as usize;
+        if match_len == 15 {
+            match_len += read_length(src, &mut pos)?;
+        }
+        match_len += 4;
+        if dst.len() + match_len > decompressed_size {
+            return Err(DecodeFailed::SizeMismatch {
+                expected: decompressed_size,
+                actual: dst.len() + match_len,
+            });
+        }
+
+        // Matches may overlap the bytes they produce, so copy one at a time.
+        let start = dst.len() - offset;
+        for i in 0..match_len {
+            let b = dst[start + i];
+            dst.push(b);
+        }
+    }
+
+    if dst.len() != decompressed_size {
+        return Err(DecodeFailed::SizeMismatch {
+            expected: decompressed_size,
+            actual: dst.len(),
+        });
+    }
+    Ok(dst)
+}
+
+const MIN_MATCH: usize = 4;
+// The last match must start at least 12 bytes before the end of the block,
+// and the last 5 bytes are always literals.
+const MF_LIMIT: usize = 12;
+const LAST_LITERALS: usize = 5;
+const MAX_OFFSET: usize = 0xFFFF;
+const HASH_BITS: u32 = 16;
+
+fn hash(b: &[u8]) -> usize {
+    let v = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    (v.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+fn write_length(dst: &mut Vec<u8>, mut len: usize) {
+    while len >= 0xFF {
+        dst.push(0xFF);
+        len -= 0xFF;
+    }
+    dst.push(len as u8);
+}
+
+fn write_sequence(dst: &mut Vec<u8>, literals: &[u8], m: Option<(usize, usize)>) {
+    let lit_nibble = literals.len().min(15);
+    let match_nibble = m.map_or(0, |(_, len)| (len - MIN_MATCH).min(15));
+    dst.push((lit_nibble << 4 | match_nibble) as u8);
+    if literals.len() >= 15 {
+        write_length(dst, literals.len() - 15);
+    }
+    dst.extend_from_slice(literals);
+    if let Some((offset, len)) = m {
+        dst.push(offset as u8);
+        dst.push((offset >> 8) as u8);
+        if len - MIN_MATCH >= 15 {
+            write_length(dst, len - MIN_MATCH - 15);
+        }
+    }
+}
+
+/// Compress `src` into a single raw LZ4 block, as read by the MonoGame
+/// content pipeline.
+pub fn compress(src: &[u8]) -> Vec<u8> {
+    let mut dst = Vec::with_capacity(src.len() / 2 + 16);
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut pos = 0;
+    let match_limit = src.len().saturating_sub(MF_LIMIT);
+
+    while pos < match_limit {
+        let h = hash(&src[pos..]);
+        let candidate = table[h];
+        table[h] = pos;
+        if candidate == usize::MAX
+            || pos - candidate > MAX_OFFSET
+            || src[candidate..candidate + MIN_MATCH] != src[pos..pos + MIN_MATCH]
+        {
+            pos += 1;
+            continue;
+        }
+
+        let max_len = src.len() - LAST_LITERALS - pos;
+        let mut len = MIN_MATCH;
+        while len < max_len && src[candidate + len] == src[pos + len] {
+            len += 1;
+        }
+        write_sequence(&mut dst, &src[anchor..pos], Some((pos - candidate, len)));
+        pos += len;
+        anchor = pos;
+    }
+
+    write_sequence(&mut dst, &src[anchor..], None);
+    dst
+}
@@ -0,0 +1,478 @@
+// A small LZX compressor producing the framed stream that the XNA runtime
+// expects: every 32KB frame becomes one verbatim (or, if that doesn't pay
+// off, uncompressed) block, prefixed by the XNB frame header.
+
+const NUM_CHARS: usize = 256;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 257;
+const NUM_PRIMARY_LENGTHS: usize = 7;
+const NUM_SECONDARY_LENGTHS: usize = 249;
+const PRETREE_NUM_ELEMENTS: usize = 20;
+
+// The stock XNA decoder always uses a 64KB window, which has 32 position slots.
+const WINDOW_SIZE: usize = 0x10000;
+const NUM_POSITION_SLOTS: usize = 32;
+const MAIN_TREE_ELEMENTS: usize = NUM_CHARS + NUM_POSITION_SLOTS * 8;
+const MAX_DISTANCE: usize = WINDOW_SIZE - 3;
+
+const FRAME_SIZE: usize = 0x8000;
+
+const BLOCKTYPE_VERBATIM: u32 = 1;
+const BLOCKTYPE_UNCOMPRESSED: u32 = 3;
+
+const HASH_BITS: usize = 15;
+const MAX_CHAIN: usize = 64;
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+// LZX reads its bitstream as little-endian 16-bit words, most significant
+// bit first.
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            out: vec![],
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    fn write(&mut self, value: u32, bits: u32) {
+        if bits == 0 {
+            return;
+        }
+        self.acc = (self.acc << bits) | (value as u64 & ((1 << bits) - 1));
+        self.bits += bits;
+        while self.bits >= 16 {
+            let word = (self.acc >> (self.bits - 16)) as u16;
+            self.out.push(word as u8);
+            self.out.push((word >> 8) as u8);
+            self.bits -= 16;
+        }
+        self.acc &= (1 << self.bits) - 1;
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 16 - self.bits);
+        }
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.out
+    }
+}
+
+struct Tables {
+    extra_bits: [u32; NUM_POSITION_SLOTS],
+    position_base: [u32; NUM_POSITION_SLOTS],
+}
+
+impl Tables {
+    fn new() -> Tables {
+        let mut extra_bits = [0; NUM_POSITION_SLOTS];
+        let mut position_base = [0; NUM_POSITION_SLOTS];
+        let mut bits = 0;
+        for i in (0..NUM_POSITION_SLOTS).step_by(2) {
+            extra_bits[i] = bits;
+            extra_bits[i + 1] = bits;
+            if i != 0 && bits < 17 {
+                bits += 1;
+            }
+        }
+        let mut base = 0;
+        for i in 0..NUM_POSITION_SLOTS {
+            position_base[i] = base;
+            base += 1 << extra_bits[i];
+        }
+        Tables {
+            extra_bits,
+            position_base,
+        }
+    }
+
+    fn position_slot(&self, formatted_offset: u32) -> usize {
+        self.position_base
+            .iter()
+            .rposition(|&base| base <= formatted_offset)
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match { length: usize, distance: usize },
+}
+
+struct Matcher {
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+const NIL: usize = usize::MAX;
+
+impl Matcher {
+    fn new() -> Matcher {
+        Matcher {
+            head: vec![NIL; 1 << HASH_BITS],
+            prev: vec![NIL; WINDOW_SIZE],
+        }
+    }
+
+    fn hash(data: &[u8], pos: usize) -> usize {
+        let v = (data[pos] as usize) << 16 | (data[pos + 1] as usize) << 8 | data[pos + 2] as usize;
+        (v.wrapping_mul(2654435761) >> 8) & ((1 << HASH_BITS) - 1)
+    }
+
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if pos + MIN_MATCH > data.len() {
+            return;
+        }
+        let h = Matcher::hash(data, pos);
+        self.prev[pos % WINDOW_SIZE] = self.head[h];
+        self.head[h] = pos;
+    }
+
+    fn longest_match(&self, data: &[u8], pos: usize, limit: usize) -> Option<(usize, usize)> {
+        let max_len = (limit - pos).min(MAX_MATCH);
+        if max_len < MIN_MATCH {
+            return None;
+        }
+        let mut best: Option<(usize, usize)> = None;
+        let mut candidate = self.head[Matcher::hash(data, pos)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == NIL || candidate >= pos || pos - candidate > MAX_DISTANCE {
+                break;
+            }
+            let len = data[candidate..]
+                .iter()
+                .zip(&data[pos..pos + max_len])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len >= MIN_MATCH && best.is_none_or(|(best_len, _)| len > best_len) {
+                best = Some((len, pos - candidate));
+                if len == max_len {
+                    break;
+                }
+            }
+            let next = self.prev[candidate % WINDOW_SIZE];
+            if next != NIL && next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        best
+    }
+}
+
+// Compute Huffman code lengths for the given symbol frequencies, limited to
+// `max_bits`. Trees always end up with at least two symbols, since decoders
+// reject incomplete code tables.
+fn code_lengths(freqs: &[u32], max_bits: u8) -> Vec<u8> {
+    let mut freqs = freqs.to_vec();
+    let used = freqs.iter().filter(|&&f| f > 0).count();
+    if used < 2 {
+        for f in freqs.iter_mut().take(2) {
+            if *f == 0 {
+                *f = 1;
+            }
+        }
+        if freqs.iter().filter(|&&f| f > 0).count() < 2 {
+            freqs[2] = 1;
+        }
+    }
+
+    loop {
+        let lengths = huffman_lengths(&freqs);
+        if lengths.iter().all(|&l| l <= max_bits) {
+            return lengths;
+        }
+        // Flatten the distribution until the tree is shallow enough.
+        for f in freqs.iter_mut() {
+            if *f > 0 {
+                *f = (*f >> 1) | 1;
+            }
+        }
+    }
+}
+
+fn huffman_lengths(freqs: &[u32]) -> Vec<u8> {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    // Nodes are leaves (one per used symbol) followed by internal nodes.
+    let mut parent = vec![];
+    let mut heap = BinaryHeap::new();
+    let mut leaves = vec![];
+    for (sym, &f) in freqs.iter().enumerate() {
+        if f > 0 {
+            heap.push(Reverse((f as u64, parent.len())));
+            parent.push(0);
+            leaves.push(sym);
+        }
+    }
+    while heap.len() > 1 {
+        let Reverse((fa, a)) = heap.pop().unwrap();
+        let Reverse((fb, b)) = heap.pop().unwrap();
+        let node = parent.len();
+        parent.push(0);
+        parent[a] = node;
+        parent[b] = node;
+        heap.push(Reverse((fa + fb, node)));
+    }
+
+    let root = parent.len() - 1;
+    let mut depth = vec![0u8; parent.len()];
+    for node in (0..root).rev() {
+        depth[node] = depth[parent[node]] + 1;
+    }
+    let mut lengths = vec![0; freqs.len()];
+    for (leaf, &sym) in leaves.iter().enumerate() {
+        lengths[sym] = depth[leaf];
+    }
+    lengths
+}
+
+// Assign canonical codes: shorter codes first, ties broken by symbol order.
+fn canonical_codes(lengths: &[u8]) -> Vec<u32> {
+    let mut codes = vec![0; lengths.len()];
+    let mut code = 0u32;
+    for len in 1..=16u8 {
+        for (sym, &l) in lengths.iter().enumerate() {
+            if l == len {
+                codes[sym] = code;
+                code += 1;
+            }
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+struct Tree {
+    lengths: Vec<u8>,
+    codes: Vec<u32>,
+}
+
+impl Tree {
+    fn new(freqs: &[u32], max_bits: u8) -> Tree {
+        let lengths = code_lengths(freqs, max_bits);
+        let codes = canonical_codes(&lengths);
+        Tree { lengths, codes }
+    }
+
+    fn write(&self, out: &mut BitWriter, sym: usize) {
+        out.write(self.codes[sym], self.lengths[sym] as u32);
+    }
+}
+
+// Encode a range of tree lengths as deltas against the previous block's
+// lengths, using the pretree.
+fn write_lengths(out: &mut BitWriter, prev: &[u8], lengths: &[u8]) {
+    let mut symbols = vec![];
+    let mut i = 0;
+    while i < lengths.len() {
+        let zeros = lengths[i..].iter().take_while(|&&l| l == 0).count();
+        if zeros >= 20 {
+            let run = zeros.min(51);
+            symbols.push((18, Some((run as u32 - 20, 5))));
+            i += run;
+        } else if zeros >= 4 {
+            symbols.push((17, Some((zeros as u32 - 4, 4))));
+            i += zeros;
+        } else {
+            let delta = (17 + prev[i] as usize - lengths[i] as usize) % 17;
+            symbols.push((delta, None));
+            i += 1;
+        }
+    }
+
+    let mut freqs = [0; PRETREE_NUM_ELEMENTS];
+    for &(sym, _) in &symbols {
+        freqs[sym] += 1;
+    }
+    let pretree = Tree::new(&freqs, 15);
+    for &len in &pretree.lengths {
+        out.write(len as u32, 4);
+    }
+    for (sym, extra) in symbols {
+        pretree.write(out, sym);
+        if let Some((value, bits)) = extra {
+            out.write(value, bits);
+        }
+    }
+}
+
+struct Encoder {
+    tables: Tables,
+    matcher: Matcher,
+    main_lengths: Vec<u8>,
+    length_lengths: Vec<u8>,
+    header_written: bool,
+}
+
+impl Encoder {
+    fn new() -> Encoder {
+        Encoder {
+            tables: Tables::new(),
+            matcher: Matcher::new(),
+            main_lengths: vec![0; MAIN_TREE_ELEMENTS],
+            length_lengths: vec![0; NUM_SECONDARY_LENGTHS],
+            header_written: false,
+        }
+    }
+
+    fn tokenize(&mut self, data: &[u8], start: usize, end: usize) -> Vec<Token> {
+        let mut tokens = vec![];
+        let mut pos = start;
+        while pos < end {
+            match self.matcher.longest_match(data, pos, end) {
+                Some((length, distance)) => {
+                    tokens.push(Token::Match { length, distance });
+                    for p in pos..pos + length {
+                        self.matcher.insert(data, p);
+                    }
+                    pos += length;
+                }
+                None => {
+                    tokens.push(Token::Literal(data[pos]));
+                    self.matcher.insert(data, pos);
+                    pos += 1;
+                }
+            }
+        }
+        tokens
+    }
+
+    fn write_block_header(&mut self, out: &mut BitWriter, block_type: u32, size: usize) {
+        if !self.header_written {
+            // No Intel E8 call translation.
+            out.write(0, 1);
+            self.header_written = true;
+        }
+        out.write(block_type, 3);
+        out.write(size as u32, 24);
+    }
+
+    fn verbatim_block(&mut self, tokens: &[Token], size: usize) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let mut main_freqs = vec![0; MAIN_TREE_ELEMENTS];
+        let mut length_freqs = vec![0; NUM_SECONDARY_LENGTHS];
+        let mut encoded = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            match *token {
+                Token::Literal(b) => {
+                    main_freqs[b as usize] += 1;
+                    encoded.push((b as usize, None, None));
+                }
+                Token::Match { length, distance } => {
+                    let formatted = distance as u32 + 2;
+                    let slot = self.tables.position_slot(formatted);
+                    let footer = (length - 2).saturating_sub(NUM_PRIMARY_LENGTHS);
+                    let header = (length - 2).min(NUM_PRIMARY_LENGTHS);
+                    let main = NUM_CHARS + (slot << 3 | header);
+                    main_freqs[main] += 1;
+                    let length_sym = if header == NUM_PRIMARY_LENGTHS {
+                        length_freqs[footer] += 1;
+                        Some(footer)
+                    } else {
+                        None
+                    };
+                    let verbatim = (
+                        formatted - self.tables.position_base[slot],
+                        self.tables.extra_bits[slot],
+                    );
+                    encoded.push((main, length_sym, Some(verbatim)));
+                }
+            }
+        }
+
+        let main_tree = Tree::new(&main_freqs, 16);
+        let length_tree = Tree::new(&length_freqs, 16);
+
+        let mut out = BitWriter::new();
+        self.write_block_header(&mut out, BLOCKTYPE_VERBATIM, size);
+        write_lengths(
+            &mut out,
+            &self.main_lengths[..NUM_CHARS],
+            &main_tree.lengths[..NUM_CHARS],
+        );
+        write_lengths(
+            &mut out,
+            &self.main_lengths[NUM_CHARS..],
+            &main_tree.lengths[NUM_CHARS..],
+        );
+        write_lengths(&mut out, &self.length_lengths, &length_tree.lengths);
+
+        for (main, length_sym, verbatim) in encoded {
+            main_tree.write(&mut out, main);
+            if let Some(sym) = length_sym {
+                length_tree.write(&mut out, sym);
+            }
+            if let Some((value, bits)) = verbatim {
+                out.write(value, bits);
+            }
+        }
+        (out.into_bytes(), main_tree.lengths, length_tree.lengths)
+    }
+
+    fn uncompressed_block(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = BitWriter::new();
+        self.write_block_header(&mut out, BLOCKTYPE_UNCOMPRESSED, data.len());
+        // The decoder discards 1-16 bits to reach a 16-bit boundary.
+        if out.bits == 0 {
+            out.write(0, 16);
+        }
+        let mut block = out.into_bytes();
+        // Repeated offsets R0, R1 and R2.
+        for _ in 0..3 {
+            block.extend_from_slice(&1u32.to_le_bytes());
+        }
+        block.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            block.push(0);
+        }
+        block
+    }
+
+    fn frame(&mut self, data: &[u8], start: usize, end: usize) -> Vec<u8> {
+        let header_written = self.header_written;
+        let tokens = self.tokenize(data, start, end);
+        let (block, main_lengths, length_lengths) = self.verbatim_block(&tokens, end - start);
+        // Incompressible frames are stored as they are; the tree lengths the
+        // decoder remembers are only updated by the blocks it actually sees.
+        if block.len() < end - start {
+            self.main_lengths = main_lengths;
+            self.length_lengths = length_lengths;
+            block
+        } else {
+            self.header_written = header_written;
+            self.uncompressed_block(&data[start..end])
+        }
+    }
+}
+
+/// Compress `data` into a sequence of LZX frames, each preceded by the frame
+/// header used in XNB files.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    let mut out = vec![];
+    let mut start = 0;
+    while start < data.len() {
+        let end = (start + FRAME_SIZE).min(data.len());
+        let block = encoder.frame(data, start, end);
+        let frame_size = end - start;
+        if frame_size != FRAME_SIZE {
+            out.push(0xFF);
+            out.extend_from_slice(&(frame_size as u16).to_be_bytes());
+        }
+        out.extend_from_slice(&(block.len() as u16).to_be_bytes());
+        out.extend_from_slice(&block);
+        start = end;
+    }
+    out
+}
@@ -0,0 +1,397 @@
+use crate::writer::framework_type;
+use crate::{Error, Parse, Serialize, TypeReaders, TypeWriters};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Vector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vector3 {
+    pub const ZERO: Vector3 = Vector3::splat(0.0);
+    pub const ONE: Vector3 = Vector3::splat(1.0);
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+
+    /// A vector with all three components set to `v`.
+    pub const fn splat(v: f32) -> Vector3 {
+        Vector3::new(v, v, v)
+    }
+
+    pub fn dot(self, other: Vector3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Vector3) -> Vector3 {
+        Vector3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// The vector scaled to a length of 1. The zero vector gives NaNs, as in
+    /// XNA.
+    pub fn normalize(self) -> Vector3 {
+        self / self.length()
+    }
+}
+
+impl From<[f32; 3]> for Vector3 {
+    fn from([x, y, z]: [f32; 3]) -> Vector3 {
+        Vector3::new(x, y, z)
+    }
+}
+
+impl From<(f32, f32, f32)> for Vector3 {
+    fn from((x, y, z): (f32, f32, f32)) -> Vector3 {
+        Vector3::new(x, y, z)
+    }
+}
+
+impl From<Vector3> for [f32; 3] {
+    fn from(v: Vector3) -> [f32; 3] {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl Neg for Vector3 {
+    type Output = Vector3;
+    fn neg(self) -> Vector3 {
+        Vector3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+// Componentwise operations between vectors, and with scalars.
+macro_rules! vector3_op {
+    ($op:ident, $method:ident, $assign:ident, $assign_method:ident) => {
+        impl $op for Vector3 {
+            type Output = Vector3;
+            fn $method(self, other: Vector3) -> Vector3 {
+                Vector3::new(
+                    self.x.$method(other.x),
+                    self.y.$method(other.y),
+                    self.z.$method(other.z),
+                )
+            }
+        }
+
+        impl $op<f32> for Vector3 {
+            type Output = Vector3;
+            fn $method(self, other: f32) -> Vector3 {
+                self.$method(Vector3::splat(other))
+            }
+        }
+
+        impl $assign for Vector3 {
+            fn $assign_method(&mut self, other: Vector3) {
+                *self = self.$method(other);
+            }
+        }
+
+        impl $assign<f32> for Vector3 {
+            fn $assign_method(&mut self, other: f32) {
+                *self = self.$method(other);
+            }
+        }
+    };
+}
+
+vector3_op!(Add, add, AddAssign, add_assign);
+vector3_op!(Sub, sub, SubAssign, sub_assign);
+vector3_op!(Mul, mul, MulAssign, mul_assign);
+vector3_op!(Div, div, DivAssign, div_assign);
+
+impl Mul<Vector3> for f32 {
+    type Output = Vector3;
+    fn mul(self, v: Vector3) -> Vector3 {
+        v * self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+/// A 4x4 matrix, stored row by row (`m[0]` holds M11 through M14).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Matrix {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix {
+    pub const IDENTITY: Matrix = Matrix {
+        m: [
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ],
+    };
+}
+
+/// A non-premultiplied 8-bit RGBA color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Parse for Color {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.ColorReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let mut rgba = [0; 4];
+        rdr.read_exact(&mut rgba)?;
+        Ok(Color {
+            r: rgba[0],
+            g: rgba[1],
+            b: rgba[2],
+            a: rgba[3],
+        })
+    }
+}
+
+impl Parse for Quaternion {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.QuaternionReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(Quaternion {
+            x: rdr.read_f32::<LittleEndian>()?,
+            y: rdr.read_f32::<LittleEndian>()?,
+            z: rdr.read_f32::<LittleEndian>()?,
+            w: rdr.read_f32::<LittleEndian>()?,
+        })
+    }
+}
+
+impl Parse for Matrix {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.MatrixReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let mut m = [[0.; 4]; 4];
+        for row in &mut m {
+            rdr.read_f32_into::<LittleEndian>(row)?;
+        }
+        Ok(Matrix { m })
+    }
+}
+
+impl Serialize for Color {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.ColorReader".to_string()
+    }
+    fn type_name() -> String {
+        framework_type("Microsoft.Xna.Framework.Color")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_all(&[self.r, self.g, self.b, self.a])
+            .map_err(Error::from)
+    }
+}
+
+impl Serialize for Quaternion {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.QuaternionReader".to_string()
+    }
+    fn type_name() -> String {
+        framework_type("Microsoft.Xna.Framework.Quaternion")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_f32::<LittleEndian>(self.x)?;
+        wtr.write_f32::<LittleEndian>(self.y)?;
+        wtr.write_f32::<LittleEndian>(self.z)?;
+        wtr.write_f32::<LittleEndian>(self.w)?;
+        Ok(())
+    }
+}
+
+impl Serialize for Matrix {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.MatrixReader".to_string()
+    }
+    fn type_name() -> String {
+        framework_type("Microsoft.Xna.Framework.Matrix")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        for v in self.m.iter().flatten() {
+            wtr.write_f32::<LittleEndian>(*v)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BoundingBox {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BoundingSphere {
+    pub center: Vector3,
+    pub radius: f32,
+}
+
+/// A plane of points `p` satisfying `normal . p + d = 0`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Plane {
+    pub normal: Vector3,
+    pub d: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Ray {
+    pub position: Vector3,
+    pub direction: Vector3,
+}
+
+pub(crate) fn read_vector3(rdr: &mut dyn Read) -> Result<Vector3, Error> {
+    Ok(Vector3 {
+        x: rdr.read_f32::<LittleEndian>()?,
+        y: rdr.read_f32::<LittleEndian>()?,
+        z: rdr.read_f32::<LittleEndian>()?,
+    })
+}
+
+impl Parse for BoundingBox {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.BoundingBoxReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(BoundingBox {
+            min: read_vector3(rdr)?,
+            max: read_vector3(rdr)?,
+        })
+    }
+}
+
+impl Parse for BoundingSphere {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.BoundingSphereReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(BoundingSphere {
+            center: read_vector3(rdr)?,
+            radius: rdr.read_f32::<LittleEndian>()?,
+        })
+    }
+}
+
+impl Parse for Plane {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.PlaneReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(Plane {
+            normal: read_vector3(rdr)?,
+            d: rdr.read_f32::<LittleEndian>()?,
+        })
+    }
+}
+
+impl Parse for Ray {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.RayReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Ok(Ray {
+            position: read_vector3(rdr)?,
+            direction: read_vector3(rdr)?,
+        })
+    }
+}
+
+impl Serialize for BoundingBox {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.BoundingBoxReader".to_string()
+    }
+    fn type_name() -> String {
+        framework_type("Microsoft.Xna.Framework.BoundingBox")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        self.min.serialize(wtr, writers)?;
+        self.max.serialize(wtr, writers)
+    }
+}
+
+impl Serialize for BoundingSphere {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.BoundingSphereReader".to_string()
+    }
+    fn type_name() -> String {
+        framework_type("Microsoft.Xna.Framework.BoundingSphere")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        self.center.serialize(wtr, writers)?;
+        wtr.write_f32::<LittleEndian>(self.radius)
+            .map_err(Error::from)
+    }
+}
+
+impl Serialize for Plane {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.PlaneReader".to_string()
+    }
+    fn type_name() -> String {
+        framework_type("Microsoft.Xna.Framework.Plane")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        self.normal.serialize(wtr, writers)?;
+        wtr.write_f32::<LittleEndian>(self.d).map_err(Error::from)
+    }
+}
+
+impl Serialize for Ray {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.RayReader".to_string()
+    }
+    fn type_name() -> String {
+        framework_type("Microsoft.Xna.Framework.Ray")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        self.position.serialize(wtr, writers)?;
+        self.direction.serialize(wtr, writers)
+    }
+}
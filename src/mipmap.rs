@@ -0,0 +1,176 @@
+//! Generation of mip levels for 8-bit RGBA textures.
+
+use crate::{dxt, Error, SurfaceFormat, Texture2d};
+use std::f32::consts::PI;
+
+/// How each mip level is filtered down from the one above it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipFilter {
+    /// Averages each 2x2 block of pixels.
+    Box,
+    /// A Kaiser windowed sinc, as used by NVIDIA's texture tools. Sharper
+    /// than `Box`, at the cost of some ringing around hard edges.
+    Kaiser,
+}
+
+impl Texture2d {
+    /// Replace any mip levels below the first with a full chain down to 1x1,
+    /// filtered from the first level. Only `Color` and DXT textures (and
+    /// their sRGB variants) are supported; DXT levels are decoded and
+    /// compressed again.
+    pub fn generate_mips(&mut self, filter: MipFilter) -> Result<(), Error> {
+        let color = self.format.without_srgb() == SurfaceFormat::Color;
+        if !color && !self.format.is_compressed() {
+            return Err(Error::UnsupportedSurfaceFormat(self.format));
+        }
+        let top = self.decode(0)?;
+        let mut mips = vec![];
+        let (mut width, mut height) = (top.width, top.height);
+        for pixels in chain(&top.pixels, width, height, filter) {
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+            mips.push(match color {
+                true => pixels,
+                false => dxt::encode(self.format, &pixels, width, height)?,
+            });
+        }
+        self.mip_data.truncate(1);
+        self.mip_data.extend(mips);
+        Ok(())
+    }
+}
+
+// Halves an image in each dimension (down to 1), averaging each 2x2 block of
+// pixels. A trailing odd row or column is averaged into the last pixel.
+pub(crate) fn half_size(pixels: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+    let (w, h) = ((width / 2).max(1), (height / 2).max(1));
+    let span = |i: usize, n: usize, len: usize| i * 2..if i + 1 == n { len } else { i * 2 + 2 };
+    let mut out = Vec::with_capacity(w * h * 4);
+    for y in 0..h {
+        for x in 0..w {
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for row in span(y, h, height) {
+                for column in span(x, w, width) {
+                    let i = (row * width + column) * 4;
+                    for (s, &c) in sum.iter_mut().zip(&pixels[i..i + 4]) {
+                        *s += c as u32;
+                    }
+                    count += 1;
+                }
+            }
+            out.extend(sum.iter().map(|s| ((s + count / 2) / count) as u8));
+        }
+    }
+    (out, w, h)
+}
+
+// Halves an image in each dimension (down to 1) with a Kaiser filter, one
+// axis at a time.
+fn kaiser_half_size(pixels: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+    let (w, h) = ((width / 2).max(1), (height / 2).max(1));
+    let src: Vec<f32> = pixels.iter().map(|&c| c as f32).collect();
+    let columns = kaiser_weights(width, w);
+    let mut across = vec![0.0; w * height * 4];
+    for y in 0..height {
+        for (x, taps) in columns.iter().enumerate() {
+            let out = &mut across[(y * w + x) * 4..][..4];
+            for &(column, weight) in taps {
+                let i = (y * width + column) * 4;
+                for (o, c) in out.iter_mut().zip(&src[i..i + 4]) {
+                    *o += c * weight;
+                }
+            }
+        }
+    }
+    let rows = kaiser_weights(height, h);
+    let mut out = Vec::with_capacity(w * h * 4);
+    for taps in &rows {
+        for x in 0..w {
+            let mut sum = [0.0f32; 4];
+            for &(row, weight) in taps {
+                let i = (row * w + x) * 4;
+                for (s, c) in sum.iter_mut().zip(&across[i..i + 4]) {
+                    *s += c * weight;
+                }
+            }
+            out.extend(sum.iter().map(|s| s.round().clamp(0.0, 255.0) as u8));
+        }
+    }
+    (out, w, h)
+}
+
+// For each of `to` pixels resampled from `from`, the source pixels it's made
+// from and their normalized weights. Taps beyond the edges are clamped.
+fn kaiser_weights(from: usize, to: usize) -> Vec<Vec<(usize, f32)>> {
+    const WIDTH: f32 = 3.0;
+    const ALPHA: f32 = 4.0;
+    let scale = from as f32 / to as f32;
+    let radius = WIDTH * scale;
+    (0..to)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let first = (center - radius).floor() as isize;
+            let last = (center + radius).ceil() as isize;
+            let mut taps: Vec<(usize, f32)> = vec![];
+            for j in first..=last {
+                let t = (j as f32 + 0.5 - center) / scale;
+                if t.abs() >= WIDTH {
+                    continue;
+                }
+                let weight = sinc(t) * bessel_i0(ALPHA * (1.0 - (t / WIDTH).powi(2)).sqrt())
+                    / bessel_i0(ALPHA);
+                let index = j.clamp(0, from as isize - 1) as usize;
+                match taps.iter_mut().find(|(k, _)| *k == index) {
+                    Some(tap) => tap.1 += weight,
+                    None => taps.push((index, weight)),
+                }
+            }
+            let total: f32 = taps.iter().map(|(_, w)| w).sum();
+            taps.iter().map(|&(j, w)| (j, w / total)).collect()
+        })
+        .collect()
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-4 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// The zeroth order modified Bessel function of the first kind, by its
+// power series.
+fn bessel_i0(x: f32) -> f32 {
+    let (mut sum, mut term) = (1.0, 1.0);
+    let mut k = 1.0;
+    while term > sum * 1e-8 {
+        term *= (x / (2.0 * k)).powi(2);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+// The mip levels below an image, down to 1x1.
+pub(crate) fn chain(
+    pixels: &[u8],
+    mut width: usize,
+    mut height: usize,
+    filter: MipFilter,
+) -> Vec<Vec<u8>> {
+    let half_size = match filter {
+        MipFilter::Box => half_size,
+        MipFilter::Kaiser => kaiser_half_size,
+    };
+    let mut mips: Vec<Vec<u8>> = vec![];
+    while width > 1 || height > 1 {
+        let level = mips.last().map_or(pixels, |mip| &mip[..]);
+        let (next, w, h) = half_size(level, width, height);
+        mips.push(next);
+        width = w;
+        height = h;
+    }
+    mips
+}
@@ -0,0 +1,226 @@
+use crate::shared::read_any_object;
+use crate::{
+    read_optional_object, BoundingSphere, Error, IndexBuffer, Matrix, Parse, Shared,
+    SharedResource, TypeReaders, VertexBuffer,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::any::Any;
+use std::fmt;
+use std::io::Read;
+use std::ops::Deref;
+
+/// An arbitrary object attached to a model by the content pipeline.
+#[derive(Clone)]
+pub struct Tag(pub SharedResource);
+
+impl Deref for Tag {
+    type Target = dyn Any;
+    fn deref(&self) -> &dyn Any {
+        &*self.0
+    }
+}
+
+impl fmt::Debug for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Tag(..)")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelBone {
+    pub name: Option<String>,
+    pub transform: Matrix,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelMeshPart {
+    pub vertex_offset: u32,
+    pub num_vertices: u32,
+    pub start_index: u32,
+    pub primitive_count: u32,
+    pub tag: Option<Tag>,
+    pub vertex_buffer: Option<Shared<VertexBuffer>>,
+    pub index_buffer: Option<Shared<IndexBuffer>>,
+    /// The effect used to draw this part. Its type depends on the effect
+    /// reader used; downcast it to get at the parameters.
+    pub effect: Option<Shared<dyn Any>>,
+}
+
+impl ModelMeshPart {
+    /// The effect used to draw this part, if it is a `T`, e.g. `BasicEffect`.
+    pub fn effect_as<T: Any>(&self) -> Option<&T> {
+        self.effect.as_ref()?.downcast_ref()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelMesh {
+    pub name: Option<String>,
+    pub parent_bone: Option<usize>,
+    pub bounds: BoundingSphere,
+    pub tag: Option<Tag>,
+    pub parts: Vec<ModelMeshPart>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Model {
+    pub bones: Vec<ModelBone>,
+    pub meshes: Vec<ModelMesh>,
+    pub root: Option<usize>,
+    pub tag: Option<Tag>,
+}
+
+impl Model {
+    /// The transform of `bone` relative to the root of the model, or `None`
+    /// if there's no such bone or its parents loop.
+    pub fn absolute_transform(&self, bone: usize) -> Option<Matrix> {
+        let mut transform = self.bones.get(bone)?.transform;
+        let mut parent = self.bones[bone].parent;
+        for _ in 0..self.bones.len() {
+            let idx = match parent {
+                Some(idx) => idx,
+                None => return Some(transform),
+            };
+            let bone = self.bones.get(idx)?;
+            transform = multiply(&transform, &bone.transform);
+            parent = bone.parent;
+        }
+        None
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..4).map(|k| a.m[i][k] * b.m[k][j]).sum();
+        }
+    }
+    Matrix { m }
+}
+
+// Bone references are 1-based, with 0 meaning none. They're stored as a byte
+// unless the model has too many bones for that.
+fn read_bone_ref(rdr: &mut dyn Read, bone_count: u32) -> Result<Option<usize>, Error> {
+    let idx = if bone_count < 255 {
+        rdr.read_u8()? as u32
+    } else {
+        rdr.read_u32::<LittleEndian>()?
+    };
+    if idx > bone_count {
+        return Err(Error::InvalidBoneReference(idx));
+    }
+    Ok(idx.checked_sub(1).map(|i| i as usize))
+}
+
+// Every chain of parents must reach a root without looping. Each bone is
+// walked through once, marked as on the current chain and then as reaching
+// a root.
+fn check_hierarchy(bones: &[ModelBone]) -> Result<(), Error> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        OnChain,
+        Rooted,
+    }
+    let mut states = vec![State::Unvisited; bones.len()];
+    for start in 0..bones.len() {
+        let mut chain = vec![];
+        let mut bone = Some(start);
+        while let Some(idx) = bone {
+            match states[idx] {
+                State::Rooted => break,
+                State::OnChain => return Err(Error::InvalidBoneReference(idx as u32 + 1)),
+                State::Unvisited => {}
+            }
+            states[idx] = State::OnChain;
+            chain.push(idx);
+            bone = bones[idx].parent;
+        }
+        for idx in chain {
+            states[idx] = State::Rooted;
+        }
+    }
+    Ok(())
+}
+
+fn read_tag(rdr: &mut dyn Read, readers: &TypeReaders) -> Result<Option<Tag>, Error> {
+    Ok(read_any_object(rdr, readers)?.map(Tag))
+}
+
+impl Model {
+    fn new(rdr: &mut dyn Read, readers: &TypeReaders) -> Result<Model, Error> {
+        let bone_count = rdr.read_u32::<LittleEndian>()?;
+        let mut bones = vec![];
+        for _ in 0..bone_count {
+            bones.push(ModelBone {
+                name: read_optional_object(rdr, readers)?,
+                transform: Matrix::try_parse(rdr, readers, vec![])?,
+                parent: None,
+                children: vec![],
+            });
+        }
+        for bone in &mut bones {
+            bone.parent = read_bone_ref(rdr, bone_count)?;
+            let child_count = rdr.read_u32::<LittleEndian>()?;
+            for _ in 0..child_count {
+                if let Some(child) = read_bone_ref(rdr, bone_count)? {
+                    bone.children.push(child);
+                }
+            }
+        }
+        check_hierarchy(&bones)?;
+
+        let mesh_count = rdr.read_u32::<LittleEndian>()?;
+        let mut meshes = vec![];
+        for _ in 0..mesh_count {
+            let name = read_optional_object(rdr, readers)?;
+            let parent_bone = read_bone_ref(rdr, bone_count)?;
+            let bounds = BoundingSphere::try_parse(rdr, readers, vec![])?;
+            let tag = read_tag(rdr, readers)?;
+            let part_count = rdr.read_u32::<LittleEndian>()?;
+            let mut parts = vec![];
+            for _ in 0..part_count {
+                parts.push(ModelMeshPart {
+                    vertex_offset: rdr.read_u32::<LittleEndian>()?,
+                    num_vertices: rdr.read_u32::<LittleEndian>()?,
+                    start_index: rdr.read_u32::<LittleEndian>()?,
+                    primitive_count: rdr.read_u32::<LittleEndian>()?,
+                    tag: read_tag(rdr, readers)?,
+                    vertex_buffer: readers.read_shared(rdr)?,
+                    index_buffer: readers.read_shared(rdr)?,
+                    effect: readers.read_shared(rdr)?,
+                });
+            }
+            meshes.push(ModelMesh {
+                name,
+                parent_bone,
+                bounds,
+                tag,
+                parts,
+            });
+        }
+
+        let root = read_bone_ref(rdr, bone_count)?;
+        let tag = read_tag(rdr, readers)?;
+        Ok(Model {
+            bones,
+            meshes,
+            root,
+            tag,
+        })
+    }
+}
+
+impl Parse for Model {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.ModelReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Model::new(rdr, readers)
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{BufferPool, Error, ReaderRegistry};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// How to treat files that deviate from the format in ways that can be
+/// recovered from, such as padding after the content or texture mip levels
+/// of the wrong size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Reject them.
+    Strict,
+    /// Accept them, recording each deviation in `XNB::warnings`.
+    Lenient,
+}
+
+/// The part of an XNB being parsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseStage {
+    ReaderTable,
+    PrimaryAsset,
+    SharedResources,
+    Done,
+}
+
+/// How far parsing has got. `processed` and `total` count bytes of content
+/// after the header, decompressed.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    pub stage: ParseStage,
+    pub processed: u64,
+    pub total: u64,
+}
+
+#[derive(Clone)]
+pub(crate) struct ProgressHook(Rc<dyn Fn(Progress)>);
+
+impl ProgressHook {
+    pub(crate) fn report(&self, progress: Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProgressHook")
+    }
+}
+
+/// Settings for parsing an XNB. The limits bound how much a file can make the
+/// parser allocate, so that files from untrusted sources can't exhaust
+/// memory by declaring huge sizes; nothing is limited by default.
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    pub(crate) max_collection_len: usize,
+    pub(crate) max_string_len: usize,
+    pub(crate) max_texture_bytes: usize,
+    pub(crate) max_total_allocation: usize,
+    pub(crate) max_depth: usize,
+    pub(crate) max_layer_cells: usize,
+    pub(crate) lossy_strings: bool,
+    pub(crate) registry: ReaderRegistry,
+    pub(crate) mode: ParseMode,
+    pub(crate) progress: Option<ProgressHook>,
+    pub(crate) cancel: Option<Arc<AtomicBool>>,
+    pub(crate) pool: Option<BufferPool>,
+    pub(crate) skip_data: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions::new()
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> ParseOptions {
+        ParseOptions {
+            max_collection_len: usize::MAX,
+            max_string_len: usize::MAX,
+            max_texture_bytes: usize::MAX,
+            max_total_allocation: usize::MAX,
+            max_depth: usize::MAX,
+            max_layer_cells: usize::MAX,
+            lossy_strings: false,
+            registry: ReaderRegistry::default(),
+            mode: ParseMode::Lenient,
+            progress: None,
+            cancel: None,
+            pool: None,
+            skip_data: false,
+        }
+    }
+
+    /// Limits suited to files from untrusted sources: generous enough for
+    /// any real game asset, but small enough that a malicious file fails
+    /// quickly instead of exhausting memory or the stack.
+    pub fn untrusted() -> ParseOptions {
+        ParseOptions::new()
+            .max_collection_len(1 << 24)
+            .max_string_len(1 << 20)
+            .max_texture_bytes(1 << 28)
+            .max_total_allocation(1 << 30)
+            .max_depth(64)
+            .max_layer_cells(1 << 22)
+    }
+
+    /// The most elements in any list, array or dictionary.
+    pub fn max_collection_len(mut self, len: usize) -> ParseOptions {
+        self.max_collection_len = len;
+        self
+    }
+
+    /// The longest string, in bytes.
+    pub fn max_string_len(mut self, len: usize) -> ParseOptions {
+        self.max_string_len = len;
+        self
+    }
+
+    /// The most data in any single mip level of a texture.
+    pub fn max_texture_bytes(mut self, len: usize) -> ParseOptions {
+        self.max_texture_bytes = len;
+        self
+    }
+
+    /// The most memory, in bytes, that collections, strings and raw data
+    /// read from the file may take up in total.
+    pub fn max_total_allocation(mut self, len: usize) -> ParseOptions {
+        self.max_total_allocation = len;
+        self
+    }
+
+    /// The deepest objects may be nested within each other.
+    pub fn max_depth(mut self, depth: usize) -> ParseOptions {
+        self.max_depth = depth;
+        self
+    }
+
+    /// The most cells in any layer of a tide map, which bounds the size of
+    /// the lookup behind `Layer::tile_at`.
+    pub fn max_layer_cells(mut self, cells: usize) -> ParseOptions {
+        self.max_layer_cells = cells;
+        self
+    }
+
+    /// Replace invalid UTF-8 in strings and chars with U+FFFD rather than failing.
+    pub fn lossy_strings(mut self, lossy: bool) -> ParseOptions {
+        self.lossy_strings = lossy;
+        self
+    }
+
+    /// Whether recoverable deviations are rejected. Parsing is lenient by
+    /// default.
+    pub fn mode(mut self, mode: ParseMode) -> ParseOptions {
+        self.mode = mode;
+        self
+    }
+
+    /// Readers for types this crate doesn't know about.
+    pub fn registry(mut self, registry: ReaderRegistry) -> ParseOptions {
+        self.registry = registry;
+        self
+    }
+
+    /// Call `hook` as parsing moves between stages, and periodically as it
+    /// works through the content, e.g. to drive a progress bar.
+    pub fn progress<F: Fn(Progress) + 'static>(mut self, hook: F) -> ParseOptions {
+        self.progress = Some(ProgressHook(Rc::new(hook)));
+        self
+    }
+
+    /// Abandon parsing with `Error::Cancelled` once `flag` is set, e.g. from
+    /// another thread. It is checked before each object and regularly while
+    /// reading (and decompressing) the content.
+    pub fn cancel_flag(mut self, flag: Arc<AtomicBool>) -> ParseOptions {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Take texture data, raw data and decompression buffers from `pool`.
+    pub fn buffer_pool(mut self, pool: BufferPool) -> ParseOptions {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Skip over texture data, sound samples, effect bytecode and vertex and
+    /// index buffers, leaving them empty, for when only the rest of an asset
+    /// is wanted.
+    pub fn skip_data(mut self, skip: bool) -> ParseOptions {
+        self.skip_data = skip;
+        self
+    }
+
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    // Fail with `error` in strict mode, or record it as a warning otherwise.
+    pub(crate) fn recover(&self, warnings: &mut Vec<Error>, error: Error) -> Result<(), Error> {
+        match self.mode {
+            ParseMode::Strict => Err(error),
+            ParseMode::Lenient => {
+                warnings.push(error);
+                Ok(())
+            }
+        }
+    }
+}
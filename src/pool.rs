@@ -0,0 +1,40 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+// More than enough for the mip chain of the largest texture.
+const MAX_POOLED: usize = 32;
+
+/// Byte buffers to reuse between parses, so that extracting many files doesn't
+/// allocate afresh for every mip level and decompressed frame. Clones share
+/// the same buffers. Hand buffers back with `put` (or `Texture2d::recycle`)
+/// once done with them.
+#[derive(Clone, Default)]
+pub struct BufferPool {
+    buffers: Rc<RefCell<Vec<Vec<u8>>>>,
+}
+
+impl BufferPool {
+    pub fn new() -> BufferPool {
+        BufferPool::default()
+    }
+
+    pub fn put(&self, mut buf: Vec<u8>) {
+        let mut buffers = self.buffers.borrow_mut();
+        if buffers.len() < MAX_POOLED && buf.capacity() > 0 {
+            buf.clear();
+            buffers.push(buf);
+        }
+    }
+
+    /// An empty buffer, with whatever capacity it had when it was put back.
+    pub(crate) fn get(&self) -> Vec<u8> {
+        self.buffers.borrow_mut().pop().unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BufferPool({} buffers)", self.buffers.borrow().len())
+    }
+}
@@ -0,0 +1,114 @@
+//! Support for classes written with `ReflectiveReader`, which serializes the
+//! members of a type one after another in declaration order. Nothing in the
+//! file describes those members, so callers declare them by implementing
+//! `Reflect`.
+
+use crate::{
+    read_object, read_optional_object, Error, ExternalReference, FromShared, Parse, Shared,
+    TypeReaders,
+};
+use byteorder::ReadBytesExt;
+use std::io::Read;
+use std::ops::Deref;
+
+pub const REFLECTIVE_READER: &str = "Microsoft.Xna.Framework.Content.ReflectiveReader";
+
+/// The members of a reflectively serialized object, read in declaration order
+/// (base class members first).
+pub struct Fields<'a> {
+    rdr: &'a mut dyn Read,
+    readers: &'a TypeReaders,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(rdr: &'a mut dyn Read, readers: &'a TypeReaders) -> Fields<'a> {
+        Fields { rdr, readers }
+    }
+
+    /// A value type member (integers, vectors, colors, ...), which is stored
+    /// inline without a type reader id.
+    pub fn value<T: Parse>(&mut self) -> Result<T, Error> {
+        T::try_parse(self.rdr, self.readers, vec![])
+    }
+
+    /// A reference type member, prefixed by the id of the reader that wrote it.
+    pub fn object<T: Parse>(&mut self) -> Result<T, Error> {
+        read_object(self.rdr, self.readers)
+    }
+
+    /// A reference type member that may be null.
+    pub fn optional_object<T: Parse>(&mut self) -> Result<Option<T>, Error> {
+        read_optional_object(self.rdr, self.readers)
+    }
+
+    /// A member written with `WriteSharedResource`. It refers to an object in
+    /// the shared resource section, which is filled in after parsing.
+    pub fn shared<T: FromShared + ?Sized + 'static>(&mut self) -> Result<Option<Shared<T>>, Error> {
+        self.readers.read_shared(self.rdr)
+    }
+
+    /// A `Nullable<T>` member.
+    pub fn nullable<T: Parse>(&mut self) -> Result<Option<T>, Error> {
+        if self.rdr.read_u8()? == 0 {
+            return Ok(None);
+        }
+        self.value().map(Some)
+    }
+
+    /// A member written with `WriteExternalReference`, which is stored as a
+    /// bare asset name rather than an object.
+    pub fn external_reference<T>(&mut self) -> Result<ExternalReference<T>, Error> {
+        ExternalReference::read(self.rdr, self.readers)
+    }
+
+    /// The underlying stream, for members that need custom handling.
+    pub fn reader(&mut self) -> &mut dyn Read {
+        self.rdr
+    }
+}
+
+pub trait Reflect: Sized {
+    /// The full name of the .NET type, without its assembly, e.g.
+    /// `StardewValley.GameData.Movies.MovieData`.
+    const TYPE: &'static str;
+    fn read_fields(fields: &mut Fields) -> Result<Self, Error>;
+}
+
+/// An object read by `ReflectiveReader` into a type implementing `Reflect`.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Reflective<T>(pub T);
+
+impl<T> Reflective<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Reflective<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Reflect> Parse for Reflective<T> {
+    const READER: &'static str = REFLECTIVE_READER;
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        match args.first() {
+            Some(&ty) if ty == T::TYPE => {}
+            other => {
+                return Err(Error::ReaderMismatch(
+                    format!("{}`1[[{}]]", REFLECTIVE_READER, other.unwrap_or(&"")),
+                    format!("{}`1[[{}]]", REFLECTIVE_READER, T::TYPE),
+                ))
+            }
+        }
+        T::read_fields(&mut Fields::new(rdr, readers)).map(Reflective)
+    }
+}
@@ -0,0 +1,97 @@
+//! Cutting regions out of textures.
+
+use crate::{Error, Rectangle, SurfaceFormat, Texture2d};
+
+impl Texture2d {
+    /// Copy `rect` out of the top mip level into a texture of the same
+    /// format, without mip levels. The region of a DXT texture is widened
+    /// to whole 4x4 blocks (within the texture) so no data has to be
+    /// compressed again.
+    pub fn crop(&self, rect: Rectangle) -> Result<Texture2d, Error> {
+        let out_of_bounds = rect.x < 0
+            || rect.y < 0
+            || rect.w <= 0
+            || rect.h <= 0
+            || rect.x as usize + rect.w as usize > self.width
+            || rect.y as usize + rect.h as usize > self.height;
+        if out_of_bounds {
+            return Err(Error::RegionOutOfBounds(rect));
+        }
+        let dim = self.format.block_dim();
+        let (x, y) = (rect.x as usize, rect.y as usize);
+        let (left, top) = (x / dim, y / dim);
+        let right = x + rect.w as usize;
+        let bottom = y + rect.h as usize;
+        let width = (right.div_ceil(dim) * dim).min(self.width) - left * dim;
+        let height = (bottom.div_ceil(dim) * dim).min(self.height) - top * dim;
+
+        let data = self.mip_data.first().ok_or(Error::MissingMipLevel(0))?;
+        let expected = self.format.mip_byte_len(self.width, self.height);
+        if data.len() < expected {
+            return Err(Error::Truncated {
+                expected,
+                actual: data.len(),
+            });
+        }
+        let block_size = self.format.block_size();
+        let stride = self.width.div_ceil(dim) * block_size;
+        let row_len = width.div_ceil(dim) * block_size;
+        let mut cropped = Vec::with_capacity(self.format.mip_byte_len(width, height));
+        for row in top..top + height.div_ceil(dim) {
+            let start = row * stride + left * block_size;
+            cropped.extend_from_slice(&data[start..start + row_len]);
+        }
+        Ok(Texture2d {
+            format: self.format,
+            width,
+            height,
+            mip_data: vec![cropped],
+        })
+    }
+
+    /// Cut the top mip level into `cell_size` cells, skipping `margin`
+    /// pixels at the left and top and `spacing` pixels between cells, as
+    /// described by a `tide::TileSheet`. Cells are numbered left to right and
+    /// then top to bottom; those not entirely within the texture are left
+    /// out. DXT textures are decoded to `Color` first, as their cells needn't
+    /// line up with blocks.
+    pub fn slice_grid(
+        &self,
+        cell_size: (u32, u32),
+        margin: (u32, u32),
+        spacing: (u32, u32),
+    ) -> Result<Vec<Texture2d>, Error> {
+        if self.format.is_compressed() {
+            let decoded = self.decode(0)?;
+            let texture = Texture2d {
+                format: SurfaceFormat::Color,
+                width: decoded.width,
+                height: decoded.height,
+                mip_data: vec![decoded.pixels],
+            };
+            return texture.slice_grid(cell_size, margin, spacing);
+        }
+        // The last cell in a row or column has no spacing after it.
+        let cells = |size: usize, cell: u32, margin: u32, spacing: u32| {
+            if cell == 0 {
+                return 0;
+            }
+            let space = size.saturating_sub(margin as usize) + spacing as usize;
+            space / (cell + spacing) as usize
+        };
+        let columns = cells(self.width, cell_size.0, margin.0, spacing.0);
+        let rows = cells(self.height, cell_size.1, margin.1, spacing.1);
+        let mut slices = Vec::with_capacity(columns * rows);
+        for row in 0..rows as u32 {
+            for column in 0..columns as u32 {
+                slices.push(self.crop(Rectangle {
+                    x: (margin.0 + column * (cell_size.0 + spacing.0)) as i32,
+                    y: (margin.1 + row * (cell_size.1 + spacing.1)) as i32,
+                    w: cell_size.0 as i32,
+                    h: cell_size.1 as i32,
+                })?);
+            }
+        }
+        Ok(slices)
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{Error, SharedResource, TypeReaders};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::rc::Rc;
+
+type ReaderFn = dyn Fn(&mut dyn Read, &TypeReaders, Vec<&str>) -> Result<SharedResource, Error>;
+
+/// Readers for types this crate doesn't know about, such as those added by a
+/// game's own content pipeline extensions. They are used for objects whose
+/// type isn't known up front (shared resources, or `SharedResource` members)
+/// when no built-in reader matches.
+#[derive(Clone, Default)]
+pub struct ReaderRegistry {
+    readers: HashMap<String, Rc<ReaderFn>>,
+}
+
+impl ReaderRegistry {
+    pub fn new() -> ReaderRegistry {
+        ReaderRegistry::default()
+    }
+
+    /// Register a reader under its full .NET name without assembly or
+    /// generic arguments, e.g. `MyGame.Content.LevelReader`. The generic
+    /// arguments of the reader, if any, are passed to `read`.
+    pub fn register<T, F>(&mut self, reader: &str, read: F)
+    where
+        T: 'static,
+        F: Fn(&mut dyn Read, &TypeReaders, Vec<&str>) -> Result<T, Error> + 'static,
+    {
+        self.readers.insert(
+            reader.to_string(),
+            Rc::new(
+                move |rdr: &mut dyn Read, readers: &TypeReaders, args: Vec<&str>| {
+                    Ok(Rc::new(read(rdr, readers, args)?) as SharedResource)
+                },
+            ),
+        );
+    }
+
+    pub fn contains(&self, reader: &str) -> bool {
+        self.readers.contains_key(reader)
+    }
+
+    pub(crate) fn read(
+        &self,
+        reader: &str,
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Option<Result<SharedResource, Error>> {
+        let read = self.readers.get(reader)?;
+        Some(read(rdr, readers, args))
+    }
+}
+
+impl fmt::Debug for ReaderRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.readers.keys()).finish()
+    }
+}
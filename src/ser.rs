@@ -0,0 +1,546 @@
+//! Writing XNBs from `serde::Serialize` types, as `ReflectiveReader` would
+//! read them. This covers assets built from plain data (Stardew Valley's data
+//! files, for instance) without writing a `Serialize` impl for the writer by
+//! hand.
+//!
+//! Rust types map onto .NET types as follows:
+//!
+//! * integers, floats, `bool` and `char` are the matching `System` types;
+//! * strings are `System.String`, and byte buffers `System.Byte[]`;
+//! * sequences are `List<T>` and maps are `Dictionary<K, V>`;
+//! * structs are classes named `<namespace>.<struct name>`, and enums with
+//!   only unit variants are enums of the same form;
+//! * `Option<T>` is a null reference or, for value types, `Nullable<T>`.
+//!
+//! Newtype structs are written as their contents. Enum variants with data
+//! have no equivalent and are rejected.
+
+use crate::writer::system_type;
+use crate::{write_7bit_encoded_int, write_string, Error, Serialize, TypeWriters, XnbWriter};
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::ser;
+use std::fmt;
+use std::io::Write;
+
+/// Writes XNBs from `serde::Serialize` types.
+pub struct Serializer {
+    namespace: String,
+    assembly: String,
+    writer: XnbWriter,
+}
+
+impl Serializer {
+    /// Structs and enums are named as members of `namespace`, in an assembly
+    /// of the same name unless `assembly` says otherwise.
+    pub fn new(namespace: &str) -> Serializer {
+        Serializer {
+            namespace: namespace.to_string(),
+            assembly: namespace.to_string(),
+            writer: XnbWriter::new(),
+        }
+    }
+
+    pub fn assembly(mut self, assembly: &str) -> Serializer {
+        self.assembly = assembly.to_string();
+        self
+    }
+
+    /// The writer used for the XNB container: platform, profile and compression.
+    pub fn writer(mut self, writer: XnbWriter) -> Serializer {
+        self.writer = writer;
+        self
+    }
+
+    pub fn write<T: ser::Serialize + ?Sized>(
+        &self,
+        wtr: &mut dyn Write,
+        asset: &T,
+    ) -> Result<(), Error> {
+        let node = asset.serialize(NodeSerializer)?;
+        if let Node::Null = node {
+            return Err(Error::Serialize("the primary asset can't be null".into()));
+        }
+        self.writer.write_with(wtr, |wtr, writers| {
+            let id = writers.register_reader(self.reader_name(&node)?);
+            write_7bit_encoded_int(wtr, id)?;
+            self.write_node(&node, wtr, writers)
+        })
+    }
+
+    pub fn to_bytes<T: ser::Serialize + ?Sized>(&self, asset: &T) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        self.write(&mut out, asset)?;
+        Ok(out)
+    }
+
+    fn type_name(&self, node: &Node) -> Result<Option<String>, Error> {
+        Ok(Some(match node {
+            Node::Null => return Ok(None),
+            Node::Primitive(p) => p.type_name(),
+            Node::String(_) => String::type_name(),
+            Node::Bytes(_) => system_type("System.Byte[]"),
+            Node::Nullable(inner) => system_type(&format!(
+                "System.Nullable`1[[{}]]",
+                self.type_name(inner)?.unwrap_or_default()
+            )),
+            Node::Seq(items) => system_type(&format!(
+                "System.Collections.Generic.List`1[[{}]]",
+                self.element_type(items.iter())?
+            )),
+            Node::Map(entries) => system_type(&format!(
+                "System.Collections.Generic.Dictionary`2[[{}],[{}]]",
+                self.element_type(entries.iter().map(|(k, _)| k))?,
+                self.element_type(entries.iter().map(|(_, v)| v))?
+            )),
+            Node::Struct(name, _) | Node::Enum(name, _) => {
+                format!("{}.{}, {}", self.namespace, name, self.assembly)
+            }
+        }))
+    }
+
+    // Collections are typed after their first non-null element, and all other
+    // elements must agree. Empty collections hold `System.Object`.
+    fn element_type<'a>(&self, items: impl Iterator<Item = &'a Node>) -> Result<String, Error> {
+        let mut element = None;
+        for item in items {
+            let ty = match self.type_name(item)? {
+                Some(ty) => ty,
+                None => continue,
+            };
+            match element {
+                None => element = Some(ty),
+                Some(ref element) if *element == ty => {}
+                Some(ref element) => {
+                    return Err(Error::Serialize(format!(
+                        "collection mixes {} and {}",
+                        element, ty
+                    )))
+                }
+            }
+        }
+        Ok(element.unwrap_or_else(|| system_type("System.Object")))
+    }
+
+    fn reader_name(&self, node: &Node) -> Result<String, Error> {
+        let ty = self.type_name(node)?.unwrap_or_default();
+        // Strip `System.Collections.Generic.List` from `List`1[[T]], ...` and
+        // so on to get at the generic arguments.
+        let args = || {
+            let start = ty.find('[').unwrap_or(0);
+            let end = ty.rfind(']').map_or(ty.len(), |i| i + 1);
+            ty[start..end].to_string()
+        };
+        Ok(match node {
+            Node::Null => unreachable!(),
+            Node::Primitive(p) => p.reader_name(),
+            Node::String(_) => String::reader_name(),
+            Node::Bytes(_) => format!(
+                "Microsoft.Xna.Framework.Content.ArrayReader`1[[{}]]",
+                u8::type_name()
+            ),
+            Node::Nullable(_) => {
+                format!("Microsoft.Xna.Framework.Content.NullableReader`1{}", args())
+            }
+            Node::Seq(_) => format!("Microsoft.Xna.Framework.Content.ListReader`1{}", args()),
+            Node::Map(_) => format!(
+                "Microsoft.Xna.Framework.Content.DictionaryReader`2{}",
+                args()
+            ),
+            Node::Struct(..) => format!(
+                "Microsoft.Xna.Framework.Content.ReflectiveReader`1[[{}]]",
+                ty
+            ),
+            Node::Enum(..) => format!("Microsoft.Xna.Framework.Content.EnumReader`1[[{}]]", ty),
+        })
+    }
+
+    fn write_node(
+        &self,
+        node: &Node,
+        wtr: &mut dyn Write,
+        writers: &mut TypeWriters,
+    ) -> Result<(), Error> {
+        match node {
+            Node::Null => unreachable!(),
+            Node::Primitive(p) => p.serialize(wtr, writers)?,
+            Node::String(s) => write_string(wtr, s)?,
+            Node::Bytes(b) => {
+                writers.register::<u8>();
+                wtr.write_u32::<LittleEndian>(b.len() as u32)?;
+                wtr.write_all(b)?;
+            }
+            Node::Nullable(inner) => {
+                wtr.write_u8(1)?;
+                self.write_member(inner, wtr, writers)?;
+            }
+            Node::Seq(items) => {
+                wtr.write_u32::<LittleEndian>(items.len() as u32)?;
+                for item in items {
+                    self.write_member(item, wtr, writers)?;
+                }
+            }
+            Node::Map(entries) => {
+                wtr.write_u32::<LittleEndian>(entries.len() as u32)?;
+                for (key, value) in entries {
+                    self.write_member(key, wtr, writers)?;
+                    self.write_member(value, wtr, writers)?;
+                }
+            }
+            Node::Struct(_, fields) => {
+                for field in fields {
+                    self.write_member(field, wtr, writers)?;
+                }
+            }
+            Node::Enum(_, index) => wtr.write_i32::<LittleEndian>(*index as i32)?,
+        }
+        Ok(())
+    }
+
+    // Value types are stored inline, and everything else as an object with a
+    // reader id. Null is a single zero byte either way: a null reference, or
+    // a `Nullable` without a value.
+    fn write_member(
+        &self,
+        node: &Node,
+        wtr: &mut dyn Write,
+        writers: &mut TypeWriters,
+    ) -> Result<(), Error> {
+        if let Node::Null = node {
+            return wtr.write_u8(0).map_err(Error::from);
+        }
+        let id = writers.register_reader(self.reader_name(node)?);
+        if !node.is_value_type() {
+            write_7bit_encoded_int(wtr, id)?;
+        }
+        self.write_node(node, wtr, writers)
+    }
+}
+
+#[derive(Debug)]
+enum Primitive {
+    Bool(bool),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Char(char),
+}
+
+macro_rules! with_primitive {
+    ($p:expr, $v:ident => $e:expr) => {
+        match $p {
+            Primitive::Bool($v) => $e,
+            Primitive::I8($v) => $e,
+            Primitive::U8($v) => $e,
+            Primitive::I16($v) => $e,
+            Primitive::U16($v) => $e,
+            Primitive::I32($v) => $e,
+            Primitive::U32($v) => $e,
+            Primitive::I64($v) => $e,
+            Primitive::U64($v) => $e,
+            Primitive::F32($v) => $e,
+            Primitive::F64($v) => $e,
+            Primitive::Char($v) => $e,
+        }
+    };
+}
+
+impl Primitive {
+    fn type_name(&self) -> String {
+        fn type_name<T: Serialize>(_: &T) -> String {
+            T::type_name()
+        }
+        with_primitive!(self, v => type_name(v))
+    }
+
+    fn reader_name(&self) -> String {
+        fn reader_name<T: Serialize>(_: &T) -> String {
+            T::reader_name()
+        }
+        with_primitive!(self, v => reader_name(v))
+    }
+
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        with_primitive!(self, v => Serialize::serialize(v, wtr, writers))
+    }
+}
+
+// The serde data model is first captured as a tree, since a collection's
+// reader can't be named until all of its elements have been seen.
+#[derive(Debug)]
+enum Node {
+    Null,
+    Primitive(Primitive),
+    String(String),
+    Bytes(Vec<u8>),
+    Nullable(Box<Node>),
+    Seq(Vec<Node>),
+    Map(Vec<(Node, Node)>),
+    Struct(&'static str, Vec<Node>),
+    Enum(&'static str, u32),
+}
+
+impl Node {
+    fn is_value_type(&self) -> bool {
+        matches!(
+            self,
+            Node::Primitive(_) | Node::Nullable(_) | Node::Enum(..)
+        )
+    }
+}
+
+// serde requires its own error type, which is converted to `Error` at the end.
+#[derive(Debug)]
+struct NodeError(String);
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+impl ser::Error for NodeError {
+    fn custom<T: fmt::Display>(msg: T) -> NodeError {
+        NodeError(msg.to_string())
+    }
+}
+
+impl From<NodeError> for Error {
+    fn from(e: NodeError) -> Error {
+        Error::Serialize(e.0)
+    }
+}
+
+fn unsupported(what: &str) -> NodeError {
+    NodeError(format!("{} can't be written to an XNB", what))
+}
+
+struct NodeSerializer;
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = NodeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = StructSerializer;
+    type SerializeTupleVariant = ser::Impossible<Node, NodeError>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = ser::Impossible<Node, NodeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::Bool(v)))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::I8(v)))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::I16(v)))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::I32(v)))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::I64(v)))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::U8(v)))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::U16(v)))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::U32(v)))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::U64(v)))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::F32(v)))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::F64(v)))
+    }
+    fn serialize_char(self, v: char) -> Result<Node, NodeError> {
+        Ok(Node::Primitive(Primitive::Char(v)))
+    }
+    fn serialize_str(self, v: &str) -> Result<Node, NodeError> {
+        Ok(Node::String(v.to_string()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, NodeError> {
+        Ok(Node::Bytes(v.to_vec()))
+    }
+    fn serialize_none(self) -> Result<Node, NodeError> {
+        Ok(Node::Null)
+    }
+    fn serialize_some<T: ser::Serialize + ?Sized>(self, value: &T) -> Result<Node, NodeError> {
+        let node = value.serialize(NodeSerializer)?;
+        Ok(if node.is_value_type() {
+            Node::Nullable(Box::new(node))
+        } else {
+            node
+        })
+    }
+    fn serialize_unit(self) -> Result<Node, NodeError> {
+        Ok(Node::Null)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, NodeError> {
+        Ok(Node::Null)
+    }
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        _variant: &'static str,
+    ) -> Result<Node, NodeError> {
+        Ok(Node::Enum(name, index))
+    }
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Node, NodeError> {
+        value.serialize(NodeSerializer)
+    }
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _value: &T,
+    ) -> Result<Node, NodeError> {
+        Err(unsupported(&format!("{}::{}", name, variant)))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, NodeError> {
+        Ok(SeqSerializer(Vec::with_capacity(len.unwrap_or(0))))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, NodeError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<StructSerializer, NodeError> {
+        Ok(StructSerializer(name, Vec::with_capacity(len)))
+    }
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, NodeError> {
+        Err(unsupported(&format!("{}::{}", name, variant)))
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, NodeError> {
+        Ok(MapSerializer(Vec::with_capacity(len.unwrap_or(0)), None))
+    }
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<StructSerializer, NodeError> {
+        Ok(StructSerializer(name, Vec::with_capacity(len)))
+    }
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, NodeError> {
+        Err(unsupported(&format!("{}::{}", name, variant)))
+    }
+}
+
+struct SeqSerializer(Vec<Node>);
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Node;
+    type Error = NodeError;
+    fn serialize_element<T: ser::Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), NodeError> {
+        self.0.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Node, NodeError> {
+        Ok(Node::Seq(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Node;
+    type Error = NodeError;
+    fn serialize_element<T: ser::Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), NodeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+    fn end(self) -> Result<Node, NodeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct StructSerializer(&'static str, Vec<Node>);
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Node;
+    type Error = NodeError;
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), NodeError> {
+        self.1.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Node, NodeError> {
+        Ok(Node::Struct(self.0, self.1))
+    }
+}
+
+impl ser::SerializeTupleStruct for StructSerializer {
+    type Ok = Node;
+    type Error = NodeError;
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NodeError> {
+        self.1.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+    fn end(self) -> Result<Node, NodeError> {
+        Ok(Node::Struct(self.0, self.1))
+    }
+}
+
+struct MapSerializer(Vec<(Node, Node)>, Option<Node>);
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Node;
+    type Error = NodeError;
+    fn serialize_key<T: ser::Serialize + ?Sized>(&mut self, key: &T) -> Result<(), NodeError> {
+        self.1 = Some(key.serialize(NodeSerializer)?);
+        Ok(())
+    }
+    fn serialize_value<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NodeError> {
+        let key = self
+            .1
+            .take()
+            .ok_or_else(|| NodeError("map value without a key".into()))?;
+        self.0.push((key, value.serialize(NodeSerializer)?));
+        Ok(())
+    }
+    fn end(self) -> Result<Node, NodeError> {
+        Ok(Node::Map(self.0))
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{
+    read_7bit_encoded_int, read_with_reader, AlphaTestEffect, BasicEffect, BoundingBox,
+    BoundingSphere, Color, DateTime, Decimal, DualTextureEffect, Effect, EffectMaterial,
+    EnvironmentMapEffect, Error, IndexBuffer, Matrix, Model, Parse, Plane, Quaternion, Ray,
+    Rectangle, SkinnedEffect, Song, SoundEffect, SpriteFont, Texture2d, TextureCube, TimeSpan,
+    TypeReaders, Vector3, VertexBuffer, VertexDeclaration, Video,
+};
+use std::any::Any;
+use std::cell::OnceCell;
+use std::fmt;
+use std::io::Read;
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// An object from the shared resource section of an XNB. Its concrete type
+/// depends on the reader that wrote it; use `downcast` to get at it.
+pub type SharedResource = Rc<dyn Any>;
+
+pub(crate) type Fixup = Box<dyn FnOnce(&[Option<SharedResource>]) -> Result<(), Error>>;
+
+/// Types that a shared resource can be resolved to: any concrete type, or
+/// `dyn Any` for references whose type isn't known up front.
+pub trait FromShared {
+    fn from_shared(resource: SharedResource) -> Option<Rc<Self>>;
+}
+
+impl<T: Any> FromShared for T {
+    fn from_shared(resource: SharedResource) -> Option<Rc<T>> {
+        resource.downcast().ok()
+    }
+}
+
+impl FromShared for dyn Any {
+    fn from_shared(resource: SharedResource) -> Option<Rc<dyn Any>> {
+        Some(resource)
+    }
+}
+
+/// A reference to a shared resource of type `T`. Every reference to the same
+/// resource shares a single `Rc`.
+pub struct Shared<T: ?Sized> {
+    index: usize,
+    resource: Rc<OnceCell<Rc<T>>>,
+}
+
+impl<T: FromShared + ?Sized + 'static> Shared<T> {
+    pub(crate) fn new(index: usize) -> Shared<T> {
+        Shared {
+            index,
+            resource: Rc::new(OnceCell::new()),
+        }
+    }
+
+    // Fills in this reference once the shared resources have been read.
+    pub(crate) fn fixup(&self) -> Fixup {
+        let index = self.index;
+        let cell = self.resource.clone();
+        Box::new(move |resources| {
+            let resource = resources
+                .get(index)
+                .and_then(|r| r.clone())
+                .and_then(T::from_shared)
+                .ok_or(Error::SharedResourceMismatch(index))?;
+            let _ = cell.set(resource);
+            Ok(())
+        })
+    }
+}
+
+impl<T: ?Sized> Shared<T> {
+    /// The index of the resource in the shared resource section.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The resource, or `None` if this reference hasn't been resolved yet.
+    /// References are always resolved by the time an `XNB` is returned.
+    pub fn get(&self) -> Option<&Rc<T>> {
+        self.resource.get()
+    }
+
+    /// The resource, shared with every other reference to it.
+    ///
+    /// # Panics
+    ///
+    /// If the reference hasn't been resolved yet, which is only the case
+    /// while the XNB is still being parsed: readers registered with a
+    /// `ReaderRegistry` must use `get` instead.
+    pub fn rc(&self) -> Rc<T> {
+        self.get().expect("unresolved shared resource").clone()
+    }
+}
+
+/// Dereferencing panics if the reference hasn't been resolved yet, as `rc`
+/// does.
+impl<T: ?Sized> Deref for Shared<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.get().expect("unresolved shared resource")
+    }
+}
+
+impl<T: ?Sized> Clone for Shared<T> {
+    fn clone(&self) -> Shared<T> {
+        Shared {
+            index: self.index,
+            resource: self.resource.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(resource) => resource.fmt(f),
+            None => write!(f, "Shared({})", self.index),
+        }
+    }
+}
+
+// Shared resources (and other members typed as `object`) can be of any type,
+// so they are dispatched on the name of their reader: first to the built-in
+// readers, then to any registered with a `ReaderRegistry`.
+pub(crate) fn read_any_object(
+    rdr: &mut dyn Read,
+    readers: &TypeReaders,
+) -> Result<Option<SharedResource>, Error> {
+    let id = read_7bit_encoded_int(rdr)? as usize;
+    if id == 0 {
+        return Ok(None);
+    }
+    let name = &readers
+        .get(id - 1)
+        .ok_or_else(|| Error::UnknownReader(id.to_string()))?
+        .name;
+    read_with_reader::<SharedResource>(name, rdr, readers).map(Some)
+}
+
+// Invokes `$m` with every type that has a built-in reader and can be read
+// without knowing its type up front.
+macro_rules! builtin_types {
+    ($m:ident) => {
+        $m!(
+            Texture2d,
+            SpriteFont,
+            String,
+            bool,
+            char,
+            u8,
+            i8,
+            i16,
+            u16,
+            i32,
+            u32,
+            i64,
+            u64,
+            f32,
+            f64,
+            Decimal,
+            DateTime,
+            TimeSpan,
+            Rectangle,
+            Vector3,
+            Color,
+            Quaternion,
+            Matrix,
+            BoundingBox,
+            BoundingSphere,
+            Plane,
+            Ray,
+            VertexDeclaration,
+            VertexBuffer,
+            IndexBuffer,
+            Model,
+            Effect,
+            BasicEffect,
+            DualTextureEffect,
+            AlphaTestEffect,
+            EnvironmentMapEffect,
+            SkinnedEffect,
+            EffectMaterial,
+            TextureCube,
+            SoundEffect,
+            Song,
+            Video
+        )
+    };
+}
+
+// Whether `reader` is one of the built-in readers in `builtin_types`.
+pub(crate) fn is_builtin_reader(reader: &str) -> bool {
+    macro_rules! known {
+        ($($ty:ty),+) => {
+            [$(<$ty as Parse>::READER),+].contains(&reader)
+        };
+    }
+    builtin_types!(known)
+}
+
+/// Reads an object with whichever reader wrote it, as long as that reader is
+/// built in or registered.
+impl Parse for SharedResource {
+    const READER: &'static str = "System.Object";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Self::parse(Self::READER, rdr, readers, args)
+    }
+
+    fn parse(
+        main: &str,
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        macro_rules! dispatch {
+            ($($ty:ty),+) => {
+                $(
+                    if main == <$ty as Parse>::READER {
+                        let value = <$ty as Parse>::parse(main, rdr, readers, args)?;
+                        return Ok(Rc::new(value));
+                    }
+                )+
+            };
+        }
+        builtin_types!(dispatch);
+        readers
+            .options
+            .registry
+            .read(main, rdr, readers, args)
+            .unwrap_or_else(|| Err(Error::UnknownReader(main.to_string())))
+    }
+}
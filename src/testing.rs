@@ -0,0 +1,128 @@
+//! Helpers for synthesizing XNB files in tests, without needing real game
+//! content on hand.
+
+use crate::writer::write_container;
+use crate::{write_7bit_encoded_int, write_string, Compression, Error, Serialize, XnbWriter};
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io::Read;
+
+/// Build the bytes of an XNB holding `asset`, as the content pipeline would.
+pub fn fixture<T: Serialize>(asset: &T) -> Result<Vec<u8>, Error> {
+    XnbWriter::new().to_bytes(asset)
+}
+
+/// Read a `len` byte UTF-8 string, as the crate's readers do once they have
+/// its length. Exposed for benchmarking.
+pub fn read_string_with_length(rdr: &mut dyn Read, len: u32) -> Result<String, Error> {
+    crate::read_string_with_length(rdr, len)
+}
+
+/// Assembles an XNB by hand: an explicit reader table, shared resource count
+/// and raw payload. Useful for producing files that `XnbWriter` can't, such as
+/// ones using readers this crate doesn't know about or malformed payloads.
+#[derive(Debug, Clone)]
+pub struct XnbFixture {
+    target: u8,
+    hidef: bool,
+    compression: Compression,
+    readers: Vec<(String, i32)>,
+    shared: u32,
+    payload: Vec<u8>,
+}
+
+impl Default for XnbFixture {
+    fn default() -> XnbFixture {
+        XnbFixture::new()
+    }
+}
+
+impl XnbFixture {
+    pub fn new() -> XnbFixture {
+        XnbFixture {
+            target: b'w',
+            hidef: false,
+            compression: Compression::None,
+            readers: vec![],
+            shared: 0,
+            payload: vec![],
+        }
+    }
+
+    pub fn target(mut self, target: char) -> XnbFixture {
+        self.target = target as u8;
+        self
+    }
+
+    pub fn hidef(mut self, hidef: bool) -> XnbFixture {
+        self.hidef = hidef;
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> XnbFixture {
+        self.compression = compression;
+        self
+    }
+
+    /// Append a type reader to the reader table. Readers are referred to by
+    /// their 1-based position in the table.
+    pub fn reader(self, name: &str) -> XnbFixture {
+        self.reader_with_version(name, 0)
+    }
+
+    pub fn reader_with_version(mut self, name: &str, version: i32) -> XnbFixture {
+        self.readers.push((name.to_string(), version));
+        self
+    }
+
+    pub fn shared_resources(mut self, count: u32) -> XnbFixture {
+        self.shared = count;
+        self
+    }
+
+    /// Append raw bytes to the payload.
+    pub fn bytes(mut self, bytes: &[u8]) -> XnbFixture {
+        self.payload.extend_from_slice(bytes);
+        self
+    }
+
+    /// Append an object tag referring to the given 1-based reader, or 0 for null.
+    pub fn object(mut self, reader: u32) -> XnbFixture {
+        write_7bit_encoded_int(&mut self.payload, reader).unwrap();
+        self
+    }
+
+    pub fn int(mut self, value: i32) -> XnbFixture {
+        self.payload.write_i32::<LittleEndian>(value).unwrap();
+        self
+    }
+
+    pub fn uint(mut self, value: u32) -> XnbFixture {
+        self.payload.write_u32::<LittleEndian>(value).unwrap();
+        self
+    }
+
+    pub fn float(mut self, value: f32) -> XnbFixture {
+        self.payload.write_f32::<LittleEndian>(value).unwrap();
+        self
+    }
+
+    pub fn string(mut self, value: &str) -> XnbFixture {
+        write_string(&mut self.payload, value).unwrap();
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut body = vec![];
+        write_7bit_encoded_int(&mut body, self.readers.len() as u32).unwrap();
+        for (name, version) in &self.readers {
+            write_string(&mut body, name).unwrap();
+            body.write_i32::<LittleEndian>(*version).unwrap();
+        }
+        write_7bit_encoded_int(&mut body, self.shared).unwrap();
+        body.extend_from_slice(&self.payload);
+
+        let mut out = vec![];
+        write_container(&mut out, self.target, self.hidef, self.compression, &body).unwrap();
+        out
+    }
+}
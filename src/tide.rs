@@ -1,8 +1,14 @@
-use crate::{read_string_with_length, Error, Parse, TypeReader};
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::{Cursor, Read};
+use crate::{
+    limit, read_exactly, read_string_with_length, Error, Parse, ParseOptions, Rectangle,
+    TypeReaders,
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read, Write};
+use std::mem;
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct TileSheet<T> {
     pub id: String,
     pub description: String,
@@ -14,7 +20,43 @@ pub struct TileSheet<T> {
     pub properties: T,
 }
 
-#[derive(Debug)]
+impl<T> TileSheet<T> {
+    /// The number of tiles in the sheet. `sheet_size` is in tiles, not
+    /// pixels.
+    pub fn tile_count(&self) -> u32 {
+        self.sheet_size.0.saturating_mul(self.sheet_size.1)
+    }
+
+    /// Where a tile is in the sheet's image, in pixels. Tiles are numbered
+    /// left to right and then top to bottom, as for `Texture2d::slice_grid`;
+    /// indices from `tile_count` on give rectangles below the sheet.
+    pub fn source_rect(&self, idx: u32) -> Rectangle {
+        let columns = self.sheet_size.0.max(1);
+        let offset = |cell: u32, margin: u32, tile: u32, spacing: u32| {
+            let pitch = tile.saturating_add(spacing);
+            margin.saturating_add(cell.saturating_mul(pitch)) as i32
+        };
+        Rectangle {
+            x: offset(
+                idx % columns,
+                self.margin.0,
+                self.tile_size.0,
+                self.spacing.0,
+            ),
+            y: offset(
+                idx / columns,
+                self.margin.1,
+                self.tile_size.1,
+                self.spacing.1,
+            ),
+            w: self.tile_size.0 as i32,
+            h: self.tile_size.1 as i32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum PropertyValue {
     Bool(bool),
     Int(i32),
@@ -27,7 +69,15 @@ fn read_tide_string(rdr: &mut dyn Read) -> Result<String, Error> {
     read_string_with_length(rdr, len)
 }
 
-fn read_tide_properties(rdr: &mut dyn Read) -> Result<Vec<(String, PropertyValue)>, Error> {
+// An error for the tag byte that was just read.
+fn invalid_tag(rdr: &Cursor<&[u8]>, tag: u8) -> Error {
+    Error::InvalidTide {
+        tag,
+        offset: rdr.position() - 1,
+    }
+}
+
+fn read_tide_properties(rdr: &mut Cursor<&[u8]>) -> Result<Vec<(String, PropertyValue)>, Error> {
     let num_properties = rdr.read_u32::<LittleEndian>()?;
 
     let mut props = vec![];
@@ -39,7 +89,7 @@ fn read_tide_properties(rdr: &mut dyn Read) -> Result<Vec<(String, PropertyValue
             1 => PropertyValue::Int(rdr.read_i32::<LittleEndian>()?),
             2 => PropertyValue::Float(rdr.read_f32::<LittleEndian>()?),
             3 => PropertyValue::String(read_tide_string(rdr)?),
-            _ => unreachable!("unexpected property type"),
+            tag => return Err(invalid_tag(rdr, tag)),
         };
         props.push((name, value));
     }
@@ -47,6 +97,7 @@ fn read_tide_properties(rdr: &mut dyn Read) -> Result<Vec<(String, PropertyValue
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StaticTile<T> {
     pub tilesheet: String,
     pub idx: u32,
@@ -56,7 +107,7 @@ pub struct StaticTile<T> {
 }
 
 fn read_static_tile<T: PropertyParse>(
-    rdr: &mut dyn Read,
+    rdr: &mut Cursor<&[u8]>,
     tilesheet: String,
     pos: (u32, u32),
 ) -> Result<StaticTile<T>, Error> {
@@ -72,17 +123,79 @@ fn read_static_tile<T: PropertyParse>(
     })
 }
 
-pub fn print_properties(properties: &[(String, PropertyValue)]) {
-    for &(ref name, ref value) in properties {
-        println!("{} = {:?}", name, value);
-    }
+/// One `name = value` line per property, for display.
+pub fn format_properties(properties: &[(String, PropertyValue)]) -> String {
+    properties
+        .iter()
+        .map(|(name, value)| format!("{} = {:?}\n", name, value))
+        .collect()
 }
 
 pub trait PropertyParse {
     fn parse(props: Vec<(String, PropertyValue)>) -> Self;
 }
 
+impl PropertyParse for Vec<(String, PropertyValue)> {
+    fn parse(props: Vec<(String, PropertyValue)>) -> Self {
+        props
+    }
+}
+
+/// Properties by name. Where a name appears twice, the last value wins.
+impl PropertyParse for HashMap<String, PropertyValue> {
+    fn parse(props: Vec<(String, PropertyValue)>) -> Self {
+        props.into_iter().collect()
+    }
+}
+
+/// Ignores the properties, for maps whose properties aren't needed.
+impl PropertyParse for () {
+    fn parse(_props: Vec<(String, PropertyValue)>) -> Self {}
+}
+
+/// Looking up properties in their parsed form.
+pub trait PropertyLookup {
+    fn property(&self, name: &str) -> Option<&PropertyValue>;
+    /// Every property, in a consistent order.
+    fn entries(&self) -> Vec<(&str, &PropertyValue)>;
+}
+
+/// Where a name appears twice, the last value is found, as for `HashMap`.
+impl PropertyLookup for Vec<(String, PropertyValue)> {
+    fn property(&self, name: &str) -> Option<&PropertyValue> {
+        self.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    fn entries(&self) -> Vec<(&str, &PropertyValue)> {
+        self.iter().map(|(n, v)| (&n[..], v)).collect()
+    }
+}
+
+/// Entries are sorted by name.
+impl PropertyLookup for HashMap<String, PropertyValue> {
+    fn property(&self, name: &str) -> Option<&PropertyValue> {
+        self.get(name)
+    }
+
+    fn entries(&self) -> Vec<(&str, &PropertyValue)> {
+        let mut entries: Vec<_> = self.iter().map(|(n, v)| (&n[..], v)).collect();
+        entries.sort_by_key(|&(n, _)| n);
+        entries
+    }
+}
+
+impl PropertyLookup for () {
+    fn property(&self, _name: &str) -> Option<&PropertyValue> {
+        None
+    }
+
+    fn entries(&self) -> Vec<(&str, &PropertyValue)> {
+        vec![]
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Map<T, U, V, W> {
     pub id: String,
     pub description: String,
@@ -103,51 +216,162 @@ impl<T: PropertyParse, U: PropertyParse, V: PropertyParse, W: PropertyParse> Par
     const READER: &'static str = "xTile.Pipeline.TideReader";
     fn try_parse(
         rdr: &mut dyn Read,
-        _readers: &[TypeReader],
+        readers: &TypeReaders,
         _args: Vec<&str>,
     ) -> Result<Self, Error> {
-        read_tide(rdr)
+        let size = rdr.read_u32::<LittleEndian>()?;
+        parse_tide(&readers.read_bytes(rdr, size as usize)?, &readers.options)
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Layer<T, U> {
     pub id: String,
     pub description: String,
+    /// The layer's tiles in row-major order, leaving out empty cells. Call
+    /// `reindex` after adding, removing or moving any.
     pub tiles: Vec<Tile<U>>,
     pub visible: bool,
     pub size: (u32, u32),
     pub tile_size: (u32, u32),
     pub properties: T,
+    // The index in `tiles` of the tile at each occupied cell.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    grid: HashMap<(u32, u32), u32>,
+}
+
+impl<T, U> Layer<T, U> {
+    /// A visible layer of `size` cells, holding `tiles` at their positions.
+    pub fn new(
+        id: String,
+        size: (u32, u32),
+        tile_size: (u32, u32),
+        tiles: Vec<Tile<U>>,
+        properties: T,
+    ) -> Layer<T, U> {
+        let mut layer = Layer {
+            id,
+            description: String::new(),
+            tiles,
+            visible: true,
+            size,
+            tile_size,
+            properties,
+            grid: HashMap::new(),
+        };
+        layer.reindex();
+        layer
+    }
+
+    /// The tile at a cell, if the cell isn't empty. Tiles moved since the
+    /// last `reindex` aren't found until it is called again.
+    pub fn tile_at(&self, x: u32, y: u32) -> Option<&Tile<U>> {
+        let index = self.cell(x, y)?;
+        self.tiles
+            .get(index)
+            .filter(|tile| tile.get_pos() == (x, y))
+    }
+
+    pub fn tile_at_mut(&mut self, x: u32, y: u32) -> Option<&mut Tile<U>> {
+        let index = self.cell(x, y)?;
+        self.tiles
+            .get_mut(index)
+            .filter(|tile| tile.get_pos() == (x, y))
+    }
+
+    /// Each tile with its cell, row by row.
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (u32, u32, &Tile<U>)> + '_ {
+        let mut cells: Vec<_> = self
+            .grid
+            .iter()
+            .filter_map(|(&(x, y), &index)| {
+                let tile = self.tiles.get(index as usize)?;
+                Some((x, y, tile)).filter(|_| tile.get_pos() == (x, y))
+            })
+            .collect();
+        cells.sort_by_key(|&(x, y, _)| (y, x));
+        cells.into_iter()
+    }
+
+    /// The tiles drawn from a tilesheet, row by row. Animated tiles are
+    /// matched by their first frame.
+    pub fn tiles_in_sheet<'a>(
+        &'a self,
+        sheet: &'a str,
+    ) -> impl Iterator<Item = (u32, u32, &'a Tile<U>)> + 'a {
+        self.iter_tiles()
+            .filter(move |(_, _, tile)| tile.get_tilesheet() == sheet)
+    }
+
+    /// The tiles with a property of this name, row by row.
+    pub fn tiles_with_property<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = (u32, u32, &'a Tile<U>)> + 'a
+    where
+        U: PropertyLookup,
+    {
+        self.iter_tiles()
+            .filter(move |(_, _, tile)| tile.properties().property(name).is_some())
+    }
+
+    /// Rebuild the lookup used by `tile_at` from each tile's position. Tiles
+    /// outside the layer are left out; where two share a cell, the last wins.
+    pub fn reindex(&mut self) {
+        let (width, height) = self.size;
+        self.grid.clear();
+        for (index, tile) in self.tiles.iter().enumerate() {
+            let (x, y) = tile.get_pos();
+            if x < width && y < height {
+                self.grid.insert((x, y), index as u32);
+            }
+        }
+    }
+
+    fn cell(&self, x: u32, y: u32) -> Option<usize> {
+        self.grid.get(&(x, y)).map(|&index| index as usize)
+    }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Tile<T> {
     Static(StaticTile<T>),
     Animated(AnimatedTile<T>),
 }
 
 impl<T> Tile<T> {
-    pub fn get_index(&self, tick: u32) -> u32 {
+    /// What's shown at `tick` milliseconds: the tile itself, or the frame of
+    /// an animation then. Animations without frames show nothing.
+    pub fn frame(&self, tick: u32) -> Option<&StaticTile<T>> {
         match *self {
-            Tile::Static(ref tile) => tile.idx,
+            Tile::Static(ref tile) => Some(tile),
+            Tile::Animated(ref tile) if tile.frames.is_empty() => None,
             Tile::Animated(ref tile) => {
-                tile.frames[(tick / tile.interval) as usize % tile.frames.len()].idx
+                let frame = tick / tile.interval.max(1);
+                tile.frames.get(frame as usize % tile.frames.len())
             }
         }
     }
 
+    /// The tile index shown at `tick`, as by `frame`, or 0 for an animation
+    /// without frames.
+    pub fn get_index(&self, tick: u32) -> u32 {
+        self.frame(tick).map_or(0, |frame| frame.idx)
+    }
+
     pub fn get_tilesheet(&self) -> &str {
         match *self {
             Tile::Static(ref tile) => &tile.tilesheet,
-            Tile::Animated(ref tile) => &tile.frames[0].tilesheet,
+            Tile::Animated(ref tile) => tile.frames.first().map_or("", |frame| &frame.tilesheet),
         }
     }
 
     pub fn get_pos(&self) -> (u32, u32) {
         match *self {
             Tile::Static(ref tile) => tile.pos,
-            Tile::Animated(ref tile) => tile.frames[0].pos,
+            Tile::Animated(ref tile) => tile.pos,
         }
     }
 
@@ -160,6 +384,7 @@ impl<T> Tile<T> {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AnimatedTile<T> {
     pub interval: u32,
     pub pos: (u32, u32),
@@ -167,7 +392,159 @@ pub struct AnimatedTile<T> {
     pub properties: T,
 }
 
+/// Write a map as `read_tide` reads it: tBIN data after its length. Only
+/// tiles that `Layer::tile_at` finds are written.
+pub fn write_tide<T, U, V, W>(map: &Map<T, U, V, W>, wtr: &mut dyn Write) -> io::Result<()>
+where
+    T: PropertyLookup,
+    U: PropertyLookup,
+    V: PropertyLookup,
+    W: PropertyLookup,
+{
+    let mut buf = b"tBIN10".to_vec();
+    write_tide_string(&mut buf, &map.id)?;
+    write_tide_string(&mut buf, &map.description)?;
+    write_tide_properties(&mut buf, &map.properties)?;
+
+    buf.write_u32::<LittleEndian>(map.tilesheets.len() as u32)?;
+    for sheet in &map.tilesheets {
+        write_tide_string(&mut buf, &sheet.id)?;
+        write_tide_string(&mut buf, &sheet.description)?;
+        write_tide_string(&mut buf, &sheet.image_source)?;
+        for &(w, h) in &[
+            sheet.sheet_size,
+            sheet.tile_size,
+            sheet.margin,
+            sheet.spacing,
+        ] {
+            buf.write_u32::<LittleEndian>(w)?;
+            buf.write_u32::<LittleEndian>(h)?;
+        }
+        write_tide_properties(&mut buf, &sheet.properties)?;
+    }
+
+    buf.write_u32::<LittleEndian>(map.layers.len() as u32)?;
+    for layer in &map.layers {
+        write_tide_string(&mut buf, &layer.id)?;
+        buf.write_u8(layer.visible as u8)?;
+        write_tide_string(&mut buf, &layer.description)?;
+        for &n in &[
+            layer.size.0,
+            layer.size.1,
+            layer.tile_size.0,
+            layer.tile_size.1,
+        ] {
+            buf.write_u32::<LittleEndian>(n)?;
+        }
+        write_tide_properties(&mut buf, &layer.properties)?;
+
+        // The tilesheet carries over from tile to tile, frames included.
+        let mut tileset = None;
+        for y in 0..layer.size.1 {
+            let mut empty = 0;
+            for x in 0..layer.size.0 {
+                let tile = match layer.tile_at(x, y) {
+                    Some(tile) => tile,
+                    None => {
+                        empty += 1;
+                        continue;
+                    }
+                };
+                if empty > 0 {
+                    buf.write_u8(b'N')?;
+                    buf.write_u32::<LittleEndian>(empty)?;
+                    empty = 0;
+                }
+                match *tile {
+                    Tile::Static(ref tile) => {
+                        write_static_tile(&mut buf, tile, &mut tileset)?;
+                    }
+                    Tile::Animated(ref tile) => {
+                        buf.write_u8(b'A')?;
+                        buf.write_u32::<LittleEndian>(tile.interval)?;
+                        buf.write_u32::<LittleEndian>(tile.frames.len() as u32)?;
+                        for frame in &tile.frames {
+                            write_static_tile(&mut buf, frame, &mut tileset)?;
+                        }
+                        write_tide_properties(&mut buf, &tile.properties)?;
+                    }
+                }
+            }
+            if empty > 0 {
+                buf.write_u8(b'N')?;
+                buf.write_u32::<LittleEndian>(empty)?;
+            }
+        }
+    }
+
+    wtr.write_u32::<LittleEndian>(buf.len() as u32)?;
+    wtr.write_all(&buf)
+}
+
+fn write_tide_string(wtr: &mut dyn Write, s: &str) -> io::Result<()> {
+    wtr.write_u32::<LittleEndian>(s.len() as u32)?;
+    wtr.write_all(s.as_bytes())
+}
+
+fn write_tide_properties<P: PropertyLookup>(wtr: &mut dyn Write, properties: &P) -> io::Result<()> {
+    let entries = properties.entries();
+    wtr.write_u32::<LittleEndian>(entries.len() as u32)?;
+    for (name, value) in entries {
+        write_tide_string(wtr, name)?;
+        match *value {
+            PropertyValue::Bool(b) => {
+                wtr.write_u8(0)?;
+                wtr.write_u8(b as u8)?;
+            }
+            PropertyValue::Int(i) => {
+                wtr.write_u8(1)?;
+                wtr.write_i32::<LittleEndian>(i)?;
+            }
+            PropertyValue::Float(f) => {
+                wtr.write_u8(2)?;
+                wtr.write_f32::<LittleEndian>(f)?;
+            }
+            PropertyValue::String(ref s) => {
+                wtr.write_u8(3)?;
+                write_tide_string(wtr, s)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Writes a tile, preceded by its tilesheet if that differs from the last.
+fn write_static_tile<'a, T: PropertyLookup>(
+    wtr: &mut dyn Write,
+    tile: &'a StaticTile<T>,
+    tileset: &mut Option<&'a str>,
+) -> io::Result<()> {
+    if *tileset != Some(&tile.tilesheet[..]) {
+        wtr.write_u8(b'T')?;
+        write_tide_string(wtr, &tile.tilesheet)?;
+        *tileset = Some(&tile.tilesheet);
+    }
+    wtr.write_u8(b'S')?;
+    wtr.write_u32::<LittleEndian>(tile.idx)?;
+    wtr.write_u8(tile.blend_mode)?;
+    write_tide_properties(wtr, &tile.properties)
+}
+
 pub fn read_tide<T, U, V, W>(rdr: &mut dyn Read) -> Result<Map<T, U, V, W>, Error>
+where
+    T: PropertyParse,
+    U: PropertyParse,
+    V: PropertyParse,
+    W: PropertyParse,
+{
+    read_tide_with_options(rdr, &ParseOptions::default())
+}
+
+/// Read tBIN data after its length, with the layer size limited by `options`.
+pub fn read_tide_with_options<T, U, V, W>(
+    rdr: &mut dyn Read,
+    options: &ParseOptions,
+) -> Result<Map<T, U, V, W>, Error>
 where
     T: PropertyParse,
     U: PropertyParse,
@@ -175,24 +552,26 @@ where
     W: PropertyParse,
 {
     let size = rdr.read_u32::<LittleEndian>()?;
-    let mut buf = vec![0; size as usize];
-    rdr.read(&mut buf)?;
+    parse_tide(&read_exactly(rdr, size as usize)?, options)
+}
 
-    let mut rdr = Cursor::new(&buf);
+fn parse_tide<T, U, V, W>(buf: &[u8], options: &ParseOptions) -> Result<Map<T, U, V, W>, Error>
+where
+    T: PropertyParse,
+    U: PropertyParse,
+    V: PropertyParse,
+    W: PropertyParse,
+{
+    let mut rdr = Cursor::new(buf);
 
-    let mut header = vec![0; 6];
-    rdr.read(&mut header)?;
+    let header = read_exactly(&mut rdr, 6)?;
     if header != b"tBIN10" {
         return Err(Error::Void);
     }
 
     let map_id = read_tide_string(&mut rdr)?;
-    println!("{}", map_id);
-
     let map_description = read_tide_string(&mut rdr)?;
-    if !map_description.is_empty() {
-        println!("{}", map_description);
-    }
+    log::debug!("map {:?}: {:?}", map_id, map_description);
 
     let properties = T::parse(read_tide_properties(&mut rdr)?);
 
@@ -201,31 +580,31 @@ where
     let num_tilesheets = rdr.read_u32::<LittleEndian>()?;
     for _ in 0..num_tilesheets {
         let tilesheet_name = read_tide_string(&mut rdr)?;
-        println!("{}", tilesheet_name);
-
         let description = read_tide_string(&mut rdr)?;
-        if !description.is_empty() {
-            println!("{}", description);
-        }
-
         let source = read_tide_string(&mut rdr)?;
-        println!("{}", source);
 
         let sheet_width = rdr.read_u32::<LittleEndian>()?;
         let sheet_height = rdr.read_u32::<LittleEndian>()?;
-        println!("{}x{}", sheet_width, sheet_height);
-
         let tile_w = rdr.read_u32::<LittleEndian>()?;
         let tile_h = rdr.read_u32::<LittleEndian>()?;
-        println!("{}x{}", tile_w, tile_h);
-
         let margin_w = rdr.read_u32::<LittleEndian>()?;
         let margin_h = rdr.read_u32::<LittleEndian>()?;
-        println!("{}x{}", margin_w, margin_h);
-
         let spacing_w = rdr.read_u32::<LittleEndian>()?;
         let spacing_h = rdr.read_u32::<LittleEndian>()?;
-        println!("{}x{}", spacing_w, spacing_h);
+        log::debug!(
+            "tilesheet {:?} ({:?}) from {:?}: {}x{} sheet, {}x{} tiles, {}x{} margin, {}x{} spacing",
+            tilesheet_name,
+            description,
+            source,
+            sheet_width,
+            sheet_height,
+            tile_w,
+            tile_h,
+            margin_w,
+            margin_h,
+            spacing_w,
+            spacing_h
+        );
 
         let properties = U::parse(read_tide_properties(&mut rdr)?);
         tilesheets.push(TileSheet {
@@ -241,26 +620,34 @@ where
     }
 
     let mut layers = vec![];
+    let mut allocated = 0usize;
 
     let num_layers = rdr.read_u32::<LittleEndian>()?;
     for _ in 0..num_layers {
-        println!("---");
-
         let layer_id = read_tide_string(&mut rdr)?;
-        println!("{}", layer_id);
-
         let visible = rdr.read_u8()? != 0;
-        println!("{}", if visible { "visible" } else { "invisible" });
         let description = read_tide_string(&mut rdr)?;
-        if !description.is_empty() {
-            println!("{}", description);
-        }
         let layer_w = rdr.read_u32::<LittleEndian>()?;
         let layer_h = rdr.read_u32::<LittleEndian>()?;
-        println!("{}x{}", layer_w, layer_h);
         let tile_w = rdr.read_u32::<LittleEndian>()?;
         let tile_h = rdr.read_u32::<LittleEndian>()?;
-        println!("{}x{}", tile_w, tile_h);
+        log::debug!(
+            "layer {:?} ({:?}, {}): {}x{} of {}x{} tiles",
+            layer_id,
+            description,
+            if visible { "visible" } else { "hidden" },
+            layer_w,
+            layer_h,
+            tile_w,
+            tile_h
+        );
+
+        let cells = layer_w as u64 * layer_h as u64;
+        limit(
+            "layer cells",
+            cells.min(usize::MAX as u64) as usize,
+            options.max_layer_cells,
+        )?;
 
         let properties = V::parse(read_tide_properties(&mut rdr)?);
 
@@ -271,40 +658,36 @@ where
         while y < layer_h {
             let mut x = 0;
             while x < layer_w {
-                match rdr.read_u8()? as char {
-                    'T' => {
+                match rdr.read_u8()? {
+                    b'T' => {
                         tileset = Some(read_tide_string(&mut rdr)?);
                     }
-                    'S' => {
-                        tiles.push(Tile::Static(read_static_tile(
-                            &mut rdr,
-                            tileset.clone().unwrap(),
-                            (x, y),
-                        )?));
+                    b'S' => {
+                        // Tiles name their tilesheet with a preceding 'T'.
+                        let sheet = tileset.clone().ok_or_else(|| invalid_tag(&rdr, b'S'))?;
+                        tiles.push(Tile::Static(read_static_tile(&mut rdr, sheet, (x, y))?));
                         x += 1;
                     }
-                    'N' => {
-                        x += rdr.read_u32::<LittleEndian>()?;
+                    b'N' => {
+                        x = x.saturating_add(rdr.read_u32::<LittleEndian>()?);
                     }
-                    'A' => {
+                    b'A' => {
                         let interval = rdr.read_u32::<LittleEndian>()?;
                         let frame_count = rdr.read_u32::<LittleEndian>()?;
                         let mut frames = vec![];
                         let mut frame = 0;
                         while frame < frame_count {
-                            match rdr.read_u8()? as char {
-                                'T' => {
+                            match rdr.read_u8()? {
+                                b'T' => {
                                     tileset = Some(read_tide_string(&mut rdr)?);
                                 }
-                                'S' => {
-                                    frames.push(read_static_tile(
-                                        &mut rdr,
-                                        tileset.clone().unwrap(),
-                                        (x, y),
-                                    )?);
+                                b'S' => {
+                                    let sheet =
+                                        tileset.clone().ok_or_else(|| invalid_tag(&rdr, b'S'))?;
+                                    frames.push(read_static_tile(&mut rdr, sheet, (x, y))?);
                                     frame += 1;
                                 }
-                                _ => unreachable!("unexpected animated frame type"),
+                                tag => return Err(invalid_tag(&rdr, tag)),
                             }
                         }
                         let properties = W::parse(read_tide_properties(&mut rdr)?);
@@ -316,13 +699,18 @@ where
                         }));
                         x += 1;
                     }
-                    _ => unreachable!("unexpected frame type"),
+                    tag => return Err(invalid_tag(&rdr, tag)),
                 }
             }
             y += 1;
         }
 
-        layers.push(Layer {
+        // Each tile takes one entry in the layer's index as well.
+        let tile_bytes = mem::size_of::<Tile<W>>() + mem::size_of::<((u32, u32), u32)>();
+        allocated = allocated.saturating_add(tiles.len().saturating_mul(tile_bytes));
+        limit("total allocation", allocated, options.max_total_allocation)?;
+
+        let mut layer = Layer {
             id: layer_id,
             description: description,
             visible: visible,
@@ -330,7 +718,10 @@ where
             tile_size: (tile_w, tile_h),
             tiles: tiles,
             properties: properties,
-        });
+            grid: HashMap::new(),
+        };
+        layer.reindex();
+        layers.push(layer);
     }
     Ok(Map {
         id: map_id,
@@ -340,3 +731,109 @@ where
         properties: properties,
     })
 }
+
+#[cfg(feature = "image")]
+impl<T, U, V, W> Map<T, U, V, W> {
+    /// Draw the visible layers, in order, as they are `tick` milliseconds
+    /// into their animations. `sheet_image` gives the image of each
+    /// tilesheet, with straight alpha (as from `Texture2d::to_image` with
+    /// `unpremultiply` set); tiles from sheets without one are left out.
+    /// The image is as big as the largest layer.
+    pub fn render<'a>(
+        &self,
+        tick: u32,
+        sheet_image: &dyn Fn(&TileSheet<U>) -> Option<&'a image::RgbaImage>,
+    ) -> image::RgbaImage {
+        let (width, height) = self.layers.iter().fold((0, 0), |(w, h), layer| {
+            let (lw, lh) = layer.pixel_size();
+            (lw.max(w), lh.max(h))
+        });
+        let images: Vec<_> = self.tilesheets.iter().map(sheet_image).collect();
+        let mut image = image::RgbaImage::new(width, height);
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            self.draw_layer(&mut image, layer, tick, &images);
+        }
+        image
+    }
+
+    /// Draw one layer as by `render`, whether or not it's visible.
+    pub fn render_layer<'a>(
+        &self,
+        layer: &Layer<V, W>,
+        tick: u32,
+        sheet_image: &dyn Fn(&TileSheet<U>) -> Option<&'a image::RgbaImage>,
+    ) -> image::RgbaImage {
+        let (width, height) = layer.pixel_size();
+        let images: Vec<_> = self.tilesheets.iter().map(sheet_image).collect();
+        let mut image = image::RgbaImage::new(width, height);
+        self.draw_layer(&mut image, layer, tick, &images);
+        image
+    }
+
+    // Composites a layer's tiles over `image`, with `images` holding each
+    // tilesheet's image.
+    fn draw_layer(
+        &self,
+        image: &mut image::RgbaImage,
+        layer: &Layer<V, W>,
+        tick: u32,
+        images: &[Option<&image::RgbaImage>],
+    ) {
+        for (x, y, tile) in layer.iter_tiles() {
+            let frame = match tile.frame(tick) {
+                Some(frame) => frame,
+                None => continue,
+            };
+            let index = self.tilesheets.iter().position(|s| s.id == frame.tilesheet);
+            let (sheet, sheet_image) = match index.and_then(|i| Some((i, images[i]?))) {
+                Some((i, sheet_image)) => (&self.tilesheets[i], sheet_image),
+                None => continue,
+            };
+            let source = sheet.source_rect(frame.idx);
+            let left = x as i64 * layer.tile_size.0 as i64;
+            let top = y as i64 * layer.tile_size.1 as i64;
+            for sy in 0..source.h.max(0) as i64 {
+                for sx in 0..source.w.max(0) as i64 {
+                    let (dx, dy) = (left + sx, top + sy);
+                    let (px, py) = (source.x as i64 + sx, source.y as i64 + sy);
+                    let in_sheet = (0..sheet_image.width() as i64).contains(&px)
+                        && (0..sheet_image.height() as i64).contains(&py);
+                    let in_image = (0..image.width() as i64).contains(&dx)
+                        && (0..image.height() as i64).contains(&dy);
+                    if !in_sheet || !in_image {
+                        continue;
+                    }
+                    let src = sheet_image.get_pixel(px as u32, py as u32);
+                    let dst = image.get_pixel_mut(dx as u32, dy as u32);
+                    over(&mut dst.0, &src.0);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl<T, U> Layer<T, U> {
+    fn pixel_size(&self) -> (u32, u32) {
+        (
+            self.size.0.saturating_mul(self.tile_size.0),
+            self.size.1.saturating_mul(self.tile_size.1),
+        )
+    }
+}
+
+// Composites a straight alpha pixel over another.
+#[cfg(feature = "image")]
+fn over(dst: &mut [u8; 4], src: &[u8; 4]) {
+    let src_alpha = src[3] as f32 / 255.0;
+    let dst_alpha = dst[3] as f32 / 255.0 * (1.0 - src_alpha);
+    let alpha = src_alpha + dst_alpha;
+    if alpha == 0.0 {
+        return;
+    }
+    for c in 0..3 {
+        let value = (src[c] as f32 * src_alpha + dst[c] as f32 * dst_alpha) / alpha;
+        dst[c] = value.round() as u8;
+    }
+    dst[3] = (alpha * 255.0).round() as u8;
+}
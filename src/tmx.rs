@@ -0,0 +1,646 @@
+//! Converting tIDE maps to and from Tiled's TMX and TSX formats.
+//!
+//! Tiled keeps animations and properties per tile of a tileset, where tIDE
+//! keeps them per cell. An animation is written to the tileset tile of its
+//! first frame, and cell properties go in an object layer named after the
+//! tile layer, as one `TileData` object per cell. That's the layout Stardew
+//! Valley's TMX support reads. Properties of tileset tiles are read into
+//! the tilesheet's properties, named `@TileIndex@<index>@<name>` as tIDE
+//! stores them.
+
+use crate::tide::{AnimatedTile, Map, PropertyLookup, PropertyValue, Tile, TileSheet};
+#[cfg(feature = "tmx")]
+use crate::tide::{Layer, PropertyParse, StaticTile};
+use crate::{xml_escape, Error};
+#[cfg(feature = "tmx")]
+use roxmltree::{Document, Node};
+use std::collections::BTreeMap;
+#[cfg(feature = "tmx")]
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+#[cfg(feature = "tmx")]
+use std::{fs, str::FromStr};
+
+const TMX_VERSION: &str = "1.2";
+
+impl<T, U, V, W> Map<T, U, V, W>
+where
+    T: PropertyLookup,
+    U: PropertyLookup,
+    V: PropertyLookup,
+    W: PropertyLookup,
+{
+    /// Write the map as a TMX map. Each tilesheet is referred to as an
+    /// external tileset at the path given by `tileset_source`; see
+    /// `write_tsx`. The map's size is that of its largest layer, and its
+    /// tile size that of its first.
+    pub fn write_tmx(
+        &self,
+        wtr: &mut dyn Write,
+        tileset_source: &dyn Fn(&TileSheet<U>) -> String,
+    ) -> io::Result<()> {
+        let (width, height) = self.layers.iter().fold((0, 0), |(w, h), layer| {
+            (w.max(layer.size.0), h.max(layer.size.1))
+        });
+        let (tile_w, tile_h) = match (self.layers.first(), self.tilesheets.first()) {
+            (Some(layer), _) => layer.tile_size,
+            (None, Some(sheet)) => sheet.tile_size,
+            (None, None) => (0, 0),
+        };
+        writeln!(wtr, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            wtr,
+            "<map version=\"{}\" orientation=\"orthogonal\" renderorder=\"right-down\" \
+             width=\"{}\" height=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" infinite=\"0\">",
+            TMX_VERSION, width, height, tile_w, tile_h
+        )?;
+        write_properties(wtr, " ", &self.properties)?;
+
+        let mut first_gid: u32 = 1;
+        let mut first_gids = vec![];
+        for sheet in &self.tilesheets {
+            writeln!(
+                wtr,
+                " <tileset firstgid=\"{}\" source=\"{}\"/>",
+                first_gid,
+                xml_escape(&tileset_source(sheet))
+            )?;
+            first_gids.push(first_gid);
+            first_gid = first_gid.saturating_add(sheet.tile_count());
+        }
+        // Tiles from a missing sheet, or beyond the end of theirs, are left
+        // out rather than turning into a tile from another.
+        let gid = |tile: &Tile<W>| {
+            let index = self
+                .tilesheets
+                .iter()
+                .position(|sheet| sheet.id == tile.get_tilesheet())?;
+            let idx = tile.get_index(0);
+            if idx >= self.tilesheets[index].tile_count() {
+                return None;
+            }
+            first_gids[index].checked_add(idx)
+        };
+
+        let mut next_id = 1;
+        let mut next_object_id = 1;
+        for layer in &self.layers {
+            writeln!(
+                wtr,
+                " <layer id=\"{}\" name=\"{}\" width=\"{}\" height=\"{}\"{}>",
+                next_id,
+                xml_escape(&layer.id),
+                layer.size.0,
+                layer.size.1,
+                if layer.visible { "" } else { " visible=\"0\"" }
+            )?;
+            next_id += 1;
+            write_properties(wtr, "  ", &layer.properties)?;
+            writeln!(wtr, "  <data encoding=\"csv\">")?;
+            for y in 0..layer.size.1 {
+                let row: Vec<String> = (0..layer.size.0)
+                    .map(|x| {
+                        let gid = layer.tile_at(x, y).and_then(&gid);
+                        gid.unwrap_or(0).to_string()
+                    })
+                    .collect();
+                let last = y + 1 == layer.size.1;
+                writeln!(wtr, "{}{}", row.join(","), if last { "" } else { "," })?;
+            }
+            writeln!(wtr, "  </data>\n </layer>")?;
+
+            let data: Vec<_> = layer
+                .iter_tiles()
+                .filter(|(_, _, tile)| !tile.properties().entries().is_empty())
+                .collect();
+            if data.is_empty() {
+                continue;
+            }
+            writeln!(
+                wtr,
+                " <objectgroup id=\"{}\" name=\"{}\">",
+                next_id,
+                xml_escape(&layer.id)
+            )?;
+            next_id += 1;
+            for (x, y, tile) in data {
+                writeln!(
+                    wtr,
+                    "  <object id=\"{}\" name=\"TileData\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\">",
+                    next_object_id,
+                    x as u64 * layer.tile_size.0 as u64,
+                    y as u64 * layer.tile_size.1 as u64,
+                    layer.tile_size.0,
+                    layer.tile_size.1
+                )?;
+                next_object_id += 1;
+                write_properties(wtr, "   ", tile.properties())?;
+                writeln!(wtr, "  </object>")?;
+            }
+            writeln!(wtr, " </objectgroup>")?;
+        }
+        writeln!(wtr, "</map>")
+    }
+
+    /// Write one of the map's tilesheets as a TSX tileset, with `image` as
+    /// the path to its image. Animations are taken from the map's layers;
+    /// where tiles with the same first frame are animated differently, the
+    /// first found is kept, and frames from other sheets are left out.
+    pub fn write_tsx(
+        &self,
+        sheet: &TileSheet<U>,
+        wtr: &mut dyn Write,
+        image: &str,
+    ) -> io::Result<()> {
+        let mut animations: BTreeMap<u32, &AnimatedTile<W>> = BTreeMap::new();
+        for layer in &self.layers {
+            for tile in &layer.tiles {
+                if let Tile::Animated(ref tile) = *tile {
+                    match tile.frames.first() {
+                        Some(frame) if frame.tilesheet == sheet.id => {
+                            animations.entry(frame.idx).or_insert(tile);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let (columns, rows) = sheet.sheet_size;
+        let extent = |count: u32, margin: u32, tile: u32, spacing: u32| {
+            let tiles = count.saturating_mul(tile);
+            let spaces = count.saturating_sub(1).saturating_mul(spacing);
+            margin
+                .saturating_mul(2)
+                .saturating_add(tiles)
+                .saturating_add(spaces)
+        };
+        writeln!(wtr, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            wtr,
+            "<tileset version=\"{}\" name=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" \
+             spacing=\"{}\" margin=\"{}\" tilecount=\"{}\" columns=\"{}\">",
+            TMX_VERSION,
+            xml_escape(&sheet.id),
+            sheet.tile_size.0,
+            sheet.tile_size.1,
+            sheet.spacing.0,
+            sheet.margin.0,
+            sheet.tile_count(),
+            columns
+        )?;
+        write_properties(wtr, " ", &sheet.properties)?;
+        writeln!(
+            wtr,
+            " <image source=\"{}\" width=\"{}\" height=\"{}\"/>",
+            xml_escape(image),
+            extent(columns, sheet.margin.0, sheet.tile_size.0, sheet.spacing.0),
+            extent(rows, sheet.margin.1, sheet.tile_size.1, sheet.spacing.1)
+        )?;
+        for (idx, tile) in animations {
+            writeln!(wtr, " <tile id=\"{}\">\n  <animation>", idx)?;
+            for frame in tile.frames.iter().filter(|f| f.tilesheet == sheet.id) {
+                writeln!(
+                    wtr,
+                    "   <frame tileid=\"{}\" duration=\"{}\"/>",
+                    frame.idx, tile.interval
+                )?;
+            }
+            writeln!(wtr, "  </animation>\n </tile>")?;
+        }
+        writeln!(wtr, "</tileset>")
+    }
+
+    /// Save the map as a TMX map at `path`, with each tilesheet as a TSX
+    /// tileset alongside it, named after the sheet. The tilesets refer to
+    /// their images by the sheets' image sources, with backslashes turned
+    /// into slashes and `.png` added where there's no extension.
+    pub fn save_tmx(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let tileset_source = |sheet: &TileSheet<U>| {
+            let name: String = sheet
+                .id
+                .chars()
+                .map(|c| if c == '/' || c == '\\' { '_' } else { c })
+                .collect();
+            format!("{}.tsx", name)
+        };
+        for sheet in &self.tilesheets {
+            let mut image = sheet.image_source.replace('\\', "/");
+            if Path::new(&image).extension().is_none() {
+                image.push_str(".png");
+            }
+            let tsx = path.with_file_name(tileset_source(sheet));
+            let mut wtr = BufWriter::new(File::create(tsx)?);
+            self.write_tsx(sheet, &mut wtr, &image)?;
+            wtr.flush()?;
+        }
+        let mut wtr = BufWriter::new(File::create(path)?);
+        self.write_tmx(&mut wtr, &tileset_source)?;
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+// Writes a `properties` element, unless there are none. Strings spanning
+// lines are written as text, as line breaks in attributes don't survive.
+fn write_properties<P: PropertyLookup>(
+    wtr: &mut dyn Write,
+    indent: &str,
+    properties: &P,
+) -> io::Result<()> {
+    let entries = properties.entries();
+    if entries.is_empty() {
+        return Ok(());
+    }
+    writeln!(wtr, "{}<properties>", indent)?;
+    for (name, value) in entries {
+        write!(wtr, "{} <property name=\"{}\"", indent, xml_escape(name))?;
+        match *value {
+            PropertyValue::Bool(b) => writeln!(wtr, " type=\"bool\" value=\"{}\"/>", b)?,
+            PropertyValue::Int(i) => writeln!(wtr, " type=\"int\" value=\"{}\"/>", i)?,
+            PropertyValue::Float(f) => writeln!(wtr, " type=\"float\" value=\"{}\"/>", f)?,
+            PropertyValue::String(ref s) if s.contains('\n') => {
+                writeln!(wtr, ">{}</property>", xml_escape(s))?
+            }
+            PropertyValue::String(ref s) => writeln!(wtr, " value=\"{}\"/>", xml_escape(s))?,
+        }
+    }
+    writeln!(wtr, "{}</properties>", indent)
+}
+
+// The flags Tiled keeps in the high bits of a gid.
+#[cfg(feature = "tmx")]
+const GID_FLAGS: u32 = 0xf000_0000;
+
+#[cfg(feature = "tmx")]
+type Properties = Vec<(String, PropertyValue)>;
+
+#[cfg(feature = "tmx")]
+impl<T, U, V, W> Map<T, U, V, W>
+where
+    T: PropertyParse,
+    U: PropertyParse,
+    V: PropertyParse,
+    W: PropertyParse,
+{
+    /// Read a TMX map, such as one from `write_tmx`. External tilesets are
+    /// read with `tileset`, given their `source`. Image sources lose their
+    /// extension and have slashes turned into backslashes, undoing
+    /// `save_tmx`.
+    ///
+    /// Only finite, orthogonal maps of tile layers can be read; object
+    /// layers other than tile data are skipped and groups are flattened.
+    /// tIDE can't flip tiles, so flips are dropped, and an animation's
+    /// interval is that of its first frame.
+    pub fn from_tmx(
+        tmx: &str,
+        tileset: &dyn Fn(&str) -> Result<String, Error>,
+    ) -> Result<Self, Error> {
+        let doc = Document::parse(tmx).map_err(|e| invalid(e.to_string()))?;
+        let map = doc.root_element();
+        if !map.has_tag_name("map") {
+            return Err(invalid("no map element"));
+        }
+        if let Some(orientation) = map.attribute("orientation").filter(|&o| o != "orthogonal") {
+            return Err(invalid(format!("{} maps aren't supported", orientation)));
+        }
+        if attribute(map, "infinite", 0)? != 0 {
+            return Err(invalid("infinite maps aren't supported"));
+        }
+        let tile_size = (required(map, "tilewidth")?, required(map, "tileheight")?);
+
+        let mut tilesets = vec![];
+        for node in map.children().filter(|n| n.has_tag_name("tileset")) {
+            let first_gid: u32 = required(node, "firstgid")?;
+            let tileset = match node.attribute("source") {
+                Some(source) => {
+                    let tsx = tileset(source)?;
+                    let doc = Document::parse(&tsx).map_err(|e| invalid(e.to_string()))?;
+                    Tileset::read(doc.root_element(), first_gid)?
+                }
+                None => Tileset::read(node, first_gid)?,
+            };
+            tilesets.push(tileset);
+        }
+        tilesets.sort_by_key(|t| t.first_gid);
+
+        let (mut tile_layers, mut object_layers) = (vec![], HashMap::new());
+        flatten_layers(map, &mut tile_layers, &mut object_layers);
+        let mut layers = vec![];
+        for node in tile_layers {
+            let name = node.attribute("name").unwrap_or("");
+            let size: (u32, u32) = (required(node, "width")?, required(node, "height")?);
+            let data = node
+                .children()
+                .find(|n| n.has_tag_name("data"))
+                .ok_or_else(|| invalid(format!("layer {} has no data", name)))?;
+            let gids = gids(data, size.0 as usize * size.1 as usize)?;
+            let mut tile_data = match object_layers.get(name) {
+                Some(&objects) => tile_data(objects, tile_size)?,
+                None => HashMap::new(),
+            };
+
+            let mut tiles = vec![];
+            for (cell, &gid) in gids.iter().enumerate() {
+                let gid = gid & !GID_FLAGS;
+                if gid == 0 {
+                    continue;
+                }
+                let pos = (
+                    (cell % size.0 as usize) as u32,
+                    (cell / size.0 as usize) as u32,
+                );
+                let tileset = tilesets
+                    .iter()
+                    .rev()
+                    .find(|t| t.first_gid <= gid)
+                    .ok_or_else(|| invalid(format!("gid {} isn't in a tileset", gid)))?;
+                let idx = gid - tileset.first_gid;
+                let properties = W::parse(tile_data.remove(&pos).unwrap_or_default());
+                let frame = |idx| StaticTile {
+                    tilesheet: tileset.sheet.id.clone(),
+                    idx,
+                    pos,
+                    blend_mode: 0,
+                    properties: W::parse(vec![]),
+                };
+                tiles.push(match tileset.animations.get(&idx) {
+                    Some(frames) => Tile::Animated(AnimatedTile {
+                        interval: frames[0].1,
+                        pos,
+                        frames: frames.iter().map(|&(idx, _)| frame(idx)).collect(),
+                        properties,
+                    }),
+                    None => Tile::Static(StaticTile {
+                        properties,
+                        ..frame(idx)
+                    }),
+                });
+            }
+            let mut layer = Layer::new(
+                name.to_string(),
+                size,
+                tile_size,
+                tiles,
+                V::parse(properties(node)?),
+            );
+            layer.visible = attribute(node, "visible", 1)? != 0;
+            layers.push(layer);
+        }
+
+        Ok(Map {
+            id: String::new(),
+            description: String::new(),
+            tilesheets: tilesets.into_iter().map(|t| t.sheet).collect(),
+            layers,
+            properties: T::parse(properties(map)?),
+        })
+    }
+
+    /// Read the TMX map at `path`, with external tilesets relative to it.
+    /// The map's id is the file's name without its extension.
+    pub fn load_tmx(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let tmx = fs::read_to_string(path)?;
+        let mut map = Map::from_tmx(&tmx, &|source| {
+            Ok(fs::read_to_string(path.with_file_name(source))?)
+        })?;
+        map.id = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        Ok(map)
+    }
+}
+
+#[cfg(feature = "tmx")]
+fn invalid(msg: impl Into<String>) -> Error {
+    Error::InvalidTmx(msg.into())
+}
+
+#[cfg(feature = "tmx")]
+fn required<N: FromStr>(node: Node, name: &str) -> Result<N, Error> {
+    let value = node
+        .attribute(name)
+        .ok_or_else(|| invalid(format!("{} has no {}", node.tag_name().name(), name)))?;
+    parse(name, value)
+}
+
+#[cfg(feature = "tmx")]
+fn attribute<N: FromStr>(node: Node, name: &str, default: N) -> Result<N, Error> {
+    match node.attribute(name) {
+        Some(value) => parse(name, value),
+        None => Ok(default),
+    }
+}
+
+#[cfg(feature = "tmx")]
+fn parse<N: FromStr>(name: &str, value: &str) -> Result<N, Error> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| invalid(format!("bad {} {:?}", name, value)))
+}
+
+// The properties of an element, in order. Types Tide lacks are read as
+// strings.
+#[cfg(feature = "tmx")]
+fn properties(node: Node) -> Result<Properties, Error> {
+    let mut props = vec![];
+    for list in node.children().filter(|n| n.has_tag_name("properties")) {
+        for property in list.children().filter(|n| n.has_tag_name("property")) {
+            let name: String = required(property, "name")?;
+            let value = property
+                .attribute("value")
+                .or_else(|| property.text())
+                .unwrap_or("");
+            let value = match property.attribute("type").unwrap_or("string") {
+                "bool" => PropertyValue::Bool(value == "true"),
+                "int" | "object" => PropertyValue::Int(parse(&name, value)?),
+                "float" => PropertyValue::Float(parse(&name, value)?),
+                _ => PropertyValue::String(value.to_string()),
+            };
+            props.push((name, value));
+        }
+    }
+    Ok(props)
+}
+
+// Tile layers in drawing order, and object layers by name.
+#[cfg(feature = "tmx")]
+fn flatten_layers<'a, 'input>(
+    node: Node<'a, 'input>,
+    tile_layers: &mut Vec<Node<'a, 'input>>,
+    object_layers: &mut HashMap<&'a str, Node<'a, 'input>>,
+) {
+    for child in node.children() {
+        match child.tag_name().name() {
+            "layer" => tile_layers.push(child),
+            "objectgroup" => {
+                object_layers.insert(child.attribute("name").unwrap_or(""), child);
+            }
+            "group" => flatten_layers(child, tile_layers, object_layers),
+            _ => {}
+        }
+    }
+}
+
+// A layer's gids, flags included, from any uncompressed encoding.
+#[cfg(feature = "tmx")]
+fn gids(data: Node, count: usize) -> Result<Vec<u32>, Error> {
+    if let Some(compression) = data.attribute("compression").filter(|c| !c.is_empty()) {
+        return Err(invalid(format!(
+            "{} compression isn't supported",
+            compression
+        )));
+    }
+    let text = data.text().unwrap_or("");
+    let gids = match data.attribute("encoding") {
+        Some("csv") => text
+            .split(',')
+            .filter(|gid| !gid.trim().is_empty())
+            .map(|gid| parse("gid", gid))
+            .collect::<Result<Vec<u32>, Error>>()?,
+        Some("base64") => from_base64(text)
+            .ok_or_else(|| invalid("bad base64 data"))?
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        None => data
+            .children()
+            .filter(|n| n.has_tag_name("tile"))
+            .map(|tile| attribute(tile, "gid", 0))
+            .collect::<Result<Vec<u32>, Error>>()?,
+        Some(encoding) => return Err(invalid(format!("unknown encoding {}", encoding))),
+    };
+    if gids.len() != count {
+        return Err(invalid(format!(
+            "expected {} tiles, found {}",
+            count,
+            gids.len()
+        )));
+    }
+    Ok(gids)
+}
+
+#[cfg(feature = "tmx")]
+fn from_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = vec![];
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6 | value as u32) & 0xffff;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Some(out)
+}
+
+// The properties of `TileData` objects, by the cell they're in.
+#[cfg(feature = "tmx")]
+fn tile_data(
+    objects: Node,
+    tile_size: (u32, u32),
+) -> Result<HashMap<(u32, u32), Properties>, Error> {
+    let mut cells = HashMap::new();
+    for object in objects.children().filter(|n| n.has_tag_name("object")) {
+        if object.attribute("name") != Some("TileData") {
+            continue;
+        }
+        let x: f64 = attribute(object, "x", 0.0)?;
+        let y: f64 = attribute(object, "y", 0.0)?;
+        let cell = (
+            (x / tile_size.0.max(1) as f64) as u32,
+            (y / tile_size.1.max(1) as f64) as u32,
+        );
+        cells.insert(cell, properties(object)?);
+    }
+    Ok(cells)
+}
+
+// A tileset as needed to place its tiles.
+#[cfg(feature = "tmx")]
+struct Tileset<U> {
+    first_gid: u32,
+    sheet: TileSheet<U>,
+    // The frames, with their durations, of animated tiles.
+    animations: HashMap<u32, Vec<(u32, u32)>>,
+}
+
+#[cfg(feature = "tmx")]
+impl<U: PropertyParse> Tileset<U> {
+    fn read(node: Node, first_gid: u32) -> Result<Tileset<U>, Error> {
+        if !node.has_tag_name("tileset") {
+            return Err(invalid("no tileset element"));
+        }
+        let id: String = node.attribute("name").unwrap_or("").to_string();
+        let image = node
+            .children()
+            .find(|n| n.has_tag_name("image"))
+            .ok_or_else(|| invalid(format!("tileset {} has no image", id)))?;
+        let source: &str = image.attribute("source").unwrap_or("");
+        let source = match source.rfind('.') {
+            Some(dot) if !source[dot..].contains('/') => &source[..dot],
+            _ => source,
+        };
+        let columns: u32 = required(node, "columns")?;
+        let tile_count: u32 = required(node, "tilecount")?;
+        let rows = if columns == 0 {
+            0
+        } else {
+            tile_count.div_ceil(columns)
+        };
+        let spacing = attribute(node, "spacing", 0)?;
+        let margin = attribute(node, "margin", 0)?;
+
+        let mut props = properties(node)?;
+        let mut animations = HashMap::new();
+        for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+            let idx: u32 = required(tile, "id")?;
+            for (name, value) in properties(tile)? {
+                props.push((format!("@TileIndex@{}@{}", idx, name), value));
+            }
+            let frames = tile
+                .children()
+                .filter(|n| n.has_tag_name("animation"))
+                .flat_map(|n| n.children().filter(|n| n.has_tag_name("frame")))
+                .map(|frame| Ok((required(frame, "tileid")?, required(frame, "duration")?)))
+                .collect::<Result<Vec<(u32, u32)>, Error>>()?;
+            if !frames.is_empty() {
+                animations.insert(idx, frames);
+            }
+        }
+
+        Ok(Tileset {
+            first_gid,
+            sheet: TileSheet {
+                id,
+                description: String::new(),
+                image_source: source.replace('/', "\\"),
+                sheet_size: (columns, rows),
+                tile_size: (required(node, "tilewidth")?, required(node, "tileheight")?),
+                margin: (margin, margin),
+                spacing: (spacing, spacing),
+                properties: U::parse(props),
+            },
+            animations,
+        })
+    }
+}
@@ -0,0 +1,576 @@
+//! Untyped parsing, for tools that need to handle any XNB without knowing
+//! what it holds up front.
+
+use crate::shared::is_builtin_reader;
+use crate::tide::{self, PropertyValue, Tile};
+use crate::walk::{walk, Visitor};
+use crate::{
+    BoundingBox, BoundingSphere, Color, DateTime, Decimal, Error, ExternalReference, Matrix, Parse,
+    Plane, Quaternion, Ray, ReaderRegistry, Rectangle, SharedResource, SurfaceFormat, Tag,
+    Texture2d, TimeSpan, TypeReaders, Vector3, ARRAY_READER, LIST_READER,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::convert::TryFrom;
+use std::io::Read;
+use std::rc::Rc;
+
+pub(crate) const DICTIONARY_READER: &str = "Microsoft.Xna.Framework.Content.DictionaryReader";
+pub(crate) const NULLABLE_READER: &str = "Microsoft.Xna.Framework.Content.NullableReader";
+const ENUM_READER: &str = "Microsoft.Xna.Framework.Content.EnumReader";
+const TIDE_READER: &str = "xTile.Pipeline.TideReader";
+
+type Properties = Vec<(String, PropertyValue)>;
+
+/// Any object read from an XNB. Collections and simple structures are broken
+/// down into values; assets with no useful breakdown (models, effects, sounds,
+/// ...) are kept whole in `Other`.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// Any integer type other than `UInt64`.
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Char(char),
+    String(String),
+    /// A `List<T>` or `T[]`.
+    Array(Vec<Value>),
+    /// A `Dictionary<K, V>`, in file order.
+    Dictionary(Vec<(Value, Value)>),
+    Nullable(Option<Box<Value>>),
+    Texture(Rc<Texture2d>),
+    ExternalReference(String),
+    /// A structure such as a `Rectangle` or a tide map, with its members
+    /// named as in this crate's equivalent type. `reader` is empty for
+    /// objects nested inside an asset rather than read by a reader.
+    Object {
+        reader: String,
+        fields: Vec<(String, Value)>,
+    },
+    Other {
+        reader: String,
+        value: Tag,
+    },
+}
+
+impl Value {
+    /// The member of an object, or the entry of a dictionary with the given
+    /// string key.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self.unwrap_nullable()? {
+            Value::Object { fields, .. } => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            Value::Dictionary(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(name))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The element of an array, or the entry of a dictionary with the given
+    /// integer key.
+    pub fn index(&self, index: i64) -> Option<&Value> {
+        match self.unwrap_nullable()? {
+            Value::Array(items) => usize::try_from(index).ok().and_then(|i| items.get(i)),
+            Value::Dictionary(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_i64() == Some(index))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Look up a nested value by a path such as
+    /// `layers[2].tiles[10].properties.Passable`. Names select members and
+    /// string dictionary keys, `[n]` selects array elements and integer
+    /// dictionary keys, and `["..."]` selects keys that aren't plain names.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        let mut value = self;
+        let mut rest = path;
+        let mut first = true;
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("[\"") {
+                let end = after.find("\"]")?;
+                value = value.field(&after[..end])?;
+                rest = &after[end + 2..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']')?;
+                value = value.index(after[..end].trim().parse().ok()?)?;
+                rest = &after[end + 1..];
+            } else {
+                let after = if first { rest } else { rest.strip_prefix('.')? };
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return None;
+                }
+                value = value.field(&after[..end])?;
+                rest = &after[end..];
+            }
+            first = false;
+        }
+        Some(value)
+    }
+
+    /// Look up a nested value and convert it to `T`; `None` if the path
+    /// doesn't exist or holds something else.
+    pub fn get_as<T: FromValue>(&self, path: &str) -> Option<T> {
+        T::from_value(self.get(path)?)
+    }
+
+    // A `Nullable` with a value is treated as that value; without one, as
+    // nothing.
+    fn unwrap_nullable(&self) -> Option<&Value> {
+        match self {
+            Value::Nullable(inner) => inner.as_deref().and_then(Value::unwrap_nullable),
+            v => Some(v),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null | Value::Nullable(None))
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.unwrap_nullable()? {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.unwrap_nullable()? {
+            Value::Int(i) => Some(*i),
+            Value::UInt(u) => i64::try_from(*u).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.unwrap_nullable()? {
+            Value::Int(i) => u64::try_from(*i).ok(),
+            Value::UInt(u) => Some(*u),
+            _ => None,
+        }
+    }
+
+    /// Floats, or integers converted to floats.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.unwrap_nullable()? {
+            Value::Float(f) => Some(*f),
+            Value::Int(i) => Some(*i as f64),
+            Value::UInt(u) => Some(*u as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_char(&self) -> Option<char> {
+        match self.unwrap_nullable()? {
+            Value::Char(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.unwrap_nullable()? {
+            Value::String(s) | Value::ExternalReference(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self.unwrap_nullable()? {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&[(Value, Value)]> {
+        match self.unwrap_nullable()? {
+            Value::Dictionary(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn as_texture(&self) -> Option<&Texture2d> {
+        match self.unwrap_nullable()? {
+            Value::Texture(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+/// Types that a `Value` can be converted to by `Value::get_as`.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+macro_rules! from_value_int {
+    ($($ty:ty),+) => {
+        $(
+            impl FromValue for $ty {
+                fn from_value(value: &Value) -> Option<$ty> {
+                    match value.unwrap_nullable()? {
+                        Value::UInt(u) => <$ty>::try_from(*u).ok(),
+                        v => v.as_i64().and_then(|i| <$ty>::try_from(i).ok()),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+from_value_int!(i8, u8, i16, u16, i32, u32, i64, u64, usize);
+
+impl FromValue for f32 {
+    fn from_value(value: &Value) -> Option<f32> {
+        value.as_f64().map(|f| f as f32)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Option<f64> {
+        value.as_f64()
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Option<bool> {
+        value.as_bool()
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: &Value) -> Option<char> {
+        value.as_char()
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Option<String> {
+        value.as_str().map(str::to_string)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Option<Vec<T>> {
+        value.as_array()?.iter().map(T::from_value).collect()
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    /// `Some(None)` for null values.
+    fn from_value(value: &Value) -> Option<Option<T>> {
+        if value.is_null() {
+            return Some(None);
+        }
+        T::from_value(value).map(Some)
+    }
+}
+
+fn object(reader: &str, fields: Vec<(&str, Value)>) -> Value {
+    Value::Object {
+        reader: reader.to_string(),
+        fields: fields
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+    }
+}
+
+fn float(v: f32) -> Value {
+    Value::Float(v.into())
+}
+
+fn vector3_fields(v: Vector3) -> Vec<(&'static str, Value)> {
+    vec![("x", float(v.x)), ("y", float(v.y)), ("z", float(v.z))]
+}
+
+fn vector3(v: Vector3) -> Value {
+    object("", vector3_fields(v))
+}
+
+fn pair(p: (u32, u32)) -> Value {
+    Value::Array(vec![Value::Int(p.0.into()), Value::Int(p.1.into())])
+}
+
+fn properties(props: Properties) -> Value {
+    Value::Dictionary(
+        props
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    PropertyValue::Bool(b) => Value::Bool(b),
+                    PropertyValue::Int(i) => Value::Int(i.into()),
+                    PropertyValue::Float(f) => float(f),
+                    PropertyValue::String(s) => Value::String(s),
+                };
+                (Value::String(name), value)
+            })
+            .collect(),
+    )
+}
+
+fn static_tile(tile: tide::StaticTile<Properties>) -> Value {
+    object(
+        "",
+        vec![
+            ("tilesheet", Value::String(tile.tilesheet)),
+            ("idx", Value::Int(tile.idx.into())),
+            ("pos", pair(tile.pos)),
+            ("blend_mode", Value::Int(tile.blend_mode.into())),
+            ("properties", properties(tile.properties)),
+        ],
+    )
+}
+
+fn tide_map(map: tide::Map<Properties, Properties, Properties, Properties>) -> Value {
+    let tilesheets = map.tilesheets.into_iter().map(|sheet| {
+        object(
+            "",
+            vec![
+                ("id", Value::String(sheet.id)),
+                ("description", Value::String(sheet.description)),
+                ("image_source", Value::String(sheet.image_source)),
+                ("sheet_size", pair(sheet.sheet_size)),
+                ("tile_size", pair(sheet.tile_size)),
+                ("margin", pair(sheet.margin)),
+                ("spacing", pair(sheet.spacing)),
+                ("properties", properties(sheet.properties)),
+            ],
+        )
+    });
+    let layers = map.layers.into_iter().map(|layer| {
+        let tiles = layer.tiles.into_iter().map(|tile| match tile {
+            Tile::Static(tile) => static_tile(tile),
+            Tile::Animated(tile) => object(
+                "",
+                vec![
+                    ("interval", Value::Int(tile.interval.into())),
+                    ("pos", pair(tile.pos)),
+                    (
+                        "frames",
+                        Value::Array(tile.frames.into_iter().map(static_tile).collect()),
+                    ),
+                    ("properties", properties(tile.properties)),
+                ],
+            ),
+        });
+        object(
+            "",
+            vec![
+                ("id", Value::String(layer.id)),
+                ("description", Value::String(layer.description)),
+                ("tiles", Value::Array(tiles.collect())),
+                ("visible", Value::Bool(layer.visible)),
+                ("size", pair(layer.size)),
+                ("tile_size", pair(layer.tile_size)),
+                ("properties", properties(layer.properties)),
+            ],
+        )
+    });
+    object(
+        TIDE_READER,
+        vec![
+            ("id", Value::String(map.id)),
+            ("description", Value::String(map.description)),
+            ("tilesheets", Value::Array(tilesheets.collect())),
+            ("layers", Value::Array(layers.collect())),
+            ("properties", properties(map.properties)),
+        ],
+    )
+}
+
+/// Whether objects written by `reader` (without assembly or generic
+/// arguments) can be read as a `Value`.
+pub(crate) fn can_read(reader: &str, registry: &ReaderRegistry) -> bool {
+    match reader {
+        ARRAY_READER | LIST_READER | DICTIONARY_READER | NULLABLE_READER | ENUM_READER
+        | TIDE_READER => true,
+        _ => {
+            reader == <ExternalReference<()> as Parse>::READER
+                || is_builtin_reader(reader)
+                || registry.contains(reader)
+        }
+    }
+}
+
+// Builds a `Value` as an object is walked.
+struct Builder;
+
+impl<'r> Visitor<dyn Read + 'r> for Builder {
+    type Output = Value;
+    type Mip = Vec<u8>;
+
+    fn null(&mut self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn leaf(
+        &mut self,
+        main: &str,
+        args: Vec<&str>,
+        rdr: &mut (dyn Read + 'r),
+        readers: &TypeReaders,
+    ) -> Result<Value, Error> {
+        read_leaf(main, args, rdr, readers)
+    }
+
+    fn array(&mut self, items: Vec<Value>) -> Result<Value, Error> {
+        Ok(Value::Array(items))
+    }
+
+    fn dictionary(&mut self, entries: Vec<(Value, Value)>) -> Result<Value, Error> {
+        Ok(Value::Dictionary(entries))
+    }
+
+    fn nullable(&mut self, value: Option<Value>) -> Result<Value, Error> {
+        Ok(Value::Nullable(value.map(Box::new)))
+    }
+
+    fn mip(
+        &mut self,
+        _level: usize,
+        size: usize,
+        rdr: &mut (dyn Read + 'r),
+        readers: &TypeReaders,
+    ) -> Result<Vec<u8>, Error> {
+        readers.read_data(rdr, size)
+    }
+
+    fn texture(
+        &mut self,
+        format: SurfaceFormat,
+        width: usize,
+        height: usize,
+        mip_data: Vec<Vec<u8>>,
+    ) -> Result<Value, Error> {
+        Ok(Value::Texture(Rc::new(Texture2d {
+            format,
+            width,
+            height,
+            mip_data,
+        })))
+    }
+}
+
+impl Parse for Value {
+    const READER: &'static str = "System.Object";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        Self::parse(Self::READER, rdr, readers, args)
+    }
+
+    fn parse(
+        main: &str,
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        walk(&mut Builder, main, args, rdr, readers)
+    }
+}
+
+/// Read an object that isn't broken down by the walk: anything but a
+/// collection, nullable or texture.
+pub(crate) fn read_leaf(
+    main: &str,
+    args: Vec<&str>,
+    rdr: &mut dyn Read,
+    readers: &TypeReaders,
+) -> Result<Value, Error> {
+    macro_rules! convert {
+        ($($ty:ty => $f:expr),+ $(,)*) => {
+            $(
+                if main == <$ty as Parse>::READER {
+                    let value = <$ty as Parse>::parse(main, rdr, readers, args)?;
+                    return Ok(($f)(value));
+                }
+            )+
+        };
+    }
+    convert!(
+        bool => Value::Bool,
+        char => Value::Char,
+        u8 => |v: u8| Value::Int(v.into()),
+        i8 => |v: i8| Value::Int(v.into()),
+        i16 => |v: i16| Value::Int(v.into()),
+        u16 => |v: u16| Value::Int(v.into()),
+        i32 => |v: i32| Value::Int(v.into()),
+        u32 => |v: u32| Value::Int(v.into()),
+        i64 => Value::Int,
+        u64 => Value::UInt,
+        f32 => float,
+        f64 => Value::Float,
+        String => Value::String,
+        ExternalReference<()> => |r: ExternalReference<()>| Value::ExternalReference(r.name),
+        // Decimals are approximated.
+        Decimal => |d: Decimal| {
+            let v = d.mantissa() as f64 / 10f64.powi(d.scale() as i32);
+            Value::Float(if d.is_negative() { -v } else { v })
+        },
+        DateTime => |d: DateTime| object(main, vec![
+            ("ticks", Value::UInt(d.ticks)),
+            ("kind", Value::String(format!("{:?}", d.kind))),
+        ]),
+        TimeSpan => |t: TimeSpan| object(main, vec![("ticks", Value::Int(t.ticks))]),
+        Rectangle => |r: Rectangle| object(main, vec![
+            ("x", Value::Int(r.x.into())),
+            ("y", Value::Int(r.y.into())),
+            ("w", Value::Int(r.w.into())),
+            ("h", Value::Int(r.h.into())),
+        ]),
+        Vector3 => |v| object(main, vector3_fields(v)),
+        Color => |c: Color| object(main, vec![
+            ("r", Value::Int(c.r.into())),
+            ("g", Value::Int(c.g.into())),
+            ("b", Value::Int(c.b.into())),
+            ("a", Value::Int(c.a.into())),
+        ]),
+        Quaternion => |q: Quaternion| object(main, vec![
+            ("x", float(q.x)),
+            ("y", float(q.y)),
+            ("z", float(q.z)),
+            ("w", float(q.w)),
+        ]),
+        Matrix => |m: Matrix| object(main, vec![(
+            "m",
+            Value::Array(m.m.iter()
+                .map(|row| Value::Array(row.iter().cloned().map(float).collect()))
+                .collect()),
+        )]),
+        BoundingBox => |b: BoundingBox| object(main, vec![
+            ("min", vector3(b.min)),
+            ("max", vector3(b.max)),
+        ]),
+        BoundingSphere => |s: BoundingSphere| object(main, vec![
+            ("center", vector3(s.center)),
+            ("radius", float(s.radius)),
+        ]),
+        Plane => |p: Plane| object(main, vec![
+            ("normal", vector3(p.normal)),
+            ("d", float(p.d)),
+        ]),
+        Ray => |r: Ray| object(main, vec![
+            ("position", vector3(r.position)),
+            ("direction", vector3(r.direction)),
+        ]),
+        tide::Map<Properties, Properties, Properties, Properties> => tide_map,
+    );
+
+    match main {
+        // Enums are written as their underlying type, which is almost
+        // always `Int32`.
+        ENUM_READER => Ok(Value::Int(rdr.read_i32::<LittleEndian>()?.into())),
+        _ => {
+            let value = SharedResource::parse(main, rdr, readers, args)?;
+            Ok(Value::Other {
+                reader: main.to_string(),
+                value: Tag(value),
+            })
+        }
+    }
+}
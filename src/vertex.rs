@@ -0,0 +1,222 @@
+use crate::{Error, Parse, TypeReaders};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Read;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum VertexElementFormat {
+    Single,
+    Vector2,
+    Vector3,
+    Vector4,
+    Color,
+    Byte4,
+    Short2,
+    Short4,
+    NormalizedShort2,
+    NormalizedShort4,
+    HalfVector2,
+    HalfVector4,
+}
+
+impl VertexElementFormat {
+    fn from(val: u32) -> Result<VertexElementFormat, Error> {
+        Ok(match val {
+            0 => VertexElementFormat::Single,
+            1 => VertexElementFormat::Vector2,
+            2 => VertexElementFormat::Vector3,
+            3 => VertexElementFormat::Vector4,
+            4 => VertexElementFormat::Color,
+            5 => VertexElementFormat::Byte4,
+            6 => VertexElementFormat::Short2,
+            7 => VertexElementFormat::Short4,
+            8 => VertexElementFormat::NormalizedShort2,
+            9 => VertexElementFormat::NormalizedShort4,
+            10 => VertexElementFormat::HalfVector2,
+            11 => VertexElementFormat::HalfVector4,
+            f => return Err(Error::UnrecognizedVertexElementFormat(f)),
+        })
+    }
+
+    /// The size of an element of this format, in bytes.
+    pub fn size(&self) -> usize {
+        match *self {
+            VertexElementFormat::Single
+            | VertexElementFormat::Color
+            | VertexElementFormat::Byte4
+            | VertexElementFormat::Short2
+            | VertexElementFormat::NormalizedShort2
+            | VertexElementFormat::HalfVector2 => 4,
+            VertexElementFormat::Vector2
+            | VertexElementFormat::Short4
+            | VertexElementFormat::NormalizedShort4
+            | VertexElementFormat::HalfVector4 => 8,
+            VertexElementFormat::Vector3 => 12,
+            VertexElementFormat::Vector4 => 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum VertexElementUsage {
+    Position,
+    Color,
+    TextureCoordinate,
+    Normal,
+    Binormal,
+    Tangent,
+    BlendIndices,
+    BlendWeight,
+    Depth,
+    Fog,
+    PointSize,
+    Sample,
+    TessellateFactor,
+}
+
+impl VertexElementUsage {
+    fn from(val: u32) -> Result<VertexElementUsage, Error> {
+        Ok(match val {
+            0 => VertexElementUsage::Position,
+            1 => VertexElementUsage::Color,
+            2 => VertexElementUsage::TextureCoordinate,
+            3 => VertexElementUsage::Normal,
+            4 => VertexElementUsage::Binormal,
+            5 => VertexElementUsage::Tangent,
+            6 => VertexElementUsage::BlendIndices,
+            7 => VertexElementUsage::BlendWeight,
+            8 => VertexElementUsage::Depth,
+            9 => VertexElementUsage::Fog,
+            10 => VertexElementUsage::PointSize,
+            11 => VertexElementUsage::Sample,
+            12 => VertexElementUsage::TessellateFactor,
+            u => return Err(Error::UnrecognizedVertexElementUsage(u)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VertexElement {
+    pub offset: u32,
+    pub format: VertexElementFormat,
+    pub usage: VertexElementUsage,
+    pub usage_index: u32,
+}
+
+/// The layout of each vertex in a vertex buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VertexDeclaration {
+    pub stride: u32,
+    pub elements: Vec<VertexElement>,
+}
+
+impl VertexDeclaration {
+    pub(crate) fn new(rdr: &mut dyn Read) -> Result<VertexDeclaration, Error> {
+        let stride = rdr.read_u32::<LittleEndian>()?;
+        let count = rdr.read_u32::<LittleEndian>()?;
+        let mut elements = vec![];
+        for _ in 0..count {
+            elements.push(VertexElement {
+                offset: rdr.read_u32::<LittleEndian>()?,
+                format: VertexElementFormat::from(rdr.read_u32::<LittleEndian>()?)?,
+                usage: VertexElementUsage::from(rdr.read_u32::<LittleEndian>()?)?,
+                usage_index: rdr.read_u32::<LittleEndian>()?,
+            });
+        }
+        Ok(VertexDeclaration { stride, elements })
+    }
+
+    /// The first element with the given usage and usage index, if any.
+    pub fn element(&self, usage: VertexElementUsage, usage_index: u32) -> Option<&VertexElement> {
+        self.elements
+            .iter()
+            .find(|e| e.usage == usage && e.usage_index == usage_index)
+    }
+}
+
+impl Parse for VertexDeclaration {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.VertexDeclarationReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        _readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        VertexDeclaration::new(rdr)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VertexBuffer {
+    pub declaration: VertexDeclaration,
+    pub vertex_count: u32,
+    pub data: Vec<u8>,
+}
+
+impl VertexBuffer {
+    /// The raw bytes of the vertex at `index`.
+    pub fn vertex(&self, index: usize) -> Option<&[u8]> {
+        let stride = self.declaration.stride as usize;
+        let start = index.checked_mul(stride)?;
+        self.data.get(start..start.checked_add(stride)?)
+    }
+}
+
+impl Parse for VertexBuffer {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.VertexBufferReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let declaration = VertexDeclaration::new(rdr)?;
+        let vertex_count = rdr.read_u32::<LittleEndian>()?;
+        let size = (declaration.stride as usize).saturating_mul(vertex_count as usize);
+        let data = readers.read_data(rdr, size)?;
+        Ok(VertexBuffer {
+            declaration,
+            vertex_count,
+            data,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IndexBuffer {
+    pub sixteen_bit: bool,
+    pub data: Vec<u8>,
+}
+
+impl IndexBuffer {
+    pub fn indices(&self) -> Vec<u32> {
+        if self.sixteen_bit {
+            self.data
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]) as u32)
+                .collect()
+        } else {
+            self.data
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        }
+    }
+}
+
+impl Parse for IndexBuffer {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.IndexBufferReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let sixteen_bit = rdr.read_u8()? != 0;
+        let size = rdr.read_u32::<LittleEndian>()?;
+        let data = readers.read_data(rdr, size as usize)?;
+        Ok(IndexBuffer { sixteen_bit, data })
+    }
+}
@@ -0,0 +1,64 @@
+use crate::external::resolve_relative;
+use crate::{read_object, Error, Parse, TypeReaders};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum VideoSoundtrackType {
+    Music,
+    Dialog,
+    MusicAndDialog,
+}
+
+impl VideoSoundtrackType {
+    fn from(val: i32) -> Result<VideoSoundtrackType, Error> {
+        Ok(match val {
+            0 => VideoSoundtrackType::Music,
+            1 => VideoSoundtrackType::Dialog,
+            2 => VideoSoundtrackType::MusicAndDialog,
+            t => return Err(Error::UnrecognizedSoundtrackType(t)),
+        })
+    }
+}
+
+/// A video clip. The video itself lives in a separate file (usually .wmv)
+/// next to the XNB.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Video {
+    pub filename: String,
+    pub duration: Duration,
+    pub width: i32,
+    pub height: i32,
+    pub frames_per_second: f32,
+    pub soundtrack_type: VideoSoundtrackType,
+}
+
+impl Video {
+    /// The path of the video file, given the path of the video's XNB.
+    pub fn path(&self, xnb: &Path) -> PathBuf {
+        resolve_relative(xnb, &self.filename)
+    }
+}
+
+impl Parse for Video {
+    const READER: &'static str = "Microsoft.Xna.Framework.Content.VideoReader";
+    fn try_parse(
+        rdr: &mut dyn Read,
+        readers: &TypeReaders,
+        _args: Vec<&str>,
+    ) -> Result<Self, Error> {
+        let filename = read_object(rdr, readers)?;
+        let duration: i32 = read_object(rdr, readers)?;
+        Ok(Video {
+            filename,
+            duration: Duration::from_millis(duration.max(0) as u64),
+            width: read_object(rdr, readers)?,
+            height: read_object(rdr, readers)?,
+            frames_per_second: read_object(rdr, readers)?,
+            soundtrack_type: VideoSoundtrackType::from(read_object(rdr, readers)?)?,
+        })
+    }
+}
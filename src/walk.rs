@@ -0,0 +1,189 @@
+//! The walk through untyped objects shared by `Value`, `BorrowedValue` and the
+//! event parser. Collections, nullables and textures are broken down here; a
+//! `Visitor` decides what to build from them.
+
+use crate::value::{DICTIONARY_READER, NULLABLE_READER};
+use crate::{
+    generic_types_from_reader, read_7bit_encoded_int, reader_from_type, type_arg, Error, Parse,
+    SurfaceFormat, Texture2d, TypeReaders, ARRAY_READER, LIST_READER,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Read;
+
+/// Builds something from objects read from an `R`.
+pub(crate) trait Visitor<R: Read + ?Sized> {
+    type Output;
+    /// One level of a texture's mip chain.
+    type Mip;
+
+    fn null(&mut self) -> Result<Self::Output, Error>;
+
+    fn begin_object(&mut self, _reader: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn end_object(&mut self, value: Self::Output) -> Result<Self::Output, Error> {
+        Ok(value)
+    }
+
+    /// Anything the walk doesn't break down, read by the reader `main`.
+    fn leaf(
+        &mut self,
+        main: &str,
+        args: Vec<&str>,
+        rdr: &mut R,
+        readers: &TypeReaders,
+    ) -> Result<Self::Output, Error>;
+
+    fn begin_array(&mut self, _len: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn array(&mut self, items: Vec<Self::Output>) -> Result<Self::Output, Error>;
+
+    fn begin_dictionary(&mut self, _len: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn dictionary(
+        &mut self,
+        entries: Vec<(Self::Output, Self::Output)>,
+    ) -> Result<Self::Output, Error>;
+
+    fn nullable(&mut self, value: Option<Self::Output>) -> Result<Self::Output, Error>;
+
+    fn begin_texture(
+        &mut self,
+        _format: SurfaceFormat,
+        _width: usize,
+        _height: usize,
+        _mip_count: usize,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Read the `size` bytes of data of mip level `level`, which have already
+    /// been checked against the parse options.
+    fn mip(
+        &mut self,
+        level: usize,
+        size: usize,
+        rdr: &mut R,
+        readers: &TypeReaders,
+    ) -> Result<Self::Mip, Error>;
+
+    fn texture(
+        &mut self,
+        format: SurfaceFormat,
+        width: usize,
+        height: usize,
+        mips: Vec<Self::Mip>,
+    ) -> Result<Self::Output, Error>;
+}
+
+/// Walk an object whose reader id may be 0, meaning null.
+pub(crate) fn optional_object<R: Read + ?Sized, V: Visitor<R>>(
+    visitor: &mut V,
+    rdr: &mut R,
+    readers: &TypeReaders,
+) -> Result<Option<V::Output>, Error> {
+    let id = read_7bit_encoded_int(rdr)? as usize;
+    if id == 0 {
+        return Ok(None);
+    }
+    let name = &readers
+        .get(id - 1)
+        .ok_or_else(|| Error::UnknownReader(id.to_string()))?
+        .name;
+    let main = name.split('`').next().unwrap().split(',').next().unwrap();
+    let args = generic_types_from_reader(name)?;
+    visitor.begin_object(main)?;
+    let value = readers
+        .nested(|| walk(visitor, main, args, rdr, readers))
+        .map_err(|e| e.within(main, readers.position()))?;
+    visitor.end_object(value).map(Some)
+}
+
+/// Walk an object read by the reader `main`.
+pub(crate) fn walk<R: Read + ?Sized, V: Visitor<R>>(
+    visitor: &mut V,
+    main: &str,
+    args: Vec<&str>,
+    rdr: &mut R,
+    readers: &TypeReaders,
+) -> Result<V::Output, Error> {
+    match main {
+        <Texture2d as Parse>::READER => texture(visitor, rdr, readers),
+        ARRAY_READER | LIST_READER => {
+            let len = rdr.read_u32::<LittleEndian>()? as usize;
+            readers.check_len::<V::Output>(len)?;
+            visitor.begin_array(len)?;
+            let items = (0..len)
+                .map(|_| member(visitor, type_arg(&args, 0)?, rdr, readers))
+                .collect::<Result<_, Error>>()?;
+            visitor.array(items)
+        }
+        DICTIONARY_READER => {
+            let len = rdr.read_u32::<LittleEndian>()? as usize;
+            readers.check_len::<(V::Output, V::Output)>(len)?;
+            visitor.begin_dictionary(len)?;
+            let entries = (0..len)
+                .map(|_| {
+                    let key = member(visitor, type_arg(&args, 0)?, rdr, readers)?;
+                    let value = member(visitor, type_arg(&args, 1)?, rdr, readers)?;
+                    Ok((key, value))
+                })
+                .collect::<Result<_, Error>>()?;
+            visitor.dictionary(entries)
+        }
+        NULLABLE_READER => {
+            if rdr.read_u8()? == 0 {
+                return visitor.nullable(None);
+            }
+            let value = member(visitor, type_arg(&args, 0)?, rdr, readers)?;
+            visitor.nullable(Some(value))
+        }
+        _ => visitor.leaf(main, args, rdr, readers),
+    }
+}
+
+// A member of a collection: value types are inline, anything else is an
+// object that may be null.
+fn member<R: Read + ?Sized, V: Visitor<R>>(
+    visitor: &mut V,
+    typename: &str,
+    rdr: &mut R,
+    readers: &TypeReaders,
+) -> Result<V::Output, Error> {
+    if let Some(reader) = reader_from_type(typename) {
+        return walk(visitor, reader, vec![], rdr, readers);
+    }
+    if typename.starts_with("System.Nullable`1") {
+        let args = generic_types_from_reader(typename)?;
+        return readers.nested(|| walk(visitor, NULLABLE_READER, args, rdr, readers));
+    }
+    match optional_object(visitor, rdr, readers)? {
+        Some(value) => Ok(value),
+        None => visitor.null(),
+    }
+}
+
+fn texture<R: Read + ?Sized, V: Visitor<R>>(
+    visitor: &mut V,
+    rdr: &mut R,
+    readers: &TypeReaders,
+) -> Result<V::Output, Error> {
+    let format = SurfaceFormat::from(rdr.read_u32::<LittleEndian>()?)?;
+    let width = rdr.read_u32::<LittleEndian>()? as usize;
+    let height = rdr.read_u32::<LittleEndian>()? as usize;
+    let mip_count = rdr.read_u32::<LittleEndian>()? as usize;
+    readers.check_len::<V::Mip>(mip_count)?;
+    visitor.begin_texture(format, width, height, mip_count)?;
+    let mut mips = vec![];
+    for level in 0..mip_count {
+        let size = rdr.read_u32::<LittleEndian>()? as usize;
+        readers.check_mip_size(format, width, height, level, size)?;
+        mips.push(visitor.mip(level, size, rdr, readers)?);
+    }
+    visitor.texture(format, width, height, mips)
+}
@@ -0,0 +1,615 @@
+use crate::tide::{self, PropertyLookup};
+use crate::{
+    lz4, lzx_compress, reader_from_type, Array, DateTime, Decimal, Dictionary, Error, Rectangle,
+    SpriteFont, Texture2d, TimeSpan, Vector3,
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::io::Write;
+use std::time::Duration;
+
+const MSCORLIB: &str =
+    "mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089";
+const XNA_FRAMEWORK: &str =
+    "Microsoft.Xna.Framework, Version=4.0.0.0, Culture=neutral, PublicKeyToken=842cf8be1de50553";
+const XNA_GRAPHICS: &str = "Microsoft.Xna.Framework.Graphics, Version=4.0.0.0, Culture=neutral, PublicKeyToken=842cf8be1de50553";
+
+pub(crate) fn system_type(name: &str) -> String {
+    format!("{}, {}", name, MSCORLIB)
+}
+
+pub(crate) fn framework_type(name: &str) -> String {
+    format!("{}, {}", name, XNA_FRAMEWORK)
+}
+
+pub(crate) fn graphics_type(name: &str) -> String {
+    format!("{}, {}", name, XNA_GRAPHICS)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Lzx,
+    Lz4,
+}
+
+/// The type reader table of an XNB being written. Readers are added the
+/// first time a type is written and are identified by their 1-based index.
+#[derive(Debug, Default)]
+pub struct TypeWriters {
+    readers: Vec<String>,
+}
+
+impl TypeWriters {
+    pub fn new() -> TypeWriters {
+        TypeWriters::default()
+    }
+
+    pub fn register<T: Serialize + ?Sized>(&mut self) -> u32 {
+        self.register_reader(T::reader_name())
+    }
+
+    /// Register a reader by name, for objects whose type is only known at
+    /// runtime.
+    pub fn register_reader(&mut self, name: String) -> u32 {
+        let idx = match self.readers.iter().position(|r| *r == name) {
+            Some(idx) => idx,
+            None => {
+                self.readers.push(name);
+                self.readers.len() - 1
+            }
+        };
+        idx as u32 + 1
+    }
+
+    pub fn readers(&self) -> &[String] {
+        &self.readers
+    }
+}
+
+pub trait Serialize {
+    fn reader_name() -> String;
+    fn type_name() -> String;
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error>;
+}
+
+impl Serialize for i32 {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.Int32Reader".to_string()
+    }
+    fn type_name() -> String {
+        system_type("System.Int32")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_i32::<LittleEndian>(*self).map_err(Error::from)
+    }
+}
+
+macro_rules! serialize_le_primitive {
+    ($ty:ty, $name:expr, $write:ident) => {
+        impl Serialize for $ty {
+            fn reader_name() -> String {
+                format!("Microsoft.Xna.Framework.Content.{}Reader", $name)
+            }
+            fn type_name() -> String {
+                system_type(concat!("System.", $name))
+            }
+            fn serialize(
+                &self,
+                wtr: &mut dyn Write,
+                _writers: &mut TypeWriters,
+            ) -> Result<(), Error> {
+                wtr.$write::<LittleEndian>(*self).map_err(Error::from)
+            }
+        }
+    };
+}
+
+serialize_le_primitive!(i16, "Int16", write_i16);
+serialize_le_primitive!(u16, "UInt16", write_u16);
+serialize_le_primitive!(u32, "UInt32", write_u32);
+serialize_le_primitive!(i64, "Int64", write_i64);
+serialize_le_primitive!(u64, "UInt64", write_u64);
+serialize_le_primitive!(f32, "Single", write_f32);
+serialize_le_primitive!(f64, "Double", write_f64);
+
+impl Serialize for u8 {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.ByteReader".to_string()
+    }
+    fn type_name() -> String {
+        system_type("System.Byte")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_u8(*self).map_err(Error::from)
+    }
+}
+
+impl Serialize for i8 {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.SByteReader".to_string()
+    }
+    fn type_name() -> String {
+        system_type("System.SByte")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_i8(*self).map_err(Error::from)
+    }
+}
+
+impl Serialize for bool {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.BooleanReader".to_string()
+    }
+    fn type_name() -> String {
+        system_type("System.Boolean")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_u8(*self as u8).map_err(Error::from)
+    }
+}
+
+impl Serialize for char {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.CharReader".to_string()
+    }
+    fn type_name() -> String {
+        system_type("System.Char")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        let mut buf = [0; 4];
+        wtr.write_all(self.encode_utf8(&mut buf).as_bytes())
+            .map_err(Error::from)
+    }
+}
+
+impl Serialize for String {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.StringReader".to_string()
+    }
+    fn type_name() -> String {
+        system_type("System.String")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        write_string(wtr, self)
+    }
+}
+
+impl Serialize for Decimal {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.DecimalReader".to_string()
+    }
+    fn type_name() -> String {
+        system_type("System.Decimal")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_u32::<LittleEndian>(self.lo)?;
+        wtr.write_u32::<LittleEndian>(self.mid)?;
+        wtr.write_u32::<LittleEndian>(self.hi)?;
+        wtr.write_u32::<LittleEndian>(self.flags)?;
+        Ok(())
+    }
+}
+
+impl Serialize for DateTime {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.DateTimeReader".to_string()
+    }
+    fn type_name() -> String {
+        system_type("System.DateTime")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_u64::<LittleEndian>(self.packed())
+            .map_err(Error::from)
+    }
+}
+
+impl Serialize for TimeSpan {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.TimeSpanReader".to_string()
+    }
+    fn type_name() -> String {
+        system_type("System.TimeSpan")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_i64::<LittleEndian>(self.ticks)
+            .map_err(Error::from)
+    }
+}
+
+impl Serialize for Duration {
+    fn reader_name() -> String {
+        TimeSpan::reader_name()
+    }
+    fn type_name() -> String {
+        TimeSpan::type_name()
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        // Spans beyond ~29,000 years don't fit in a TimeSpan; saturate rather than wrap.
+        let ticks = (self.as_nanos() / 100).min(i64::MAX as u128) as i64;
+        TimeSpan { ticks }.serialize(wtr, writers)
+    }
+}
+
+impl Serialize for Rectangle {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.RectangleReader".to_string()
+    }
+    fn type_name() -> String {
+        framework_type("Microsoft.Xna.Framework.Rectangle")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_i32::<LittleEndian>(self.x)?;
+        wtr.write_i32::<LittleEndian>(self.y)?;
+        wtr.write_i32::<LittleEndian>(self.w)?;
+        wtr.write_i32::<LittleEndian>(self.h)?;
+        Ok(())
+    }
+}
+
+impl Serialize for Vector3 {
+    fn reader_name() -> String {
+        "Microsoft.Xna.Framework.Content.Vector3Reader".to_string()
+    }
+    fn type_name() -> String {
+        framework_type("Microsoft.Xna.Framework.Vector3")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        wtr.write_f32::<LittleEndian>(self.x)?;
+        wtr.write_f32::<LittleEndian>(self.y)?;
+        wtr.write_f32::<LittleEndian>(self.z)?;
+        Ok(())
+    }
+}
+
+impl Serialize for Texture2d {
+    fn reader_name() -> String {
+        graphics_type("Microsoft.Xna.Framework.Content.Texture2DReader")
+    }
+    fn type_name() -> String {
+        graphics_type("Microsoft.Xna.Framework.Graphics.Texture2D")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        for (level, data) in self.mip_data.iter().enumerate() {
+            let expected = self.format.mip_size(self.width, self.height, level);
+            if data.len() != expected {
+                return Err(Error::InvalidMipSize {
+                    level,
+                    expected,
+                    actual: data.len(),
+                });
+            }
+        }
+        wtr.write_u32::<LittleEndian>(self.format as u32)?;
+        wtr.write_u32::<LittleEndian>(self.width as u32)?;
+        wtr.write_u32::<LittleEndian>(self.height as u32)?;
+        wtr.write_u32::<LittleEndian>(self.mip_data.len() as u32)?;
+        for data in &self.mip_data {
+            wtr.write_u32::<LittleEndian>(data.len() as u32)?;
+            wtr.write_all(data)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, U, V, W> Serialize for tide::Map<T, U, V, W>
+where
+    T: PropertyLookup,
+    U: PropertyLookup,
+    V: PropertyLookup,
+    W: PropertyLookup,
+{
+    fn reader_name() -> String {
+        "xTile.Pipeline.TideReader, xTile".to_string()
+    }
+    fn type_name() -> String {
+        "xTile.Map, xTile".to_string()
+    }
+    fn serialize(&self, wtr: &mut dyn Write, _writers: &mut TypeWriters) -> Result<(), Error> {
+        tide::write_tide(self, wtr)?;
+        Ok(())
+    }
+}
+
+impl<T: Serialize> Serialize for Vec<T> {
+    fn reader_name() -> String {
+        format!(
+            "Microsoft.Xna.Framework.Content.ListReader`1[[{}]]",
+            T::type_name()
+        )
+    }
+    fn type_name() -> String {
+        system_type(&format!(
+            "System.Collections.Generic.List`1[[{}]]",
+            T::type_name()
+        ))
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        writers.register::<T>();
+        wtr.write_u32::<LittleEndian>(self.len() as u32)?;
+        for val in self {
+            write_dictionary_member(wtr, writers, val)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Serialize> Serialize for Array<T> {
+    fn reader_name() -> String {
+        format!(
+            "Microsoft.Xna.Framework.Content.ArrayReader`1[[{}]]",
+            T::type_name()
+        )
+    }
+    fn type_name() -> String {
+        // Array types are named after their element: `System.Int32[], mscorlib, ...`.
+        let element = T::type_name();
+        match element.find(", ") {
+            Some(idx) => format!("{}[]{}", &element[..idx], &element[idx..]),
+            None => format!("{}[]", element),
+        }
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        self.vec.serialize(wtr, writers)
+    }
+}
+
+fn dictionary_reader_name<K: Serialize, V: Serialize>() -> String {
+    format!(
+        "Microsoft.Xna.Framework.Content.DictionaryReader`2[[{}],[{}]]",
+        K::type_name(),
+        V::type_name()
+    )
+}
+
+fn dictionary_type_name<K: Serialize, V: Serialize>() -> String {
+    system_type(&format!(
+        "System.Collections.Generic.Dictionary`2[[{}],[{}]]",
+        K::type_name(),
+        V::type_name()
+    ))
+}
+
+fn write_dictionary<'a, K: Serialize + 'a, V: Serialize + 'a>(
+    wtr: &mut dyn Write,
+    writers: &mut TypeWriters,
+    len: usize,
+    entries: impl Iterator<Item = (&'a K, &'a V)>,
+) -> Result<(), Error> {
+    writers.register::<K>();
+    writers.register::<V>();
+    wtr.write_u32::<LittleEndian>(len as u32)?;
+    for (key, value) in entries {
+        write_dictionary_member(wtr, writers, key)?;
+        write_dictionary_member(wtr, writers, value)?;
+    }
+    Ok(())
+}
+
+impl<K: Serialize + Eq + Hash, V: Serialize> Serialize for HashMap<K, V> {
+    fn reader_name() -> String {
+        dictionary_reader_name::<K, V>()
+    }
+    fn type_name() -> String {
+        dictionary_type_name::<K, V>()
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        write_dictionary(wtr, writers, self.len(), self.iter())
+    }
+}
+
+impl<K: Serialize + Ord, V: Serialize> Serialize for BTreeMap<K, V> {
+    fn reader_name() -> String {
+        dictionary_reader_name::<K, V>()
+    }
+    fn type_name() -> String {
+        dictionary_type_name::<K, V>()
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        write_dictionary(wtr, writers, self.len(), self.iter())
+    }
+}
+
+impl<K: Serialize + Eq + Hash, V: Serialize> Serialize for Dictionary<K, V> {
+    fn reader_name() -> String {
+        dictionary_reader_name::<K, V>()
+    }
+    fn type_name() -> String {
+        dictionary_type_name::<K, V>()
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        self.map.serialize(wtr, writers)
+    }
+}
+
+impl Serialize for SpriteFont {
+    fn reader_name() -> String {
+        graphics_type("Microsoft.Xna.Framework.Content.SpriteFontReader")
+    }
+    fn type_name() -> String {
+        graphics_type("Microsoft.Xna.Framework.Graphics.SpriteFont")
+    }
+    fn serialize(&self, wtr: &mut dyn Write, writers: &mut TypeWriters) -> Result<(), Error> {
+        write_object(wtr, writers, &self.texture)?;
+        write_object(wtr, writers, &self.glyphs)?;
+        write_object(wtr, writers, &self.cropping)?;
+        write_object(wtr, writers, &self.char_map)?;
+        wtr.write_i32::<LittleEndian>(self.v_spacing)?;
+        wtr.write_f32::<LittleEndian>(self.h_spacing)?;
+        write_object(wtr, writers, &self.kerning)?;
+        write_nullable(wtr, self.default.as_ref(), |wtr, c| {
+            let mut buf = [0; 4];
+            wtr.write_all(c.encode_utf8(&mut buf).as_bytes())
+                .map_err(Error::from)
+        })
+    }
+}
+
+pub fn write_object<T: Serialize>(
+    wtr: &mut dyn Write,
+    writers: &mut TypeWriters,
+    value: &T,
+) -> Result<(), Error> {
+    let id = writers.register::<T>();
+    write_7bit_encoded_int(wtr, id)?;
+    value.serialize(wtr, writers)
+}
+
+// Value types are stored inline when they appear as generic arguments, while
+// everything else is written as a polymorphic object with a reader id.
+fn write_dictionary_member<T: Serialize>(
+    wtr: &mut dyn Write,
+    writers: &mut TypeWriters,
+    value: &T,
+) -> Result<(), Error> {
+    let type_name = T::type_name();
+    if reader_from_type(type_name.split(',').next().unwrap_or("")).is_some() {
+        writers.register::<T>();
+        value.serialize(wtr, writers)
+    } else {
+        write_object(wtr, writers, value)
+    }
+}
+
+/// Write a .NET `BinaryWriter` string: a 7-bit encoded byte length followed
+/// by the UTF-8 bytes.
+pub fn write_string(wtr: &mut dyn Write, s: &str) -> Result<(), Error> {
+    write_7bit_encoded_int(wtr, s.len() as u32)?;
+    wtr.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+/// Write `value` seven bits at a time, least significant group first, with the
+/// high bit of each byte marking that more bytes follow.
+pub fn write_7bit_encoded_int(wtr: &mut dyn Write, mut value: u32) -> Result<(), Error> {
+    while value >= 0x80 {
+        wtr.write_u8(value as u8 | 0x80)?;
+        value >>= 7;
+    }
+    wtr.write_u8(value as u8)?;
+    Ok(())
+}
+
+/// Write a `Nullable<T>`: a boolean presence flag, followed by the value
+/// written with `write` when there is one.
+pub fn write_nullable<T, F: Fn(&mut dyn Write, &T) -> Result<(), Error>>(
+    wtr: &mut dyn Write,
+    value: Option<&T>,
+    write: F,
+) -> Result<(), Error> {
+    match value {
+        Some(value) => {
+            wtr.write_u8(1)?;
+            write(wtr, value)
+        }
+        None => wtr.write_u8(0).map_err(Error::from),
+    }
+}
+
+pub struct XnbWriter {
+    target: u8,
+    hidef: bool,
+    compression: Compression,
+}
+
+impl Default for XnbWriter {
+    fn default() -> XnbWriter {
+        XnbWriter::new()
+    }
+}
+
+impl XnbWriter {
+    pub fn new() -> XnbWriter {
+        XnbWriter {
+            target: b'w',
+            hidef: false,
+            compression: Compression::None,
+        }
+    }
+
+    /// The target platform: 'w' (Windows), 'm' (Windows Phone) or 'x' (Xbox 360).
+    pub fn target(mut self, target: char) -> XnbWriter {
+        self.target = target as u8;
+        self
+    }
+
+    pub fn hidef(mut self, hidef: bool) -> XnbWriter {
+        self.hidef = hidef;
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> XnbWriter {
+        self.compression = compression;
+        self
+    }
+
+    pub fn write<T: Serialize>(&self, wtr: &mut dyn Write, asset: &T) -> Result<(), Error> {
+        self.write_with(wtr, |wtr, writers| write_object(wtr, writers, asset))
+    }
+
+    /// Write an XNB whose primary asset is written by `primary`, which must
+    /// register every reader it uses.
+    pub fn write_with<F>(&self, wtr: &mut dyn Write, primary: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut dyn Write, &mut TypeWriters) -> Result<(), Error>,
+    {
+        let mut writers = TypeWriters::new();
+        let mut asset = vec![];
+        primary(&mut asset, &mut writers)?;
+
+        let mut body = vec![];
+        write_7bit_encoded_int(&mut body, writers.readers.len() as u32)?;
+        for reader in &writers.readers {
+            write_string(&mut body, reader)?;
+            body.write_i32::<LittleEndian>(0)?;
+        }
+        // Shared resources.
+        write_7bit_encoded_int(&mut body, 0)?;
+        body.extend_from_slice(&asset);
+
+        write_container(wtr, self.target, self.hidef, self.compression, &body)
+    }
+
+    pub fn to_bytes<T: Serialize>(&self, asset: &T) -> Result<Vec<u8>, Error> {
+        let mut out = vec![];
+        self.write(&mut out, asset)?;
+        Ok(out)
+    }
+}
+
+// Write the XNB header followed by `body`, which holds everything from the
+// reader table onwards, compressing it as requested.
+pub(crate) fn write_container(
+    wtr: &mut dyn Write,
+    target: u8,
+    hidef: bool,
+    compression: Compression,
+    body: &[u8],
+) -> Result<(), Error> {
+    let mut flags = if hidef { 0x01 } else { 0x00 };
+    wtr.write_all(b"XNB")?;
+    wtr.write_u8(target)?;
+    wtr.write_u8(5)?;
+    match compression {
+        Compression::None => {
+            wtr.write_u8(flags)?;
+            wtr.write_u32::<LittleEndian>(body.len() as u32 + 10)?;
+            wtr.write_all(body)?;
+        }
+        Compression::Lzx | Compression::Lz4 => {
+            let compressed = if compression == Compression::Lzx {
+                flags |= 0x80;
+                lzx_compress::compress(body)
+            } else {
+                flags |= 0x40;
+                lz4::compress(body)
+            };
+            wtr.write_u8(flags)?;
+            wtr.write_u32::<LittleEndian>(compressed.len() as u32 + 14)?;
+            wtr.write_u32::<LittleEndian>(body.len() as u32)?;
+            wtr.write_all(&compressed)?;
+        }
+    }
+    Ok(())
+}
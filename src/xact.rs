@@ -0,0 +1,635 @@
+//! XACT projects: the global engine settings (.xgs), and the sound banks
+//! (.xsb) which name the cues a game plays and map them onto waves stored in
+//! separate wave banks (.xwb).
+
+use crate::Error;
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::time::Duration;
+
+const SOUND_BANK_MAGIC: &[u8] = b"SDBK";
+const ENGINE_SETTINGS_MAGIC: &[u8] = b"XGSF";
+const CONTENT_VERSION: u16 = 46;
+
+// Track event types. Only the play events refer to waves.
+const EVENT_STOP: u32 = 0;
+const EVENT_PLAY_WAVE: u32 = 1;
+const EVENT_PLAY_WAVE_TRACK_VARIATION: u32 = 3;
+const EVENT_PLAY_WAVE_EFFECT_VARIATION: u32 = 4;
+const EVENT_PLAY_WAVE_TRACK_EFFECT_VARIATION: u32 = 6;
+const EVENT_PITCH: u32 = 7;
+const EVENT_VOLUME: u32 = 8;
+const EVENT_MARKER: u32 = 9;
+const EVENT_PITCH_REPEATING: u32 = 16;
+const EVENT_VOLUME_REPEATING: u32 = 17;
+const EVENT_MARKER_REPEATING: u32 = 18;
+
+/// A wave stored in one of the sound bank's wave banks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WaveReference {
+    /// Index into `SoundBank::wave_banks`.
+    pub wave_bank: u8,
+    /// Index of the wave within its wave bank.
+    pub track: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Sound {
+    pub category: u16,
+    /// XACT's encoded volume; 0xB4 is 0 dB.
+    pub volume: u8,
+    /// Pitch offset in hundredths of a semitone.
+    pub pitch: i16,
+    pub priority: u8,
+    /// Every wave the sound may play, across all of its tracks.
+    pub waves: Vec<WaveReference>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CueTarget {
+    /// Index into `SoundBank::sounds`.
+    Sound(usize),
+    Wave(WaveReference),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Cue {
+    pub name: String,
+    /// The sounds or waves the cue picks from when played. Cues without
+    /// variations have exactly one.
+    pub variations: Vec<CueTarget>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SoundBank {
+    pub name: String,
+    /// Names of the wave banks this sound bank draws waves from.
+    pub wave_banks: Vec<String>,
+    pub cues: Vec<Cue>,
+    pub sounds: Vec<Sound>,
+}
+
+impl SoundBank {
+    pub fn from_buffer(rdr: &mut dyn Read) -> Result<SoundBank, Error> {
+        let mut buf = vec![];
+        rdr.read_to_end(&mut buf)?;
+        SoundBank::from_bytes(&buf)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<SoundBank, Error> {
+        // Xbox 360 banks are byte-swapped, magic included.
+        match buf.get(0..4) {
+            Some(SOUND_BANK_MAGIC) => parse_sound_bank::<LittleEndian>(buf),
+            Some(b"KBDS") => parse_sound_bank::<BigEndian>(buf),
+            _ => Err(Error::InvalidSoundBank),
+        }
+    }
+
+    pub fn cue(&self, name: &str) -> Option<&Cue> {
+        self.cues.iter().find(|c| c.name == name)
+    }
+
+    /// Every wave the named cue may play.
+    pub fn cue_waves(&self, name: &str) -> Vec<WaveReference> {
+        let mut waves = vec![];
+        for target in self.cue(name).into_iter().flat_map(|c| &c.variations) {
+            match *target {
+                CueTarget::Sound(i) => waves.extend_from_slice(&self.sounds[i].waves),
+                CueTarget::Wave(w) => waves.push(w),
+            }
+        }
+        waves
+    }
+
+    pub fn wave_bank_name(&self, wave: &WaveReference) -> Option<&str> {
+        self.wave_banks
+            .get(wave.wave_bank as usize)
+            .map(|s| s.as_str())
+    }
+}
+
+fn read_fixed_string(rdr: &mut dyn Read, len: usize) -> Result<String, Error> {
+    let mut buf = vec![0; len];
+    rdr.read_exact(&mut buf)?;
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(len);
+    Ok(String::from_utf8_lossy(&buf[..end]).into_owned())
+}
+
+fn read_c_string(rdr: &mut dyn Read) -> Result<String, Error> {
+    let mut buf = vec![];
+    loop {
+        match rdr.read_u8()? {
+            0 => break,
+            b => buf.push(b),
+        }
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn skip(rdr: &mut Cursor<&[u8]>, count: u64) {
+    rdr.set_position(rdr.position() + count);
+}
+
+// Check the content version and skip the rest of the header common to all
+// XACT files.
+fn read_header<B: ByteOrder>(rdr: &mut Cursor<&[u8]>) -> Result<(), Error> {
+    skip(rdr, 4);
+    let version = rdr.read_u16::<B>()?;
+    if version != CONTENT_VERSION {
+        return Err(Error::UnsupportedXactVersion(version));
+    }
+    // Tool version, CRC, last modified time and platform.
+    skip(rdr, 2 + 2 + 8 + 1);
+    Ok(())
+}
+
+fn read_names(rdr: &mut Cursor<&[u8]>, offset: u32, count: usize) -> Result<Vec<String>, Error> {
+    rdr.set_position(u64::from(offset));
+    (0..count).map(|_| read_c_string(rdr)).collect()
+}
+
+fn read_wave_reference<B: ByteOrder>(rdr: &mut Cursor<&[u8]>) -> Result<WaveReference, Error> {
+    let track = rdr.read_u16::<B>()?;
+    let wave_bank = rdr.read_u8()?;
+    Ok(WaveReference { wave_bank, track })
+}
+
+// Returns the wave references of a track variation table.
+fn read_track_variation<B: ByteOrder>(
+    rdr: &mut Cursor<&[u8]>,
+) -> Result<Vec<WaveReference>, Error> {
+    let count = rdr.read_u16::<B>()?;
+    // Variation type, then four unknown bytes.
+    skip(rdr, 6);
+    let mut waves = vec![];
+    for _ in 0..count {
+        waves.push(read_wave_reference::<B>(rdr)?);
+        // Minimum and maximum weight.
+        skip(rdr, 2);
+    }
+    Ok(waves)
+}
+
+fn skip_effect_variation(rdr: &mut Cursor<&[u8]>) {
+    // Pitch and volume ranges, filter frequency and Q ranges, then flags.
+    skip(rdr, 2 + 2 + 1 + 1 + 4 * 4 + 2);
+}
+
+fn read_events<B: ByteOrder>(
+    rdr: &mut Cursor<&[u8]>,
+    waves: &mut Vec<WaveReference>,
+) -> Result<(), Error> {
+    let count = rdr.read_u8()?;
+    for _ in 0..count {
+        let info = rdr.read_u32::<B>()?;
+        // Random timestamp offset.
+        skip(rdr, 2);
+        match info & 0x1F {
+            EVENT_STOP => skip(rdr, 2),
+            EVENT_PLAY_WAVE => {
+                skip(rdr, 2);
+                waves.push(read_wave_reference::<B>(rdr)?);
+                // Loop count, position and angle.
+                skip(rdr, 5);
+            }
+            EVENT_PLAY_WAVE_TRACK_VARIATION => {
+                skip(rdr, 2 + 5);
+                waves.extend(read_track_variation::<B>(rdr)?);
+            }
+            EVENT_PLAY_WAVE_EFFECT_VARIATION => {
+                skip(rdr, 2);
+                waves.push(read_wave_reference::<B>(rdr)?);
+                skip(rdr, 5);
+                skip_effect_variation(rdr);
+            }
+            EVENT_PLAY_WAVE_TRACK_EFFECT_VARIATION => {
+                skip(rdr, 2 + 5);
+                skip_effect_variation(rdr);
+                waves.extend(read_track_variation::<B>(rdr)?);
+            }
+            t @ EVENT_PITCH
+            | t @ EVENT_VOLUME
+            | t @ EVENT_PITCH_REPEATING
+            | t @ EVENT_VOLUME_REPEATING => {
+                skip(rdr, 2);
+                let settings = rdr.read_u8()?;
+                if settings & 0x01 != 0 {
+                    // A ramp: initial value, slope and slope delta, duration.
+                    skip(rdr, 4 * 3 + 2);
+                } else {
+                    // Equation flags, two values and five unknown bytes.
+                    skip(rdr, 1 + 4 * 2 + 5);
+                    if t == EVENT_PITCH_REPEATING || t == EVENT_VOLUME_REPEATING {
+                        skip(rdr, 4);
+                    }
+                }
+            }
+            EVENT_MARKER => skip(rdr, 1 + 4),
+            EVENT_MARKER_REPEATING => skip(rdr, 1 + 4 + 4),
+            t => return Err(Error::UnrecognizedSoundEvent(t)),
+        }
+    }
+    Ok(())
+}
+
+fn read_sound<B: ByteOrder>(rdr: &mut Cursor<&[u8]>) -> Result<Sound, Error> {
+    let flags = rdr.read_u8()?;
+    let category = rdr.read_u16::<B>()?;
+    let volume = rdr.read_u8()?;
+    let pitch = rdr.read_i16::<B>()?;
+    let priority = rdr.read_u8()?;
+    // Length of the entry.
+    skip(rdr, 2);
+
+    let complex = flags & 0x01 != 0;
+    let mut waves = vec![];
+    let mut track_count = 0;
+    if complex {
+        track_count = rdr.read_u8()?;
+    } else {
+        waves.push(read_wave_reference::<B>(rdr)?);
+    }
+
+    // RPC and DSP preset data, each prefixed by a length that includes itself.
+    if flags & 0x0E != 0 {
+        let len = rdr.read_u16::<B>()?;
+        skip(rdr, u64::from(len).saturating_sub(2));
+    }
+    if flags & 0x10 != 0 {
+        let len = rdr.read_u16::<B>()?;
+        skip(rdr, u64::from(len).saturating_sub(2));
+    }
+
+    for _ in 0..track_count {
+        // Volume, then the offset of the track's events.
+        skip(rdr, 1);
+        let events = rdr.read_u32::<B>()?;
+        // Filter type, Q and frequency.
+        skip(rdr, 4);
+        let next = rdr.position();
+        rdr.set_position(u64::from(events));
+        read_events::<B>(rdr, &mut waves)?;
+        rdr.set_position(next);
+    }
+
+    Ok(Sound {
+        category,
+        volume,
+        pitch,
+        priority,
+        waves,
+    })
+}
+
+fn read_variation_table<B: ByteOrder>(
+    rdr: &mut Cursor<&[u8]>,
+    sounds: &HashMap<u32, usize>,
+) -> Result<Vec<CueTarget>, Error> {
+    let count = rdr.read_u16::<B>()?;
+    let kind = (rdr.read_u16::<B>()? >> 3) & 0x07;
+    // Unknown value, then the index of the variable selecting the variation.
+    skip(rdr, 4);
+    let mut targets = vec![];
+    for _ in 0..count {
+        let target = match kind {
+            0 => {
+                let sound = rdr.read_u32::<B>()?;
+                // Minimum and maximum weight.
+                skip(rdr, 2);
+                CueTarget::Sound(sound_index(sounds, sound)?)
+            }
+            1 => {
+                let wave = read_wave_reference::<B>(rdr)?;
+                skip(rdr, 2);
+                CueTarget::Wave(wave)
+            }
+            3 => {
+                let sound = rdr.read_u32::<B>()?;
+                // Float weights and flags.
+                skip(rdr, 12);
+                CueTarget::Sound(sound_index(sounds, sound)?)
+            }
+            4 => CueTarget::Wave(read_wave_reference::<B>(rdr)?),
+            k => return Err(Error::UnrecognizedVariationTable(k)),
+        };
+        targets.push(target);
+    }
+    Ok(targets)
+}
+
+fn sound_index(sounds: &HashMap<u32, usize>, offset: u32) -> Result<usize, Error> {
+    sounds
+        .get(&offset)
+        .cloned()
+        .ok_or(Error::InvalidSoundReference(offset))
+}
+
+fn parse_sound_bank<B: ByteOrder>(buf: &[u8]) -> Result<SoundBank, Error> {
+    let mut rdr = Cursor::new(buf);
+    read_header::<B>(&mut rdr)?;
+    let simple_cue_count = rdr.read_u16::<B>()?;
+    let complex_cue_count = rdr.read_u16::<B>()?;
+    // Unknown value and total cue count.
+    skip(&mut rdr, 4);
+    let wave_bank_count = rdr.read_u8()?;
+    let sound_count = rdr.read_u16::<B>()?;
+    // Cue name table length and an unknown value.
+    skip(&mut rdr, 4);
+    let simple_cue_offset = rdr.read_u32::<B>()?;
+    let complex_cue_offset = rdr.read_u32::<B>()?;
+    let cue_name_offset = rdr.read_u32::<B>()?;
+    skip(&mut rdr, 4);
+    // Variation tables are found through the cues that use them.
+    let _variation_offset = rdr.read_u32::<B>()?;
+    // Transition table, then wave bank names, then cue name hash tables.
+    skip(&mut rdr, 4);
+    let wave_bank_name_offset = rdr.read_u32::<B>()?;
+    skip(&mut rdr, 8);
+    let sound_offset = rdr.read_u32::<B>()?;
+    let name = read_fixed_string(&mut rdr, 64)?;
+
+    rdr.set_position(u64::from(wave_bank_name_offset));
+    let mut wave_banks = vec![];
+    for _ in 0..wave_bank_count {
+        wave_banks.push(read_fixed_string(&mut rdr, 64)?);
+    }
+
+    // Cues refer to sounds by their offset in the file.
+    rdr.set_position(u64::from(sound_offset));
+    let mut sounds = vec![];
+    let mut sound_offsets = HashMap::new();
+    for i in 0..sound_count as usize {
+        sound_offsets.insert(rdr.position() as u32, i);
+        sounds.push(read_sound::<B>(&mut rdr)?);
+    }
+
+    let mut variations = vec![];
+    rdr.set_position(u64::from(simple_cue_offset));
+    for _ in 0..simple_cue_count {
+        // Flags.
+        skip(&mut rdr, 1);
+        let sound = rdr.read_u32::<B>()?;
+        variations.push(vec![CueTarget::Sound(sound_index(&sound_offsets, sound)?)]);
+    }
+    rdr.set_position(u64::from(complex_cue_offset));
+    for _ in 0..complex_cue_count {
+        let flags = rdr.read_u8()?;
+        let offset = rdr.read_u32::<B>()?;
+        // Transition table offset, instance limit, fade in and out and
+        // instance flags.
+        skip(&mut rdr, 4 + 1 + 2 + 2 + 1);
+        if flags & 0x04 != 0 {
+            variations.push(vec![CueTarget::Sound(sound_index(&sound_offsets, offset)?)]);
+        } else {
+            let next = rdr.position();
+            rdr.set_position(u64::from(offset));
+            variations.push(read_variation_table::<B>(&mut rdr, &sound_offsets)?);
+            rdr.set_position(next);
+        }
+    }
+
+    rdr.set_position(u64::from(cue_name_offset));
+    let mut cues = vec![];
+    for variations in variations {
+        cues.push(Cue {
+            name: read_c_string(&mut rdr)?,
+            variations,
+        });
+    }
+
+    Ok(SoundBank {
+        name,
+        wave_banks,
+        cues,
+        sounds,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Category {
+    pub name: String,
+    /// Index into `EngineSettings::categories`.
+    pub parent: Option<u16>,
+    /// XACT's encoded volume; 0xB4 is 0 dB.
+    pub volume: u8,
+    /// The number of sounds in the category that may play at once.
+    pub instance_limit: u8,
+    pub fade_in: Duration,
+    pub fade_out: Duration,
+    /// What happens when a sound would exceed the instance limit; 0 to fail
+    /// to play, then queue, replace oldest, quietest and lowest priority.
+    pub max_instance_behavior: u8,
+    pub visibility: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Variable {
+    pub name: String,
+    pub accessibility: u8,
+    pub initial_value: f32,
+    pub min_value: f32,
+    pub max_value: f32,
+}
+
+impl Variable {
+    pub fn is_public(&self) -> bool {
+        self.accessibility & 0x01 != 0
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.accessibility & 0x02 != 0
+    }
+
+    /// Whether each cue instance has its own copy, rather than there being
+    /// one global value.
+    pub fn is_per_cue(&self) -> bool {
+        self.accessibility & 0x04 != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CurveType {
+    Linear,
+    Fast,
+    Slow,
+    SinCos,
+}
+
+impl CurveType {
+    fn from(val: u8) -> Result<CurveType, Error> {
+        Ok(match val {
+            0 => CurveType::Linear,
+            1 => CurveType::Fast,
+            2 => CurveType::Slow,
+            3 => CurveType::SinCos,
+            t => return Err(Error::UnrecognizedCurveType(t)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CurvePoint {
+    pub x: f32,
+    pub y: f32,
+    /// The shape of the curve between this point and the next.
+    pub curve: CurveType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum RpcParameter {
+    Volume,
+    Pitch,
+    ReverbSend,
+    FilterFrequency,
+    FilterQFactor,
+    /// A parameter of one of the DSP presets.
+    Dsp(u16),
+}
+
+impl From<u16> for RpcParameter {
+    fn from(val: u16) -> RpcParameter {
+        match val {
+            0 => RpcParameter::Volume,
+            1 => RpcParameter::Pitch,
+            2 => RpcParameter::ReverbSend,
+            3 => RpcParameter::FilterFrequency,
+            4 => RpcParameter::FilterQFactor,
+            p => RpcParameter::Dsp(p),
+        }
+    }
+}
+
+/// A runtime parameter control: a curve mapping a variable onto a sound
+/// parameter.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RpcCurve {
+    /// Index into `EngineSettings::variables`.
+    pub variable: u16,
+    pub parameter: RpcParameter,
+    pub points: Vec<CurvePoint>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EngineSettings {
+    pub categories: Vec<Category>,
+    pub variables: Vec<Variable>,
+    pub rpc_curves: Vec<RpcCurve>,
+}
+
+impl EngineSettings {
+    pub fn from_buffer(rdr: &mut dyn Read) -> Result<EngineSettings, Error> {
+        let mut buf = vec![];
+        rdr.read_to_end(&mut buf)?;
+        EngineSettings::from_bytes(&buf)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<EngineSettings, Error> {
+        match buf.get(0..4) {
+            Some(ENGINE_SETTINGS_MAGIC) => parse_engine_settings::<LittleEndian>(buf),
+            Some(b"FSGX") => parse_engine_settings::<BigEndian>(buf),
+            _ => Err(Error::InvalidEngineSettings),
+        }
+    }
+
+    pub fn category(&self, name: &str) -> Option<&Category> {
+        self.categories.iter().find(|c| c.name == name)
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().find(|v| v.name == name)
+    }
+}
+
+fn parse_engine_settings<B: ByteOrder>(buf: &[u8]) -> Result<EngineSettings, Error> {
+    let mut rdr = Cursor::new(buf);
+    read_header::<B>(&mut rdr)?;
+    let category_count = rdr.read_u16::<B>()? as usize;
+    let variable_count = rdr.read_u16::<B>()? as usize;
+    // Two unknown counts.
+    skip(&mut rdr, 4);
+    let rpc_count = rdr.read_u16::<B>()?;
+    // DSP preset and parameter counts.
+    skip(&mut rdr, 4);
+    let category_offset = rdr.read_u32::<B>()?;
+    let variable_offset = rdr.read_u32::<B>()?;
+    // An unknown offset and the category name hash table, then another
+    // unknown offset and the variable name hash table.
+    skip(&mut rdr, 16);
+    let category_name_offset = rdr.read_u32::<B>()?;
+    let variable_name_offset = rdr.read_u32::<B>()?;
+    let rpc_offset = rdr.read_u32::<B>()?;
+
+    let names = read_names(&mut rdr, category_name_offset, category_count)?;
+    rdr.set_position(u64::from(category_offset));
+    let mut categories = vec![];
+    for name in names {
+        let instance_limit = rdr.read_u8()?;
+        let fade_in = Duration::from_millis(rdr.read_u16::<B>()?.into());
+        let fade_out = Duration::from_millis(rdr.read_u16::<B>()?.into());
+        let max_instance_behavior = rdr.read_u8()? >> 3;
+        let parent = rdr.read_u16::<B>()?;
+        categories.push(Category {
+            name,
+            parent: if parent == 0xFFFF { None } else { Some(parent) },
+            volume: rdr.read_u8()?,
+            instance_limit,
+            fade_in,
+            fade_out,
+            max_instance_behavior,
+            visibility: rdr.read_u8()?,
+        });
+    }
+
+    let names = read_names(&mut rdr, variable_name_offset, variable_count)?;
+    rdr.set_position(u64::from(variable_offset));
+    let mut variables = vec![];
+    for name in names {
+        variables.push(Variable {
+            name,
+            accessibility: rdr.read_u8()?,
+            initial_value: rdr.read_f32::<B>()?,
+            min_value: rdr.read_f32::<B>()?,
+            max_value: rdr.read_f32::<B>()?,
+        });
+    }
+
+    rdr.set_position(u64::from(rpc_offset));
+    let mut rpc_curves = vec![];
+    for _ in 0..rpc_count {
+        let variable = rdr.read_u16::<B>()?;
+        let point_count = rdr.read_u8()?;
+        let parameter = rdr.read_u16::<B>()?.into();
+        let mut points = vec![];
+        for _ in 0..point_count {
+            points.push(CurvePoint {
+                x: rdr.read_f32::<B>()?,
+                y: rdr.read_f32::<B>()?,
+                curve: CurveType::from(rdr.read_u8()?)?,
+            });
+        }
+        rpc_curves.push(RpcCurve {
+            variable,
+            parameter,
+            points,
+        });
+    }
+
+    Ok(EngineSettings {
+        categories,
+        variables,
+        rpc_curves,
+    })
+}
@@ -0,0 +1,393 @@
+//! The unpacked layout used by the `xnbcli` tool: a `.json` descriptor
+//! holding the header, reader table and content of an XNB, with textures
+//! exported alongside it as `.png` files. Assets can be unpacked into this
+//! layout and packed back into an XNB.
+
+use crate::json::key;
+use crate::{
+    generic_types_from_reader, reader_from_type, type_arg, write_7bit_encoded_int, write_string,
+    Color, Compression, Error, Rectangle, Serialize, SurfaceFormat, Texture2d, TypeWriters, Value,
+    Vector3, XnbWriter, ARRAY_READER, LIST_READER, XNB,
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde_json::{json, Map, Value as Json};
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Error as IoError, Write};
+use std::path::Path;
+
+const RECTANGLE_READER: &str = "Microsoft.Xna.Framework.Content.RectangleReader";
+const DICTIONARY_READER: &str = "Microsoft.Xna.Framework.Content.DictionaryReader";
+const NULLABLE_READER: &str = "Microsoft.Xna.Framework.Content.NullableReader";
+const ENUM_READER: &str = "Microsoft.Xna.Framework.Content.EnumReader";
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+const TEXTURE_READER: &str = "Microsoft.Xna.Framework.Content.Texture2DReader";
+// xnbcli exports maps as the original tbin data, which isn't kept.
+const TIDE_READER: &str = "xTile.Pipeline.TideReader";
+
+fn write_png(path: &Path, texture: &Texture2d) -> Result<(), Error> {
+    if texture.format != SurfaceFormat::Color {
+        return Err(Error::UnsupportedUnpack(format!(
+            "{:?} texture",
+            texture.format
+        )));
+    }
+    let data = texture.mip_data.first().ok_or(Error::MissingMipLevel(0))?;
+    let wtr = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(wtr, texture.width as u32, texture.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut wtr = encoder.write_header().map_err(IoError::from)?;
+    wtr.write_image_data(data).map_err(IoError::from)?;
+    Ok(())
+}
+
+// Convert a value nested inside the content, using xnbcli's names for
+// structure members.
+fn content(value: &Value) -> Result<Json, Error> {
+    Ok(match *value {
+        Value::Array(ref values) => values.iter().map(content).collect::<Result<_, _>>()?,
+        Value::Dictionary(ref entries) => {
+            let mut object = Map::new();
+            for (k, v) in entries {
+                let k = key(k)
+                    .ok_or_else(|| Error::UnsupportedUnpack(format!("dictionary key {:?}", k)))?;
+                object.insert(k, content(v)?);
+            }
+            Json::Object(object)
+        }
+        Value::Nullable(Some(ref value)) => content(value)?,
+        Value::Object {
+            ref reader,
+            ref fields,
+        } => {
+            if reader == TIDE_READER {
+                return Err(Error::UnsupportedUnpack(reader.clone()));
+            }
+            let mut object = Map::new();
+            for (name, value) in fields {
+                let name = match (reader.as_str(), name.as_str()) {
+                    (RECTANGLE_READER, "w") => "width",
+                    (RECTANGLE_READER, "h") => "height",
+                    (_, name) => name,
+                };
+                object.insert(name.to_string(), content(value)?);
+            }
+            Json::Object(object)
+        }
+        Value::Texture(_) => return Err(Error::UnsupportedUnpack("nested texture".to_string())),
+        Value::Other { ref reader, .. } => return Err(Error::UnsupportedUnpack(reader.clone())),
+        ref value => value.to_json(),
+    })
+}
+
+/// The xnbcli descriptor for `xnb`, exporting any texture to
+/// `<dir>/<name>.png`. `header` is the start of the original file.
+fn descriptor(xnb: &XNB<Value>, header: &[u8], dir: &Path, name: &str) -> Result<Json, Error> {
+    let content = match xnb.primary {
+        Value::Texture(ref texture) => {
+            let export = format!("{}.png", name);
+            write_png(&dir.join(&export), texture)?;
+            json!({ "format": texture.format as u32, "export": export })
+        }
+        ref value => content(value)?,
+    };
+    let readers: Vec<Json> = xnb
+        .readers
+        .iter()
+        .map(|r| json!({ "type": r.name(), "version": r.version() }))
+        .collect();
+    Ok(json!({
+        "header": {
+            "target": (header[3] as char).to_string(),
+            "formatVersion": header[4],
+            "hidef": header[5] & 0x01 != 0,
+            "compressed": header[5] & 0xc0 != 0,
+        },
+        "readers": readers,
+        "content": content,
+    }))
+}
+
+/// Unpack an XNB into `dir` the way xnbcli does, as `<name>.json` plus
+/// `<name>.png` for textures. Only textures in the `Color` format can be
+/// exported, and assets without a plain data representation (models,
+/// effects, maps, ...) aren't supported.
+pub fn unpack(bytes: &[u8], dir: &Path, name: &str) -> Result<(), Error> {
+    let xnb = XNB::<Value>::from_buffer(&mut &bytes[..])?;
+    let descriptor = descriptor(&xnb, bytes, dir, name)?;
+    let json =
+        serde_json::to_string_pretty(&descriptor).map_err(|e| Error::Serialize(e.to_string()))?;
+    fs::write(dir.join(format!("{}.json", name)), json)?;
+    Ok(())
+}
+
+fn invalid(json: &Json, expected: &str) -> Error {
+    Error::InvalidDescriptor(format!("expected {}, found {}", expected, json))
+}
+
+fn field<'a>(json: &'a Json, name: &str) -> Result<&'a Json, Error> {
+    json.get(name)
+        .ok_or_else(|| invalid(json, &format!("a `{}` member", name)))
+}
+
+fn integer<T: TryFrom<i64>>(json: &Json) -> Result<T, Error> {
+    json.as_i64()
+        .and_then(|i| T::try_from(i).ok())
+        .ok_or_else(|| invalid(json, "an integer"))
+}
+
+fn float(json: &Json) -> Result<f64, Error> {
+    json.as_f64().ok_or_else(|| invalid(json, "a number"))
+}
+
+fn string(json: &Json) -> Result<&str, Error> {
+    json.as_str().ok_or_else(|| invalid(json, "a string"))
+}
+
+fn read_png(path: &Path) -> Result<Texture2d, Error> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut rdr = decoder.read_info().map_err(IoError::from)?;
+    let mut buf = vec![0; rdr.output_buffer_size()];
+    let info = rdr.next_frame(&mut buf).map_err(IoError::from)?;
+    let pixels = &buf[..info.buffer_size()];
+    let data = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels
+            .chunks(3)
+            .flat_map(|p| vec![p[0], p[1], p[2], 0xff])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks(2)
+            .flat_map(|p| vec![p[0], p[0], p[0], p[1]])
+            .collect(),
+        _ => pixels.iter().flat_map(|&p| vec![p, p, p, 0xff]).collect(),
+    };
+    Ok(Texture2d {
+        format: SurfaceFormat::Color,
+        width: info.width as usize,
+        height: info.height as usize,
+        mip_data: vec![data],
+    })
+}
+
+fn reader_main(reader: &str) -> &str {
+    reader.split('`').next().unwrap().split(',').next().unwrap()
+}
+
+// The reader for a reference type, which must already be in the reader
+// table. Returns its 1-based id.
+fn reader_id(writers: &TypeWriters, typename: &str) -> Result<u32, Error> {
+    let (main, args) = if typename == "System.String" {
+        (STRING_READER, vec![])
+    } else if typename == "Microsoft.Xna.Framework.Graphics.Texture2D" {
+        (TEXTURE_READER, vec![])
+    } else if typename.starts_with("System.Collections.Generic.List`1") {
+        (LIST_READER, generic_types_from_reader(typename)?)
+    } else if typename.starts_with("System.Collections.Generic.Dictionary`2") {
+        (DICTIONARY_READER, generic_types_from_reader(typename)?)
+    } else if let Some(element) = typename.strip_suffix("[]") {
+        (ARRAY_READER, vec![element])
+    } else {
+        return Err(Error::UnknownReader(typename.to_string()));
+    };
+    writers
+        .readers()
+        .iter()
+        .position(|r| {
+            reader_main(r) == main && generic_types_from_reader(r).is_ok_and(|a| a == args)
+        })
+        .map(|idx| idx as u32 + 1)
+        .ok_or_else(|| Error::UnknownReader(typename.to_string()))
+}
+
+// Value types are written inline; anything else is written as an object,
+// with its reader id or 0 for null.
+fn write_member(
+    wtr: &mut dyn Write,
+    writers: &mut TypeWriters,
+    typename: &str,
+    json: &Json,
+    dir: &Path,
+) -> Result<(), Error> {
+    if let Some(reader) = reader_from_type(typename) {
+        return write_content(wtr, writers, reader, vec![], json, dir);
+    }
+    if typename.starts_with("System.Nullable`1") {
+        let args = generic_types_from_reader(typename)?;
+        return write_content(wtr, writers, NULLABLE_READER, args, json, dir);
+    }
+    if json.is_null() {
+        return write_7bit_encoded_int(wtr, 0);
+    }
+    let id = reader_id(writers, typename)?;
+    write_7bit_encoded_int(wtr, id)?;
+    let reader = writers.readers()[id as usize - 1].clone();
+    write_content(
+        wtr,
+        writers,
+        reader_main(&reader),
+        generic_types_from_reader(&reader)?,
+        json,
+        dir,
+    )
+}
+
+// Dictionary keys are always strings in JSON, so parse them back into the
+// key type.
+fn key_content(typename: &str, key: &str) -> Result<Json, Error> {
+    match typename {
+        "System.String" | "System.Char" => Ok(Json::String(key.to_string())),
+        _ => serde_json::from_str(key).map_err(|_| invalid(&key.into(), typename)),
+    }
+}
+
+fn write_content(
+    wtr: &mut dyn Write,
+    writers: &mut TypeWriters,
+    main: &str,
+    args: Vec<&str>,
+    json: &Json,
+    dir: &Path,
+) -> Result<(), Error> {
+    match main {
+        "Microsoft.Xna.Framework.Content.BooleanReader" => {
+            let b = json.as_bool().ok_or_else(|| invalid(json, "a boolean"))?;
+            wtr.write_u8(b as u8)?;
+        }
+        "Microsoft.Xna.Framework.Content.ByteReader" => wtr.write_u8(integer(json)?)?,
+        "Microsoft.Xna.Framework.Content.SByteReader" => wtr.write_i8(integer(json)?)?,
+        "Microsoft.Xna.Framework.Content.Int16Reader" => {
+            wtr.write_i16::<LittleEndian>(integer(json)?)?
+        }
+        "Microsoft.Xna.Framework.Content.UInt16Reader" => {
+            wtr.write_u16::<LittleEndian>(integer(json)?)?
+        }
+        "Microsoft.Xna.Framework.Content.Int32Reader" | ENUM_READER => {
+            wtr.write_i32::<LittleEndian>(integer(json)?)?
+        }
+        "Microsoft.Xna.Framework.Content.UInt32Reader" => {
+            wtr.write_u32::<LittleEndian>(integer(json)?)?
+        }
+        "Microsoft.Xna.Framework.Content.Int64Reader" => {
+            wtr.write_i64::<LittleEndian>(integer(json)?)?
+        }
+        "Microsoft.Xna.Framework.Content.UInt64Reader" => {
+            let u = json.as_u64().ok_or_else(|| invalid(json, "an integer"))?;
+            wtr.write_u64::<LittleEndian>(u)?
+        }
+        "Microsoft.Xna.Framework.Content.SingleReader" => {
+            wtr.write_f32::<LittleEndian>(float(json)? as f32)?
+        }
+        "Microsoft.Xna.Framework.Content.DoubleReader" => {
+            wtr.write_f64::<LittleEndian>(float(json)?)?
+        }
+        "Microsoft.Xna.Framework.Content.CharReader" => {
+            let mut chars = string(json)?.chars();
+            let c = match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(invalid(json, "a single character")),
+            };
+            c.serialize(wtr, writers)?;
+        }
+        STRING_READER => write_string(wtr, string(json)?)?,
+        RECTANGLE_READER => Rectangle {
+            x: integer(field(json, "x")?)?,
+            y: integer(field(json, "y")?)?,
+            w: integer(field(json, "width")?)?,
+            h: integer(field(json, "height")?)?,
+        }
+        .serialize(wtr, writers)?,
+        "Microsoft.Xna.Framework.Content.Vector3Reader" => Vector3::new(
+            float(field(json, "x")?)? as f32,
+            float(field(json, "y")?)? as f32,
+            float(field(json, "z")?)? as f32,
+        )
+        .serialize(wtr, writers)?,
+        "Microsoft.Xna.Framework.Content.ColorReader" => Color {
+            r: integer(field(json, "r")?)?,
+            g: integer(field(json, "g")?)?,
+            b: integer(field(json, "b")?)?,
+            a: integer(field(json, "a")?)?,
+        }
+        .serialize(wtr, writers)?,
+        TEXTURE_READER => {
+            let export = string(field(json, "export")?)?;
+            read_png(&dir.join(export))?.serialize(wtr, writers)?;
+        }
+        ARRAY_READER | LIST_READER => {
+            let values = json.as_array().ok_or_else(|| invalid(json, "an array"))?;
+            wtr.write_u32::<LittleEndian>(values.len() as u32)?;
+            for value in values {
+                write_member(wtr, writers, type_arg(&args, 0)?, value, dir)?;
+            }
+        }
+        DICTIONARY_READER => {
+            let entries = json.as_object().ok_or_else(|| invalid(json, "an object"))?;
+            wtr.write_u32::<LittleEndian>(entries.len() as u32)?;
+            for (key, value) in entries {
+                let (k, v) = (type_arg(&args, 0)?, type_arg(&args, 1)?);
+                write_member(wtr, writers, k, &key_content(k, key)?, dir)?;
+                write_member(wtr, writers, v, value, dir)?;
+            }
+        }
+        NULLABLE_READER => {
+            if json.is_null() {
+                wtr.write_u8(0)?;
+            } else {
+                wtr.write_u8(1)?;
+                write_member(wtr, writers, type_arg(&args, 0)?, json, dir)?;
+            }
+        }
+        _ => return Err(Error::UnknownReader(main.to_string())),
+    }
+    Ok(())
+}
+
+/// Pack the xnbcli descriptor `<dir>/<name>.json`, along with any files it
+/// exports, back into an XNB. The reader table is written as listed in the
+/// descriptor, and the first reader is used for the content.
+pub fn pack(dir: &Path, name: &str) -> Result<Vec<u8>, Error> {
+    let descriptor: Json = serde_json::from_slice(&fs::read(dir.join(format!("{}.json", name)))?)
+        .map_err(|e| Error::InvalidDescriptor(e.to_string()))?;
+    let header = field(&descriptor, "header")?;
+    let target = string(field(header, "target")?)?;
+    let target = target
+        .chars()
+        .next()
+        .ok_or_else(|| invalid(header, "a target"))?;
+    let hidef = header.get("hidef").and_then(Json::as_bool).unwrap_or(false);
+    // `compressed` may be a flag or the compression bits of the header.
+    let compression = match header.get("compressed") {
+        Some(&Json::Bool(true)) => Compression::Lzx,
+        Some(json) if json.as_u64() == Some(0x40) => Compression::Lz4,
+        Some(json) if json.as_u64().unwrap_or(0) != 0 => Compression::Lzx,
+        _ => Compression::None,
+    };
+    let readers = field(&descriptor, "readers")?
+        .as_array()
+        .ok_or_else(|| invalid(&descriptor["readers"], "an array"))?
+        .iter()
+        .map(|r| field(r, "type").and_then(string))
+        .collect::<Result<Vec<_>, _>>()?;
+    let content = field(&descriptor, "content")?;
+
+    let mut out = vec![];
+    XnbWriter::new()
+        .target(target)
+        .hidef(hidef)
+        .compression(compression)
+        .write_with(&mut out, |wtr, writers| {
+            for reader in &readers {
+                writers.register_reader(reader.to_string());
+            }
+            let primary = readers
+                .first()
+                .ok_or_else(|| Error::InvalidDescriptor("no readers".to_string()))?;
+            write_7bit_encoded_int(wtr, 1)?;
+            let args = generic_types_from_reader(primary)?;
+            write_content(wtr, writers, reader_main(primary), args, content, dir)
+        })?;
+    Ok(out)
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+use xnb::testing::XnbFixture;
+use xnb::{Parse, SoundEffect, WaveFormat, XNB};
+
+// 16-bit mono PCM at 22050Hz, as a PCMWAVEFORMAT.
+fn pcm_format() -> Vec<u8> {
+    let mut format = vec![];
+    format.extend_from_slice(&1u16.to_le_bytes());
+    format.extend_from_slice(&1u16.to_le_bytes());
+    format.extend_from_slice(&22050u32.to_le_bytes());
+    format.extend_from_slice(&44100u32.to_le_bytes());
+    format.extend_from_slice(&2u16.to_le_bytes());
+    format.extend_from_slice(&16u16.to_le_bytes());
+    format
+}
+
+fn sound_effect(format: &[u8], data: &[u8]) -> SoundEffect {
+    let bytes = XnbFixture::new()
+        .reader(SoundEffect::READER)
+        .object(1)
+        .uint(format.len() as u32)
+        .bytes(format)
+        .uint(data.len() as u32)
+        .bytes(data)
+        .uint(1)
+        .uint(2)
+        .uint(1500)
+        .build();
+    XNB::<SoundEffect>::from_buffer(&mut &bytes[..])
+        .unwrap()
+        .primary
+}
+
+#[test]
+fn reader_layout() {
+    let sound = sound_effect(&pcm_format(), &[1, 2, 3, 4]);
+    assert_eq!(sound.format, WaveFormat(pcm_format()));
+    assert_eq!(sound.format.format_tag(), 1);
+    assert_eq!(sound.format.channels(), 1);
+    assert_eq!(sound.format.sample_rate(), 22050);
+    assert_eq!(sound.format.avg_bytes_per_sec(), 44100);
+    assert_eq!(sound.format.block_align(), 2);
+    assert_eq!(sound.format.bits_per_sample(), 16);
+    assert_eq!(sound.data, [1, 2, 3, 4]);
+    assert_eq!((sound.loop_start, sound.loop_length), (1, 2));
+    assert_eq!(sound.duration, Duration::from_millis(1500));
+
+    // Fields past the end of a short header read as zero.
+    assert_eq!(WaveFormat(vec![1, 0]).sample_rate(), 0);
+}
+
+fn wav(sound: &SoundEffect) -> Vec<u8> {
+    let mut out = vec![];
+    sound.write_wav(&mut out).unwrap();
+    out
+}
+
+#[test]
+fn wav_output() {
+    let sound = sound_effect(&pcm_format(), &[1, 2, 3, 4]);
+    let mut expected = b"RIFF".to_vec();
+    expected.extend_from_slice(&40u32.to_le_bytes());
+    expected.extend_from_slice(b"WAVEfmt ");
+    expected.extend_from_slice(&16u32.to_le_bytes());
+    expected.extend_from_slice(&pcm_format());
+    expected.extend_from_slice(b"data");
+    expected.extend_from_slice(&4u32.to_le_bytes());
+    expected.extend_from_slice(&[1, 2, 3, 4]);
+    assert_eq!(wav(&sound), expected);
+}
+
+#[test]
+fn wav_padding() {
+    // Odd chunks are followed by a pad byte, which their sizes leave out but
+    // the RIFF size counts.
+    let mut format = pcm_format();
+    format.push(9);
+    let sound = sound_effect(&format, &[1, 2, 3]);
+    let mut expected = b"RIFF".to_vec();
+    expected.extend_from_slice(&42u32.to_le_bytes());
+    expected.extend_from_slice(b"WAVEfmt ");
+    expected.extend_from_slice(&17u32.to_le_bytes());
+    expected.extend_from_slice(&format);
+    expected.push(0);
+    expected.extend_from_slice(b"data");
+    expected.extend_from_slice(&3u32.to_le_bytes());
+    expected.extend_from_slice(&[1, 2, 3, 0]);
+    assert_eq!(wav(&sound), expected);
+}
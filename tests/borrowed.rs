@@ -0,0 +1,112 @@
+use std::borrow::Cow;
+use xnb::testing::XnbFixture;
+use xnb::{BorrowedValue, BorrowedXnb, Compression, Error, ParseOptions, Value, XNB};
+
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+const TEXTURE_READER: &str = "Microsoft.Xna.Framework.Content.Texture2DReader";
+
+// A list of a string, a 2x1 texture and a dictionary of nullable integers.
+fn fixture(string: &[u8]) -> Vec<u8> {
+    XnbFixture::new()
+        .reader("Microsoft.Xna.Framework.Content.ListReader`1[[System.Object, mscorlib]]")
+        .reader(STRING_READER)
+        .reader(TEXTURE_READER)
+        .reader(
+            "Microsoft.Xna.Framework.Content.DictionaryReader`2[[System.Int32, mscorlib],\
+             [System.Nullable`1[[System.Single, mscorlib]], mscorlib]]",
+        )
+        .object(1)
+        .uint(3)
+        .object(2)
+        .bytes(&[string.len() as u8])
+        .bytes(string)
+        .object(3)
+        .uint(0)
+        .uint(2)
+        .uint(1)
+        .uint(1)
+        .uint(8)
+        .bytes(&[1, 2, 3, 4, 5, 6, 7, 8])
+        .object(4)
+        .uint(2)
+        .int(1)
+        .bytes(&[1])
+        .float(0.5)
+        .int(2)
+        .bytes(&[0])
+        .build()
+}
+
+fn within(bytes: &[u8], borrowed: &[u8]) -> bool {
+    bytes.as_ptr_range().contains(&borrowed.as_ptr())
+}
+
+#[test]
+fn borrows_strings_and_mip_data() {
+    let bytes = fixture(b"town");
+    let xnb = BorrowedXnb::from_bytes(&bytes).unwrap();
+    assert!(xnb.warnings.is_empty());
+    let items = match &xnb.primary {
+        BorrowedValue::Array(items) => items,
+        primary => panic!("{:?}", primary),
+    };
+    match &items[0] {
+        BorrowedValue::String(Cow::Borrowed(s)) => {
+            assert_eq!(*s, "town");
+            assert!(within(&bytes, s.as_bytes()));
+        }
+        item => panic!("{:?}", item),
+    }
+    match &items[1] {
+        BorrowedValue::Texture(texture) => {
+            assert_eq!((texture.width, texture.height), (2, 1));
+            assert_eq!(texture.mip_data, [&[1, 2, 3, 4, 5, 6, 7, 8][..]]);
+            assert!(within(&bytes, texture.mip_data[0]));
+        }
+        item => panic!("{:?}", item),
+    }
+}
+
+#[test]
+fn lossy_strings_are_owned() {
+    let bytes = fixture(b"t\xffwn");
+    assert!(matches!(
+        BorrowedXnb::from_bytes(&bytes),
+        Err(Error::Parse { .. })
+    ));
+    let options = ParseOptions::new().lossy_strings(true);
+    let xnb = BorrowedXnb::from_bytes_with_options(&bytes, &options).unwrap();
+    match &xnb.primary {
+        BorrowedValue::Array(items) => match &items[0] {
+            BorrowedValue::String(Cow::Owned(s)) => assert_eq!(s, "t\u{fffd}wn"),
+            item => panic!("{:?}", item),
+        },
+        primary => panic!("{:?}", primary),
+    }
+}
+
+#[test]
+fn into_owned_matches_value() {
+    let bytes = fixture(b"town");
+    let owned = BorrowedXnb::from_bytes(&bytes)
+        .unwrap()
+        .primary
+        .into_owned();
+    let value = XNB::<Value>::from_buffer(&mut &bytes[..]).unwrap().primary;
+    // `Value` has no `PartialEq`, as it can hold any asset.
+    assert_eq!(format!("{:?}", owned), format!("{:?}", value));
+    assert_eq!(value.get_as::<String>("[0]").as_deref(), Some("town"));
+    assert_eq!(value.get_as::<f32>("[2][1]"), Some(0.5));
+    assert!(value.get("[2][2]").unwrap().is_null());
+}
+
+#[test]
+fn compressed_files_are_rejected() {
+    let bytes = XnbFixture::new()
+        .compression(Compression::Lz4)
+        .reader(STRING_READER)
+        .object(1)
+        .string("town")
+        .build();
+    assert!(matches!(BorrowedXnb::from_bytes(&bytes), Err(Error::Void)));
+}
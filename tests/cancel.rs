@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use xnb::testing::XnbFixture;
+use xnb::{Error, Parse, ParseOptions, ParseStage, Texture2d, XNB};
+
+// A string followed by three shared strings, each large enough to span
+// several progress reports.
+fn strings() -> Vec<u8> {
+    let text = "x".repeat(100_000);
+    let mut fixture = XnbFixture::new()
+        .reader("Microsoft.Xna.Framework.Content.StringReader")
+        .shared_resources(3);
+    for _ in 0..4 {
+        fixture = fixture.object(1).string(&text);
+    }
+    fixture.build()
+}
+
+fn parse(bytes: &[u8], options: &ParseOptions) -> Result<XNB<String>, Error> {
+    XNB::<String>::from_buffer_with_options(&mut &bytes[..], options)
+}
+
+#[test]
+fn cancelled_before_parsing() {
+    let flag = Arc::new(AtomicBool::new(true));
+    let options = ParseOptions::new().cancel_flag(flag);
+    assert!(matches!(parse(&strings(), &options), Err(Error::Cancelled)));
+}
+
+#[test]
+fn cancelled_while_parsing() {
+    let flag = Arc::new(AtomicBool::new(false));
+    let hook_flag = flag.clone();
+    let options = ParseOptions::new()
+        .cancel_flag(flag.clone())
+        .progress(move |progress| {
+            if progress.stage == ParseStage::SharedResources {
+                hook_flag.store(true, Ordering::Relaxed);
+            }
+        });
+    // Cancellation isn't wrapped in the readers it happened within.
+    assert!(matches!(parse(&strings(), &options), Err(Error::Cancelled)));
+
+    // Once the flag is cleared, parsing goes through.
+    flag.store(false, Ordering::Relaxed);
+    let options = ParseOptions::new().cancel_flag(flag);
+    assert_eq!(parse(&strings(), &options).unwrap().shared.len(), 3);
+}
+
+#[test]
+fn cancelled_within_an_object() {
+    // A 256x256 texture with three mip levels, which are read one after the
+    // other with nothing in between.
+    let mut fixture = XnbFixture::new()
+        .reader(Texture2d::READER)
+        .object(1)
+        .uint(0)
+        .uint(256)
+        .uint(256)
+        .uint(3);
+    for level in 0..3 {
+        let data = vec![0; (256 >> level) * (256 >> level) * 4];
+        fixture = fixture.uint(data.len() as u32).bytes(&data);
+    }
+    let flag = Arc::new(AtomicBool::new(false));
+    let hook_flag = flag.clone();
+    let options = ParseOptions::new()
+        .cancel_flag(flag)
+        .progress(move |progress| {
+            // Once the first mip level has been read.
+            let first_mip = 256 * 256 * 4;
+            if progress.stage == ParseStage::PrimaryAsset && progress.processed > first_mip {
+                hook_flag.store(true, Ordering::Relaxed);
+            }
+        });
+    let result = XNB::<Texture2d>::from_buffer_with_options(&mut &fixture.build()[..], &options);
+    assert!(matches!(result, Err(Error::Cancelled)));
+}
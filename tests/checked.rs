@@ -0,0 +1,280 @@
+//! Inputs that once made parsing panic, overflow, loop or allocate without
+//! bound. `XNB::from_buffer_checked` must turn every one of them into an
+//! error (or a successful parse) instead.
+
+use std::io::Cursor;
+use xnb::testing::XnbFixture;
+use xnb::{Error, LazyTexture2d, Model, ParseOptions, Song, Value, XNB};
+
+const INT_READER: &str = "Microsoft.Xna.Framework.Content.Int32Reader";
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+const TEXTURE_READER: &str = "Microsoft.Xna.Framework.Content.Texture2DReader";
+const OBJECT_LIST_READER: &str =
+    "Microsoft.Xna.Framework.Content.ListReader`1[[System.Object, mscorlib]]";
+
+fn parse(bytes: &[u8]) -> Result<XNB<Value>, Error> {
+    XNB::from_buffer_checked(&mut &bytes[..])
+}
+
+// The error itself, without where parsing got to.
+fn cause(error: Error) -> Error {
+    match error {
+        Error::Parse { error, .. } => *error,
+        error => error,
+    }
+}
+
+fn sample() -> Vec<u8> {
+    XnbFixture::new()
+        .reader(OBJECT_LIST_READER)
+        .reader(INT_READER)
+        .reader(STRING_READER)
+        .object(1)
+        .uint(3)
+        .object(2)
+        .int(7)
+        .object(3)
+        .string("hello")
+        .object(0)
+        .build()
+}
+
+#[test]
+fn sample_parses() {
+    match parse(&sample()).unwrap().primary {
+        Value::Array(ref values) => match values[..] {
+            [Value::Int(7), Value::String(ref s), Value::Null] => assert_eq!(s, "hello"),
+            ref values => panic!("{:?}", values),
+        },
+        value => panic!("{:?}", value),
+    }
+}
+
+#[test]
+fn truncated() {
+    let bytes = sample();
+    for len in 0..bytes.len() {
+        assert!(parse(&bytes[..len]).is_err(), "length {}", len);
+    }
+}
+
+#[test]
+fn corrupted_bytes() {
+    let bytes = sample();
+    for i in 0..bytes.len() {
+        for &mask in &[0x01, 0x80, 0xFF] {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= mask;
+            let _ = parse(&corrupted);
+        }
+    }
+}
+
+#[test]
+fn overlong_reader_count() {
+    for count in &[&[0xFF; 6][..], &[0x80, 0x80, 0x80, 0x80, 0x10]] {
+        let mut bytes = b"XNBw\x05\x00\x10\x00\x00\x00".to_vec();
+        bytes.extend_from_slice(count);
+        match parse(&bytes) {
+            Err(Error::InvalidEncodedInt) => {}
+            result => panic!("{:?}", result.map(|xnb| xnb.primary)),
+        }
+    }
+}
+
+#[test]
+fn malformed_reader_names() {
+    for name in &[
+        "Microsoft.Xna.Framework.Content.ListReader`1[[",
+        "Microsoft.Xna.Framework.Content.ListReader`1",
+        "Microsoft.Xna.Framework.Content.DictionaryReader`2[[System.Int32]]",
+        "`]]][[[",
+        "",
+    ] {
+        let bytes = XnbFixture::new().reader(name).object(1).uint(1).build();
+        assert!(parse(&bytes).is_err(), "{:?}", name);
+    }
+}
+
+#[test]
+fn unknown_reader_id() {
+    let bytes = XnbFixture::new().reader(INT_READER).object(200).build();
+    assert!(parse(&bytes).is_err());
+}
+
+#[test]
+fn huge_collection() {
+    let bytes = XnbFixture::new()
+        .reader(OBJECT_LIST_READER)
+        .object(1)
+        .uint(u32::MAX)
+        .build();
+    assert!(parse(&bytes).is_err());
+}
+
+#[test]
+fn huge_string() {
+    let bytes = XnbFixture::new()
+        .reader(STRING_READER)
+        .object(1)
+        .bytes(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F])
+        .build();
+    assert!(parse(&bytes).is_err());
+}
+
+#[test]
+fn deep_nesting() {
+    let mut fixture = XnbFixture::new().reader(OBJECT_LIST_READER);
+    for _ in 0..100_000 {
+        fixture = fixture.object(1).uint(1);
+    }
+    let bytes = fixture.object(0).build();
+    assert!(parse(&bytes).is_err());
+}
+
+#[test]
+fn huge_texture_dimensions() {
+    let mut fixture = XnbFixture::new()
+        .reader(TEXTURE_READER)
+        .object(1)
+        .uint(0)
+        .uint(u32::MAX)
+        .uint(u32::MAX)
+        .uint(70);
+    for _ in 0..70 {
+        fixture = fixture.uint(0);
+    }
+    // Every mip level is the wrong size, which lenient parsing tolerates.
+    let xnb = parse(&fixture.build()).unwrap();
+    assert_eq!(xnb.warnings.len(), 70);
+}
+
+#[test]
+fn huge_texture_data() {
+    let bytes = XnbFixture::new()
+        .reader(TEXTURE_READER)
+        .object(1)
+        .uint(0)
+        .uint(1)
+        .uint(1)
+        .uint(1)
+        .uint(u32::MAX)
+        .build();
+    assert!(parse(&bytes).is_err());
+}
+
+#[test]
+fn string_limits() {
+    let song = XnbFixture::new()
+        .reader("Microsoft.Xna.Framework.Content.SongReader")
+        .reader(INT_READER)
+        .object(1)
+        .string(&"a".repeat(100))
+        .object(2)
+        .int(1000)
+        .build();
+    let options = ParseOptions::new().max_string_len(50);
+    let result = XNB::<Song>::from_buffer_with_options(&mut &song[..], &options);
+    assert!(matches!(
+        result.map_err(cause),
+        Err(Error::LimitExceeded { requested: 100, .. })
+    ));
+
+    let texture = XnbFixture::new().reader(TEXTURE_READER).object(1).build();
+    let options = ParseOptions::new().max_string_len(TEXTURE_READER.len() - 1);
+    assert!(matches!(
+        LazyTexture2d::from_reader(&mut Cursor::new(texture), &options),
+        Err(Error::LimitExceeded { .. })
+    ));
+}
+
+#[test]
+fn cyclic_bones() {
+    let identity: Vec<f32> = (0..16)
+        .map(|i| if i % 5 == 0 { 1.0 } else { 0.0 })
+        .collect();
+    // Parents as 1-based references: a bone that's its own parent, and two
+    // bones that are each other's.
+    for parents in &[&[1][..], &[2, 1], &[0, 3, 2]] {
+        let mut fixture = XnbFixture::new()
+            .reader("Microsoft.Xna.Framework.Content.ModelReader")
+            .object(1)
+            .uint(parents.len() as u32);
+        for _ in parents.iter() {
+            fixture = fixture.object(0);
+            for &v in &identity {
+                fixture = fixture.float(v);
+            }
+        }
+        for &parent in parents.iter() {
+            fixture = fixture.bytes(&[parent]).uint(0);
+        }
+        let bytes = fixture.uint(0).bytes(&[0]).object(0).build();
+        match XNB::<Model>::from_buffer_checked(&mut &bytes[..]).map_err(cause) {
+            Err(Error::InvalidBoneReference(_)) => {}
+            result => panic!("{:?}", result.map(|xnb| xnb.primary)),
+        }
+    }
+}
+
+#[test]
+fn corrupt_lz4() {
+    // One literal, then a match reaching back further than that.
+    let block = [0x10, b'a', 5, 0];
+    let mut bytes = b"XNBw\x05\x40".to_vec();
+    bytes.extend_from_slice(&(14 + block.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&100u32.to_le_bytes());
+    bytes.extend_from_slice(&block);
+    match parse(&bytes).map_err(cause) {
+        Err(Error::Lz4Decompress(_)) => {}
+        result => panic!("{:?}", result.map(|xnb| xnb.primary)),
+    }
+}
+
+// A tBIN map with no tilesheets and one layer of the given size, whose cells
+// are `cells`.
+fn tide(width: u32, height: u32, cells: &[u8]) -> Vec<u8> {
+    fn string(tbin: &mut Vec<u8>, s: &str) {
+        tbin.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        tbin.extend_from_slice(s.as_bytes());
+    }
+    let mut tbin = b"tBIN10".to_vec();
+    string(&mut tbin, "map");
+    string(&mut tbin, "");
+    // No properties, no tilesheets and one layer.
+    for &v in &[0u32, 0, 1] {
+        tbin.extend_from_slice(&v.to_le_bytes());
+    }
+    string(&mut tbin, "Back");
+    tbin.push(1);
+    string(&mut tbin, "");
+    for &v in &[width, height, 16, 16, 0] {
+        tbin.extend_from_slice(&v.to_le_bytes());
+    }
+    tbin.extend_from_slice(cells);
+    XnbFixture::new()
+        .reader("xTile.Pipeline.TideReader")
+        .object(1)
+        .uint(tbin.len() as u32)
+        .bytes(&tbin)
+        .build()
+}
+
+#[test]
+fn animation_without_frames() {
+    let mut cells = vec![b'A'];
+    for &v in &[100u32, 0, 0] {
+        cells.extend_from_slice(&v.to_le_bytes());
+    }
+    assert!(parse(&tide(1, 1, &cells)).is_ok());
+}
+
+#[test]
+fn huge_layer() {
+    let mut cells = vec![b'N'];
+    cells.extend_from_slice(&u32::MAX.to_le_bytes());
+    match parse(&tide(u32::MAX, 1, &cells)).map_err(cause) {
+        Err(Error::LimitExceeded { .. }) => {}
+        result => panic!("{:?}", result.map(|xnb| xnb.primary)),
+    }
+}
@@ -0,0 +1,108 @@
+//! Assets written compressed must read back exactly as they were written.
+
+use xnb::{compress_xnb, decompress_xnb, lz4, Compression, XnbWriter, XNB};
+
+const FRAME: usize = 0x8000;
+
+// Deterministic bytes that no LZ-style encoder can shrink.
+fn random(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn text(len: usize) -> Vec<u8> {
+    b"the quick brown fox jumps over the lazy dog. "
+        .iter()
+        .cycle()
+        .take(len)
+        .copied()
+        .collect()
+}
+
+// How many bytes the XNB body spends on everything but the payload.
+fn overhead() -> usize {
+    let bytes = XnbWriter::new().to_bytes(&Vec::<u8>::new()).unwrap();
+    bytes.len() - 10
+}
+
+fn round_trip(compression: Compression, payload: Vec<u8>) {
+    let plain = XnbWriter::new().to_bytes(&payload).unwrap();
+    let packed = XnbWriter::new()
+        .compression(compression)
+        .to_bytes(&payload)
+        .unwrap();
+    let flag = if compression == Compression::Lzx {
+        0x80
+    } else {
+        0x40
+    };
+    assert_eq!(packed[5] & 0xC0, flag);
+    assert_eq!(decompress_xnb(&packed).unwrap(), plain);
+
+    let xnb = XNB::<Vec<u8>>::from_buffer(&mut &packed[..]).unwrap();
+    assert_eq!(xnb.primary, payload);
+}
+
+#[test]
+fn lzx_empty() {
+    let header = b"XNBw\x05\x00\x0a\x00\x00\x00";
+    let packed = compress_xnb(header).unwrap();
+    assert_eq!(packed[5], 0x80);
+    assert_eq!(decompress_xnb(&packed).unwrap(), header);
+
+    round_trip(Compression::Lzx, vec![]);
+}
+
+#[test]
+fn lzx_one_full_frame() {
+    round_trip(Compression::Lzx, text(FRAME - overhead()));
+}
+
+#[test]
+fn lzx_many_frames() {
+    round_trip(Compression::Lzx, text(100_000));
+}
+
+#[test]
+fn lzx_incompressible() {
+    round_trip(Compression::Lzx, random(70_000));
+}
+
+#[test]
+fn lzx_odd_final_frame() {
+    let mut payload = text(FRAME);
+    payload.extend(random(FRAME));
+    payload.extend(text(12345 - overhead()));
+    round_trip(Compression::Lzx, payload);
+}
+
+#[test]
+fn lz4_round_trips() {
+    round_trip(Compression::Lz4, vec![]);
+    round_trip(Compression::Lz4, text(FRAME - overhead()));
+    round_trip(Compression::Lz4, text(100_000));
+    round_trip(Compression::Lz4, random(70_000));
+    round_trip(Compression::Lz4, vec![7; 1000]);
+}
+
+#[test]
+fn lz4_blocks() {
+    // One literal, then a 20 byte match at offset 1 that copies its own
+    // output and needs an extra length byte.
+    let block = [0x1F, b'a', 1, 0, 1, 0x10, b'b'];
+    let mut expected = vec![b'a'; 21];
+    expected.push(b'b');
+    assert_eq!(lz4::decompress(&block, 22).unwrap(), expected);
+
+    // Twenty literals, with the length extended past the token.
+    let mut block = vec![0xF0, 5];
+    block.extend(text(20));
+    assert_eq!(lz4::decompress(&block, 20).unwrap(), text(20));
+}
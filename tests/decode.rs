@@ -0,0 +1,341 @@
+use xnb::{MipFilter, Rectangle, SurfaceFormat, Texture2d};
+
+fn decode(format: SurfaceFormat, data: &[u8]) -> Vec<u8> {
+    let texture = Texture2d {
+        format,
+        width: data.len() / 2,
+        height: 1,
+        mip_data: vec![data.to_vec()],
+    };
+    texture.decode(0).unwrap().pixels
+}
+
+fn packed(values: &[u16]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[test]
+fn bgr565() {
+    let pixels = decode(
+        SurfaceFormat::Bgr565,
+        &packed(&[0xF800, 0x07E0, 0x001F, 0x8410]),
+    );
+    assert_eq!(
+        pixels,
+        vec![255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 132, 130, 132, 255]
+    );
+}
+
+#[test]
+fn bgra5551() {
+    let pixels = decode(SurfaceFormat::Bgra5551, &packed(&[0xFC00, 0x03E0, 0x801F]));
+    assert_eq!(pixels, vec![255, 0, 0, 255, 0, 255, 0, 0, 0, 0, 255, 255]);
+}
+
+#[test]
+fn bgra4444() {
+    let pixels = decode(SurfaceFormat::Bgra4444, &packed(&[0xFF00, 0x00F0, 0x800F]));
+    assert_eq!(pixels, vec![255, 0, 0, 255, 0, 255, 0, 0, 0, 0, 255, 136]);
+}
+
+#[test]
+fn wide_channels() {
+    let texture = Texture2d {
+        format: SurfaceFormat::Rgba64,
+        width: 1,
+        height: 1,
+        mip_data: vec![packed(&[0x1234, 0xFFFF, 0, 0x8080])],
+    };
+    assert_eq!(
+        texture.decode_rgba16(0).unwrap().pixels,
+        vec![0x1234, 0xFFFF, 0, 0x8080]
+    );
+    assert_eq!(texture.decode(0).unwrap().pixels, vec![0x12, 0xFF, 0, 0x80]);
+
+    let texture = Texture2d {
+        format: SurfaceFormat::Rgba1010102,
+        width: 1,
+        height: 1,
+        mip_data: vec![(0x3FFu32 | 0x200 << 10 | 1 << 30).to_le_bytes().to_vec()],
+    };
+    assert_eq!(
+        texture.decode_rgba16(0).unwrap().pixels,
+        vec![0xFFFF, 0x8020, 0, 0x5555]
+    );
+    assert_eq!(texture.decode(0).unwrap().pixels, vec![255, 128, 0, 85]);
+
+    let texture = Texture2d {
+        format: SurfaceFormat::Color,
+        width: 1,
+        height: 1,
+        mip_data: vec![vec![0, 1, 128, 255]],
+    };
+    assert_eq!(
+        texture.decode_rgba16(0).unwrap().pixels,
+        vec![0, 257, 0x8080, 0xFFFF]
+    );
+}
+
+#[test]
+fn float_formats() {
+    let texture = Texture2d {
+        format: SurfaceFormat::Vector2,
+        width: 1,
+        height: 1,
+        mip_data: vec![[4.5f32, -1.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect()],
+    };
+    assert_eq!(
+        texture.decode_f32(0).unwrap().pixels,
+        vec![4.5, -1.0, 0.0, 1.0]
+    );
+    assert_eq!(texture.decode(0).unwrap().pixels, vec![255, 0, 0, 255]);
+
+    let mut hdr = vec![];
+    texture.decode_f32(0).unwrap().write_hdr(&mut hdr).unwrap();
+    let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 1\n";
+    assert_eq!(&hdr[..header.len()], &header[..]);
+    // 4.5 is 0.5625 * 2^3.
+    assert_eq!(&hdr[header.len()..], &[144, 0, 0, 131]);
+}
+
+#[test]
+fn surface_layouts() {
+    assert_eq!(SurfaceFormat::Bgr565.bytes_per_pixel(), Some(2));
+    assert_eq!(SurfaceFormat::Vector4.mip_byte_len(3, 2), 96);
+    assert_eq!(SurfaceFormat::Dxt1.bytes_per_pixel(), None);
+    assert_eq!(SurfaceFormat::Dxt1.mip_byte_len(5, 4), 16);
+    assert_eq!(SurfaceFormat::Dxt5.mip_byte_len(1, 1), 16);
+    assert_eq!(SurfaceFormat::Dxt3.mip_byte_len(0, 4), 0);
+}
+
+#[test]
+fn pixel_access() {
+    let texture = Texture2d {
+        format: SurfaceFormat::Bgr565,
+        width: 2,
+        height: 2,
+        mip_data: vec![packed(&[0xF800, 0x07E0, 0x001F, 0])],
+    };
+    let mut image = texture.decode(0).unwrap();
+    assert_eq!(image.get_pixel(0, 1), Some(&[0, 0, 255, 255]));
+    assert_eq!(image.get_pixel(2, 0), None);
+    image.get_pixel_mut(1, 1).unwrap()[3] = 0;
+    let rows: Vec<_> = image.rows().collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[1], &[0, 0, 255, 255, 0, 0, 0, 0]);
+}
+
+#[test]
+fn generate_mips() {
+    let mut texture = Texture2d {
+        format: SurfaceFormat::Color,
+        width: 8,
+        height: 3,
+        mip_data: vec![(0..8 * 3).flat_map(|_| vec![200, 100, 50, 255]).collect()],
+    };
+    for &filter in &[MipFilter::Box, MipFilter::Kaiser] {
+        texture.generate_mips(filter).unwrap();
+        let sizes: Vec<_> = texture.mip_data.iter().map(|mip| mip.len()).collect();
+        assert_eq!(sizes, [96, 16, 8, 4]);
+        // A flat colour stays flat.
+        assert_eq!(texture.decode(3).unwrap().pixels, [200, 100, 50, 255]);
+    }
+
+    let mut compressed = texture.compress(SurfaceFormat::Dxt1).unwrap();
+    compressed.mip_data.truncate(1);
+    compressed.generate_mips(MipFilter::Kaiser).unwrap();
+    assert_eq!(compressed.mip_data.len(), 4);
+}
+
+#[test]
+fn premultiplied_alpha() {
+    let mut image = xnb::Rgba8Image {
+        width: 3,
+        height: 1,
+        pixels: vec![200, 100, 0, 128, 50, 60, 70, 0, 10, 20, 30, 255],
+    };
+    image.premultiply();
+    assert_eq!(image.pixels, [100, 50, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255]);
+    image.unpremultiply();
+    assert_eq!(
+        image.pixels,
+        [199, 100, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255]
+    );
+}
+
+#[test]
+fn srgb_formats() {
+    let texture = Texture2d {
+        format: SurfaceFormat::Bgra32SRgb,
+        width: 2,
+        height: 1,
+        mip_data: vec![vec![0, 128, 255, 10, 255, 255, 255, 255]],
+    };
+    assert!(texture.format.is_srgb());
+    assert_eq!(texture.format.without_srgb(), SurfaceFormat::Bgra32);
+    assert_eq!(
+        texture.decode(0).unwrap().pixels,
+        [255, 128, 0, 10, 255, 255, 255, 255]
+    );
+    // Floats come out linear, and converting back gives the original.
+    let mut linear = texture.decode_f32(0).unwrap();
+    assert!((linear.pixels[1] - 0.2158).abs() < 1e-3);
+    assert_eq!(linear.pixels[4..], [1.0, 1.0, 1.0, 1.0]);
+    linear.linear_to_srgb();
+    assert!((linear.pixels[1] - 128.0 / 255.0).abs() < 1e-4);
+}
+
+#[test]
+fn crop() {
+    let texture = Texture2d {
+        format: SurfaceFormat::Alpha8,
+        width: 4,
+        height: 3,
+        mip_data: vec![(0..12).collect(), vec![0, 0]],
+    };
+    let cropped = texture
+        .crop(Rectangle {
+            x: 1,
+            y: 1,
+            w: 2,
+            h: 2,
+        })
+        .unwrap();
+    assert_eq!((cropped.width, cropped.height), (2, 2));
+    assert_eq!(cropped.mip_data, [[5, 6, 9, 10]]);
+    assert!(texture
+        .crop(Rectangle {
+            x: 3,
+            y: 0,
+            w: 2,
+            h: 1
+        })
+        .is_err());
+
+    // DXT regions grow to whole blocks, but not past the texture.
+    let dxt = Texture2d {
+        format: SurfaceFormat::Dxt5,
+        width: 10,
+        height: 8,
+        mip_data: vec![(0..6 * 16).collect()],
+    };
+    let cropped = dxt
+        .crop(Rectangle {
+            x: 5,
+            y: 5,
+            w: 4,
+            h: 1,
+        })
+        .unwrap();
+    assert_eq!((cropped.width, cropped.height), (6, 4));
+    assert_eq!(cropped.mip_data[0], (4 * 16..6 * 16).collect::<Vec<u8>>());
+}
+
+#[test]
+fn rectangles() {
+    let a = Rectangle::from((0, 0, 4, 3));
+    assert_eq!((a.right(), a.bottom()), (4, 3));
+    assert!(a.contains((3, 2)) && !a.contains((4, 0)));
+    let b = Rectangle::from((2, 1, 5, 5));
+    assert_eq!(a.intersection(&b), Some(Rectangle::from((2, 1, 2, 2))));
+    assert!(!a.intersects(&Rectangle::from((4, 0, 1, 1))));
+    assert_eq!(<(i32, i32, i32, i32)>::from(b), (2, 1, 5, 5));
+
+    // Sizes come straight from files, so extreme ones mustn't overflow.
+    let far = Rectangle::from((i32::MAX, i32::MAX, 1, 1));
+    let negative = Rectangle::from((i32::MIN, i32::MIN, i32::MIN, i32::MIN));
+    assert_eq!(far.intersection(&negative), None);
+    assert_eq!(negative.intersection(&far), None);
+    let wide = Rectangle::from((i32::MIN, i32::MIN, i32::MAX, i32::MAX));
+    assert_eq!(far.intersection(&wide), None);
+}
+
+#[test]
+fn slice_grid() {
+    // Two columns and one row of 2x2 cells, after a margin of 1 and with 1
+    // pixel between them.
+    let texture = Texture2d {
+        format: SurfaceFormat::Alpha8,
+        width: 7,
+        height: 4,
+        mip_data: vec![(0..28).collect()],
+    };
+    let cells = texture.slice_grid((2, 2), (1, 1), (1, 1)).unwrap();
+    assert_eq!(cells.len(), 2);
+    assert_eq!(cells[0].mip_data, [[8, 9, 15, 16]]);
+    assert_eq!(cells[1].mip_data, [[11, 12, 18, 19]]);
+    assert!(texture
+        .slice_grid((0, 2), (0, 0), (0, 0))
+        .unwrap()
+        .is_empty());
+
+    let dxt = texture
+        .compress(SurfaceFormat::Dxt1)
+        .unwrap()
+        .slice_grid((3, 3), (0, 0), (0, 0))
+        .unwrap();
+    assert_eq!(dxt.len(), 2);
+    assert_eq!(dxt[1].format, SurfaceFormat::Color);
+    assert_eq!((dxt[1].width, dxt[1].height), (3, 3));
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn to_image() {
+    // Opaque red and a half transparent white, premultiplied.
+    let texture = Texture2d {
+        format: SurfaceFormat::Color,
+        width: 2,
+        height: 1,
+        mip_data: vec![
+            vec![255, 0, 0, 255, 128, 128, 128, 128],
+            vec![192, 64, 64, 192],
+        ],
+    };
+    let image = texture.to_image(0, false).unwrap();
+    assert_eq!(image.dimensions(), (2, 1));
+    assert_eq!(image.into_raw(), texture.mip_data[0]);
+    let image = texture.to_image(0, true).unwrap();
+    assert_eq!(image.into_raw(), [255, 0, 0, 255, 255, 255, 255, 128]);
+    let image = texture.to_image(1, true).unwrap();
+    assert_eq!(image.dimensions(), (1, 1));
+    assert_eq!(image.into_raw(), [255, 85, 85, 192]);
+    assert!(texture.to_image(2, false).is_err());
+
+    let path = std::env::temp_dir().join(format!("xnb-to-image-{}.png", std::process::id()));
+    texture.save_png(0, &path, true).unwrap();
+    let saved = image::open(&path).unwrap().to_rgba8();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(saved, texture.to_image(0, true).unwrap());
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn from_image() {
+    // Red on the left, half transparent blue on the right.
+    let image = image::RgbaImage::from_fn(4, 2, |x, _| match x < 2 {
+        true => image::Rgba([255, 0, 0, 255]),
+        false => image::Rgba([0, 0, 255, 128]),
+    });
+    let image = image::DynamicImage::ImageRgba8(image);
+
+    let texture = Texture2d::from_image(&image, false, false);
+    assert_eq!(texture.format, SurfaceFormat::Color);
+    assert_eq!((texture.width, texture.height), (4, 2));
+    assert_eq!(texture.mip_data.len(), 1);
+    assert_eq!(texture.to_image(0, false).unwrap(), image.to_rgba8());
+
+    let texture = Texture2d::from_image(&image, true, true);
+    assert_eq!(texture.mip_data.len(), 3);
+    assert_eq!(texture.mip_data[1], [255, 0, 0, 255, 0, 0, 128, 128]);
+    // Premultiplied before filtering, so the translucent blue weighs less.
+    assert_eq!(texture.mip_data[2], [128, 0, 64, 192]);
+    assert_eq!(texture.to_image(0, true).unwrap(), image.to_rgba8());
+    assert_eq!(texture.decode(2).unwrap().width, 1);
+}
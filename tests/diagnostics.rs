@@ -0,0 +1,104 @@
+use xnb::testing::XnbFixture;
+use xnb::{Diagnostics, Error, Parse, ParseMode, ParseOptions, ReaderRegistry, Texture2d};
+
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+const LEVEL_READER: &str = "Game.Content.LevelReader";
+
+fn examine(fixture: XnbFixture, options: &ParseOptions) -> Diagnostics {
+    Diagnostics::from_buffer(&mut &fixture.build()[..], options)
+}
+
+#[test]
+fn clean_file() {
+    let fixture = XnbFixture::new()
+        .reader(STRING_READER)
+        .object(1)
+        .string("town");
+    let diagnostics = examine(fixture, &ParseOptions::new());
+    assert!(diagnostics.unknown_readers.is_empty());
+    assert!(diagnostics.version_mismatches.is_empty());
+    assert_eq!(diagnostics.trailing_bytes, 0);
+    assert!(diagnostics.warnings.is_empty());
+    assert!(diagnostics.error.is_none());
+}
+
+#[test]
+fn unknown_readers() {
+    let fixture = XnbFixture::new()
+        .reader(&format!("{}, Game", LEVEL_READER))
+        .reader(STRING_READER)
+        .object(1)
+        .int(3);
+    let diagnostics = examine(fixture.clone(), &ParseOptions::new());
+    assert_eq!(
+        diagnostics.unknown_readers,
+        [format!("{}, Game", LEVEL_READER)]
+    );
+    // The unknown reader still stops parsing.
+    match diagnostics.error {
+        Some(Error::Parse { error, .. }) => {
+            assert!(matches!(*error, Error::UnknownReader(_)), "{:?}", error)
+        }
+        error => panic!("{:?}", error),
+    }
+
+    let mut registry = ReaderRegistry::new();
+    registry.register(LEVEL_READER, |rdr, readers, _| {
+        i32::try_parse(rdr, readers, vec![])
+    });
+    let diagnostics = examine(fixture, &ParseOptions::new().registry(registry));
+    assert!(diagnostics.unknown_readers.is_empty());
+    assert!(diagnostics.error.is_none());
+}
+
+#[test]
+fn versions_and_trailing_bytes() {
+    let fixture = XnbFixture::new()
+        .reader_with_version(STRING_READER, 2)
+        .object(1)
+        .string("town")
+        .bytes(&[0; 5]);
+    // Parsing is lenient whatever the options say.
+    let strict = ParseOptions::new().mode(ParseMode::Strict);
+    let diagnostics = examine(fixture, &strict);
+    assert_eq!(
+        diagnostics.version_mismatches,
+        [(STRING_READER.to_string(), 2)]
+    );
+    assert_eq!(diagnostics.trailing_bytes, 5);
+    assert!(diagnostics.warnings.is_empty());
+    assert!(diagnostics.error.is_none());
+}
+
+#[test]
+fn other_warnings() {
+    // A 4x4 texture whose only mip level is 4 bytes short.
+    let fixture = XnbFixture::new()
+        .reader(Texture2d::READER)
+        .object(1)
+        .uint(0)
+        .uint(4)
+        .uint(4)
+        .uint(1)
+        .uint(60)
+        .bytes(&[0; 60]);
+    let diagnostics = examine(fixture, &ParseOptions::new().mode(ParseMode::Strict));
+    assert!(matches!(
+        diagnostics.warnings[..],
+        [Error::InvalidMipSize { level: 0, .. }]
+    ));
+    assert!(diagnostics.error.is_none());
+}
+
+#[test]
+fn truncated_file() {
+    let mut bytes = XnbFixture::new()
+        .reader(STRING_READER)
+        .object(1)
+        .string("town")
+        .build();
+    bytes.truncate(bytes.len() - 2);
+    let diagnostics = Diagnostics::from_buffer(&mut &bytes[..], &ParseOptions::new());
+    assert!(diagnostics.unknown_readers.is_empty());
+    assert!(diagnostics.error.is_some());
+}
@@ -0,0 +1,112 @@
+use xnb::dxt::{decode, decode_scalar, encode};
+use xnb::SurfaceFormat;
+
+// Red and blue endpoints, with the indices 0, 1, 2, 3 along the first row.
+const RED_BLUE: [u8; 8] = [0x00, 0xF8, 0x1F, 0x00, 0b1110_0100, 0, 0, 0];
+
+#[test]
+fn dxt1_palettes() {
+    let pixels = decode(SurfaceFormat::Dxt1, &RED_BLUE, 4, 4).unwrap();
+    assert_eq!(
+        &pixels[..16],
+        &[255, 0, 0, 255, 0, 0, 255, 255, 170, 0, 85, 255, 85, 0, 170, 255]
+    );
+
+    // Swapping the endpoints selects three colours and transparent black.
+    let mut block = RED_BLUE;
+    block[..4].copy_from_slice(&[0x1F, 0x00, 0x00, 0xF8]);
+    let pixels = decode(SurfaceFormat::Dxt1, &block, 4, 4).unwrap();
+    assert_eq!(&pixels[8..16], &[127, 0, 127, 255, 0, 0, 0, 0]);
+}
+
+#[test]
+fn dxt3_and_dxt5_alpha() {
+    let mut block = [0; 16];
+    block[0] = 0x5F;
+    block[8..].copy_from_slice(&RED_BLUE);
+    let pixels = decode(SurfaceFormat::Dxt3, &block, 4, 4).unwrap();
+    assert_eq!((pixels[3], pixels[7], pixels[11]), (255, 85, 0));
+
+    // Endpoints 255 and 0, with indices 1, 1, 0.
+    let mut block = [0; 16];
+    block[..3].copy_from_slice(&[255, 0, 0b0000_1001]);
+    block[8..].copy_from_slice(&RED_BLUE);
+    let pixels = decode(SurfaceFormat::Dxt5, &block, 4, 4).unwrap();
+    assert_eq!((pixels[3], pixels[7], pixels[11]), (0, 0, 255));
+}
+
+#[test]
+fn partial_blocks() {
+    let data: Vec<u8> = (0..16 * 64).map(|i| (i * 7 % 251) as u8).collect();
+    for &format in &[
+        SurfaceFormat::Dxt1,
+        SurfaceFormat::Dxt3,
+        SurfaceFormat::Dxt5,
+    ] {
+        let pixels = decode(format, &data, 30, 29).unwrap();
+        assert_eq!(pixels.len(), 30 * 29 * 4);
+        assert_eq!(pixels, decode_scalar(format, &data, 30, 29).unwrap());
+    }
+    assert!(decode(SurfaceFormat::Dxt5, &data[..10], 4, 4).is_err());
+}
+
+#[test]
+fn encode_equal_endpoints() {
+    // These two colours come out with equal endpoints, which DXT1 reads as
+    // three colours and transparent black.
+    let mut pixels = vec![];
+    for i in 0..16 {
+        let colour = if ![2, 3, 8, 10].contains(&i) {
+            [59, 85, 251]
+        } else {
+            [198, 185, 12]
+        };
+        pixels.extend_from_slice(&colour);
+        pixels.push(255);
+    }
+    let data = encode(SurfaceFormat::Dxt1, &pixels, 4, 4).unwrap();
+    assert_eq!(data[..2], data[2..4]);
+    let decoded = decode(SurfaceFormat::Dxt1, &data, 4, 4).unwrap();
+    assert!(decoded.chunks_exact(4).all(|p| p[3] == 255));
+}
+
+#[test]
+fn encode_round_trip() {
+    // A diagonal gradient fading out to the right, with a transparent corner.
+    let (width, height) = (13, 10);
+    let mut pixels = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            let alpha = if x < 2 && y < 2 { 0 } else { 255 - x * 8 };
+            pixels.extend_from_slice(&[
+                ((x + y) * 10) as u8,
+                ((x + y) * 6) as u8,
+                128,
+                alpha as u8,
+            ]);
+        }
+    }
+    for &format in &[
+        SurfaceFormat::Dxt1,
+        SurfaceFormat::Dxt3,
+        SurfaceFormat::Dxt5,
+    ] {
+        let data = encode(format, &pixels, width, height).unwrap();
+        assert_eq!(data.len(), format.mip_byte_len(width, height));
+        let decoded = decode(format, &data, width, height).unwrap();
+        for (original, decoded) in pixels.chunks_exact(4).zip(decoded.chunks_exact(4)) {
+            for c in 0..3 {
+                if original[3] >= 128 || format != SurfaceFormat::Dxt1 {
+                    assert!((original[c] as i32 - decoded[c] as i32).abs() <= 16);
+                }
+            }
+            let alpha = match format {
+                SurfaceFormat::Dxt1 if original[3] < 128 => 0,
+                SurfaceFormat::Dxt1 => 255,
+                _ => original[3],
+            };
+            assert!((alpha as i32 - decoded[3] as i32).abs() <= 10);
+        }
+    }
+    assert!(encode(SurfaceFormat::Color, &pixels, width, height).is_err());
+}
@@ -0,0 +1,174 @@
+use xnb::testing::XnbFixture;
+use xnb::{
+    AlphaTestEffect, BasicEffect, CompareFunction, DualTextureEffect, Effect, EffectFormat,
+    EffectMaterial, EnvironmentMapEffect, Parse, SkinnedEffect, Vector3, XNB,
+};
+
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+const INT_READER: &str = "Microsoft.Xna.Framework.Content.Int32Reader";
+
+fn start<T: Parse>() -> XnbFixture {
+    XnbFixture::new().reader(T::READER).object(1)
+}
+
+fn vectors(mut fixture: XnbFixture, vectors: &[[f32; 3]]) -> XnbFixture {
+    for v in vectors {
+        fixture = fixture.float(v[0]).float(v[1]).float(v[2]);
+    }
+    fixture
+}
+
+fn read<T: Parse>(fixture: XnbFixture) -> T {
+    let xnb = XNB::<T>::from_buffer(&mut &fixture.build()[..]).unwrap();
+    assert!(xnb.warnings.is_empty());
+    xnb.primary
+}
+
+#[test]
+fn effect_formats() {
+    for &(bytecode, format) in &[
+        (&[0xCF, 0x0B, 0xF0, 0xBC, 0x10, 0x00][..], EffectFormat::Fxb),
+        (b"MGFX\x0Adata", EffectFormat::Mgfx(10)),
+        // A header cut short before its version.
+        (b"MGFX", EffectFormat::Mgfx(0)),
+        (b"MGF", EffectFormat::Unknown),
+        (b"DXBC", EffectFormat::Unknown),
+        (b"", EffectFormat::Unknown),
+    ] {
+        let effect: Effect = read(
+            start::<Effect>()
+                .uint(bytecode.len() as u32)
+                .bytes(bytecode),
+        );
+        assert_eq!(effect.bytecode, bytecode);
+        assert_eq!(effect.size(), bytecode.len());
+        assert_eq!(effect.format(), format);
+    }
+}
+
+#[test]
+fn basic_effect() {
+    let textured = start::<BasicEffect>().string("textures\\wood");
+    let textured = vectors(textured, &[[1., 2., 3.], [4., 5., 6.], [7., 8., 9.]]);
+    let effect: BasicEffect = read(textured.float(16.).float(0.5).bytes(&[1]));
+    assert_eq!(effect.texture.unwrap().name, "textures\\wood");
+    assert_eq!(effect.diffuse_color, Vector3::new(1., 2., 3.));
+    assert_eq!(effect.emissive_color, Vector3::new(4., 5., 6.));
+    assert_eq!(effect.specular_color, Vector3::new(7., 8., 9.));
+    assert_eq!((effect.specular_power, effect.alpha), (16., 0.5));
+    assert!(effect.vertex_color_enabled);
+
+    // An empty name means there's no texture.
+    let plain = start::<BasicEffect>().string("");
+    let plain = vectors(plain, &[[0.; 3]; 3]);
+    let effect: BasicEffect = read(plain.float(0.).float(1.).bytes(&[0]));
+    assert!(effect.texture.is_none());
+    assert!(!effect.vertex_color_enabled);
+}
+
+#[test]
+fn dual_texture_effect() {
+    let fixture = start::<DualTextureEffect>().string("").string("detail");
+    let effect: DualTextureEffect = read(vectors(fixture, &[[1., 0., 1.]]).float(0.25).bytes(&[1]));
+    assert!(effect.texture.is_none());
+    assert_eq!(effect.texture2.unwrap().name, "detail");
+    assert_eq!(effect.diffuse_color, Vector3::new(1., 0., 1.));
+    assert_eq!(effect.alpha, 0.25);
+    assert!(effect.vertex_color_enabled);
+}
+
+#[test]
+fn alpha_test_effect() {
+    let fixture = start::<AlphaTestEffect>()
+        .string("leaves")
+        .uint(5)
+        .uint(128);
+    let effect: AlphaTestEffect = read(vectors(fixture, &[[0.5; 3]]).float(1.).bytes(&[0]));
+    assert_eq!(effect.texture.unwrap().name, "leaves");
+    assert_eq!(effect.alpha_function, CompareFunction::GreaterEqual);
+    assert_eq!(effect.reference_alpha, 128);
+    assert_eq!(effect.diffuse_color, Vector3::splat(0.5));
+    assert_eq!(effect.alpha, 1.);
+
+    let unknown = start::<AlphaTestEffect>().string("").uint(8).uint(0);
+    let bytes = vectors(unknown, &[[0.; 3]]).float(1.).bytes(&[0]).build();
+    assert!(XNB::<AlphaTestEffect>::from_buffer(&mut &bytes[..]).is_err());
+}
+
+#[test]
+fn environment_map_effect() {
+    let fixture = start::<EnvironmentMapEffect>()
+        .string("metal")
+        .string("sky")
+        .float(0.75);
+    let fixture = vectors(fixture, &[[1., 1., 0.]]).float(0.5);
+    let fixture = vectors(fixture, &[[0.25; 3], [0., 0., 1.]]).float(0.9);
+    let effect: EnvironmentMapEffect = read(fixture);
+    assert_eq!(effect.texture.unwrap().name, "metal");
+    assert_eq!(effect.environment_map.unwrap().name, "sky");
+    assert_eq!(effect.environment_map_amount, 0.75);
+    assert_eq!(effect.environment_map_specular, Vector3::new(1., 1., 0.));
+    assert_eq!(effect.fresnel_factor, 0.5);
+    assert_eq!(effect.diffuse_color, Vector3::splat(0.25));
+    assert_eq!(effect.emissive_color, Vector3::new(0., 0., 1.));
+    assert_eq!(effect.alpha, 0.9);
+}
+
+#[test]
+fn skinned_effect() {
+    let fixture = start::<SkinnedEffect>().string("").uint(4);
+    let fixture = vectors(fixture, &[[1.; 3], [2.; 3], [3.; 3]]);
+    let effect: SkinnedEffect = read(fixture.float(8.).float(0.5));
+    assert!(effect.texture.is_none());
+    assert_eq!(effect.weights_per_vertex, 4);
+    assert_eq!(effect.diffuse_color, Vector3::splat(1.));
+    assert_eq!(effect.emissive_color, Vector3::splat(2.));
+    assert_eq!(effect.specular_color, Vector3::splat(3.));
+    assert_eq!((effect.specular_power, effect.alpha), (8., 0.5));
+}
+
+#[test]
+fn effect_material() {
+    // Parameters are a dictionary of strings to objects of any type, which
+    // leaves out null values.
+    let fixture = XnbFixture::new()
+        .reader(EffectMaterial::READER)
+        .reader(
+            "Microsoft.Xna.Framework.Content.DictionaryReader`2[[System.String],[System.Object]]",
+        )
+        .reader(STRING_READER)
+        .reader(INT_READER)
+        .object(1)
+        .string("effects\\water")
+        .object(2)
+        .uint(3)
+        .object(3)
+        .string("Waves")
+        .object(4)
+        .int(3)
+        .object(3)
+        .string("Tint")
+        .object(3)
+        .string("blue")
+        .object(3)
+        .string("Unset")
+        .object(0);
+    let material: EffectMaterial = read(fixture);
+    assert_eq!(material.effect.name, "effects\\water");
+    assert_eq!(material.parameters.len(), 2);
+    assert_eq!(material.parameters["Waves"].downcast_ref::<i32>(), Some(&3));
+    assert_eq!(
+        material.parameters["Tint"]
+            .downcast_ref::<String>()
+            .unwrap(),
+        "blue"
+    );
+
+    let empty = XnbFixture::new()
+        .reader(EffectMaterial::READER)
+        .object(1)
+        .string("effects\\plain")
+        .object(0);
+    let material: EffectMaterial = read(empty);
+    assert!(material.parameters.is_empty());
+}
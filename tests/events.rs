@@ -0,0 +1,179 @@
+use xnb::events::{self, Event};
+use xnb::testing::XnbFixture;
+use xnb::{Error, ParseMode, ParseOptions};
+
+const LIST_READER: &str = "Microsoft.Xna.Framework.Content.ListReader";
+const DICTIONARY_READER: &str = "Microsoft.Xna.Framework.Content.DictionaryReader";
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+const TEXTURE_READER: &str = "Microsoft.Xna.Framework.Content.Texture2DReader";
+
+// Events as strings, with data given by its length.
+fn describe(event: Event) -> String {
+    match event {
+        Event::Readers(readers) => format!("Readers({})", readers.len()),
+        Event::Data(data) => format!("Data({})", data.len()),
+        event => format!("{:?}", event),
+    }
+}
+
+fn parse(bytes: &[u8], options: &ParseOptions) -> Result<(Vec<String>, Vec<Error>), Error> {
+    let mut events = vec![];
+    let warnings = events::parse(&mut &bytes[..], options, |event| {
+        events.push(describe(event));
+        Ok(())
+    })?;
+    Ok((events, warnings))
+}
+
+fn string(fixture: XnbFixture, s: &str) -> XnbFixture {
+    fixture.object(3).string(s)
+}
+
+// A list holding a dictionary of nullable integers, a null and a texture,
+// followed by a shared string.
+fn fixture(mips: &[Vec<u8>]) -> Vec<u8> {
+    let fixture = XnbFixture::new()
+        .reader(&format!("{}`1[[System.Object, mscorlib]]", LIST_READER))
+        .reader(&format!(
+            "{}`2[[System.String, mscorlib],[System.Nullable`1[[System.Int32, mscorlib]], mscorlib]]",
+            DICTIONARY_READER
+        ))
+        .reader(STRING_READER)
+        .reader(TEXTURE_READER)
+        .shared_resources(1)
+        .object(1)
+        .uint(3)
+        .object(2)
+        .uint(2);
+    let fixture = string(fixture, "a").bytes(&[1]).int(5);
+    let fixture = string(fixture, "b").bytes(&[0]).object(0);
+    let mut fixture = fixture
+        .object(4)
+        .uint(0)
+        .uint(256)
+        .uint(128)
+        .uint(mips.len() as u32);
+    for mip in mips {
+        fixture = fixture.uint(mip.len() as u32).bytes(mip);
+    }
+    string(fixture, "shared").build()
+}
+
+fn mips() -> Vec<Vec<u8>> {
+    vec![
+        (0..256 * 128 * 4).map(|i| i as u8).collect(),
+        vec![7; 128 * 64 * 4],
+    ]
+}
+
+#[test]
+fn file_order() {
+    let (events, warnings) = parse(&fixture(&mips()), &ParseOptions::new()).unwrap();
+    assert!(warnings.is_empty());
+    let list = format!("BeginObject {{ reader: {:?} }}", LIST_READER);
+    let dictionary = format!("BeginObject {{ reader: {:?} }}", DICTIONARY_READER);
+    let string = format!("BeginObject {{ reader: {:?} }}", STRING_READER);
+    let texture = format!("BeginObject {{ reader: {:?} }}", TEXTURE_READER);
+    let expected = [
+        "Readers(4)",
+        &list,
+        "BeginArray { len: 3 }",
+        &dictionary,
+        "BeginDictionary { len: 2 }",
+        &string,
+        "Primitive(String(\"a\"))",
+        "EndObject",
+        "Primitive(Int(5))",
+        &string,
+        "Primitive(String(\"b\"))",
+        "EndObject",
+        "Null",
+        "EndDictionary",
+        "EndObject",
+        "Null",
+        &texture,
+        "BeginTexture { format: Color, width: 256, height: 128, mip_count: 2 }",
+        "MipLevel { level: 0, size: 131072 }",
+        "Data(65536)",
+        "Data(65536)",
+        "MipLevel { level: 1, size: 32768 }",
+        "Data(32768)",
+        "EndTexture",
+        "EndObject",
+        "EndArray",
+        "EndObject",
+        "SharedResource(0)",
+        &string,
+        "Primitive(String(\"shared\"))",
+        "EndObject",
+    ];
+    assert_eq!(events, expected);
+}
+
+#[test]
+fn data_chunks() {
+    let mips = mips();
+    let mut data = vec![vec![]; mips.len()];
+    let mut level = 0;
+    events::parse(&mut &fixture(&mips)[..], &ParseOptions::new(), |event| {
+        match event {
+            Event::MipLevel { level: l, .. } => level = l,
+            Event::Data(chunk) => data[level].extend_from_slice(chunk),
+            _ => {}
+        }
+        Ok(())
+    })
+    .unwrap();
+    assert!(data == mips);
+}
+
+#[test]
+fn handler_errors_stop_parsing() {
+    let mut count = 0;
+    let result = events::parse(&mut &fixture(&mips())[..], &ParseOptions::new(), |event| {
+        count += 1;
+        match event {
+            Event::Null => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    });
+    assert!(matches!(result, Err(Error::Cancelled)));
+    // Everything up to the dictionary's null value.
+    assert_eq!(count, 13);
+}
+
+#[test]
+fn invalid_mip_size() {
+    let bytes = fixture(&[vec![0; 16]]);
+    let (events, warnings) = parse(&bytes, &ParseOptions::new()).unwrap();
+    assert!(events.contains(&"MipLevel { level: 0, size: 16 }".to_string()));
+    assert!(matches!(
+        warnings[..],
+        [Error::InvalidMipSize {
+            level: 0,
+            expected: 131072,
+            actual: 16
+        }]
+    ));
+
+    let strict = ParseOptions::new().mode(ParseMode::Strict);
+    match parse(&bytes, &strict) {
+        Err(Error::Parse { error, .. }) => {
+            assert!(
+                matches!(*error, Error::InvalidMipSize { .. }),
+                "{:?}",
+                error
+            )
+        }
+        result => panic!("{:?}", result),
+    }
+}
+
+#[test]
+fn null_primary_asset() {
+    let bytes = XnbFixture::new().reader(STRING_READER).object(0).build();
+    assert!(matches!(
+        parse(&bytes, &ParseOptions::new()),
+        Err(Error::UnexpectedNull)
+    ));
+}
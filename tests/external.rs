@@ -0,0 +1,39 @@
+use std::path::Path;
+use xnb::testing::XnbFixture;
+use xnb::{ExternalReference, Parse, Texture2d, XNB};
+
+fn reference(name: &str) -> ExternalReference<Texture2d> {
+    let bytes = XnbFixture::new()
+        .reader(ExternalReference::<Texture2d>::READER)
+        .object(1)
+        .string(name)
+        .build();
+    XNB::<ExternalReference<Texture2d>>::from_buffer(&mut &bytes[..])
+        .unwrap()
+        .primary
+}
+
+#[test]
+fn names() {
+    // The name is a bare string, not an object.
+    assert_eq!(reference("Maps\\spring_town").name, "Maps\\spring_town");
+    assert_eq!(reference("").name, "");
+}
+
+#[test]
+fn resolve() {
+    // Names are relative to the referring XNB, with Windows separators.
+    let referrer = Path::new("Content/Maps/Town.xnb");
+    assert_eq!(
+        reference("spring_town").resolve(referrer),
+        Path::new("Content/Maps/spring_town.xnb")
+    );
+    assert_eq!(
+        reference("..\\TileSheets\\outdoors").resolve(referrer),
+        Path::new("Content/Maps/../TileSheets/outdoors.xnb")
+    );
+    assert_eq!(
+        reference("Fonts/small").resolve(Path::new("Town.xnb")),
+        Path::new("Fonts/small.xnb")
+    );
+}
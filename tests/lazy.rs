@@ -0,0 +1,87 @@
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use xnb::testing::XnbFixture;
+use xnb::{Compression, Error, LazyTexture2d, Parse, ParseOptions, SurfaceFormat, Texture2d};
+
+// Counts the bytes read through it.
+struct Counting<R> {
+    rdr: R,
+    read: usize,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.rdr.read(buf)?;
+        self.read += n;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Counting<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.rdr.seek(pos)
+    }
+}
+
+// A 64x64 texture with a full mip chain, each level filled with its number.
+fn texture(compression: Compression) -> Vec<u8> {
+    let mut fixture = XnbFixture::new()
+        .compression(compression)
+        .reader(Texture2d::READER)
+        .object(1)
+        .uint(0)
+        .uint(64)
+        .uint(64)
+        .uint(7);
+    for level in 0..7 {
+        let data = vec![level as u8; (64 >> level) * (64 >> level) * 4];
+        fixture = fixture.uint(data.len() as u32).bytes(&data);
+    }
+    fixture.build()
+}
+
+#[test]
+fn mips_are_read_on_demand() {
+    let mut rdr = Counting {
+        rdr: Cursor::new(texture(Compression::None)),
+        read: 0,
+    };
+    let texture = LazyTexture2d::from_reader(&mut rdr, &ParseOptions::new()).unwrap();
+    assert_eq!(texture.format, SurfaceFormat::Color);
+    assert_eq!((texture.width, texture.height), (64, 64));
+    assert_eq!(texture.mip_count(), 7);
+    assert_eq!(texture.mip_len(0), Some(64 * 64 * 4));
+    assert_eq!(texture.mip_len(6), Some(4));
+    assert_eq!(texture.mip_len(7), None);
+    // Only the headers, not the 21,844 bytes of mip data.
+    assert!(rdr.read < 200, "{}", rdr.read);
+
+    rdr.read = 0;
+    assert_eq!(texture.mip(&mut rdr, 2).unwrap(), vec![2; 16 * 16 * 4]);
+    assert_eq!(rdr.read, 16 * 16 * 4);
+    // Levels can be read in any order.
+    assert_eq!(texture.mip(&mut rdr, 6).unwrap(), [6; 4]);
+    assert_eq!(texture.mip(&mut rdr, 0).unwrap(), vec![0; 64 * 64 * 4]);
+    assert!(matches!(
+        texture.mip(&mut rdr, 7),
+        Err(Error::MissingMipLevel(7))
+    ));
+}
+
+#[test]
+fn rejected_files() {
+    let mut rdr = Cursor::new(texture(Compression::Lz4));
+    assert!(matches!(
+        LazyTexture2d::from_reader(&mut rdr, &ParseOptions::new()),
+        Err(Error::Void)
+    ));
+
+    let bytes = XnbFixture::new()
+        .reader("Microsoft.Xna.Framework.Content.StringReader")
+        .object(1)
+        .string("town")
+        .build();
+    assert!(matches!(
+        LazyTexture2d::from_reader(&mut Cursor::new(bytes), &ParseOptions::new()),
+        Err(Error::ReaderMismatch(..))
+    ));
+}
@@ -0,0 +1,59 @@
+use xnb::testing::XnbFixture;
+use xnb::{BoundingBox, BoundingSphere, Matrix, Parse, Plane, Quaternion, Ray, Vector3, XNB};
+
+fn read<T: Parse>(values: &[f32]) -> T {
+    let mut fixture = XnbFixture::new().reader(T::READER).object(1);
+    for &v in values {
+        fixture = fixture.float(v);
+    }
+    XNB::<T>::from_buffer(&mut &fixture.build()[..])
+        .unwrap()
+        .primary
+}
+
+#[test]
+fn quaternion() {
+    let q: Quaternion = read(&[0.5, -0.5, 0.25, 1.0]);
+    assert_eq!((q.x, q.y, q.z, q.w), (0.5, -0.5, 0.25, 1.0));
+}
+
+#[test]
+fn matrix_rows() {
+    // M11, M12, M13, M14, M21, ...
+    let values: Vec<f32> = (11..=44)
+        .filter(|v| (1..=4).contains(&(v % 10)))
+        .map(|v| v as f32)
+        .collect();
+    let matrix: Matrix = read(&values);
+    assert_eq!(matrix.m[0], [11., 12., 13., 14.]);
+    assert_eq!(matrix.m[1], [21., 22., 23., 24.]);
+    assert_eq!(matrix.m[3], [41., 42., 43., 44.]);
+}
+
+#[test]
+fn bounding_box() {
+    let bounds: BoundingBox = read(&[-1., -2., -3., 1., 2., 3.]);
+    assert_eq!(bounds.min, Vector3::new(-1., -2., -3.));
+    assert_eq!(bounds.max, Vector3::new(1., 2., 3.));
+}
+
+#[test]
+fn bounding_sphere() {
+    let sphere: BoundingSphere = read(&[1., 2., 3., 4.]);
+    assert_eq!(sphere.center, Vector3::new(1., 2., 3.));
+    assert_eq!(sphere.radius, 4.);
+}
+
+#[test]
+fn plane() {
+    let plane: Plane = read(&[0., 1., 0., -5.]);
+    assert_eq!(plane.normal, Vector3::new(0., 1., 0.));
+    assert_eq!(plane.d, -5.);
+}
+
+#[test]
+fn ray() {
+    let ray: Ray = read(&[1., 2., 3., 0., 0., -1.]);
+    assert_eq!(ray.position, Vector3::new(1., 2., 3.));
+    assert_eq!(ray.direction, Vector3::new(0., 0., -1.));
+}
@@ -0,0 +1,194 @@
+use std::rc::Rc;
+use xnb::testing::XnbFixture;
+use xnb::{BasicEffect, Model, VertexElementFormat, VertexElementUsage, XNB};
+
+const MODEL_READER: &str = "Microsoft.Xna.Framework.Content.ModelReader";
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+const VERTEX_BUFFER_READER: &str = "Microsoft.Xna.Framework.Content.VertexBufferReader";
+const INDEX_BUFFER_READER: &str = "Microsoft.Xna.Framework.Content.IndexBufferReader";
+const BASIC_EFFECT_READER: &str = "Microsoft.Xna.Framework.Content.BasicEffectReader";
+
+fn matrix(fixture: XnbFixture, scale: f32, translation: [f32; 3]) -> XnbFixture {
+    let mut fixture = fixture;
+    for row in 0..3 {
+        for col in 0..4 {
+            fixture = fixture.float(if row == col { scale } else { 0.0 });
+        }
+    }
+    for &v in &translation {
+        fixture = fixture.float(v);
+    }
+    fixture.float(1.0)
+}
+
+// Bone references are a byte below 255 bones and a u32 from then on.
+fn bone_ref(fixture: XnbFixture, bone_count: u32, bone: Option<u32>) -> XnbFixture {
+    let value = bone.map_or(0, |bone| bone + 1);
+    if bone_count < 255 {
+        fixture.bytes(&[value as u8])
+    } else {
+        fixture.uint(value)
+    }
+}
+
+// A root bone scaling by 2, with every other bone its child and translating
+// by its own index along x. One mesh on bone 1 has two parts sharing a vertex
+// buffer, index buffer and effect.
+fn model(bone_count: u32) -> Vec<u8> {
+    let mut fixture = XnbFixture::new()
+        .reader(MODEL_READER)
+        .reader(STRING_READER)
+        .reader(VERTEX_BUFFER_READER)
+        .reader(INDEX_BUFFER_READER)
+        .reader(BASIC_EFFECT_READER)
+        .shared_resources(3)
+        .object(1)
+        .uint(bone_count);
+    for bone in 0..bone_count {
+        fixture = if bone == 0 {
+            matrix(fixture.object(2).string("root"), 2.0, [0.0; 3])
+        } else {
+            matrix(fixture.object(0), 1.0, [bone as f32, 0.0, 0.0])
+        };
+    }
+    fixture = bone_ref(fixture, bone_count, None).uint(bone_count - 1);
+    for child in 1..bone_count {
+        fixture = bone_ref(fixture, bone_count, Some(child));
+    }
+    for _ in 1..bone_count {
+        fixture = bone_ref(fixture, bone_count, Some(0)).uint(0);
+    }
+
+    fixture = bone_ref(
+        fixture.uint(1).object(2).string("mesh"),
+        bone_count,
+        Some(1),
+    )
+    .float(0.0)
+    .float(1.0)
+    .float(2.0)
+    .float(5.0)
+    .object(0)
+    .uint(2);
+    for &(start, count) in &[(0, 1), (3, 1)] {
+        fixture = fixture
+            .uint(0)
+            .uint(3)
+            .uint(start)
+            .uint(count)
+            .object(0)
+            .object(1)
+            .object(2)
+            .object(3);
+    }
+    fixture = bone_ref(fixture, bone_count, Some(0))
+        .object(2)
+        .string("tag");
+
+    // The shared resources: a vertex buffer of three positions, six 16-bit
+    // indices and an effect. The vertices have a stride of 12 and a single
+    // element: a Vector3 (2) position (0) at offset 0.
+    fixture = fixture
+        .object(3)
+        .uint(12)
+        .uint(1)
+        .uint(0)
+        .uint(2)
+        .uint(0)
+        .uint(0)
+        .uint(3);
+    for v in 0..9 {
+        fixture = fixture.float(v as f32);
+    }
+    fixture = fixture.object(4).bytes(&[1]).uint(12);
+    for &i in &[0u16, 1, 2, 2, 1, 0] {
+        fixture = fixture.bytes(&i.to_le_bytes());
+    }
+    fixture = fixture.object(5).string("");
+    for v in 0..9 {
+        fixture = fixture.float(v as f32 / 10.0);
+    }
+    fixture.float(16.0).float(1.0).bytes(&[1]).build()
+}
+
+fn check(bone_count: u32) {
+    let xnb = XNB::<Model>::from_buffer(&mut &model(bone_count)[..]).unwrap();
+    assert!(xnb.warnings.is_empty());
+    let model = &xnb.primary;
+
+    assert_eq!(model.bones.len(), bone_count as usize);
+    assert_eq!(model.bones[0].name.as_deref(), Some("root"));
+    assert_eq!(model.bones[0].parent, None);
+    assert_eq!(
+        model.bones[0].children,
+        (1..bone_count as usize).collect::<Vec<_>>()
+    );
+    let last = bone_count as usize - 1;
+    assert_eq!(model.bones[last].name, None);
+    assert_eq!(model.bones[last].parent, Some(0));
+    assert_eq!(model.root, Some(0));
+    assert_eq!(
+        model
+            .tag
+            .as_ref()
+            .unwrap()
+            .downcast_ref::<String>()
+            .unwrap(),
+        "tag"
+    );
+
+    // A child's transform is applied before its parent's.
+    let transform = model.absolute_transform(last).unwrap();
+    assert_eq!(transform.m[0][0], 2.0);
+    assert_eq!(transform.m[3][..3], [2.0 * last as f32, 0.0, 0.0]);
+    assert!(model.absolute_transform(bone_count as usize).is_none());
+
+    let mesh = &model.meshes[0];
+    assert_eq!(mesh.name.as_deref(), Some("mesh"));
+    assert_eq!(mesh.parent_bone, Some(1));
+    assert_eq!(mesh.bounds.radius, 5.0);
+    let (first, second) = (&mesh.parts[0], &mesh.parts[1]);
+    assert_eq!((second.start_index, second.primitive_count), (3, 1));
+
+    let vertices = first.vertex_buffer.as_ref().unwrap();
+    assert_eq!(vertices.vertex_count, 3);
+    assert_eq!(
+        vertices.declaration.elements[0].format,
+        VertexElementFormat::Vector3
+    );
+    assert_eq!(
+        vertices.declaration.elements[0].usage,
+        VertexElementUsage::Position
+    );
+    let indices = first.index_buffer.as_ref().unwrap();
+    assert_eq!(indices.indices(), [0, 1, 2, 2, 1, 0]);
+    let effect = first.effect_as::<BasicEffect>().unwrap();
+    assert!(effect.texture.is_none());
+    assert_eq!(effect.specular_power, 16.0);
+
+    // Both parts resolve to the very same resources.
+    assert!(Rc::ptr_eq(
+        &vertices.rc(),
+        &second.vertex_buffer.as_ref().unwrap().rc()
+    ));
+    assert!(Rc::ptr_eq(
+        &indices.rc(),
+        &second.index_buffer.as_ref().unwrap().rc()
+    ));
+    assert!(Rc::ptr_eq(
+        &first.effect.as_ref().unwrap().rc(),
+        &second.effect.as_ref().unwrap().rc()
+    ));
+}
+
+#[test]
+fn byte_bone_references() {
+    check(3);
+    check(254);
+}
+
+#[test]
+fn u32_bone_references() {
+    check(255);
+    check(300);
+}
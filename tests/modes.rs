@@ -0,0 +1,102 @@
+use xnb::testing::XnbFixture;
+use xnb::{BorrowedXnb, Error, Parse, ParseMode, ParseOptions, Texture2d, XNB};
+
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+
+fn string() -> XnbFixture {
+    XnbFixture::new()
+        .reader(STRING_READER)
+        .object(1)
+        .string("town")
+}
+
+fn parse(bytes: &[u8], mode: ParseMode) -> Result<Vec<Error>, Error> {
+    let options = ParseOptions::new().mode(mode);
+    XNB::<String>::from_buffer_with_options(&mut &bytes[..], &options).map(|xnb| xnb.warnings)
+}
+
+#[test]
+fn trailing_data() {
+    let bytes = string().bytes(&[1, 2, 3]).build();
+    let warnings = parse(&bytes, ParseMode::Lenient).unwrap();
+    assert!(matches!(warnings[..], [Error::TrailingData(3)]));
+    assert!(matches!(
+        parse(&bytes, ParseMode::Strict),
+        Err(Error::TrailingData(3))
+    ));
+}
+
+#[test]
+fn file_size_mismatch() {
+    // Bytes past the size given in the header.
+    let mut bytes = string().build();
+    let size = bytes.len();
+    bytes.extend_from_slice(&[1, 2]);
+    let warnings = parse(&bytes, ParseMode::Lenient).unwrap();
+    match warnings[..] {
+        [Error::FileSizeMismatch { expected, actual }, Error::TrailingData(2)] => {
+            assert_eq!((expected, actual), (size, size + 2))
+        }
+        _ => panic!("{:?}", warnings),
+    }
+    assert!(matches!(
+        parse(&bytes, ParseMode::Strict),
+        Err(Error::FileSizeMismatch { .. })
+    ));
+
+    let xnb = BorrowedXnb::from_bytes(&bytes).unwrap();
+    assert!(matches!(
+        xnb.warnings[..],
+        [Error::FileSizeMismatch { .. }, Error::TrailingData(2)]
+    ));
+    let options = ParseOptions::new().mode(ParseMode::Strict);
+    assert!(matches!(
+        BorrowedXnb::from_bytes_with_options(&bytes, &options),
+        Err(Error::FileSizeMismatch { .. })
+    ));
+}
+
+#[test]
+fn invalid_mip_size() {
+    // A 4x4 texture whose only mip level is 4 bytes short.
+    let bytes = XnbFixture::new()
+        .reader(Texture2d::READER)
+        .object(1)
+        .uint(0)
+        .uint(4)
+        .uint(4)
+        .uint(1)
+        .uint(60)
+        .bytes(&[0; 60])
+        .build();
+    let options = ParseOptions::new();
+    let xnb = XNB::<Texture2d>::from_buffer_with_options(&mut &bytes[..], &options).unwrap();
+    assert_eq!(xnb.primary.mip_data[0].len(), 60);
+    assert!(matches!(
+        xnb.warnings[..],
+        [Error::InvalidMipSize {
+            level: 0,
+            expected: 64,
+            actual: 60
+        }]
+    ));
+
+    let options = ParseOptions::new().mode(ParseMode::Strict);
+    match XNB::<Texture2d>::from_buffer_with_options(&mut &bytes[..], &options) {
+        Err(Error::Parse { error, .. }) => {
+            assert!(
+                matches!(*error, Error::InvalidMipSize { .. }),
+                "{:?}",
+                error
+            )
+        }
+        result => panic!("{:?}", result.map(|xnb| xnb.warnings)),
+    }
+}
+
+#[test]
+fn well_formed_files_have_no_warnings() {
+    let bytes = string().build();
+    assert!(parse(&bytes, ParseMode::Strict).unwrap().is_empty());
+    assert!(parse(&bytes, ParseMode::Lenient).unwrap().is_empty());
+}
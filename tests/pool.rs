@@ -0,0 +1,72 @@
+use xnb::testing::XnbFixture;
+use xnb::{BufferPool, Compression, Parse, ParseOptions, Texture2d, XNB};
+
+// A 16x16 texture with a single mip level filled with `fill`.
+fn texture(compression: Compression, fill: u8) -> Vec<u8> {
+    XnbFixture::new()
+        .compression(compression)
+        .reader(Texture2d::READER)
+        .object(1)
+        .uint(0)
+        .uint(16)
+        .uint(16)
+        .uint(1)
+        .uint(1024)
+        .bytes(&[fill; 1024])
+        .build()
+}
+
+fn parse(bytes: &[u8], pool: &BufferPool) -> Texture2d {
+    let options = ParseOptions::new().buffer_pool(pool.clone());
+    XNB::<Texture2d>::from_buffer_with_options(&mut &bytes[..], &options)
+        .unwrap()
+        .primary
+}
+
+fn pooled(pool: &BufferPool) -> String {
+    format!("{:?}", pool)
+}
+
+#[test]
+fn textures_reuse_recycled_buffers() {
+    let pool = BufferPool::new();
+    let first = parse(&texture(Compression::None, 1), &pool);
+    let data = first.mip_data[0].as_ptr();
+    first.recycle(&pool);
+    assert_eq!(pooled(&pool), "BufferPool(1 buffers)");
+
+    let second = parse(&texture(Compression::None, 2), &pool);
+    assert_eq!(second.mip_data[0].as_ptr(), data);
+    assert_eq!(second.mip_data[0], [2; 1024]);
+    assert_eq!(pooled(&pool), "BufferPool(0 buffers)");
+}
+
+#[test]
+fn decompression_buffers_are_returned() {
+    let pool = BufferPool::new();
+    let bytes = texture(Compression::Lz4, 3);
+    let mut counts = vec![];
+    for _ in 0..3 {
+        let texture = parse(&bytes, &pool);
+        assert_eq!(texture.mip_data[0], [3; 1024]);
+        texture.recycle(&pool);
+        counts.push(pooled(&pool));
+    }
+    assert_ne!(counts[0], "BufferPool(0 buffers)");
+    // Once every buffer needed is pooled, parses stop adding to it.
+    assert_eq!(counts[1], counts[2]);
+}
+
+#[test]
+fn pool_is_bounded() {
+    let pool = BufferPool::new();
+    // Buffers without an allocation aren't worth keeping.
+    pool.put(Vec::new());
+    assert_eq!(pooled(&pool), "BufferPool(0 buffers)");
+    for _ in 0..40 {
+        pool.put(Vec::with_capacity(16));
+    }
+    assert_eq!(pooled(&pool), "BufferPool(32 buffers)");
+    // Clones share buffers.
+    assert_eq!(pooled(&pool.clone()), "BufferPool(32 buffers)");
+}
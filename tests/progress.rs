@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use xnb::testing::XnbFixture;
+use xnb::{Compression, ParseOptions, ParseStage, Progress, XNB};
+
+// A string followed by three shared strings, each large enough to span
+// several progress reports.
+fn strings(compression: Compression) -> XnbFixture {
+    let text = "x".repeat(100_000);
+    let mut fixture = XnbFixture::new()
+        .compression(compression)
+        .reader("Microsoft.Xna.Framework.Content.StringReader")
+        .shared_resources(3);
+    for _ in 0..4 {
+        fixture = fixture.object(1).string(&text);
+    }
+    fixture
+}
+
+fn reports(fixture: &XnbFixture) -> Vec<Progress> {
+    let reports = Rc::new(RefCell::new(vec![]));
+    let hook_reports = reports.clone();
+    let options = ParseOptions::new().progress(move |p| hook_reports.borrow_mut().push(p));
+    let xnb = XNB::<String>::from_buffer_with_options(&mut &fixture.build()[..], &options).unwrap();
+    assert_eq!(xnb.shared.len(), 3);
+    let reports = reports.borrow().clone();
+    reports
+}
+
+fn check(reports: &[Progress]) {
+    let total = reports[0].total;
+    // Four strings of 100,000 bytes, each with a 3 byte length and reader id.
+    assert!(total > 400_000 && total < 400_100, "{}", total);
+    for pair in reports.windows(2) {
+        assert!(pair[0].processed <= pair[1].processed, "{:?}", pair);
+    }
+    assert!(reports.iter().all(|p| p.total == total));
+
+    let stages: Vec<_> = reports.iter().map(|p| p.stage).collect();
+    let mut distinct = stages.clone();
+    distinct.dedup();
+    assert_eq!(
+        distinct,
+        [
+            ParseStage::ReaderTable,
+            ParseStage::PrimaryAsset,
+            ParseStage::SharedResources,
+            ParseStage::Done
+        ]
+    );
+    // Reports within the shared resources, not just when they start.
+    let shared = stages
+        .iter()
+        .filter(|&&s| s == ParseStage::SharedResources)
+        .count();
+    assert!(shared > 2, "{:?}", reports);
+
+    let last = reports.last().unwrap();
+    assert_eq!(last.processed, total);
+}
+
+#[test]
+fn monotonic_progress() {
+    check(&reports(&strings(Compression::None)));
+}
+
+#[test]
+fn compressed_progress() {
+    // Progress counts decompressed bytes.
+    let uncompressed = reports(&strings(Compression::None));
+    let compressed = reports(&strings(Compression::Lz4));
+    check(&compressed);
+    assert_eq!(compressed[0].total, uncompressed[0].total);
+}
@@ -0,0 +1,100 @@
+use xnb::testing::XnbFixture;
+use xnb::{Error, Fields, Reflect, Reflective, REFLECTIVE_READER, XNB};
+
+#[derive(Debug, PartialEq)]
+struct Person {
+    name: String,
+    age: i32,
+}
+
+impl Reflect for Person {
+    const TYPE: &'static str = "Game.Person";
+    fn read_fields(fields: &mut Fields) -> Result<Person, Error> {
+        Ok(Person {
+            name: fields.object()?,
+            age: fields.value()?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Movie {
+    title: String,
+    year: i32,
+    director: Reflective<Person>,
+    cast: Vec<Reflective<Person>>,
+    rating: Option<f32>,
+    sequel: Option<String>,
+}
+
+impl Reflect for Movie {
+    const TYPE: &'static str = "Game.Movie";
+    fn read_fields(fields: &mut Fields) -> Result<Movie, Error> {
+        Ok(Movie {
+            title: fields.object()?,
+            year: fields.value()?,
+            director: fields.object()?,
+            cast: fields.object()?,
+            rating: fields.nullable()?,
+            sequel: fields.optional_object()?,
+        })
+    }
+}
+
+fn person(fixture: XnbFixture, name: &str, age: i32) -> XnbFixture {
+    fixture.object(3).object(2).string(name).int(age)
+}
+
+// A movie whose director and cast are reflective objects of their own.
+fn movie() -> Vec<u8> {
+    let fixture = XnbFixture::new()
+        .reader(&format!("{}`1[[Game.Movie, Game]]", REFLECTIVE_READER))
+        .reader("Microsoft.Xna.Framework.Content.StringReader")
+        .reader(&format!("{}`1[[Game.Person, Game]]", REFLECTIVE_READER))
+        .reader("Microsoft.Xna.Framework.Content.ListReader`1[[Game.Person, Game]]")
+        .object(1)
+        .object(2)
+        .string("Heat")
+        .int(1995);
+    let fixture = person(fixture, "Mann", 52).object(4).uint(2);
+    let fixture = person(person(fixture, "Pacino", 55), "De Niro", 52);
+    fixture.bytes(&[1]).float(8.5).object(0).build()
+}
+
+fn reflective(name: &str, age: i32) -> Reflective<Person> {
+    Reflective(Person {
+        name: name.to_string(),
+        age,
+    })
+}
+
+#[test]
+fn nested_members() {
+    let xnb = XNB::<Reflective<Movie>>::from_buffer(&mut &movie()[..]).unwrap();
+    assert!(xnb.warnings.is_empty());
+    assert_eq!(
+        xnb.primary.into_inner(),
+        Movie {
+            title: "Heat".to_string(),
+            year: 1995,
+            director: reflective("Mann", 52),
+            cast: vec![reflective("Pacino", 55), reflective("De Niro", 52)],
+            rating: Some(8.5),
+            sequel: None,
+        }
+    );
+}
+
+#[test]
+fn wrong_type() {
+    match XNB::<Reflective<Person>>::from_buffer(&mut &movie()[..]) {
+        Err(Error::Parse { error, .. }) => match *error {
+            Error::ReaderMismatch(found, expected) => {
+                assert!(found.contains("Game.Movie"), "{}", found);
+                assert!(expected.contains("Game.Person"), "{}", expected);
+            }
+            error => panic!("{:?}", error),
+        },
+        result => panic!("{:?}", result.map(|xnb| xnb.primary)),
+    }
+}
@@ -0,0 +1,61 @@
+use xnb::testing::XnbFixture;
+use xnb::{Error, Parse, ParseOptions, ReaderRegistry, SharedResource, XNB};
+
+const LEVEL_READER: &str = "Game.Content.LevelReader";
+
+#[derive(Debug, PartialEq)]
+struct Level {
+    number: i32,
+    args: Vec<String>,
+}
+
+fn registry() -> ReaderRegistry {
+    let mut registry = ReaderRegistry::new();
+    registry.register(LEVEL_READER, |rdr, readers, args| {
+        Ok(Level {
+            number: i32::try_parse(rdr, readers, vec![])?,
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        })
+    });
+    registry
+}
+
+fn read(reader: &str, registry: ReaderRegistry) -> Result<XNB<SharedResource>, Error> {
+    let bytes = XnbFixture::new().reader(reader).object(1).int(3).build();
+    let options = ParseOptions::new().registry(registry);
+    XNB::from_buffer_with_options(&mut &bytes[..], &options)
+}
+
+#[test]
+fn registered_reader() {
+    let registry = registry();
+    assert!(registry.contains(LEVEL_READER));
+    assert!(!registry.contains("Game.Content.MapReader"));
+
+    let xnb = read(LEVEL_READER, registry).unwrap();
+    let level = xnb.primary.downcast_ref::<Level>().unwrap();
+    assert_eq!(level.number, 3);
+    assert!(level.args.is_empty());
+}
+
+#[test]
+fn generic_arguments() {
+    let reader = format!("{}`1[[System.Int32, mscorlib]]", LEVEL_READER);
+    let xnb = read(&reader, registry()).unwrap();
+    let level = xnb.primary.downcast_ref::<Level>().unwrap();
+    assert_eq!(level.number, 3);
+    assert_eq!(level.args, ["System.Int32"]);
+}
+
+#[test]
+fn unknown_reader() {
+    for registry in [ReaderRegistry::new(), registry()] {
+        match read("Game.Content.MapReader", registry) {
+            Err(Error::Parse { error, .. }) => match *error {
+                Error::UnknownReader(name) => assert_eq!(name, "Game.Content.MapReader"),
+                error => panic!("{:?}", error),
+            },
+            result => panic!("{:?}", result.map(|xnb| xnb.primary)),
+        }
+    }
+}
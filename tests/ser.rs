@@ -0,0 +1,137 @@
+#![cfg(feature = "serde")]
+
+use serde::Serialize;
+use std::collections::HashMap;
+use xnb::ser::Serializer;
+use xnb::testing::XnbFixture;
+use xnb::{Fields, ParseOptions, ReaderRegistry, Value, REFLECTIVE_READER, XNB};
+
+#[derive(Serialize)]
+enum Kind {
+    #[allow(dead_code)]
+    Light,
+    Heavy,
+}
+
+#[derive(Serialize)]
+struct Item {
+    count: Option<i32>,
+    limit: Option<i32>,
+    label: Option<String>,
+    note: Option<String>,
+    tags: Vec<String>,
+    stats: HashMap<String, i32>,
+    kind: Kind,
+}
+
+fn item() -> Item {
+    Item {
+        count: Some(3),
+        limit: None,
+        label: Some("sword".to_string()),
+        note: None,
+        tags: vec!["a".to_string(), "b".to_string()],
+        stats: vec![("hp".to_string(), 10)].into_iter().collect(),
+        kind: Kind::Heavy,
+    }
+}
+
+const INT: &str =
+    "System.Int32, mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089";
+const STRING: &str =
+    "System.String, mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089";
+
+#[test]
+fn reflective_struct() {
+    let bytes = Serializer::new("Game.Data").to_bytes(&item()).unwrap();
+
+    // Readers are added as their types are first written. Null members are
+    // a single zero byte and add none.
+    let expected = XnbFixture::new()
+        .reader(&format!(
+            "{}`1[[Game.Data.Item, Game.Data]]",
+            REFLECTIVE_READER
+        ))
+        .reader(&format!(
+            "Microsoft.Xna.Framework.Content.NullableReader`1[[{}]]",
+            INT
+        ))
+        .reader("Microsoft.Xna.Framework.Content.Int32Reader")
+        .reader("Microsoft.Xna.Framework.Content.StringReader")
+        .reader(&format!(
+            "Microsoft.Xna.Framework.Content.ListReader`1[[{}]]",
+            STRING
+        ))
+        .reader(&format!(
+            "Microsoft.Xna.Framework.Content.DictionaryReader`2[[{}],[{}]]",
+            STRING, INT
+        ))
+        .reader("Microsoft.Xna.Framework.Content.EnumReader`1[[Game.Data.Kind, Game.Data]]")
+        .object(1)
+        .bytes(&[1])
+        .int(3)
+        .bytes(&[0])
+        .object(4)
+        .string("sword")
+        .object(0)
+        .object(5)
+        .uint(2)
+        .object(4)
+        .string("a")
+        .object(4)
+        .string("b")
+        .object(6)
+        .uint(1)
+        .object(4)
+        .string("hp")
+        .int(10)
+        .int(1)
+        .build();
+    assert_eq!(bytes, expected);
+
+    // Nothing in the file describes the members, so read them as declared.
+    let mut registry = ReaderRegistry::new();
+    registry.register(REFLECTIVE_READER, |rdr, readers, _| {
+        let mut fields = Fields::new(rdr, readers);
+        let nullable = |v: Option<i32>| Value::Nullable(v.map(|v| Box::new(Value::Int(v.into()))));
+        let string = |v: Option<String>| v.map_or(Value::Null, Value::String);
+        let fields = vec![
+            ("count".to_string(), nullable(fields.nullable()?)),
+            ("limit".to_string(), nullable(fields.nullable()?)),
+            ("label".to_string(), string(fields.optional_object()?)),
+            ("note".to_string(), string(fields.optional_object()?)),
+            ("tags".to_string(), fields.object::<Value>()?),
+            ("stats".to_string(), fields.object::<Value>()?),
+            (
+                "kind".to_string(),
+                Value::Int(fields.value::<i32>()?.into()),
+            ),
+        ];
+        Ok(Value::Object {
+            reader: REFLECTIVE_READER.to_string(),
+            fields,
+        })
+    });
+    let options = ParseOptions::new().registry(registry);
+    let xnb = XNB::<Value>::from_buffer_with_options(&mut &bytes[..], &options).unwrap();
+    assert_eq!(xnb.readers.len(), 7);
+    assert!(xnb.warnings.is_empty());
+    let item = match xnb.primary {
+        Value::Other { ref value, .. } => value.downcast_ref::<Value>().unwrap(),
+        ref other => panic!("{:?}", other),
+    };
+    assert_eq!(item.field("count").and_then(Value::as_i64), Some(3));
+    assert!(item.field("limit").unwrap().is_null());
+    assert_eq!(item.field("label").and_then(Value::as_str), Some("sword"));
+    assert!(item.field("note").unwrap().is_null());
+    match item.field("tags") {
+        Some(Value::Array(tags)) => {
+            let tags: Vec<_> = tags.iter().filter_map(Value::as_str).collect();
+            assert_eq!(tags, ["a", "b"]);
+        }
+        other => panic!("{:?}", other),
+    }
+    let stats = item.field("stats").unwrap();
+    assert_eq!(stats.field("hp").and_then(Value::as_i64), Some(10));
+    assert_eq!(item.field("kind").and_then(Value::as_i64), Some(1));
+}
@@ -0,0 +1,101 @@
+use std::rc::Rc;
+use xnb::testing::XnbFixture;
+use xnb::{Error, ParseOptions, ReaderRegistry, Shared, SharedResource, XNB};
+
+const HOLDER_READER: &str = "Game.HolderReader";
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+const INT_READER: &str = "Microsoft.Xna.Framework.Content.Int32Reader";
+
+// An object of a game's own type holding two references to shared strings.
+struct Holder {
+    first: Shared<String>,
+    second: Shared<String>,
+    resolved_while_reading: bool,
+}
+
+fn registry() -> ReaderRegistry {
+    let mut registry = ReaderRegistry::new();
+    registry.register(HOLDER_READER, |rdr, readers, _| {
+        let first = readers.read_shared::<String>(rdr)?.unwrap();
+        let second = readers.read_shared::<String>(rdr)?.unwrap();
+        Ok(Holder {
+            resolved_while_reading: first.get().is_some(),
+            first,
+            second,
+        })
+    });
+    registry
+}
+
+fn read(fixture: XnbFixture) -> Result<XNB<SharedResource>, Error> {
+    let options = ParseOptions::new().registry(registry());
+    XNB::from_buffer_with_options(&mut &fixture.build()[..], &options)
+}
+
+#[test]
+fn references_resolve_to_one_rc() {
+    let xnb = read(
+        XnbFixture::new()
+            .reader(HOLDER_READER)
+            .reader(STRING_READER)
+            .shared_resources(2)
+            .object(1)
+            // Both references are to the second resource, counting from 1.
+            .bytes(&[2, 2])
+            .object(2)
+            .string("first")
+            .object(2)
+            .string("second"),
+    )
+    .unwrap();
+    let holder = xnb.primary.downcast_ref::<Holder>().unwrap();
+
+    // References are only filled in once the shared resources are read.
+    assert!(!holder.resolved_while_reading);
+    assert_eq!((holder.first.index(), holder.second.index()), (1, 1));
+    assert_eq!(*holder.first, "second");
+    assert!(Rc::ptr_eq(&holder.first.rc(), &holder.second.rc()));
+    let resource = xnb.shared_resource::<String>(1).unwrap();
+    assert!(Rc::ptr_eq(&holder.first.rc(), &resource));
+    assert_eq!(*xnb.shared_resource::<String>(0).unwrap(), "first");
+}
+
+// A holder referring to the resources `refs`, followed by a null resource, a
+// string and an integer.
+fn holder(refs: [u8; 2]) -> XnbFixture {
+    XnbFixture::new()
+        .reader(HOLDER_READER)
+        .reader(STRING_READER)
+        .reader(INT_READER)
+        .shared_resources(3)
+        .object(1)
+        .bytes(&refs)
+        .object(0)
+        .object(2)
+        .string("text")
+        .object(3)
+        .int(7)
+}
+
+#[test]
+fn shared_resource_section() {
+    let xnb = read(holder([2, 2])).unwrap();
+    assert_eq!(xnb.shared.len(), 3);
+    assert!(xnb.shared[0].is_none());
+    assert_eq!(*xnb.shared_resource::<String>(1).unwrap(), "text");
+    assert_eq!(*xnb.shared_resource::<i32>(2).unwrap(), 7);
+    assert!(xnb.shared_resource::<String>(2).is_none());
+    assert!(xnb.shared_resource::<String>(3).is_none());
+}
+
+#[test]
+fn mismatched_references() {
+    // A string reference to an integer, to the null resource and past the
+    // last resource.
+    for &(refs, index) in &[([2, 3], 2), ([1, 2], 0), ([2, 4], 3)] {
+        match read(holder(refs)) {
+            Err(Error::SharedResourceMismatch(i)) => assert_eq!(i, index),
+            result => panic!("{:?}", result.map(|xnb| xnb.shared)),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use xnb::testing::XnbFixture;
+use xnb::{Parse, ParseOptions, Texture2d, Value, XNB};
+
+// Counts the bytes read through it.
+struct Counting<R> {
+    rdr: R,
+    read: usize,
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.rdr.read(buf)?;
+        self.read += n;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Counting<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.rdr.seek(pos)
+    }
+}
+
+const DATA_LEN: usize = 64 * 64 * 4;
+
+// A 64x64 texture with two mip levels, followed by a shared string.
+fn texture() -> XnbFixture {
+    XnbFixture::new()
+        .reader(Texture2d::READER)
+        .reader("Microsoft.Xna.Framework.Content.StringReader")
+        .shared_resources(1)
+        .object(1)
+        .uint(0)
+        .uint(64)
+        .uint(64)
+        .uint(2)
+        .uint(DATA_LEN as u32)
+        .bytes(&[1; DATA_LEN])
+        .uint(DATA_LEN as u32 / 4)
+        .bytes(&[2; DATA_LEN / 4])
+        .object(2)
+        .string("after")
+}
+
+fn check(xnb: &XNB<Texture2d>) {
+    assert!(xnb.warnings.is_empty(), "{:?}", xnb.warnings);
+    assert_eq!((xnb.primary.width, xnb.primary.height), (64, 64));
+    assert_eq!(xnb.primary.mip_data.len(), 2);
+    assert!(xnb.primary.mip_data.iter().all(Vec::is_empty));
+    assert_eq!(*xnb.shared_resource::<String>(0).unwrap(), "after");
+}
+
+#[test]
+fn skip_while_reading() {
+    let options = ParseOptions::new().skip_data(true);
+    let bytes = texture().build();
+    check(&XNB::<Texture2d>::from_buffer_with_options(&mut &bytes[..], &options).unwrap());
+
+    let value = XNB::<Value>::from_buffer_with_options(&mut &bytes[..], &options).unwrap();
+    let texture = value.primary.as_texture().unwrap();
+    assert!(texture.mip_data.iter().all(Vec::is_empty));
+    assert_eq!(
+        value.shared[0]
+            .as_ref()
+            .unwrap()
+            .downcast_ref::<String>()
+            .unwrap(),
+        "after"
+    );
+}
+
+#[test]
+fn skip_by_seeking() {
+    let options = ParseOptions::new().skip_data(true);
+    let bytes = texture().build();
+    let mut rdr = Counting {
+        rdr: Cursor::new(&bytes),
+        read: 0,
+    };
+    check(&XNB::<Texture2d>::from_seekable_with_options(&mut rdr, &options).unwrap());
+    // Everything but the texture data.
+    assert_eq!(rdr.read, bytes.len() - DATA_LEN * 5 / 4);
+
+    // Without skipping, the data is read as usual.
+    rdr.rdr.set_position(0);
+    let xnb = XNB::<Texture2d>::from_seekable_with_options(&mut rdr, &ParseOptions::new()).unwrap();
+    assert_eq!(xnb.primary.mip_data[0], [1; DATA_LEN]);
+    assert_eq!(xnb.primary.mip_data[1], [2; DATA_LEN / 4]);
+}
+
+#[test]
+fn skip_past_the_end() {
+    let options = ParseOptions::new().skip_data(true);
+    let mut bytes = texture().build();
+    bytes.truncate(bytes.len() - DATA_LEN / 2);
+    // Skipped data still has to be there.
+    assert!(XNB::<Texture2d>::from_buffer_with_options(&mut &bytes[..], &options).is_err());
+    assert!(
+        XNB::<Texture2d>::from_seekable_with_options(&mut Cursor::new(&bytes), &options).is_err()
+    );
+}
@@ -0,0 +1,196 @@
+use xnb::testing::fixture;
+use xnb::{
+    Alignment, BmFontFormat, LayoutOptions, Rectangle, SpriteFont, SurfaceFormat, Texture2d,
+    Vector3, XNB,
+};
+
+fn font(default: Option<char>) -> SpriteFont {
+    SpriteFont {
+        texture: Texture2d {
+            format: SurfaceFormat::Color,
+            width: 1,
+            height: 1,
+            mip_data: vec![vec![0xFF; 4]],
+        },
+        glyphs: vec![Rectangle {
+            x: 0,
+            y: 0,
+            w: 1,
+            h: 1,
+        }],
+        cropping: vec![Rectangle {
+            x: 0,
+            y: 0,
+            w: 1,
+            h: 1,
+        }],
+        char_map: vec!['é'],
+        v_spacing: 12,
+        h_spacing: 1.5,
+        kerning: vec![],
+        default,
+    }
+}
+
+#[test]
+fn non_ascii_default_character() {
+    for &default in &[None, Some('?'), Some('é'), Some('□'), Some('😀')] {
+        let bytes = fixture(&font(default)).unwrap();
+        let xnb = XNB::<SpriteFont>::from_buffer(&mut &bytes[..]).unwrap();
+        assert_eq!(xnb.primary.default, default);
+        assert_eq!(xnb.primary.char_map, vec!['é']);
+        assert!(xnb.warnings.is_empty());
+    }
+}
+
+#[test]
+fn glyphs_and_measuring() {
+    let mut font = font(Some('é'));
+    font.char_map.push('x');
+    font.glyphs.push(Rectangle {
+        x: 1,
+        y: 0,
+        w: 2,
+        h: 3,
+    });
+    font.cropping.push(Rectangle {
+        x: 0,
+        y: 1,
+        w: 2,
+        h: 14,
+    });
+    font.kerning = vec![Default::default(); 2];
+
+    let glyph = font.glyph('x').unwrap();
+    assert_eq!((glyph.source.x, glyph.cropping.h), (1, 14));
+    assert!(font.glyph('y').is_none());
+
+    // Missing characters are measured as the default, and the second line
+    // is as tall as its tallest glyph.
+    assert_eq!(font.measure_string("ée"), (1.5, 12.0));
+    assert_eq!(font.measure_string("é\r\nxx"), (1.5, 26.0));
+    assert_eq!(font.measure_string(""), (0.0, 12.0));
+
+    // Bearings and widths come from the kerning; a negative left bearing
+    // only counts after the first glyph on a line.
+    font.kerning[1] = Vector3::new(-1.0, 4.0, 2.0);
+    assert_eq!(font.glyph('x').unwrap().left_side_bearing, -1.0);
+    assert_eq!(font.measure_string("x"), (6.0, 14.0));
+    assert_eq!(font.measure_string("éx"), (6.5, 14.0));
+}
+
+#[test]
+fn layout() {
+    let mut font = font(None);
+    font.cropping[0].y = 2;
+    let placed = font.layout("éé\néq");
+    let positions: Vec<_> = placed.iter().map(|p| (p.x, p.y)).collect();
+    assert_eq!(positions, [(0.0, 2.0), (1.5, 2.0), (0.0, 14.0)]);
+    assert_eq!(placed[1].glyph.source, font.glyphs[0]);
+}
+
+#[test]
+fn wrapping_and_alignment() {
+    let mut font = font(None);
+    font.kerning = vec![Default::default()];
+    font.h_spacing = 2.0;
+    // Glyphs have no width, so each é after the first on a line moves on by
+    // the spacing, and the font has no space. "éé éé" is 6 wide.
+    let options = LayoutOptions::new().max_width(5.0);
+    let placed = font.layout_with("éé éé\né", &options);
+    let positions: Vec<_> = placed.iter().map(|p| (p.x, p.y)).collect();
+    assert_eq!(
+        positions,
+        [
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (0.0, 12.0),
+            (2.0, 12.0),
+            (0.0, 24.0)
+        ]
+    );
+    assert_eq!(font.measure_string_with("éé éé\né", &options), (2.0, 36.0));
+
+    // Words too long for a line are broken between characters.
+    let placed = font.layout_with("éééé", &LayoutOptions::new().max_width(3.0));
+    assert_eq!(placed[2].y, 12.0);
+
+    let options = LayoutOptions::new()
+        .max_width(10.0)
+        .alignment(Alignment::Right);
+    let placed = font.layout_with("éé\né", &options);
+    assert_eq!((placed[0].x, placed[2].x), (8.0, 10.0));
+    let options = options.alignment(Alignment::Center);
+    assert_eq!(font.layout_with("é", &options)[0].x, 5.0);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn render() {
+    // A 2x1 atlas with a half transparent (premultiplied) white glyph.
+    let mut font = font(None);
+    font.texture = Texture2d {
+        format: SurfaceFormat::Color,
+        width: 2,
+        height: 1,
+        mip_data: vec![vec![0, 0, 0, 0, 128, 128, 128, 128]],
+    };
+    font.glyphs[0].x = 1;
+    font.kerning = vec![Default::default()];
+    let image = font.render("éé").unwrap();
+    assert_eq!(image.dimensions(), (3, 12));
+    assert_eq!(image.get_pixel(0, 0).0, [255, 255, 255, 128]);
+    assert_eq!(image.get_pixel(1, 0).0, [0, 0, 0, 0]);
+    assert_eq!(image.get_pixel(2, 0).0, [255, 255, 255, 128]);
+}
+
+#[test]
+fn bmfont() {
+    let mut font = font(None);
+    font.kerning = vec![Default::default()];
+    let mut text = vec![];
+    font.write_bmfont(&mut text, "font_0.png", BmFontFormat::Text)
+        .unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.contains("common lineHeight=12 base=12 scaleW=1 scaleH=1"));
+    assert!(text.contains("page id=0 file=\"font_0.png\""));
+    assert!(text.contains(
+        "char id=233 x=0 y=0 width=1 height=1 xoffset=0 yoffset=0 xadvance=2 page=0 chnl=15"
+    ));
+
+    let mut xml = vec![];
+    font.write_bmfont(&mut xml, "a&b.png", BmFontFormat::Xml)
+        .unwrap();
+    let xml = String::from_utf8(xml).unwrap();
+    assert!(xml.contains("<page id=\"0\" file=\"a&amp;b.png\"/>"));
+    assert!(xml.contains("<char id=\"233\" x=\"0\""));
+    assert!(xml.trim_end().ends_with("</chars>\n</font>"));
+}
+
+#[test]
+fn repack() {
+    let mut font = font(None);
+    let mut pixels = vec![0; 4 * 3 * 4];
+    pixels[(4 + 1) * 4..(4 + 3) * 4].copy_from_slice(&[9; 8]);
+    font.texture = Texture2d {
+        format: SurfaceFormat::Color,
+        width: 4,
+        height: 3,
+        mip_data: vec![pixels],
+    };
+    font.glyphs[0] = Rectangle::from((0, 0, 4, 3));
+    font.cropping[0] = Rectangle::from((1, 2, 4, 3));
+    font.char_map.push(' ');
+    font.glyphs.push(Rectangle::from((0, 0, 1, 1)));
+    font.cropping.push(Rectangle::from((0, 0, 1, 1)));
+
+    let packed = font.repack(1).unwrap();
+    assert_eq!(packed.glyphs[0], Rectangle::from((0, 0, 2, 1)));
+    assert_eq!(packed.cropping[0], Rectangle::from((2, 3, 4, 3)));
+    assert!(packed.glyphs[1].is_empty());
+    assert_eq!((packed.texture.width, packed.texture.height), (3, 1));
+    assert_eq!(
+        packed.texture.mip_data[0],
+        [9, 9, 9, 9, 9, 9, 9, 9, 0, 0, 0, 0]
+    );
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use xnb::tide::{
+    format_properties, read_tide, read_tide_with_options, write_tide, Map, PropertyValue, Tile,
+};
+use xnb::{Error, ParseOptions, Rectangle, XnbWriter, XNB};
+
+// Builds tBIN data, prefixed with its length as `read_tide` expects.
+struct Tbin(Vec<u8>);
+
+impl Tbin {
+    fn new() -> Tbin {
+        Tbin(b"tBIN10".to_vec())
+    }
+
+    fn byte(mut self, v: u8) -> Tbin {
+        self.0.push(v);
+        self
+    }
+
+    fn uint(mut self, v: u32) -> Tbin {
+        self.0.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    fn string(self, s: &str) -> Tbin {
+        let mut tbin = self.uint(s.len() as u32);
+        tbin.0.extend_from_slice(s.as_bytes());
+        tbin
+    }
+
+    fn int_property(self, name: &str, v: i32) -> Tbin {
+        self.string(name).byte(1).uint(v as u32)
+    }
+
+    fn build(self) -> Vec<u8> {
+        let mut data = (self.0.len() as u32).to_le_bytes().to_vec();
+        data.extend_from_slice(&self.0);
+        data
+    }
+}
+
+// A map with one 2x2 tilesheet of 16x16 tiles and one 3x2 layer: a static
+// tile at (0, 0), nothing until an animated tile at (2, 0), a gap and a
+// static tile at (2, 1).
+fn map_data() -> Vec<u8> {
+    Tbin::new()
+        .string("map")
+        .string("")
+        .uint(2)
+        .int_property("a", 1)
+        .int_property("a", 2)
+        .uint(1)
+        .string("sheet")
+        .string("")
+        .string("tiles.png")
+        .uint(2)
+        .uint(2)
+        .uint(16)
+        .uint(16)
+        .uint(0)
+        .uint(0)
+        .uint(0)
+        .uint(0)
+        .uint(0)
+        .uint(1)
+        .string("Back")
+        .byte(1)
+        .string("")
+        .uint(3)
+        .uint(2)
+        .uint(16)
+        .uint(16)
+        .uint(0)
+        .byte(b'T')
+        .string("sheet")
+        .byte(b'S')
+        .uint(1)
+        .byte(0)
+        .uint(0)
+        .byte(b'N')
+        .uint(1)
+        .byte(b'A')
+        .uint(250)
+        .uint(2)
+        .byte(b'S')
+        .uint(2)
+        .byte(0)
+        .uint(0)
+        .byte(b'S')
+        .uint(3)
+        .byte(0)
+        .uint(0)
+        .uint(1)
+        .int_property("anim", 7)
+        .byte(b'N')
+        .uint(2)
+        .byte(b'S')
+        .uint(0)
+        .byte(0)
+        .uint(0)
+        .build()
+}
+
+type Props = HashMap<String, PropertyValue>;
+
+#[test]
+fn builtin_properties() {
+    let map: Map<Props, (), Props, Vec<(String, PropertyValue)>> =
+        read_tide(&mut &map_data()[..]).unwrap();
+    assert_eq!(map.properties["a"], PropertyValue::Int(2));
+    assert_eq!(
+        *map.layers[0].tiles[1].properties(),
+        [("anim".to_string(), PropertyValue::Int(7))]
+    );
+    assert_eq!(
+        format_properties(map.layers[0].tiles[1].properties()),
+        "anim = Int(7)\n"
+    );
+}
+
+#[test]
+fn tile_at() {
+    let mut map: Map<(), (), (), ()> = read_tide(&mut &map_data()[..]).unwrap();
+    let layer = &mut map.layers[0];
+    assert_eq!(layer.tile_at(0, 0).unwrap().get_index(0), 1);
+    assert_eq!(layer.tile_at(2, 0).unwrap().get_index(250), 3);
+    assert_eq!(layer.tile_at(2, 1).unwrap().get_pos(), (2, 1));
+    assert!(layer.tile_at(1, 0).is_none());
+    assert!(layer.tile_at(3, 0).is_none());
+
+    if let Some(Tile::Static(tile)) = layer.tile_at_mut(0, 0) {
+        tile.pos = (1, 1);
+    }
+    // A moved tile is no longer where the lookup last put it.
+    assert!(layer.tile_at(0, 0).is_none());
+    assert!(layer
+        .iter_tiles()
+        .all(|(x, y, tile)| tile.get_pos() == (x, y)));
+    layer.reindex();
+    assert!(layer.tile_at(0, 0).is_none());
+    assert_eq!(layer.tile_at(1, 1).unwrap().get_index(0), 1);
+}
+
+#[test]
+fn layer_cell_limit() {
+    let options = ParseOptions::new().max_layer_cells(5);
+    let result = read_tide_with_options::<(), (), (), ()>(&mut &map_data()[..], &options);
+    assert!(matches!(
+        result,
+        Err(Error::LimitExceeded { requested: 6, .. })
+    ));
+    let options = ParseOptions::new().max_layer_cells(6);
+    assert!(read_tide_with_options::<(), (), (), ()>(&mut &map_data()[..], &options).is_ok());
+}
+
+// A map with no tilesheets and a single layer, up to its first cell.
+fn layer(width: u32, height: u32) -> Tbin {
+    Tbin::new()
+        .string("map")
+        .string("")
+        .uint(0)
+        .uint(0)
+        .uint(1)
+        .string("Back")
+        .byte(1)
+        .string("")
+        .uint(width)
+        .uint(height)
+        .uint(16)
+        .uint(16)
+        .uint(0)
+}
+
+#[test]
+fn huge_layer() {
+    // Only the cells with tiles take memory, however big the layer says it is.
+    let data = layer(u32::MAX, 1).byte(b'N').uint(u32::MAX).build();
+    let map: Map<(), (), (), ()> = read_tide(&mut &data[..]).unwrap();
+    assert!(map.layers[0].tiles.is_empty());
+    assert!(map.layers[0].tile_at(u32::MAX - 1, 0).is_none());
+
+    let result =
+        read_tide_with_options::<(), (), (), ()>(&mut &data[..], &ParseOptions::untrusted());
+    assert!(matches!(result, Err(Error::LimitExceeded { .. })));
+}
+
+#[test]
+fn animation_without_frames() {
+    let data = layer(1, 1).byte(b'A').uint(100).uint(0).uint(0).build();
+    let map: Map<(), (), (), ()> = read_tide(&mut &data[..]).unwrap();
+    let tile = map.layers[0].tile_at(0, 0).unwrap();
+    assert_eq!(tile.get_pos(), (0, 0));
+    assert!(tile.frame(250).is_none());
+    assert_eq!(tile.get_index(250), 0);
+    assert_eq!(tile.get_tilesheet(), "");
+}
+
+#[test]
+fn iter_tiles() {
+    let map: Map<(), (), (), Props> = read_tide(&mut &map_data()[..]).unwrap();
+    let layer = &map.layers[0];
+    let cells: Vec<_> = layer.iter_tiles().map(|(x, y, _)| (x, y)).collect();
+    assert_eq!(cells, [(0, 0), (2, 0), (2, 1)]);
+    assert_eq!(layer.tiles_in_sheet("sheet").count(), 3);
+    assert_eq!(layer.tiles_in_sheet("other").count(), 0);
+    let animated: Vec<_> = layer.tiles_with_property("anim").collect();
+    assert_eq!((animated.len(), animated[0].0, animated[0].1), (1, 2, 0));
+}
+
+#[test]
+fn source_rect() {
+    let mut map: Map<(), (), (), ()> = read_tide(&mut &map_data()[..]).unwrap();
+    let sheet = &mut map.tilesheets[0];
+    sheet.margin = (1, 2);
+    sheet.spacing = (3, 4);
+    assert_eq!(sheet.tile_count(), 4);
+    assert_eq!(sheet.source_rect(0), Rectangle::from((1, 2, 16, 16)));
+    assert_eq!(sheet.source_rect(3), Rectangle::from((20, 22, 16, 16)));
+}
+
+#[test]
+fn tmx_export() {
+    let map: Map<Props, (), (), Props> = read_tide(&mut &map_data()[..]).unwrap();
+    let mut tmx = vec![];
+    map.write_tmx(&mut tmx, &|sheet| format!("{}.tsx", sheet.id))
+        .unwrap();
+    let tmx = String::from_utf8(tmx).unwrap();
+    assert!(tmx.contains("width=\"3\" height=\"2\" tilewidth=\"16\" tileheight=\"16\""));
+    assert!(tmx.contains("<property name=\"a\" type=\"int\" value=\"2\"/>"));
+    assert!(tmx.contains("<tileset firstgid=\"1\" source=\"sheet.tsx\"/>"));
+    assert!(tmx.contains("<data encoding=\"csv\">\n2,0,3,\n0,0,1\n  </data>"));
+    assert!(tmx.contains("<object id=\"1\" name=\"TileData\" x=\"32\" y=\"0\""));
+    assert!(tmx.contains("<property name=\"anim\" type=\"int\" value=\"7\"/>"));
+
+    let mut tsx = vec![];
+    map.write_tsx(&map.tilesheets[0], &mut tsx, "tiles.png")
+        .unwrap();
+    let tsx = String::from_utf8(tsx).unwrap();
+    assert!(tsx.contains("tilecount=\"4\" columns=\"2\""));
+    assert!(tsx.contains("<image source=\"tiles.png\" width=\"32\" height=\"32\"/>"));
+    assert!(tsx.contains(
+        "<tile id=\"2\">\n  <animation>\n   <frame tileid=\"2\" duration=\"250\"/>\n   \
+         <frame tileid=\"3\" duration=\"250\"/>"
+    ));
+}
+
+#[test]
+fn tide_round_trip() {
+    type Ordered = Vec<(String, PropertyValue)>;
+    let map: Map<Ordered, Ordered, Ordered, Ordered> = read_tide(&mut &map_data()[..]).unwrap();
+    let mut data = vec![];
+    write_tide(&map, &mut data).unwrap();
+    assert_eq!(data, map_data());
+
+    let bytes = XnbWriter::new().to_bytes(&map).unwrap();
+    let xnb: XNB<Map<Ordered, Ordered, Ordered, Ordered>> =
+        XNB::from_buffer(&mut &bytes[..]).unwrap();
+    let mut data = vec![];
+    write_tide(&xnb.primary, &mut data).unwrap();
+    assert_eq!(data, map_data());
+}
+
+#[cfg(feature = "tmx")]
+#[test]
+fn tmx_round_trip() {
+    type Tmx = Map<Props, Props, Props, Props>;
+    let map: Tmx = read_tide(&mut &map_data()[..]).unwrap();
+    let mut tmx = vec![];
+    map.write_tmx(&mut tmx, &|sheet| format!("{}.tsx", sheet.id))
+        .unwrap();
+    let mut tsx = vec![];
+    map.write_tsx(&map.tilesheets[0], &mut tsx, "tiles.png")
+        .unwrap();
+    let tmx = String::from_utf8(tmx).unwrap();
+    let imported = Tmx::from_tmx(&tmx, &|source| {
+        assert_eq!(source, "sheet.tsx");
+        Ok(String::from_utf8(tsx.clone()).unwrap())
+    })
+    .unwrap();
+
+    assert_eq!(imported.properties, map.properties);
+    let sheet = &imported.tilesheets[0];
+    assert_eq!((&sheet.id[..], &sheet.image_source[..]), ("sheet", "tiles"));
+    assert_eq!((sheet.sheet_size, sheet.tile_size), ((2, 2), (16, 16)));
+    let layer = &imported.layers[0];
+    assert_eq!((layer.size, layer.visible), ((3, 2), true));
+    let cells: Vec<_> = layer
+        .iter_tiles()
+        .map(|(x, y, tile)| (x, y, tile.get_index(0), tile.get_index(250)))
+        .collect();
+    assert_eq!(cells, [(0, 0, 1, 1), (2, 0, 2, 3), (2, 1, 0, 0)]);
+    assert_eq!(
+        layer.tile_at(2, 0).unwrap().properties()["anim"],
+        PropertyValue::Int(7)
+    );
+
+    let csv = tmx.replace("2,0,3,\n0,0,1", "2,0,3,\n0,0");
+    assert!(Tmx::from_tmx(&csv, &|_| Ok(String::new())).is_err());
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn render() {
+    let mut map: Map<(), (), (), ()> = read_tide(&mut &map_data()[..]).unwrap();
+    // Tile 0 is red, 1 green, 2 half transparent blue and 3 transparent.
+    let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 128], [0; 4]];
+    let sheet = image::RgbaImage::from_fn(32, 32, |x, y| {
+        image::Rgba(colors[(y / 16 * 2 + x / 16) as usize])
+    });
+    let image = map.render(0, &|_| Some(&sheet));
+    assert_eq!(image.dimensions(), (48, 32));
+    assert_eq!(image.get_pixel(0, 0).0, [0, 255, 0, 255]);
+    assert_eq!(image.get_pixel(20, 0).0, [0, 0, 0, 0]);
+    assert_eq!(image.get_pixel(40, 15).0, [0, 0, 255, 128]);
+    assert_eq!(image.get_pixel(40, 16).0, [255, 0, 0, 255]);
+    // The animation's second frame is transparent.
+    assert_eq!(
+        map.render(250, &|_| Some(&sheet)).get_pixel(40, 0).0,
+        [0; 4]
+    );
+    assert_eq!(map.render(0, &|_| None).get_pixel(0, 0).0, [0; 4]);
+
+    map.layers[0].visible = false;
+    assert_eq!(map.render(0, &|_| Some(&sheet)).get_pixel(0, 0).0, [0; 4]);
+    let layer = map.render_layer(&map.layers[0], 0, &|_| Some(&sheet));
+    assert_eq!(layer.get_pixel(0, 0).0, [0, 255, 0, 255]);
+}
@@ -0,0 +1,239 @@
+use xnb::testing::XnbFixture;
+use xnb::{Parse, Rectangle, Value, VertexDeclaration, XNB};
+
+// A list of an integer, a `UInt64`, a rectangle, an enum, a vertex
+// declaration and a null.
+fn objects() -> Value {
+    let bytes = XnbFixture::new()
+        .reader("Microsoft.Xna.Framework.Content.ListReader`1[[System.Object, mscorlib]]")
+        .reader("Microsoft.Xna.Framework.Content.Int32Reader")
+        .reader("Microsoft.Xna.Framework.Content.UInt64Reader")
+        .reader(Rectangle::READER)
+        .reader("Microsoft.Xna.Framework.Content.EnumReader`1[[Game.Direction, Game]]")
+        .reader(VertexDeclaration::READER)
+        .object(1)
+        .uint(6)
+        .object(2)
+        .int(-3)
+        .object(3)
+        .bytes(&u64::MAX.to_le_bytes())
+        .object(4)
+        .int(1)
+        .int(2)
+        .int(3)
+        .int(4)
+        .object(5)
+        .int(2)
+        .object(6)
+        .uint(16)
+        .uint(0)
+        .object(0)
+        .build();
+    XNB::<Value>::from_buffer(&mut &bytes[..]).unwrap().primary
+}
+
+#[test]
+fn value_tree() {
+    let value = objects();
+    let items = value.as_array().unwrap();
+    assert_eq!(items.len(), 6);
+    assert_eq!(items[0].as_i64(), Some(-3));
+    assert_eq!(items[0].as_u64(), None);
+    assert!(matches!(items[1], Value::UInt(u64::MAX)));
+    assert_eq!(items[1].as_i64(), None);
+    match &items[2] {
+        Value::Object { reader, fields } => {
+            assert_eq!(reader, Rectangle::READER);
+            let names: Vec<_> = fields.iter().map(|(name, _)| name.as_str()).collect();
+            assert_eq!(names, ["x", "y", "w", "h"]);
+            assert_eq!(items[2].field("h").and_then(Value::as_i64), Some(4));
+        }
+        item => panic!("{:?}", item),
+    }
+    // Enums are read as their underlying integer.
+    assert_eq!(items[3].as_i64(), Some(2));
+    // Assets with no breakdown are kept whole.
+    match &items[4] {
+        Value::Other { reader, value } => {
+            assert_eq!(reader, VertexDeclaration::READER);
+            let declaration = value.0.downcast_ref::<VertexDeclaration>().unwrap();
+            assert_eq!(declaration.stride, 16);
+        }
+        item => panic!("{:?}", item),
+    }
+    assert!(items[5].is_null());
+}
+
+fn string(s: &str) -> Value {
+    Value::String(s.to_string())
+}
+
+// A cut-down map: `layers[0].tiles[1].properties` holds `Passable`, and
+// `names` is keyed by integers, some negative.
+fn map() -> Value {
+    let tile = |passable: bool| Value::Object {
+        reader: String::new(),
+        fields: vec![(
+            "properties".to_string(),
+            Value::Dictionary(vec![
+                (string("Passable"), Value::Bool(passable)),
+                (string("odd.key"), Value::Int(7)),
+            ]),
+        )],
+    };
+    Value::Object {
+        reader: "xTile.Pipeline.TideReader".to_string(),
+        fields: vec![
+            (
+                "layers".to_string(),
+                Value::Array(vec![Value::Object {
+                    reader: String::new(),
+                    fields: vec![(
+                        "tiles".to_string(),
+                        Value::Array(vec![tile(false), tile(true)]),
+                    )],
+                }]),
+            ),
+            (
+                "names".to_string(),
+                Value::Dictionary(vec![
+                    (Value::Int(-1), string("none")),
+                    (Value::Int(3), string("three")),
+                ]),
+            ),
+            (
+                "size".to_string(),
+                Value::Nullable(Some(Box::new(Value::Array(vec![
+                    Value::Int(40),
+                    Value::Int(-30),
+                ])))),
+            ),
+            ("spawn".to_string(), Value::Nullable(None)),
+        ],
+    }
+}
+
+#[test]
+fn paths() {
+    let map = map();
+    assert_eq!(
+        map.get_as::<bool>("layers[0].tiles[1].properties.Passable"),
+        Some(true)
+    );
+    assert_eq!(
+        map.get_as::<bool>("layers[0].tiles[0].properties[\"Passable\"]"),
+        Some(false)
+    );
+    assert_eq!(
+        map.get_as::<i32>("layers[0].tiles[0].properties[\"odd.key\"]"),
+        Some(7)
+    );
+    assert_eq!(
+        map.get_as::<i32>("layers[ 0 ].tiles[0].properties[\"odd.key\"]"),
+        Some(7)
+    );
+    assert!(std::ptr::eq(map.get("").unwrap(), &map));
+
+    // Negative indices only select integer dictionary keys.
+    assert_eq!(map.get_as::<String>("names[-1]").as_deref(), Some("none"));
+    assert_eq!(map.get_as::<String>("names[3]").as_deref(), Some("three"));
+    assert!(map.get("names[0]").is_none());
+    assert!(map.get("layers[-1]").is_none());
+
+    // Nullables with a value are looked through.
+    assert_eq!(map.get_as::<i32>("size[1]"), Some(-30));
+    assert_eq!(map.get_as::<Vec<i64>>("size"), Some(vec![40, -30]));
+    assert!(map.get("spawn").unwrap().is_null());
+    assert_eq!(map.get_as::<Option<i32>>("spawn"), Some(None));
+    assert!(map.get("spawn.x").is_none());
+}
+
+#[test]
+fn missing_and_malformed_paths() {
+    let map = map();
+    for path in &[
+        "missing",
+        "layers[1]",
+        "layers[0].missing",
+        "layers[0].tiles[1].properties.Missing",
+        "layers.tiles",
+        "names.none",
+        ".layers",
+        "layers.",
+        "layers..tiles",
+        "layers[0",
+        "layers[x]",
+        "layers[0]tiles",
+        "layers[0].tiles[1].properties[\"Passable",
+    ] {
+        assert!(map.get(path).is_none(), "{}", path);
+    }
+}
+
+#[test]
+fn typed_results() {
+    let map = map();
+    // Out of range or of another type.
+    assert_eq!(map.get_as::<u8>("size[1]"), None);
+    assert_eq!(map.get_as::<u32>("size[0]"), Some(40));
+    assert_eq!(map.get_as::<f32>("size[0]"), Some(40.0));
+    assert_eq!(map.get_as::<String>("size[0]"), None);
+    assert_eq!(map.get_as::<bool>("names[3]"), None);
+    assert_eq!(map.get_as::<Vec<String>>("size"), None);
+    assert_eq!(
+        map.get_as::<Option<String>>("names[3]"),
+        Some(Some("three".to_string()))
+    );
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn json_escaping() {
+    let value = Value::Dictionary(vec![
+        (string("quote\"key"), string("back\\slash")),
+        (string("line\nbreak"), string("tab\tand \u{1}")),
+        (string("é"), Value::Char('"')),
+    ]);
+    assert_eq!(
+        value.to_json().to_string(),
+        r#"{"line\nbreak":"tab\tand \u0001","quote\"key":"back\\slash","é":"\""}"#
+    );
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn json_shapes() {
+    use std::rc::Rc;
+    use xnb::{SurfaceFormat, Texture2d};
+
+    // Dictionaries whose keys can't be strings become lists of pairs.
+    let value = Value::Dictionary(vec![(Value::Float(0.5), Value::Float(f64::NAN))]);
+    assert_eq!(value.to_json().to_string(), "[[0.5,null]]");
+
+    let value = Value::Object {
+        reader: "Game.Reader".to_string(),
+        fields: vec![
+            (
+                "id".to_string(),
+                Value::Nullable(Some(Box::new(Value::Int(-1)))),
+            ),
+            ("name".to_string(), Value::Nullable(None)),
+            (
+                "texture".to_string(),
+                Value::Texture(Rc::new(Texture2d {
+                    format: SurfaceFormat::Alpha8,
+                    width: 2,
+                    height: 1,
+                    mip_data: vec![vec![1, 2, 3, 4], vec![0xff]],
+                })),
+            ),
+        ],
+    };
+    assert_eq!(
+        value.to_json().to_string(),
+        concat!(
+            r#"{"$reader":"Game.Reader","id":-1,"name":null,"#,
+            r#""texture":{"format":"Alpha8","height":1,"mips":["AQIDBA==","/w=="],"width":2}}"#
+        )
+    );
+}
@@ -0,0 +1,104 @@
+use xnb::testing::XnbFixture;
+use xnb::{
+    Error, IndexBuffer, Parse, VertexBuffer, VertexDeclaration, VertexElementFormat,
+    VertexElementUsage, XNB,
+};
+
+// A declaration with a stride of 16, and elements given as offset, format,
+// usage and usage index.
+fn declaration(fixture: XnbFixture, elements: &[[u32; 4]]) -> XnbFixture {
+    let mut fixture = fixture.uint(16).uint(elements.len() as u32);
+    for element in elements {
+        for &v in element {
+            fixture = fixture.uint(v);
+        }
+    }
+    fixture
+}
+
+fn parse<T: Parse>(fixture: XnbFixture) -> Result<T, Error> {
+    match XNB::<T>::from_buffer(&mut &fixture.build()[..]) {
+        Ok(xnb) => Ok(xnb.primary),
+        Err(Error::Parse { error, .. }) => Err(*error),
+        Err(error) => Err(error),
+    }
+}
+
+// A Vector3 position and a color.
+const ELEMENTS: &[[u32; 4]] = &[[0, 2, 0, 0], [12, 4, 1, 0]];
+
+#[test]
+fn vertex_declaration() {
+    let fixture = XnbFixture::new()
+        .reader(VertexDeclaration::READER)
+        .object(1);
+    let declaration: VertexDeclaration = parse(declaration(fixture, ELEMENTS)).unwrap();
+    assert_eq!(declaration.stride, 16);
+    let color = declaration.element(VertexElementUsage::Color, 0).unwrap();
+    assert_eq!(color.offset, 12);
+    assert_eq!(color.format, VertexElementFormat::Color);
+    assert_eq!(color.format.size(), 4);
+    assert!(declaration.element(VertexElementUsage::Color, 1).is_none());
+}
+
+#[test]
+fn unknown_formats_and_usages() {
+    let start = || {
+        XnbFixture::new()
+            .reader(VertexDeclaration::READER)
+            .object(1)
+    };
+    let result = parse::<VertexDeclaration>(declaration(start(), &[[0, 12, 0, 0]]));
+    assert!(matches!(
+        result,
+        Err(Error::UnrecognizedVertexElementFormat(12))
+    ));
+    let result = parse::<VertexDeclaration>(declaration(start(), &[[0, 2, 13, 0]]));
+    assert!(matches!(
+        result,
+        Err(Error::UnrecognizedVertexElementUsage(13))
+    ));
+}
+
+#[test]
+fn vertex_buffer() {
+    let fixture = XnbFixture::new().reader(VertexBuffer::READER).object(1);
+    let data: Vec<u8> = (0..32).collect();
+    let fixture = declaration(fixture, ELEMENTS).uint(2).bytes(&data);
+    let buffer: VertexBuffer = parse(fixture).unwrap();
+    assert_eq!(buffer.vertex_count, 2);
+    assert_eq!(buffer.vertex(0), Some(&data[..16]));
+    assert_eq!(buffer.vertex(1), Some(&data[16..]));
+    assert_eq!(buffer.vertex(2), None);
+    assert_eq!(buffer.vertex(usize::MAX), None);
+    assert_eq!(buffer.vertex(usize::MAX / 16), None);
+}
+
+#[test]
+fn index_buffers() {
+    let indices = [0x0102u16, 0x0304, 0xFFFF];
+    let mut data = vec![];
+    for i in &indices {
+        data.extend_from_slice(&i.to_le_bytes());
+    }
+    let fixture = XnbFixture::new()
+        .reader(IndexBuffer::READER)
+        .object(1)
+        .bytes(&[1])
+        .uint(data.len() as u32)
+        .bytes(&data);
+    let buffer: IndexBuffer = parse(fixture).unwrap();
+    assert!(buffer.sixteen_bit);
+    assert_eq!(buffer.indices(), [0x0102, 0x0304, 0xFFFF]);
+
+    // The same bytes as 32-bit indices, leaving out the incomplete last one.
+    let fixture = XnbFixture::new()
+        .reader(IndexBuffer::READER)
+        .object(1)
+        .bytes(&[0])
+        .uint(data.len() as u32)
+        .bytes(&data);
+    let buffer: IndexBuffer = parse(fixture).unwrap();
+    assert!(!buffer.sixteen_bit);
+    assert_eq!(buffer.indices(), [0x0304_0102]);
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+use std::time::Duration;
+use xnb::testing::XnbFixture;
+use xnb::{Error, Parse, Video, VideoSoundtrackType, XNB};
+
+// Every field is an object with its own reader.
+fn video(soundtrack_type: i32) -> Vec<u8> {
+    XnbFixture::new()
+        .reader(Video::READER)
+        .reader("Microsoft.Xna.Framework.Content.StringReader")
+        .reader("Microsoft.Xna.Framework.Content.Int32Reader")
+        .reader("Microsoft.Xna.Framework.Content.SingleReader")
+        .object(1)
+        .object(2)
+        .string("intro\\logo.wmv")
+        .object(3)
+        .int(2500)
+        .object(3)
+        .int(1280)
+        .object(3)
+        .int(720)
+        .object(4)
+        .float(29.97)
+        .object(3)
+        .int(soundtrack_type)
+        .build()
+}
+
+#[test]
+fn video_fields() {
+    let video = XNB::<Video>::from_buffer(&mut &video(2)[..])
+        .unwrap()
+        .primary;
+    assert_eq!(video.filename, "intro\\logo.wmv");
+    assert_eq!(video.duration, Duration::from_millis(2500));
+    assert_eq!((video.width, video.height), (1280, 720));
+    assert_eq!(video.frames_per_second, 29.97);
+    assert_eq!(video.soundtrack_type, VideoSoundtrackType::MusicAndDialog);
+    assert_eq!(
+        video.path(Path::new("Content/Videos/intro.xnb")),
+        Path::new("Content/Videos/intro/logo.wmv")
+    );
+}
+
+#[test]
+fn unknown_soundtrack_type() {
+    let result = XNB::<Video>::from_buffer(&mut &video(3)[..]);
+    let error = match result {
+        Err(Error::Parse { error, .. }) => *error,
+        result => panic!("{:?}", result.map(|xnb| xnb.primary)),
+    };
+    assert!(matches!(error, Error::UnrecognizedSoundtrackType(3)));
+}
@@ -0,0 +1,282 @@
+//! Assets written with `XnbWriter` must read back as they were, and writing
+//! what was read must give the same bytes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::time::Duration;
+use xnb::{
+    Array, DateTime, DateTimeKind, Decimal, Dictionary, Error, Parse, Rectangle, Serialize,
+    SpriteFont, SurfaceFormat, Texture2d, TimeSpan, Vector3, XnbWriter, XNB,
+};
+
+fn round_trip<T: Serialize + Parse>(asset: &T) -> XNB<T> {
+    let bytes = XnbWriter::new().to_bytes(asset).unwrap();
+    let xnb = XNB::<T>::from_buffer(&mut &bytes[..]).unwrap();
+    assert!(xnb.warnings.is_empty());
+    assert_eq!(XnbWriter::new().to_bytes(&xnb.primary).unwrap(), bytes);
+    xnb
+}
+
+fn assert_round_trip<T: Serialize + Parse + PartialEq + Debug>(asset: T) {
+    assert_eq!(round_trip(&asset).primary, asset);
+}
+
+#[test]
+fn primitives() {
+    assert_round_trip(-7i32);
+    assert_round_trip(200u8);
+    assert_round_trip(true);
+    assert_round_trip('é');
+    assert_round_trip(String::new());
+    assert_round_trip("hello, world".to_string());
+    assert_round_trip(Rectangle::from((-1, 2, 30, 40)));
+    assert_round_trip(Vector3::new(0.5, -1.0, 2.25));
+
+    let xnb = round_trip(&"hello".to_string());
+    let readers: Vec<_> = xnb.readers.iter().map(|r| r.name()).collect();
+    assert_eq!(readers, ["Microsoft.Xna.Framework.Content.StringReader"]);
+}
+
+#[test]
+fn header() {
+    let bytes = XnbWriter::new()
+        .target('x')
+        .hidef(true)
+        .to_bytes(&1i32)
+        .unwrap();
+    assert_eq!(&bytes[..6], b"XNBx\x05\x01");
+    assert_eq!(&bytes[6..10], &(bytes.len() as u32).to_le_bytes());
+    assert_eq!(XNB::<i32>::from_buffer(&mut &bytes[..]).unwrap().primary, 1);
+}
+
+fn assert_textures_eq(a: &Texture2d, b: &Texture2d) {
+    assert_eq!((a.format, a.width, a.height), (b.format, b.width, b.height));
+    assert_eq!(a.mip_data, b.mip_data);
+}
+
+#[test]
+fn textures_with_mips() {
+    let color = Texture2d {
+        format: SurfaceFormat::Color,
+        width: 4,
+        height: 2,
+        mip_data: vec![(0..32).collect(), (100..108).collect(), vec![1, 2, 3, 4]],
+    };
+    assert_textures_eq(&round_trip(&color).primary, &color);
+
+    // Compressed mip levels are at least one block.
+    let dxt1 = Texture2d {
+        format: SurfaceFormat::Dxt1,
+        width: 8,
+        height: 8,
+        mip_data: vec![vec![0x11; 32], vec![0x22; 8], vec![0x33; 8], vec![0x44; 8]],
+    };
+    assert_textures_eq(&round_trip(&dxt1).primary, &dxt1);
+}
+
+fn font(default: Option<char>) -> SpriteFont {
+    SpriteFont {
+        texture: Texture2d {
+            format: SurfaceFormat::Color,
+            width: 2,
+            height: 2,
+            mip_data: vec![(0..16).collect(), vec![9; 4]],
+        },
+        glyphs: vec![Rectangle::from((0, 0, 1, 2)), Rectangle::from((1, 0, 1, 2))],
+        cropping: vec![
+            Rectangle::from((0, 1, 1, 12)),
+            Rectangle::from((-1, 0, 2, 14)),
+        ],
+        char_map: vec!['a', '😀'],
+        v_spacing: 14,
+        h_spacing: -0.5,
+        kerning: vec![Vector3::new(0.0, 1.0, 0.5), Vector3::new(1.0, 2.0, -1.0)],
+        default,
+    }
+}
+
+#[test]
+fn sprite_fonts() {
+    for &default in &[Some('?'), None] {
+        let font = font(default);
+        let xnb = round_trip(&font);
+        let read = &xnb.primary;
+        assert_textures_eq(&read.texture, &font.texture);
+        assert_eq!(read.glyphs, font.glyphs);
+        assert_eq!(read.cropping, font.cropping);
+        assert_eq!(read.char_map, font.char_map);
+        assert_eq!(read.v_spacing, font.v_spacing);
+        assert_eq!(read.h_spacing, font.h_spacing);
+        assert_eq!(read.kerning, font.kerning);
+        assert_eq!(read.default, font.default);
+    }
+}
+
+const MSCORLIB: &str =
+    "mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089";
+const INT: &str =
+    "System.Int32, mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089";
+
+fn reader_names<T>(xnb: &XNB<T>) -> Vec<String> {
+    xnb.readers.iter().map(|r| r.name().to_string()).collect()
+}
+
+#[test]
+fn lists_and_arrays() {
+    // Value type members are written inline, object members with a reader id.
+    assert_round_trip(Vec::<i32>::new());
+    assert_round_trip(vec![1i32, -2, 3]);
+    assert_round_trip(vec![String::new(), "a".to_string(), "bc".to_string()]);
+    assert_round_trip(vec![vec![1i32], vec![], vec![2, 3]]);
+    assert_round_trip(Array::from(vec![
+        Vector3::new(1.0, 2.0, 3.0),
+        Vector3::new(-1.0, 0.0, 0.5),
+    ]));
+    assert_round_trip(Array::from(vec!["x".to_string(), "y".to_string()]));
+
+    let xnb = round_trip(&vec![vec![1i32]]);
+    let list = format!("System.Collections.Generic.List`1[[{}]], {}", INT, MSCORLIB);
+    assert_eq!(
+        reader_names(&xnb)[..2],
+        [
+            format!("Microsoft.Xna.Framework.Content.ListReader`1[[{}]]", list),
+            format!("Microsoft.Xna.Framework.Content.ListReader`1[[{}]]", INT),
+        ]
+    );
+}
+
+#[test]
+fn dictionaries() {
+    // Entries are written in iteration order, so only maps with a fixed order
+    // can hold more than one and still rewrite to the same bytes.
+    let map: HashMap<i32, String> = vec![(7, "seven".to_string())].into_iter().collect();
+    assert_round_trip(map);
+    assert_round_trip(HashMap::<String, i32>::new());
+
+    let map: BTreeMap<String, Rectangle> = vec![
+        ("a".to_string(), Rectangle::from((0, 0, 1, 1))),
+        ("b".to_string(), Rectangle::from((1, 2, 3, 4))),
+    ]
+    .into_iter()
+    .collect();
+    assert_round_trip(map);
+
+    let map: BTreeMap<i32, Vec<String>> = vec![
+        (1, vec!["one".to_string()]),
+        (2, vec!["two".to_string(), "deux".to_string()]),
+    ]
+    .into_iter()
+    .collect();
+    assert_round_trip(map);
+
+    let dictionary = Dictionary {
+        map: vec![("list".to_string(), vec![1i32, 2])]
+            .into_iter()
+            .collect(),
+    };
+    let xnb = round_trip(&dictionary);
+    assert_eq!(xnb.primary.map, dictionary.map);
+    assert_eq!(
+        reader_names(&xnb)[1..],
+        [
+            "Microsoft.Xna.Framework.Content.StringReader".to_string(),
+            format!("Microsoft.Xna.Framework.Content.ListReader`1[[{}]]", INT),
+            "Microsoft.Xna.Framework.Content.Int32Reader".to_string(),
+        ]
+    );
+}
+
+// The last `len` bytes written for `asset`.
+fn tail<T: Serialize>(asset: &T, len: usize) -> Vec<u8> {
+    let bytes = XnbWriter::new().to_bytes(asset).unwrap();
+    bytes[bytes.len() - len..].to_vec()
+}
+
+#[test]
+fn date_times() {
+    // The kind is packed into the top two bits of the ticks.
+    for &(kind, bits) in &[
+        (DateTimeKind::Unspecified, 0u64),
+        (DateTimeKind::Utc, 1),
+        (DateTimeKind::Local, 2),
+    ] {
+        let date = DateTime {
+            ticks: 630_822_816_000_000_000,
+            kind,
+        };
+        assert_round_trip(date);
+        let packed = date.ticks | bits << 62;
+        assert_eq!(tail(&date, 8), packed.to_le_bytes());
+    }
+
+    // Ticks that would spill into the kind are cut off.
+    let date = DateTime {
+        ticks: u64::MAX,
+        kind: DateTimeKind::Unspecified,
+    };
+    let read = round_trip(&date).primary;
+    assert_eq!(read.ticks, (1 << 62) - 1);
+    assert_eq!(read.kind, DateTimeKind::Unspecified);
+}
+
+#[test]
+fn decimals() {
+    // -123.45, as the mantissa 12345 scaled by 10^2.
+    let decimal = Decimal {
+        lo: 12345,
+        mid: 0,
+        hi: 0,
+        flags: 0x8000_0000 | 2 << 16,
+    };
+    assert_round_trip(decimal);
+    assert_eq!(
+        (decimal.mantissa(), decimal.scale(), decimal.is_negative()),
+        (12345, 2, true)
+    );
+    let mut expected = vec![];
+    for &v in &[12345u32, 0, 0, 0x8002_0000] {
+        expected.extend_from_slice(&v.to_le_bytes());
+    }
+    assert_eq!(tail(&decimal, 16), expected);
+
+    // The largest mantissa uses all 96 bits.
+    let decimal = Decimal {
+        lo: u32::MAX,
+        mid: u32::MAX,
+        hi: u32::MAX,
+        flags: 28 << 16,
+    };
+    assert_round_trip(decimal);
+    assert_eq!(decimal.mantissa(), (1 << 96) - 1);
+    assert_eq!(decimal.scale(), 28);
+    assert!(!decimal.is_negative());
+}
+
+#[test]
+fn time_spans() {
+    for &ticks in &[0, 15_000_000, -15_000_000, i64::MAX, i64::MIN] {
+        assert_round_trip(TimeSpan { ticks });
+    }
+    let span = TimeSpan { ticks: -15_000_000 };
+    assert!(span.is_negative());
+    assert_eq!(span.abs_duration(), Duration::from_millis(1500));
+
+    // Durations are written as whole ticks of 100ns.
+    assert_round_trip(Duration::from_nanos(1_000_000_500));
+    let read = round_trip(&Duration::from_nanos(150)).primary;
+    assert_eq!(read, Duration::from_nanos(100));
+
+    // Durations too long for a TimeSpan saturate rather than wrap.
+    assert_eq!(tail(&Duration::MAX, 8), i64::MAX.to_le_bytes());
+    let read = round_trip(&Duration::MAX).primary;
+    assert_eq!(read, TimeSpan { ticks: i64::MAX }.abs_duration());
+
+    // A negative span has no `Duration`.
+    let bytes = XnbWriter::new().to_bytes(&span).unwrap();
+    match XNB::<Duration>::from_buffer(&mut &bytes[..]) {
+        Err(Error::Parse { error, .. }) => {
+            assert!(matches!(*error, Error::NegativeTimeSpan(-15_000_000)))
+        }
+        result => panic!("{:?}", result.map(|xnb| xnb.primary)),
+    }
+}
@@ -0,0 +1,360 @@
+use std::time::Duration;
+use xnb::xact::{
+    CueTarget, CurvePoint, CurveType, EngineSettings, RpcParameter, SoundBank, WaveReference,
+};
+
+// Builds XACT files in either byte order. Offsets that aren't known until
+// later are written as placeholders and patched.
+struct Writer {
+    data: Vec<u8>,
+    big_endian: bool,
+}
+
+impl Writer {
+    // Starts a file with the common header. Magic numbers are byte-swapped
+    // along with everything else.
+    fn new(magic: &[u8; 4], big_endian: bool) -> Writer {
+        let mut wtr = Writer {
+            data: vec![],
+            big_endian,
+        };
+        wtr.u32(u32::from_le_bytes(*magic));
+        wtr.u16(46);
+        wtr.zeros(13);
+        wtr
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        let mut bytes = bytes.to_vec();
+        if self.big_endian {
+            bytes.reverse();
+        }
+        self.data.extend_from_slice(&bytes);
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.data.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn zeros(&mut self, count: usize) {
+        self.data.resize(self.data.len() + count, 0);
+    }
+
+    fn fixed_string(&mut self, s: &str, len: usize) {
+        self.data.extend_from_slice(s.as_bytes());
+        self.zeros(len - s.len());
+    }
+
+    fn c_string(&mut self, s: &str) {
+        self.data.extend_from_slice(s.as_bytes());
+        self.u8(0);
+    }
+
+    fn wave(&mut self, bank: u8, track: u16) {
+        self.u16(track);
+        self.u8(bank);
+    }
+
+    fn pos(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    // Writes a placeholder offset, returning where it is.
+    fn placeholder(&mut self) -> u32 {
+        let pos = self.pos();
+        self.u32(0);
+        pos
+    }
+
+    // A complex cue with a variation table, returning where its offset is.
+    fn placeholder_cue(&mut self) -> u32 {
+        self.u8(0);
+        let offset = self.placeholder();
+        self.zeros(10);
+        offset
+    }
+
+    fn patch(&mut self, at: u32, v: u32) {
+        let mut bytes = v.to_le_bytes();
+        if self.big_endian {
+            bytes.reverse();
+        }
+        let at = at as usize;
+        self.data[at..at + 4].copy_from_slice(&bytes);
+    }
+}
+
+fn wave(wave_bank: u8, track: u16) -> WaveReference {
+    WaveReference { wave_bank, track }
+}
+
+// Two wave banks and two sounds: a simple one playing a single wave, and a
+// complex one whose track plays a wave and then picks from two more. The
+// cues are one simple cue and three complex ones: a single sound, a choice
+// of sounds and a choice of waves.
+fn sound_bank(big_endian: bool) -> Vec<u8> {
+    let mut wtr = Writer::new(b"SDBK", big_endian);
+    wtr.u16(1);
+    wtr.u16(3);
+    wtr.zeros(4);
+    wtr.u8(2);
+    wtr.u16(2);
+    wtr.zeros(4);
+    let simple_cues = wtr.placeholder();
+    let complex_cues = wtr.placeholder();
+    let cue_names = wtr.placeholder();
+    wtr.zeros(4);
+    let variations = wtr.placeholder();
+    wtr.zeros(4);
+    let wave_bank_names = wtr.placeholder();
+    wtr.zeros(8);
+    let sounds = wtr.placeholder();
+    wtr.fixed_string("Sounds", 64);
+
+    let pos = wtr.pos();
+    wtr.patch(wave_bank_names, pos);
+    wtr.fixed_string("Music", 64);
+    wtr.fixed_string("Effects", 64);
+
+    let simple_sound = wtr.pos();
+    wtr.patch(sounds, simple_sound);
+    wtr.u8(0);
+    wtr.u16(1);
+    wtr.u8(0xB4);
+    wtr.u16(-100i16 as u16);
+    wtr.u8(2);
+    wtr.u16(12);
+    wtr.wave(1, 5);
+
+    let complex_sound = wtr.pos();
+    wtr.u8(0x01);
+    wtr.u16(2);
+    wtr.u8(0x90);
+    wtr.u16(50);
+    wtr.u8(0);
+    wtr.u16(19);
+    wtr.u8(1);
+    wtr.u8(0);
+    let events = wtr.placeholder();
+    wtr.zeros(4);
+
+    let pos = wtr.pos();
+    wtr.patch(events, pos);
+    wtr.u8(2);
+    wtr.u32(1);
+    wtr.zeros(2 + 2);
+    wtr.wave(0, 3);
+    wtr.zeros(5);
+    wtr.u32(3);
+    wtr.zeros(2 + 2 + 5);
+    wtr.u16(2);
+    wtr.zeros(6);
+    wtr.wave(0, 7);
+    wtr.zeros(2);
+    wtr.wave(1, 8);
+    wtr.zeros(2);
+
+    let pos = wtr.pos();
+    wtr.patch(simple_cues, pos);
+    wtr.u8(0);
+    wtr.u32(simple_sound);
+
+    let pos = wtr.pos();
+    wtr.patch(complex_cues, pos);
+    wtr.u8(0x04);
+    wtr.u32(complex_sound);
+    wtr.zeros(10);
+    let sound_table = wtr.placeholder_cue();
+    let wave_table = wtr.placeholder_cue();
+
+    let pos = wtr.pos();
+    wtr.patch(variations, pos);
+    wtr.patch(sound_table, pos);
+    wtr.u16(2);
+    wtr.u16(0);
+    wtr.zeros(4);
+    for sound in [simple_sound, complex_sound] {
+        wtr.u32(sound);
+        wtr.zeros(2);
+    }
+    let pos = wtr.pos();
+    wtr.patch(wave_table, pos);
+    wtr.u16(2);
+    wtr.u16(4 << 3);
+    wtr.zeros(4);
+    wtr.wave(0, 9);
+    wtr.wave(1, 10);
+
+    let pos = wtr.pos();
+    wtr.patch(cue_names, pos);
+    for name in ["Click", "Ambient", "Random", "Waves"] {
+        wtr.c_string(name);
+    }
+    wtr.data
+}
+
+#[test]
+fn sound_banks() {
+    for &big_endian in &[false, true] {
+        let bank = SoundBank::from_bytes(&sound_bank(big_endian)).unwrap();
+        assert_eq!(bank.name, "Sounds");
+        assert_eq!(bank.wave_banks, ["Music", "Effects"]);
+
+        assert_eq!(bank.sounds.len(), 2);
+        let sound = &bank.sounds[0];
+        assert_eq!(
+            (sound.category, sound.volume, sound.pitch, sound.priority),
+            (1, 0xB4, -100, 2)
+        );
+        assert_eq!(sound.waves, [wave(1, 5)]);
+        let sound = &bank.sounds[1];
+        assert_eq!((sound.category, sound.pitch), (2, 50));
+        assert_eq!(sound.waves, [wave(0, 3), wave(0, 7), wave(1, 8)]);
+
+        let cues: Vec<_> = bank.cues.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(cues, ["Click", "Ambient", "Random", "Waves"]);
+        assert_eq!(bank.cue("Click").unwrap().variations, [CueTarget::Sound(0)]);
+        assert_eq!(
+            bank.cue("Ambient").unwrap().variations,
+            [CueTarget::Sound(1)]
+        );
+        assert_eq!(
+            bank.cue("Random").unwrap().variations,
+            [CueTarget::Sound(0), CueTarget::Sound(1)]
+        );
+        assert_eq!(
+            bank.cue("Waves").unwrap().variations,
+            [CueTarget::Wave(wave(0, 9)), CueTarget::Wave(wave(1, 10))]
+        );
+        assert_eq!(bank.cue_waves("Random").len(), 4);
+        assert_eq!(bank.wave_bank_name(&wave(1, 10)), Some("Effects"));
+        assert_eq!(bank.wave_bank_name(&wave(2, 0)), None);
+    }
+}
+
+// Two categories, the second inside the first, two variables and two RPC
+// curves.
+fn engine_settings(big_endian: bool) -> Vec<u8> {
+    let mut wtr = Writer::new(b"XGSF", big_endian);
+    wtr.u16(2);
+    wtr.u16(2);
+    wtr.zeros(4);
+    wtr.u16(2);
+    wtr.zeros(4);
+    let categories = wtr.placeholder();
+    let variables = wtr.placeholder();
+    wtr.zeros(16);
+    let category_names = wtr.placeholder();
+    let variable_names = wtr.placeholder();
+    let rpc_curves = wtr.placeholder();
+
+    let pos = wtr.pos();
+    wtr.patch(categories, pos);
+    for &(limit, fade_in, fade_out, behavior, parent, volume, visibility) in &[
+        (255, 0, 0, 0, 0xFFFF, 0xB4, 1),
+        (1, 500, 1000, 2 << 3, 0, 0x80, 2),
+    ] {
+        wtr.u8(limit);
+        wtr.u16(fade_in);
+        wtr.u16(fade_out);
+        wtr.u8(behavior);
+        wtr.u16(parent);
+        wtr.u8(volume);
+        wtr.u8(visibility);
+    }
+
+    let pos = wtr.pos();
+    wtr.patch(variables, pos);
+    for &(accessibility, initial, min, max) in &[(0x05, 0.0, 0.0, 1000.0), (0x03, 1.0, 0.0, 1.0)] {
+        wtr.u8(accessibility);
+        wtr.f32(initial);
+        wtr.f32(min);
+        wtr.f32(max);
+    }
+
+    let pos = wtr.pos();
+    wtr.patch(category_names, pos);
+    wtr.c_string("Global");
+    wtr.c_string("Music");
+    let pos = wtr.pos();
+    wtr.patch(variable_names, pos);
+    wtr.c_string("Distance");
+    wtr.c_string("Volume");
+
+    let pos = wtr.pos();
+    wtr.patch(rpc_curves, pos);
+    wtr.u16(0);
+    wtr.u8(2);
+    wtr.u16(0);
+    for &(x, y, curve) in &[(0.0, 0.0, 0), (1000.0, -96.0, 1)] {
+        wtr.f32(x);
+        wtr.f32(y);
+        wtr.u8(curve);
+    }
+    wtr.u16(1);
+    wtr.u8(1);
+    wtr.u16(7);
+    wtr.f32(0.5);
+    wtr.f32(2.0);
+    wtr.u8(3);
+    wtr.data
+}
+
+#[test]
+fn engine_settings_files() {
+    for &big_endian in &[false, true] {
+        let settings = EngineSettings::from_bytes(&engine_settings(big_endian)).unwrap();
+
+        assert_eq!(settings.categories.len(), 2);
+        let global = settings.category("Global").unwrap();
+        assert_eq!((global.parent, global.instance_limit), (None, 255));
+        let music = settings.category("Music").unwrap();
+        assert_eq!(music.parent, Some(0));
+        assert_eq!((music.volume, music.visibility), (0x80, 2));
+        assert_eq!(music.fade_in, Duration::from_millis(500));
+        assert_eq!(music.fade_out, Duration::from_secs(1));
+        assert_eq!(music.max_instance_behavior, 2);
+
+        let distance = settings.variable("Distance").unwrap();
+        assert!(distance.is_public() && distance.is_per_cue() && !distance.is_read_only());
+        assert_eq!(distance.max_value, 1000.0);
+        let volume = settings.variable("Volume").unwrap();
+        assert!(volume.is_read_only() && !volume.is_per_cue());
+        assert_eq!(volume.initial_value, 1.0);
+
+        let curves = &settings.rpc_curves;
+        assert_eq!(curves.len(), 2);
+        assert_eq!(
+            (curves[0].variable, curves[0].parameter),
+            (0, RpcParameter::Volume)
+        );
+        assert_eq!(
+            curves[0].points[1],
+            CurvePoint {
+                x: 1000.0,
+                y: -96.0,
+                curve: CurveType::Fast,
+            }
+        );
+        assert_eq!(curves[1].parameter, RpcParameter::Dsp(7));
+        assert_eq!(curves[1].points[0].curve, CurveType::SinCos);
+    }
+}
+
+#[test]
+fn wrong_magic() {
+    assert!(SoundBank::from_bytes(&engine_settings(false)).is_err());
+    assert!(EngineSettings::from_bytes(&sound_bank(true)).is_err());
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "xnbcli")]
+
+use std::fs;
+use std::path::PathBuf;
+use xnb::testing::XnbFixture;
+use xnb::xnbcli::{pack, unpack};
+use xnb::Error;
+
+const STRING_READER: &str = "Microsoft.Xna.Framework.Content.StringReader";
+const TEXTURE_READER: &str = "Microsoft.Xna.Framework.Content.Texture2DReader";
+
+// A directory of its own for each test, as tests run in parallel.
+fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("xnb-{}-{}", test, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn round_trip(test: &str, bytes: Vec<u8>) {
+    let dir = temp_dir(test);
+    unpack(&bytes, &dir, "asset").unwrap();
+    let packed = pack(&dir, "asset");
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(packed.unwrap(), bytes);
+}
+
+#[test]
+fn dictionary() {
+    let mut fixture = XnbFixture::new()
+        .reader(
+            "Microsoft.Xna.Framework.Content.DictionaryReader`2\
+             [[System.String, mscorlib],[System.String, mscorlib]]",
+        )
+        .reader(STRING_READER)
+        .object(1)
+        .uint(2);
+    for (k, v) in [("greeting", "hello"), ("name", "world")] {
+        fixture = fixture.object(2).string(k).object(2).string(v);
+    }
+    round_trip("dictionary", fixture.build());
+}
+
+#[test]
+fn structs() {
+    let rectangle = XnbFixture::new()
+        .reader("Microsoft.Xna.Framework.Content.RectangleReader")
+        .object(1)
+        .int(-4)
+        .int(8)
+        .int(16)
+        .int(32);
+    round_trip("rectangle", rectangle.build());
+
+    let color = XnbFixture::new()
+        .reader("Microsoft.Xna.Framework.Content.ColorReader")
+        .object(1)
+        .bytes(&[255, 128, 0, 64]);
+    round_trip("color", color.build());
+
+    let vector = XnbFixture::new()
+        .reader("Microsoft.Xna.Framework.Content.Vector3Reader")
+        .object(1)
+        .float(1.5)
+        .float(-2.0)
+        .float(0.25);
+    round_trip("vector", vector.build());
+}
+
+#[test]
+fn nullable() {
+    let reader = "Microsoft.Xna.Framework.Content.NullableReader`1[[System.Int32, mscorlib]]";
+    let some = XnbFixture::new()
+        .reader(reader)
+        .object(1)
+        .bytes(&[1])
+        .int(7);
+    round_trip("nullable-some", some.build());
+    let none = XnbFixture::new().reader(reader).object(1).bytes(&[0]);
+    round_trip("nullable-none", none.build());
+}
+
+#[test]
+fn texture() {
+    let pixels: Vec<u8> = (0..2 * 3 * 4).map(|i| i as u8 * 10).collect();
+    let texture = XnbFixture::new()
+        .reader(TEXTURE_READER)
+        .object(1)
+        .uint(0)
+        .uint(2)
+        .uint(3)
+        .uint(1)
+        .uint(pixels.len() as u32)
+        .bytes(&pixels);
+    round_trip("texture", texture.build());
+
+    let empty = XnbFixture::new()
+        .reader(TEXTURE_READER)
+        .object(1)
+        .uint(0)
+        .uint(2)
+        .uint(3)
+        .uint(0)
+        .build();
+    let dir = temp_dir("empty-texture");
+    let result = unpack(&empty, &dir, "asset");
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(matches!(result, Err(Error::MissingMipLevel(0))));
+}